@@ -1,54 +1,600 @@
-use std::borrow::Cow;
+use id_arena::Id;
 
-use crate::span::Spanned;
+use crate::{
+    span::{Span, Spanned},
+    symbol::{sym, Symbol},
+};
+
+mod arena;
+pub mod print;
+pub mod tree;
+pub mod visit;
+
+pub use arena::{Ast, NodeId, NodeMap};
+
+pub type ItemId = Id<Item>;
+pub type StmtId = Id<Statement>;
+pub type ExprId = Id<Expression>;
+
+#[derive(Debug)]
+pub struct Item {
+    pub id: NodeId,
+    pub span: Span,
+    pub kind: ItemKind,
+}
 
 #[derive(Debug)]
-pub enum Item<'a> {
+pub enum ItemKind {
     Function {
-        name: Spanned<Identifier<'a>>,
-        arguments: Vec<(Spanned<Identifier<'a>>, Spanned<Type<'a>>)>,
-        return_type: Spanned<Type<'a>>,
-        body: Spanned<Block<'a>>,
+        name: Spanned<Identifier>,
+        arguments: Vec<Param>,
+        return_type: Spanned<Type>,
+        body: Block,
     },
 }
 
-pub type Block<'a> = Vec<Spanned<Statement<'a>>>;
+#[derive(Debug)]
+pub struct Param {
+    pub id: NodeId,
+    pub name: Spanned<Identifier>,
+    pub ty: Spanned<Type>,
+}
 
 #[derive(Debug)]
-pub enum Statement<'a> {
-    Expr(Spanned<Expression<'a>>),
-    ReturnExpr(Spanned<Expression<'a>>),
+pub struct Block {
+    pub id: NodeId,
+    pub span: Span,
+    pub statements: Vec<StmtId>,
+}
+
+#[derive(Debug)]
+pub struct Statement {
+    pub id: NodeId,
+    pub span: Span,
+    pub kind: StatementKind,
+}
+
+#[derive(Debug)]
+pub enum StatementKind {
+    Let {
+        pattern: Box<Pattern>,
+        ty: Option<Spanned<Type>>,
+        /// `None` for `let x;`, which is assigned later
+        value: Option<ExprId>,
+    },
+    Expr(ExprId),
+    ReturnExpr(ExprId),
 }
 
 #[derive(Debug)]
-pub enum Expression<'a> {
-    Name(Spanned<Identifier<'a>>),
+pub struct Expression {
+    pub id: NodeId,
+    pub span: Span,
+    pub kind: ExpressionKind,
 }
 
 #[derive(Debug)]
-pub struct Identifier<'a>(Cow<'a, str>);
-impl<'a, S: Into<Cow<'a, str>>> From<S> for Identifier<'a> {
-    fn from(value: S) -> Self {
-        Self(value.into())
+pub enum ExpressionKind {
+    Name(Spanned<Identifier>),
+    Path(Path),
+    /// `#name`, stored without the `#`
+    Intrinsic(Spanned<Identifier>),
+    /// `42` or `42u8`, with the suffix naming an integer type
+    IntegerLiteral {
+        value: u128,
+        suffix: Option<Symbol>,
+    },
+    /// Stored as written, the type decides how it's read
+    FloatLiteral(Symbol),
+    CharLiteral(char),
+    /// The contents of the literal, with escapes replaced
+    StringLiteral(Symbol),
+    BoolLiteral(bool),
+    Binary {
+        op: Spanned<BinaryOp>,
+        lhs: ExprId,
+        rhs: ExprId,
+    },
+    Unary {
+        op: Spanned<UnaryOp>,
+        operand: ExprId,
+    },
+    /// `&operand` or `&mut operand`
+    Reference {
+        mutable: bool,
+        operand: ExprId,
+    },
+    /// `expr as ty`
+    Cast {
+        expr: ExprId,
+        ty: Spanned<Type>,
+    },
+    /// `expr?`
+    Try(ExprId),
+    /// `callee(arguments)`
+    Call {
+        callee: ExprId,
+        arguments: Vec<ExprId>,
+    },
+    /// `[a, b, c]`
+    Array(Vec<ExprId>),
+    /// `[value; count]`
+    ArrayRepeat {
+        value: ExprId,
+        count: ExprId,
+    },
+    /// `(a, b)`, `(a,)` or `()`
+    Tuple(Vec<ExprId>),
+    /// `base[index]`
+    Index {
+        base: ExprId,
+        index: ExprId,
+    },
+    /// `base.0`
+    TupleField {
+        base: ExprId,
+        field: Spanned<u32>,
+    },
+    /// `start..end` or `start..=end`
+    Range {
+        start: ExprId,
+        end: ExprId,
+        inclusive: bool,
+    },
+    /// `{ statements }`
+    Block(Block),
+    /// `if condition { .. }`, where `else_branch` is either a [`ExpressionKind::Block`]
+    /// or another [`ExpressionKind::If`]
+    If {
+        condition: ExprId,
+        then_branch: Block,
+        else_branch: Option<ExprId>,
+    },
+    /// `while condition { .. }`
+    While {
+        condition: ExprId,
+        body: Block,
+    },
+    /// `for pattern in iterable { .. }`
+    For {
+        pattern: Pattern,
+        iterable: ExprId,
+        body: Block,
+    },
+    /// `loop { .. }`
+    Loop(Block),
+    /// `break` or `break value`
+    Break(Option<ExprId>),
+    Continue,
+    /// `return` or `return value`
+    Return(Option<ExprId>),
+    /// `target = value`
+    Assign {
+        target: ExprId,
+        value: ExprId,
+    },
+    /// `target += value` and the like, with `op` spanning the whole operator
+    CompoundAssign {
+        op: Spanned<BinaryOp>,
+        target: ExprId,
+        value: ExprId,
+    },
+    /// `|params| body`, or `|| body` without any
+    Closure {
+        params: Vec<ClosureParam>,
+        body: ExprId,
+    },
+    /// `match scrutinee { pattern => body, .. }`
+    Match {
+        scrutinee: ExprId,
+        arms: Vec<MatchArm>,
+    },
+}
+
+impl ExpressionKind {
+    /// Expressions ending in a block, which don't need a `;` to be used as statements
+    pub const fn is_block_like(&self) -> bool {
+        matches!(
+            self,
+            Self::Block(_)
+                | Self::If { .. }
+                | Self::While { .. }
+                | Self::For { .. }
+                | Self::Loop(_)
+                | Self::Match { .. }
+        )
+    }
+
+    /// How tightly the expression binds, see [`Precedence`]
+    pub const fn precedence(&self) -> u8 {
+        match self {
+            Self::Range { .. } => Precedence::RANGE,
+            Self::Binary { op, .. } => op.data.precedence(),
+            Self::Cast { .. } => Precedence::CAST,
+            Self::Unary { .. } | Self::Reference { .. } => Precedence::PREFIX,
+            Self::Index { .. } | Self::TupleField { .. } | Self::Try(_) | Self::Call { .. } => {
+                Precedence::POSTFIX
+            }
+            Self::Return(_) | Self::Break(_) | Self::Closure { .. } => Precedence::JUMP,
+            Self::Assign { .. } | Self::CompoundAssign { .. } => Precedence::ASSIGN,
+            Self::Name(_)
+            | Self::Path(_)
+            | Self::Intrinsic(_)
+            | Self::IntegerLiteral { .. }
+            | Self::FloatLiteral(_)
+            | Self::CharLiteral(_)
+            | Self::StringLiteral(_)
+            | Self::BoolLiteral(_)
+            | Self::Array(_)
+            | Self::ArrayRepeat { .. }
+            | Self::Tuple(_)
+            | Self::Block(_)
+            | Self::If { .. }
+            | Self::While { .. }
+            | Self::For { .. }
+            | Self::Loop(_)
+            | Self::Match { .. }
+            | Self::Continue => Precedence::PRIMARY,
+        }
+    }
+}
+
+/// A closure parameter, where the type can be left for inference
+#[derive(Debug)]
+pub struct ClosureParam {
+    pub pattern: Pattern,
+    pub ty: Option<Spanned<Type>>,
+}
+
+/// `pattern => body`, the first of which whose pattern matches is taken
+#[derive(Debug)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: ExprId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    And,
+    Or,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+impl BinaryOp {
+    pub fn from_operator(slice: &str) -> Option<Self> {
+        Some(match slice {
+            "+" => Self::Add,
+            "-" => Self::Sub,
+            "*" => Self::Mul,
+            "/" => Self::Div,
+            "%" => Self::Rem,
+            "&&" => Self::And,
+            "||" => Self::Or,
+            "==" => Self::Eq,
+            "!=" => Self::Ne,
+            "<" => Self::Lt,
+            ">" => Self::Gt,
+            "<=" => Self::Le,
+            ">=" => Self::Ge,
+            _ => return None,
+        })
+    }
+
+    /// Binding power of the operator, higher binds tighter.
+    /// Ranges sit below every binary operator at [`Precedence::RANGE`].
+    pub const fn precedence(self) -> u8 {
+        match self {
+            Self::Or => Precedence::OR,
+            Self::And => Precedence::AND,
+            Self::Eq | Self::Ne | Self::Lt | Self::Gt | Self::Le | Self::Ge => {
+                Precedence::COMPARISON
+            }
+            Self::Add | Self::Sub => Precedence::SUM,
+            Self::Mul | Self::Div | Self::Rem => Precedence::PRODUCT,
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Rem => "%",
+            Self::And => "&&",
+            Self::Or => "||",
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Gt => ">",
+            Self::Le => "<=",
+            Self::Ge => ">=",
+        }
     }
 }
 
-impl std::fmt::Display for Identifier<'_> {
+impl std::fmt::Display for BinaryOp {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    /// `-operand`
+    Neg,
+    /// `!operand`
+    Not,
+    /// `*operand`
+    Deref,
+}
+
+impl UnaryOp {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Neg => "-",
+            Self::Not => "!",
+            Self::Deref => "*",
+        }
     }
 }
 
+impl std::fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Binding powers, from loosest to tightest.
+/// Prefix operators (`-`, `!`, `*`, `&`) bind tighter than every binary operator
+/// and postfix operators (`[]`, `.0`, `?`, calls) tighter still.
+pub struct Precedence;
+
+impl Precedence {
+    /// `return`, `break` and closures, which take everything after them as their value
+    pub const JUMP: u8 = 0;
+    /// `=` and compound assignments, which group to the right
+    pub const ASSIGN: u8 = 1;
+    pub const RANGE: u8 = 2;
+    pub const OR: u8 = 3;
+    pub const AND: u8 = 4;
+    pub const COMPARISON: u8 = 5;
+    pub const SUM: u8 = 6;
+    pub const PRODUCT: u8 = 7;
+    pub const CAST: u8 = 8;
+    pub const PREFIX: u8 = 9;
+    pub const POSTFIX: u8 = 10;
+    /// Literals, names and expressions with their own delimiters
+    pub const PRIMARY: u8 = 11;
+}
+
+/// Patterns live inline in their parent node, but still get a [`NodeId`]
+/// since bindings are what names resolve to.
 #[derive(Debug)]
-pub struct Type<'a>(Cow<'a, str>);
-impl<'a, S: Into<Cow<'a, str>>> From<S> for Type<'a> {
-    fn from(value: S) -> Self {
-        Self(value.into())
+pub struct Pattern {
+    pub id: NodeId,
+    pub span: Span,
+    pub kind: PatternKind,
+}
+
+#[derive(Debug)]
+pub enum PatternKind {
+    /// `_`
+    Wildcard,
+    /// A name, which the resolver tells apart from a variant without a value like `None`
+    Binding(Identifier),
+    /// `(a, b)`, `(a,)` or `()`
+    Tuple(Vec<Pattern>),
+    /// `1`, `-1`, `'a'` or `true`
+    Literal(LiteralPattern),
+    /// `start..end` or `start..=end`
+    Range {
+        start: Box<LiteralPattern>,
+        end: Box<LiteralPattern>,
+        inclusive: bool,
+    },
+    /// `Some(inner)`, with as many fields as are written
+    Variant {
+        name: Spanned<Identifier>,
+        fields: Vec<Pattern>,
+    },
+}
+
+/// The literals patterns can compare with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiteralPattern {
+    /// `42`, `-42` or `42u8`, with the suffix naming an integer type
+    Int {
+        value: u128,
+        negative: bool,
+        suffix: Option<Symbol>,
+    },
+    Char(char),
+    Bool(bool),
+}
+
+impl std::fmt::Display for LiteralPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int {
+                value,
+                negative,
+                suffix,
+            } => {
+                if *negative {
+                    f.write_str("-")?;
+                }
+                write!(f, "{value}")?;
+                match suffix {
+                    Some(suffix) => write!(f, "{suffix}"),
+                    None => Ok(()),
+                }
+            }
+            Self::Char(value) => write!(f, "{value:?}"),
+            Self::Bool(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// A variant of `Option` or `Result`, which are the only types with variants
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Some,
+    None,
+    Ok,
+    Err,
+}
+
+impl Variant {
+    pub fn from_name(name: Identifier) -> Option<Self> {
+        Some(match name.symbol() {
+            sym::Some => Self::Some,
+            sym::None => Self::None,
+            sym::Ok => Self::Ok,
+            sym::Err => Self::Err,
+            _ => return None,
+        })
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Some => "Some",
+            Self::None => "None",
+            Self::Ok => "Ok",
+            Self::Err => "Err",
+        }
+    }
+
+    /// Whether the variant holds a value, which all but `None` do
+    pub const fn has_value(self) -> bool {
+        !matches!(self, Self::None)
+    }
+
+    /// Tells the variants of a type apart once it's been lowered: `None` and `Ok` are `0`,
+    /// `Some` and `Err` are `1`
+    pub const fn discriminant(self) -> u128 {
+        match self {
+            Self::None | Self::Ok => 0,
+            Self::Some | Self::Err => 1,
+        }
     }
 }
 
-impl std::fmt::Display for Type<'_> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Identifier(Symbol);
+
+impl Identifier {
+    pub const fn symbol(self) -> Symbol {
+        self.0
+    }
+}
+
+impl From<Symbol> for Identifier {
+    fn from(value: Symbol) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Identifier {
+    fn from(value: &str) -> Self {
+        Self(Symbol::intern(value))
+    }
+}
+
+impl std::fmt::Display for Identifier {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
     }
 }
+
+/// `a::b::c`, always with at least two segments.
+/// Single identifiers are stored as an [`Identifier`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    pub segments: Vec<Spanned<Identifier>>,
+}
+
+impl std::fmt::Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i != 0 {
+                write!(f, "::")?;
+            }
+            write!(f, "{}", segment.data)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Type {
+    Named(Identifier),
+    /// `[element; length]`
+    Array {
+        element: Box<Spanned<Type>>,
+        length: Spanned<u128>,
+    },
+    /// `(a, b)`, `(a,)` or `()`
+    Tuple(Vec<Spanned<Type>>),
+    /// `&inner` or `&mut inner`
+    Reference {
+        mutable: bool,
+        inner: Box<Spanned<Type>>,
+    },
+    /// `Name<arguments>`, such as `Option<i32>`
+    Generic {
+        name: Identifier,
+        arguments: Vec<Spanned<Type>>,
+    },
+}
+
+impl From<&str> for Type {
+    fn from(value: &str) -> Self {
+        Self::Named(value.into())
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Named(name) => write!(f, "{name}"),
+            Self::Reference { mutable, inner } => {
+                write!(f, "&{}{}", if *mutable { "mut " } else { "" }, inner.data)
+            }
+            Self::Array { element, length } => write!(f, "[{}; {}]", element.data, length.data),
+            Self::Generic { name, arguments } => {
+                write!(f, "{name}<")?;
+                for (i, argument) in arguments.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", argument.data)?;
+                }
+                write!(f, ">")
+            }
+            Self::Tuple(elements) => {
+                write!(f, "(")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element.data)?;
+                }
+                if elements.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
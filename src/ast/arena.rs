@@ -0,0 +1,227 @@
+use std::ops::{Index, IndexMut};
+
+use id_arena::Arena;
+
+use crate::span::{Span, Spanned};
+
+use super::{
+    Block, ExprId, Expression, ExpressionKind, Identifier, Item, ItemId, ItemKind, Param, Pattern,
+    PatternKind, Statement, StatementKind, StmtId, Type,
+};
+
+/// Identity of any node in an [`Ast`], unique across all the files parsed into it.
+/// Side tables (resolved names, inferred types, ...) are keyed on it through [`NodeMap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl std::fmt::Display for NodeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Owns every item, statement and expression, which refer to each other by id.
+#[derive(Debug, Default)]
+pub struct Ast {
+    pub items: Arena<Item>,
+    pub statements: Arena<Statement>,
+    pub expressions: Arena<Expression>,
+    spans: NodeMap<Span>,
+    next_id: u32,
+}
+
+impl Ast {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn next_id(&mut self, span: Span) -> NodeId {
+        let id = NodeId(self.next_id);
+        self.next_id += 1;
+        self.spans.insert(id, span);
+        id
+    }
+
+    pub fn alloc_item(&mut self, span: Span, kind: ItemKind) -> ItemId {
+        let id = self.next_id(span);
+        self.items.alloc(Item { id, span, kind })
+    }
+
+    pub fn alloc_statement(&mut self, span: Span, kind: StatementKind) -> StmtId {
+        let id = self.next_id(span);
+        self.statements.alloc(Statement { id, span, kind })
+    }
+
+    pub fn alloc_expression(&mut self, span: Span, kind: ExpressionKind) -> ExprId {
+        let id = self.next_id(span);
+        self.expressions.alloc(Expression { id, span, kind })
+    }
+
+    pub fn new_pattern(&mut self, span: Span, kind: PatternKind) -> Pattern {
+        Pattern {
+            id: self.next_id(span),
+            span,
+            kind,
+        }
+    }
+
+    pub fn new_block(&mut self, span: Span, statements: Vec<StmtId>) -> Block {
+        Block {
+            id: self.next_id(span),
+            span,
+            statements,
+        }
+    }
+
+    pub fn new_param(&mut self, name: Spanned<Identifier>, ty: Spanned<Type>) -> Param {
+        Param {
+            id: self.next_id(Span::from_ends(name.span, ty.span).unwrap()),
+            name,
+            ty,
+        }
+    }
+
+    /// Used when an already allocated expression turns out to be parenthesized
+    pub fn respan_expression(&mut self, id: ExprId, span: Span) {
+        let expression = &mut self.expressions[id];
+        expression.span = span;
+        self.spans.insert(expression.id, span);
+    }
+
+    pub fn respan_pattern(&mut self, pattern: &mut Pattern, span: Span) {
+        pattern.span = span;
+        self.spans.insert(pattern.id, span);
+    }
+
+    /// Span of any node, including the ones that aren't stored in an arena
+    pub fn span(&self, id: NodeId) -> Span {
+        self.spans[id]
+    }
+
+    /// Number of [`NodeId`]s handed out so far
+    pub const fn node_count(&self) -> usize {
+        self.next_id as usize
+    }
+}
+
+impl Index<ItemId> for Ast {
+    type Output = Item;
+
+    fn index(&self, index: ItemId) -> &Self::Output {
+        &self.items[index]
+    }
+}
+
+impl IndexMut<ItemId> for Ast {
+    fn index_mut(&mut self, index: ItemId) -> &mut Self::Output {
+        &mut self.items[index]
+    }
+}
+
+impl Index<StmtId> for Ast {
+    type Output = Statement;
+
+    fn index(&self, index: StmtId) -> &Self::Output {
+        &self.statements[index]
+    }
+}
+
+impl IndexMut<StmtId> for Ast {
+    fn index_mut(&mut self, index: StmtId) -> &mut Self::Output {
+        &mut self.statements[index]
+    }
+}
+
+impl Index<ExprId> for Ast {
+    type Output = Expression;
+
+    fn index(&self, index: ExprId) -> &Self::Output {
+        &self.expressions[index]
+    }
+}
+
+impl IndexMut<ExprId> for Ast {
+    fn index_mut(&mut self, index: ExprId) -> &mut Self::Output {
+        &mut self.expressions[index]
+    }
+}
+
+/// Dense side table from [`NodeId`] to `T`
+#[derive(Debug, Clone)]
+pub struct NodeMap<T> {
+    values: Vec<Option<T>>,
+}
+
+impl<T> Default for NodeMap<T> {
+    fn default() -> Self {
+        Self { values: Vec::new() }
+    }
+}
+
+impl<T> NodeMap<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, id: NodeId, value: T) -> Option<T> {
+        if self.values.len() <= id.index() {
+            self.values.resize_with(id.index() + 1, || None);
+        }
+        self.values[id.index()].replace(value)
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        self.values.get(id.index()).and_then(Option::as_ref)
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut T> {
+        self.values.get_mut(id.index()).and_then(Option::as_mut)
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn remove(&mut self, id: NodeId) -> Option<T> {
+        self.values.get_mut(id.index()).and_then(Option::take)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &T)> {
+        self.values
+            .iter()
+            .enumerate()
+            .filter_map(|(i, value)| value.as_ref().map(|value| (NodeId(i as u32), value)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.iter().filter(|value| value.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.iter().all(Option::is_none)
+    }
+}
+
+impl<T> Index<NodeId> for NodeMap<T> {
+    type Output = T;
+
+    fn index(&self, index: NodeId) -> &Self::Output {
+        self.get(index).expect("no entry for node")
+    }
+}
+
+impl<T> FromIterator<(NodeId, T)> for NodeMap<T> {
+    fn from_iter<I: IntoIterator<Item = (NodeId, T)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        for (id, value) in iter {
+            map.insert(id, value);
+        }
+        map
+    }
+}
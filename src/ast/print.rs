@@ -0,0 +1,997 @@
+//! Turns an [`Ast`] back into Coral source.
+//!
+//! Parentheses are only added where the parser would otherwise read a different tree, so
+//! parsing the output gives back the same [`Ast`] up to spans. Given the [`Trivia`] of the
+//! file, comments and blank lines are kept too. Comments are placed between statements and
+//! items, so the few that were in the middle of one end up after it.
+
+use crate::{
+    lexer::trivia::{Comment, CommentKind, Trivia},
+    pretty::Doc,
+};
+
+use super::{
+    Ast, Block, ExprId, ExpressionKind, ItemId, ItemKind, LiteralPattern, Pattern, PatternKind,
+    Precedence, StatementKind, StmtId,
+};
+
+/// Line width used when printing without a formatter configuration
+pub const WIDTH: usize = 100;
+/// Spaces per indentation level used when printing without a formatter configuration
+pub const INDENT: usize = 4;
+
+/// Prints `items` separated by blank lines
+pub fn print_items(ast: &Ast, items: &[ItemId]) -> String {
+    let mut out = document(ast, items, None).render(WIDTH, INDENT);
+    out.push('\n');
+    out
+}
+
+pub fn print_item(ast: &Ast, id: ItemId) -> String {
+    Printer::new(ast, None).item(id).render(WIDTH, INDENT)
+}
+
+/// Prints an expression as if it started a line at the outermost indentation
+pub fn print_expression(ast: &Ast, id: ExprId) -> String {
+    Printer::new(ast, None)
+        .expression(id, 0)
+        .render(WIDTH, INDENT)
+}
+
+/// The layout of `items`, all from the file `trivia` was lexed from if it's given
+pub fn document(ast: &Ast, items: &[ItemId], trivia: Option<Trivia<'_>>) -> Doc {
+    let mut printer = Printer::new(ast, trivia);
+    let mut lines = Vec::new();
+    let mut cursor = None;
+    for &item in items {
+        let span = ast[item].span;
+        let start = lines.len();
+        printer.leading(&mut lines, &mut cursor, span.start());
+        if start != 0 && lines.get(start) != Some(&Line::Blank) {
+            lines.insert(start, Line::Blank);
+        }
+        let doc = printer.item(item);
+        let trailing = printer.trailing(span.end());
+        lines.push(Line::Statement {
+            body: doc,
+            trailing,
+            needs_semicolon: false,
+        });
+        cursor = Some(span.end());
+    }
+    printer.leading(&mut lines, &mut cursor, usize::MAX);
+    Doc::join(lines.into_iter().map(Line::into_doc), &Doc::HardLine)
+}
+
+/// One line of a block or a file, before the choice of `;`s that depends on the next line
+#[derive(Debug, PartialEq)]
+enum Line {
+    Blank,
+    Comment(Doc),
+    Statement {
+        body: Doc,
+        trailing: Doc,
+        /// Whether this is a block-like expression statement, which needs a `;` if it's the
+        /// last statement or the next one could continue it
+        needs_semicolon: bool,
+    },
+}
+
+impl Line {
+    fn into_doc(self) -> Doc {
+        match self {
+            Self::Blank => Doc::nil(),
+            Self::Comment(doc) => doc,
+            Self::Statement { body, trailing, .. } => Doc::concat([body, trailing]),
+        }
+    }
+}
+
+struct Printer<'a, 's> {
+    ast: &'a Ast,
+    trivia: Option<Trivia<'s>>,
+}
+
+impl<'a, 's> Printer<'a, 's> {
+    const fn new(ast: &'a Ast, trivia: Option<Trivia<'s>>) -> Self {
+        Self { ast, trivia }
+    }
+
+    fn comment(&self, comment: &Comment) -> Doc {
+        let text = self.trivia.as_ref().unwrap().text(comment);
+        match comment.kind {
+            CommentKind::Line => Doc::text(text.trim_end().to_string()),
+            CommentKind::Block => Doc::text(text.to_string()),
+        }
+    }
+
+    /// Adds the comments before `offset` on lines of their own, keeping a blank line wherever
+    /// the source had some since `cursor`. Blank lines are dropped at the start, where `cursor`
+    /// is `None`, and before `offset` if it's `usize::MAX` for the end.
+    fn leading(&mut self, lines: &mut Vec<Line>, cursor: &mut Option<usize>, offset: usize) {
+        let Some(trivia) = &mut self.trivia else {
+            return;
+        };
+        let comments = trivia.take_before(offset).to_vec();
+        let trivia = self.trivia.as_ref().unwrap();
+        for comment in &comments {
+            if cursor.is_some_and(|cursor| trivia.blank_line_between(cursor, comment.span.start()))
+            {
+                lines.push(Line::Blank);
+            }
+            lines.push(Line::Comment(self.comment(comment)));
+            *cursor = Some(comment.span.end());
+        }
+        if offset != usize::MAX
+            && cursor.is_some_and(|cursor| trivia.blank_line_between(cursor, offset))
+        {
+            lines.push(Line::Blank);
+        }
+    }
+
+    /// Comments from inside whatever ends at `end` that nothing inside took, and the comment
+    /// right after it on the same line
+    fn trailing(&mut self, end: usize) -> Doc {
+        let Some(trivia) = &mut self.trivia else {
+            return Doc::nil();
+        };
+        let mut comments = trivia.take_before(end).to_vec();
+        comments.extend(trivia.take_trailing(end));
+        Doc::concat(
+            comments
+                .iter()
+                .map(|comment| Doc::concat([Doc::text(" "), self.comment(comment)])),
+        )
+    }
+
+    fn item(&mut self, id: ItemId) -> Doc {
+        match &self.ast[id].kind {
+            ItemKind::Function {
+                name,
+                arguments,
+                return_type,
+                body,
+            } => {
+                let arguments = arguments
+                    .iter()
+                    .map(|param| Doc::text(format!("{}: {}", param.name.data, param.ty.data)));
+                Doc::concat([
+                    Doc::text(format!("fn {}", name.data)),
+                    list("(", arguments, ")", false),
+                    Doc::text(format!(": {} ", return_type.data)),
+                    self.block(body),
+                ])
+            }
+        }
+    }
+
+    fn block(&mut self, block: &Block) -> Doc {
+        let mut lines = Vec::new();
+        let mut cursor = None;
+        for &id in &block.statements {
+            let span = self.ast[id].span;
+            self.leading(&mut lines, &mut cursor, span.start());
+            let body = self.statement(id);
+            let trailing = self.trailing(span.end());
+            let needs_semicolon = matches!(
+                self.ast[id].kind,
+                StatementKind::Expr(expr) if self.ast[expr].kind.is_block_like()
+            );
+            lines.push(Line::Statement {
+                body,
+                trailing,
+                needs_semicolon,
+            });
+            cursor = Some(span.end());
+        }
+        let mut end = Vec::new();
+        // Blank lines before the `}` aren't kept
+        self.leading(&mut end, &mut cursor, block.span.end().saturating_sub(1));
+        if end.last() == Some(&Line::Blank) {
+            end.pop();
+        }
+        lines.extend(end);
+        if lines.is_empty() {
+            return Doc::text("{}");
+        }
+        // Without a `;`, the last statement would become the value of the block and the next
+        // one could continue the expression, as in `if a { b } [c]`
+        let mut next_start: Option<Option<String>> = None;
+        for line in lines.iter_mut().rev() {
+            if let Line::Statement {
+                body,
+                needs_semicolon,
+                ..
+            } = line
+            {
+                let continues = next_start.as_ref().is_none_or(|start| {
+                    start
+                        .as_deref()
+                        .is_some_and(|start| start.starts_with(['[', '(', '-', '*', '&', '|']))
+                });
+                let start = body.first_text().map(str::to_string);
+                if *needs_semicolon && continues {
+                    *body = Doc::concat([std::mem::replace(body, Doc::nil()), Doc::text(";")]);
+                }
+                next_start = Some(start);
+            }
+        }
+        Doc::concat([
+            Doc::text("{"),
+            Doc::concat(
+                lines
+                    .into_iter()
+                    .map(|line| Doc::concat([Doc::HardLine, line.into_doc()])),
+            )
+            .nest(),
+            Doc::HardLine,
+            Doc::text("}"),
+        ])
+    }
+
+    fn statement(&mut self, id: StmtId) -> Doc {
+        match &self.ast[id].kind {
+            StatementKind::Let { pattern, ty, value } => {
+                let mut head = format!("let {}", pattern_text(pattern));
+                if let Some(ty) = ty {
+                    head.push_str(&format!(": {}", ty.data));
+                }
+                match *value {
+                    Some(value) => {
+                        let value = self.assigned(value, 0);
+                        Doc::concat([Doc::text(head), Doc::text(" ="), value, Doc::text(";")])
+                    }
+                    None => Doc::text(head + ";"),
+                }
+            }
+            &StatementKind::Expr(expr) => {
+                let doc = self.expression(expr, 0);
+                if self.ast[expr].kind.is_block_like() {
+                    doc
+                } else {
+                    Doc::concat([doc, Doc::text(";")])
+                }
+            }
+            &StatementKind::ReturnExpr(expr) => self.expression(expr, 0),
+        }
+    }
+
+    /// The value after the `=` of a `let` or an assignment, starting with the space or line
+    /// break that separates it from the `=`
+    fn assigned(&mut self, value: ExprId, min_precedence: u8) -> Doc {
+        let doc = self.expression(value, min_precedence);
+        // Block-like values start on the line of the `=` no matter how long they are,
+        // and so do lists, which rather break between their brackets
+        let kind = &self.ast[value].kind;
+        if kind.is_block_like()
+            || matches!(kind, ExpressionKind::Tuple(_) | ExpressionKind::Array(_))
+        {
+            Doc::concat([Doc::text(" "), doc])
+        } else {
+            Doc::concat([Doc::Line, doc]).nest().group()
+        }
+    }
+
+    /// Lays out `id`, in parentheses if it binds looser than `min_precedence`
+    fn expression(&mut self, id: ExprId, min_precedence: u8) -> Doc {
+        let kind = &self.ast[id].kind;
+        let doc = match kind {
+            ExpressionKind::Name(name) => Doc::text(name.data.symbol().as_str()),
+            ExpressionKind::Path(path) => Doc::text(path.to_string()),
+            ExpressionKind::Intrinsic(name) => Doc::text(format!("#{}", name.data)),
+            ExpressionKind::IntegerLiteral { value, suffix } => match suffix {
+                Some(suffix) => Doc::text(format!("{value}{suffix}")),
+                None => Doc::text(value.to_string()),
+            },
+            ExpressionKind::FloatLiteral(value) => Doc::text(value.as_str()),
+            &ExpressionKind::CharLiteral(value) => Doc::text(format!("'{}'", escape(value, '\''))),
+            ExpressionKind::StringLiteral(value) => Doc::text(format!(
+                "\"{}\"",
+                value
+                    .as_str()
+                    .chars()
+                    .map(|c| escape(c, '"'))
+                    .collect::<String>()
+            )),
+            ExpressionKind::BoolLiteral(value) => Doc::text(value.to_string()),
+            ExpressionKind::Binary { op, .. } => {
+                // `a + b - c` is one chain, which breaks before every operator at once
+                let precedence = op.data.precedence();
+                let mut rest = Vec::new();
+                let mut first = id;
+                while let ExpressionKind::Binary { op, lhs, rhs } = &self.ast[first].kind {
+                    if op.data.precedence() != precedence {
+                        break;
+                    }
+                    rest.push((op.data, *rhs));
+                    first = *lhs;
+                }
+                let first = self.expression(first, precedence);
+                let rest: Vec<_> = rest
+                    .into_iter()
+                    .rev()
+                    .map(|(op, rhs)| {
+                        Doc::concat([
+                            Doc::Line,
+                            Doc::text(format!("{op} ")),
+                            self.expression(rhs, precedence + 1),
+                        ])
+                    })
+                    .collect();
+                Doc::concat([first, Doc::concat(rest).nest()]).group()
+            }
+            ExpressionKind::Unary { op, operand } => Doc::concat([
+                Doc::text(op.data.as_str()),
+                self.expression(*operand, Precedence::PREFIX),
+            ]),
+            &ExpressionKind::Reference { mutable, operand } => Doc::concat([
+                Doc::text(if mutable { "&mut " } else { "&" }),
+                self.expression(operand, Precedence::PREFIX),
+            ]),
+            ExpressionKind::Cast { expr, ty } => Doc::concat([
+                self.expression(*expr, Precedence::CAST),
+                Doc::text(format!(" as {}", ty.data)),
+            ]),
+            &ExpressionKind::Try(expr) => {
+                Doc::concat([self.expression(expr, Precedence::POSTFIX), Doc::text("?")])
+            }
+            ExpressionKind::Array(elements) => {
+                let elements: Vec<_> = elements.iter().map(|&id| self.expression(id, 0)).collect();
+                list("[", elements, "]", false)
+            }
+            &ExpressionKind::ArrayRepeat { value, count } => Doc::concat([
+                Doc::text("["),
+                Doc::concat([
+                    Doc::SoftLine,
+                    self.expression(value, 0),
+                    Doc::text(";"),
+                    Doc::Line,
+                    self.expression(count, 0),
+                ])
+                .nest(),
+                Doc::SoftLine,
+                Doc::text("]"),
+            ])
+            .group(),
+            ExpressionKind::Tuple(elements) => {
+                let single = elements.len() == 1;
+                let elements: Vec<_> = elements.iter().map(|&id| self.expression(id, 0)).collect();
+                list("(", elements, ")", single)
+            }
+            &ExpressionKind::Index { base, index } => Doc::concat([
+                self.expression(base, Precedence::POSTFIX),
+                Doc::text("["),
+                self.expression(index, 0),
+                Doc::text("]"),
+            ]),
+            ExpressionKind::TupleField { base, field } => {
+                // `1.0` would lex as a float
+                let base = if matches!(self.ast[*base].kind, ExpressionKind::IntegerLiteral { .. })
+                {
+                    Doc::concat([Doc::text("("), self.expression(*base, 0), Doc::text(")")])
+                } else {
+                    self.expression(*base, Precedence::POSTFIX)
+                };
+                Doc::concat([base, Doc::text(format!(".{}", field.data))])
+            }
+            ExpressionKind::Call { callee, arguments } => {
+                let callee = self.expression(*callee, Precedence::POSTFIX);
+                let arguments: Vec<_> =
+                    arguments.iter().map(|&id| self.expression(id, 0)).collect();
+                Doc::concat([callee, list("(", arguments, ")", false)])
+            }
+            ExpressionKind::Return(None) => Doc::text("return"),
+            &ExpressionKind::Return(Some(value)) => {
+                Doc::concat([Doc::text("return "), self.expression(value, 0)])
+            }
+            ExpressionKind::Break(None) => Doc::text("break"),
+            &ExpressionKind::Break(Some(value)) => {
+                Doc::concat([Doc::text("break "), self.expression(value, 0)])
+            }
+            ExpressionKind::Continue => Doc::text("continue"),
+            &ExpressionKind::Assign { target, value } => Doc::concat([
+                self.expression(target, Precedence::RANGE),
+                Doc::text(" ="),
+                self.assigned(value, Precedence::ASSIGN),
+            ]),
+            &ExpressionKind::CompoundAssign { op, target, value } => Doc::concat([
+                self.expression(target, Precedence::RANGE),
+                Doc::text(format!(" {}=", op.data)),
+                self.assigned(value, Precedence::ASSIGN),
+            ]),
+            ExpressionKind::Closure { params, body } => {
+                let params: Vec<_> = params
+                    .iter()
+                    .map(|param| match &param.ty {
+                        Some(ty) => format!("{}: {}", pattern_text(&param.pattern), ty.data),
+                        None => pattern_text(&param.pattern),
+                    })
+                    .collect();
+                Doc::concat([
+                    Doc::text(format!("|{}| ", params.join(", "))),
+                    self.expression(*body, 0),
+                ])
+            }
+            &ExpressionKind::Range {
+                start,
+                end,
+                inclusive,
+            } => Doc::concat([
+                self.expression(start, Precedence::RANGE + 1),
+                Doc::text(if inclusive { "..=" } else { ".." }),
+                self.expression(end, Precedence::RANGE + 1),
+            ]),
+            ExpressionKind::Block(block) => self.block(block),
+            ExpressionKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let mut docs = vec![
+                    Doc::text("if "),
+                    self.expression(*condition, Precedence::RANGE),
+                    Doc::text(" "),
+                    self.block(then_branch),
+                ];
+                if let Some(else_branch) = else_branch {
+                    docs.push(Doc::text(" else "));
+                    docs.push(self.expression(*else_branch, 0));
+                }
+                Doc::concat(docs)
+            }
+            ExpressionKind::While { condition, body } => Doc::concat([
+                Doc::text("while "),
+                self.expression(*condition, Precedence::RANGE),
+                Doc::text(" "),
+                self.block(body),
+            ]),
+            ExpressionKind::For {
+                pattern,
+                iterable,
+                body,
+            } => Doc::concat([
+                Doc::text(format!("for {} in ", pattern_text(pattern))),
+                self.expression(*iterable, Precedence::RANGE),
+                Doc::text(" "),
+                self.block(body),
+            ]),
+            ExpressionKind::Loop(body) => Doc::concat([Doc::text("loop "), self.block(body)]),
+            ExpressionKind::Match { scrutinee, arms } => {
+                let scrutinee = self.expression(*scrutinee, Precedence::RANGE);
+                let arms: Vec<_> = arms
+                    .iter()
+                    .map(|arm| {
+                        let mut body = self.expression(arm.body, 0);
+                        let comma = if self.ast[arm.body].kind.is_block_like() {
+                            Doc::nil()
+                        } else {
+                            // A body starting with a block would end at that block, as in
+                            // `{ a }.0`
+                            if body.first_text().is_some_and(|start| {
+                                start.starts_with('{')
+                                    || ["if ", "while ", "for ", "loop ", "match "]
+                                        .iter()
+                                        .any(|keyword| start.starts_with(keyword))
+                            }) {
+                                body = Doc::concat([Doc::text("("), body, Doc::text(")")]);
+                            }
+                            Doc::text(",")
+                        };
+                        Doc::concat([
+                            Doc::HardLine,
+                            Doc::text(format!("{} => ", pattern_text(&arm.pattern))),
+                            body,
+                            comma,
+                        ])
+                    })
+                    .collect();
+                let body = if arms.is_empty() {
+                    Doc::text(" {}")
+                } else {
+                    Doc::concat([
+                        Doc::text(" {"),
+                        Doc::concat(arms).nest(),
+                        Doc::HardLine,
+                        Doc::text("}"),
+                    ])
+                };
+                Doc::concat([Doc::text("match "), scrutinee, body])
+            }
+        };
+        if kind.precedence() < min_precedence {
+            Doc::concat([Doc::text("("), doc, Doc::text(")")])
+        } else {
+            doc
+        }
+    }
+}
+
+/// `open a, b, c close`, with one element per line and a trailing comma if it doesn't fit.
+/// A `single` element tuple always gets its comma.
+fn list(
+    open: &'static str,
+    elements: impl IntoIterator<Item = Doc>,
+    close: &'static str,
+    single: bool,
+) -> Doc {
+    let elements: Vec<_> = elements.into_iter().collect();
+    if elements.is_empty() {
+        return Doc::text(format!("{open}{close}"));
+    }
+    let trailing_comma = if single {
+        Doc::text(",")
+    } else {
+        Doc::if_break(Doc::text(","), Doc::nil())
+    };
+    Doc::concat([
+        Doc::text(open),
+        Doc::concat([
+            Doc::SoftLine,
+            Doc::join(elements, &Doc::concat([Doc::text(","), Doc::Line])),
+            trailing_comma,
+        ])
+        .nest(),
+        Doc::SoftLine,
+        Doc::text(close),
+    ])
+    .group()
+}
+
+/// `c` as it's written inside a literal quoted with `quote`
+fn escape(c: char, quote: char) -> String {
+    match c {
+        '\n' => "\\n".to_string(),
+        '\r' => "\\r".to_string(),
+        '\t' => "\\t".to_string(),
+        '\0' => "\\0".to_string(),
+        '\\' => "\\\\".to_string(),
+        c if c == quote => format!("\\{c}"),
+        c => c.to_string(),
+    }
+}
+
+fn pattern_text(pattern: &Pattern) -> String {
+    match &pattern.kind {
+        PatternKind::Wildcard => "_".to_string(),
+        PatternKind::Binding(name) => name.to_string(),
+        PatternKind::Tuple(elements) => {
+            let elements: Vec<_> = elements.iter().map(pattern_text).collect();
+            if elements.len() == 1 {
+                format!("({},)", elements[0])
+            } else {
+                format!("({})", elements.join(", "))
+            }
+        }
+        PatternKind::Literal(literal) => literal_text(literal),
+        PatternKind::Range {
+            start,
+            end,
+            inclusive,
+        } => format!(
+            "{}{}{}",
+            literal_text(start),
+            if *inclusive { "..=" } else { ".." },
+            literal_text(end)
+        ),
+        PatternKind::Variant { name, fields } => {
+            let fields: Vec<_> = fields.iter().map(pattern_text).collect();
+            format!("{}({})", name.data, fields.join(", "))
+        }
+    }
+}
+
+fn literal_text(literal: &LiteralPattern) -> String {
+    match *literal {
+        LiteralPattern::Char(value) => format!("'{}'", escape(value, '\'')),
+        _ => literal.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value;
+
+    use crate::{
+        ast::{
+            tree::to_trees, Ast, BinaryOp, Block, ClosureParam, ExprId, ExpressionKind, Identifier,
+            ItemId, ItemKind, LiteralPattern, MatchArm, Path, Pattern, PatternKind, StatementKind,
+            Type, UnaryOp,
+        },
+        error::PrintingContext,
+        fs::File,
+        lexer::{tokens::Tokens, Token},
+        parser::parse_file,
+        span::{Span, Spanned},
+        transaction::ParserState,
+        FileArena,
+    };
+
+    use super::{document, print_items};
+
+    fn parse(arena: &mut FileArena, source: String) -> (Ast, Vec<ItemId>) {
+        let file = arena.alloc(File::new("printed.coral", source));
+        let tokens = Tokens::from(Token::lexer_from_file(arena, file));
+        let mut state = ParserState::new(PrintingContext::new(arena), tokens);
+        let items = parse_file(&mut state);
+        let (_, _, ast) = state.into_parts();
+        (ast, items)
+    }
+
+    /// The tree of `items` with every span removed
+    fn shape(ast: &Ast, arena: &FileArena, items: &[ItemId]) -> Value {
+        fn strip(value: &mut Value) {
+            match value {
+                Value::Object(object) => {
+                    object.remove("span");
+                    object.values_mut().for_each(strip);
+                }
+                Value::Array(array) => array.iter_mut().for_each(strip),
+                _ => {}
+            }
+        }
+        let mut value = serde_json::to_value(to_trees(ast, arena, items)).unwrap();
+        strip(&mut value);
+        value
+    }
+
+    #[test]
+    fn parenthesizes_by_precedence() {
+        let source = "fn f(): i32 {
+            let (a, (b,)): (i32, (u8,)) = (1 + 2) * 3 - (4 - 5);
+            -(x as u8) as i32;
+            &mut (a..b)[0]?;
+            (0..1)..=2;
+            if a { b } else if c { { d } } else { e };
+            while x {}; [1];
+            a = b = c..d; a += (b = c); (a = b) -= c;
+            for (i, _) in (a = b) {} loop { break (break) }; continue;
+            *&&mut y.0.1
+        }";
+        let mut arena = FileArena::new();
+        let (ast, items) = parse(&mut arena, source.to_string());
+        let printed = print_items(&ast, &items);
+        assert_eq!(
+            printed,
+            "fn f(): i32 {
+    let (a, (b,)): (i32, (u8,)) = (1 + 2) * 3 - (4 - 5);
+    -(x as u8) as i32;
+    &mut (a..b)[0]?;
+    (0..1)..=2;
+    if a {
+        b
+    } else if c {
+        {
+            d
+        }
+    } else {
+        e
+    }
+    while x {};
+    [1];
+    a = b = c..d;
+    a += b = c;
+    (a = b) -= c;
+    for (i, _) in (a = b) {}
+    loop {
+        break break
+    }
+    continue;
+    *&&mut y.0.1
+}
+"
+        );
+        let (reparsed, reparsed_items) = parse(&mut arena, printed);
+        assert_eq!(
+            shape(&reparsed, &arena, &reparsed_items),
+            shape(&ast, &arena, &items)
+        );
+    }
+
+    /// xorshift64, so failures reproduce from the seed alone
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    /// Builds random trees of the shapes the parser can produce
+    struct Generator {
+        rng: Rng,
+        ast: Ast,
+        span: Span,
+    }
+
+    const NAMES: [&str; 4] = ["a", "b", "xs", "_"];
+
+    impl Generator {
+        fn name(&mut self) -> Spanned<Identifier> {
+            let name = NAMES[self.rng.below(NAMES.len())];
+            self.span.spanned(name.into())
+        }
+
+        fn expression(&mut self, depth: usize) -> ExprId {
+            let kind = if depth == 0 {
+                match self.rng.below(9) {
+                    0 => ExpressionKind::Name(self.name()),
+                    1 => ExpressionKind::Path(Path {
+                        segments: vec![self.name(), self.name()],
+                    }),
+                    2 => ExpressionKind::Intrinsic(self.span.spanned("line".into())),
+                    3 => ExpressionKind::FloatLiteral(["1.5", "2e-3"][self.rng.below(2)].into()),
+                    4 => ExpressionKind::CharLiteral(['a', '\'', '\n'][self.rng.below(3)]),
+                    5 => ExpressionKind::StringLiteral(["", "a\"b\\"][self.rng.below(2)].into()),
+                    6 => ExpressionKind::BoolLiteral(self.rng.below(2) == 0),
+                    7 => ExpressionKind::Continue,
+                    _ => ExpressionKind::IntegerLiteral {
+                        value: self.rng.below(100) as u128,
+                        suffix: (self.rng.below(3) == 0).then(|| "u8".into()),
+                    },
+                }
+            } else {
+                let depth = depth - 1;
+                match self.rng.below(25) {
+                    0 => ExpressionKind::Binary {
+                        op: self.span.spanned(
+                            [BinaryOp::Add, BinaryOp::Sub, BinaryOp::Mul, BinaryOp::Lt]
+                                [self.rng.below(4)],
+                        ),
+                        lhs: self.expression(depth),
+                        rhs: self.expression(depth),
+                    },
+                    1 => ExpressionKind::Binary {
+                        op: self.span.spanned(
+                            [BinaryOp::And, BinaryOp::Or, BinaryOp::Eq, BinaryOp::Rem]
+                                [self.rng.below(4)],
+                        ),
+                        lhs: self.expression(depth),
+                        rhs: self.expression(depth),
+                    },
+                    2 => ExpressionKind::Unary {
+                        op: self.span.spanned(
+                            [UnaryOp::Neg, UnaryOp::Not, UnaryOp::Deref][self.rng.below(3)],
+                        ),
+                        operand: self.expression(depth),
+                    },
+                    3 => ExpressionKind::Reference {
+                        mutable: self.rng.below(2) == 0,
+                        operand: self.expression(depth),
+                    },
+                    4 => ExpressionKind::Cast {
+                        expr: self.expression(depth),
+                        ty: self.ty(depth, false),
+                    },
+                    5 => ExpressionKind::Try(self.expression(depth)),
+                    6 => ExpressionKind::Array(self.expressions(depth)),
+                    7 => ExpressionKind::ArrayRepeat {
+                        value: self.expression(depth),
+                        count: self.expression(depth),
+                    },
+                    8 => ExpressionKind::Tuple(self.expressions(depth)),
+                    9 => ExpressionKind::Index {
+                        base: self.expression(depth),
+                        index: self.expression(depth),
+                    },
+                    10 => ExpressionKind::TupleField {
+                        base: self.expression(depth),
+                        field: self.span.spanned(self.rng.below(3) as u32),
+                    },
+                    11 => ExpressionKind::Range {
+                        start: self.expression(depth),
+                        end: self.expression(depth),
+                        inclusive: self.rng.below(2) == 0,
+                    },
+                    12 => ExpressionKind::Block(self.block(depth)),
+                    13 => ExpressionKind::If {
+                        condition: self.expression(depth),
+                        then_branch: self.block(depth),
+                        else_branch: match self.rng.below(3) {
+                            0 => None,
+                            1 => {
+                                let block = self.block(depth);
+                                Some(
+                                    self.ast
+                                        .alloc_expression(self.span, ExpressionKind::Block(block)),
+                                )
+                            }
+                            _ => Some(self.expression_if(depth)),
+                        },
+                    },
+                    14 => ExpressionKind::While {
+                        condition: self.expression(depth),
+                        body: self.block(depth),
+                    },
+                    15 => ExpressionKind::Call {
+                        callee: self.expression(depth),
+                        arguments: self.expressions(depth),
+                    },
+                    16 => ExpressionKind::Return(
+                        (self.rng.below(2) == 0).then(|| self.expression(depth)),
+                    ),
+                    17 => ExpressionKind::Closure {
+                        params: (0..self.rng.below(3))
+                            .map(|_| ClosureParam {
+                                pattern: self.pattern(depth),
+                                ty: (self.rng.below(2) == 0).then(|| self.ty(depth, true)),
+                            })
+                            .collect(),
+                        body: self.expression(depth),
+                    },
+                    18 => ExpressionKind::For {
+                        pattern: self.pattern(depth),
+                        iterable: self.expression(depth),
+                        body: self.block(depth),
+                    },
+                    19 => ExpressionKind::Loop(self.block(depth)),
+                    20 => ExpressionKind::Break(
+                        (self.rng.below(2) == 0).then(|| self.expression(depth)),
+                    ),
+                    21 => ExpressionKind::Assign {
+                        target: self.expression(depth),
+                        value: self.expression(depth),
+                    },
+                    22 => ExpressionKind::CompoundAssign {
+                        op: self.span.spanned(
+                            [BinaryOp::Add, BinaryOp::Sub, BinaryOp::Mul, BinaryOp::Rem]
+                                [self.rng.below(4)],
+                        ),
+                        target: self.expression(depth),
+                        value: self.expression(depth),
+                    },
+                    23 => ExpressionKind::Match {
+                        scrutinee: self.expression(depth),
+                        arms: (0..self.rng.below(4))
+                            .map(|_| MatchArm {
+                                pattern: self.pattern(depth),
+                                body: self.expression(depth),
+                            })
+                            .collect(),
+                    },
+                    _ => return self.expression(0),
+                }
+            };
+            self.ast.alloc_expression(self.span, kind)
+        }
+
+        fn expression_if(&mut self, depth: usize) -> ExprId {
+            let kind = ExpressionKind::If {
+                condition: self.expression(depth),
+                then_branch: self.block(depth),
+                else_branch: None,
+            };
+            self.ast.alloc_expression(self.span, kind)
+        }
+
+        fn expressions(&mut self, depth: usize) -> Vec<ExprId> {
+            (0..self.rng.below(3))
+                .map(|_| self.expression(depth))
+                .collect()
+        }
+
+        fn block(&mut self, depth: usize) -> Block {
+            let count = self.rng.below(4);
+            let mut statements = Vec::new();
+            for i in 0..count {
+                let kind = match self.rng.below(3) {
+                    0 => StatementKind::Let {
+                        pattern: Box::new(self.pattern(depth)),
+                        ty: (self.rng.below(2) == 0).then(|| self.ty(depth, true)),
+                        value: (self.rng.below(4) != 0).then(|| self.expression(depth)),
+                    },
+                    1 if i + 1 == count => StatementKind::ReturnExpr(self.expression(depth)),
+                    _ => StatementKind::Expr(self.expression(depth)),
+                };
+                statements.push(self.ast.alloc_statement(self.span, kind));
+            }
+            self.ast.new_block(self.span, statements)
+        }
+
+        fn pattern(&mut self, depth: usize) -> Pattern {
+            let kind = match self.rng.below(if depth == 0 { 4 } else { 6 }) {
+                0 => PatternKind::Wildcard,
+                1 => PatternKind::Binding(["a", "b"][self.rng.below(2)].into()),
+                2 => PatternKind::Literal(self.literal()),
+                3 => PatternKind::Range {
+                    start: Box::new(self.literal()),
+                    end: Box::new(self.literal()),
+                    inclusive: self.rng.below(2) == 0,
+                },
+                4 => PatternKind::Variant {
+                    name: self.span.spanned(["Some", "Err"][self.rng.below(2)].into()),
+                    fields: (0..self.rng.below(3))
+                        .map(|_| self.pattern(depth - 1))
+                        .collect(),
+                },
+                _ => PatternKind::Tuple(
+                    (0..self.rng.below(3))
+                        .map(|_| self.pattern(depth - 1))
+                        .collect(),
+                ),
+            };
+            self.ast.new_pattern(self.span, kind)
+        }
+
+        fn literal(&mut self) -> LiteralPattern {
+            match self.rng.below(3) {
+                0 => LiteralPattern::Int {
+                    value: self.rng.below(100) as u128,
+                    negative: self.rng.below(2) == 0,
+                    suffix: (self.rng.below(3) == 0).then(|| "i8".into()),
+                },
+                1 => LiteralPattern::Char(['a', '\'', '\n'][self.rng.below(3)]),
+                _ => LiteralPattern::Bool(self.rng.below(2) == 0),
+            }
+        }
+
+        /// Cast targets can't be `generic` outside of brackets
+        fn ty(&mut self, depth: usize, generic: bool) -> Spanned<Type> {
+            let ty = match self.rng.below(if depth == 0 { 1 } else { 5 }) {
+                0 => Type::from(["i32", "u8", "bool"][self.rng.below(3)]),
+                1 => Type::Array {
+                    element: Box::new(self.ty(depth - 1, true)),
+                    length: self.span.spanned(self.rng.below(10) as u128),
+                },
+                2 => Type::Tuple(
+                    (0..self.rng.below(3))
+                        .map(|_| self.ty(depth - 1, true))
+                        .collect(),
+                ),
+                3 if generic => Type::Generic {
+                    name: ["Option", "Result"][self.rng.below(2)].into(),
+                    arguments: (0..1 + self.rng.below(2))
+                        .map(|_| self.ty(depth - 1, true))
+                        .collect(),
+                },
+                _ => Type::Reference {
+                    mutable: self.rng.below(2) == 0,
+                    inner: Box::new(self.ty(depth - 1, generic)),
+                },
+            };
+            self.span.spanned(ty)
+        }
+
+        fn item(&mut self, depth: usize) -> ItemId {
+            let kind = ItemKind::Function {
+                name: self.span.spanned("f".into()),
+                arguments: (0..self.rng.below(3))
+                    .map(|_| {
+                        let name = self.name();
+                        let ty = self.ty(depth, true);
+                        self.ast.new_param(name, ty)
+                    })
+                    .collect(),
+                return_type: self.ty(depth, true),
+                body: self.block(depth),
+            };
+            self.ast.alloc_item(self.span, kind)
+        }
+    }
+
+    #[test]
+    fn parse_print_round_trip() {
+        let mut arena = FileArena::new();
+        let placeholder = arena.alloc(File::new("generated.coral", ""));
+        for seed in 1..=500 {
+            let mut generator = Generator {
+                rng: Rng(seed),
+                ast: Ast::new(),
+                span: Span::new(placeholder, 0..0),
+            };
+            let items: Vec<_> = (0..2).map(|_| generator.item(4)).collect();
+            // Narrow enough that most groups break
+            let narrow = document(&generator.ast, &items, None).render(10, 2);
+            for printed in [print_items(&generator.ast, &items), narrow] {
+                let (reparsed, reparsed_items) = parse(&mut arena, printed.clone());
+                assert_eq!(
+                    shape(&reparsed, &arena, &reparsed_items),
+                    shape(&generator.ast, &arena, &items),
+                    "seed {seed} printed:\n{printed}"
+                );
+            }
+        }
+    }
+}
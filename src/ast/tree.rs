@@ -0,0 +1,1708 @@
+//! Owned, serializable form of an [`Ast`], for `--emit=ast-json` and `--emit=ast-sexpr`.
+//!
+//! Nodes nest instead of referring to each other by id, and spans name their file instead of
+//! holding a [`FileId`], so the output stands on its own. [`from_json`] allocates a tree back into
+//! an [`Ast`], resolving file names against the files it was parsed from.
+
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    fs::FileId,
+    span::{Span, Spanned},
+    FileArena,
+};
+
+use super::{
+    Ast, BinaryOp, Block, ClosureParam, ExprId, ExpressionKind, Identifier, ItemId, ItemKind,
+    LiteralPattern, MatchArm, Param, Path, Pattern, PatternKind, StatementKind, StmtId, Type,
+    UnaryOp,
+};
+
+#[derive(Debug, Error)]
+pub enum TreeError {
+    #[error("invalid AST JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("no file named `{0}` was loaded")]
+    UnknownFile(String),
+    #[error("`{file}` has no byte range {start}..{end}")]
+    InvalidSpan {
+        file: String,
+        start: usize,
+        end: usize,
+    },
+    #[error("`{0}` is not an operator")]
+    UnknownOperator(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpanTree {
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpannedTree<T> {
+    pub value: T,
+    pub span: SpanTree,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ItemTree {
+    Function {
+        span: SpanTree,
+        name: SpannedTree<String>,
+        arguments: Vec<ParamTree>,
+        return_type: TypeTree,
+        body: BlockTree,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParamTree {
+    pub span: SpanTree,
+    pub name: SpannedTree<String>,
+    pub ty: TypeTree,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockTree {
+    pub span: SpanTree,
+    pub statements: Vec<StatementTree>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatementTree {
+    Let {
+        span: SpanTree,
+        pattern: Box<PatternTree>,
+        ty: Option<TypeTree>,
+        value: Option<ExpressionTree>,
+    },
+    Expr {
+        span: SpanTree,
+        expr: ExpressionTree,
+    },
+    ReturnExpr {
+        span: SpanTree,
+        expr: ExpressionTree,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExpressionTree {
+    Name {
+        span: SpanTree,
+        name: SpannedTree<String>,
+    },
+    Path {
+        span: SpanTree,
+        segments: Vec<SpannedTree<String>>,
+    },
+    Intrinsic {
+        span: SpanTree,
+        name: SpannedTree<String>,
+    },
+    IntegerLiteral {
+        span: SpanTree,
+        value: u128,
+        suffix: Option<String>,
+    },
+    /// As written, so the value round-trips exactly
+    FloatLiteral {
+        span: SpanTree,
+        value: String,
+    },
+    CharLiteral {
+        span: SpanTree,
+        value: char,
+    },
+    StringLiteral {
+        span: SpanTree,
+        value: String,
+    },
+    BoolLiteral {
+        span: SpanTree,
+        value: bool,
+    },
+    Binary {
+        span: SpanTree,
+        op: SpannedTree<String>,
+        lhs: Box<ExpressionTree>,
+        rhs: Box<ExpressionTree>,
+    },
+    Unary {
+        span: SpanTree,
+        op: SpannedTree<String>,
+        operand: Box<ExpressionTree>,
+    },
+    Reference {
+        span: SpanTree,
+        mutable: bool,
+        operand: Box<ExpressionTree>,
+    },
+    Cast {
+        span: SpanTree,
+        expr: Box<ExpressionTree>,
+        ty: TypeTree,
+    },
+    Try {
+        span: SpanTree,
+        expr: Box<ExpressionTree>,
+    },
+    Array {
+        span: SpanTree,
+        elements: Vec<ExpressionTree>,
+    },
+    ArrayRepeat {
+        span: SpanTree,
+        value: Box<ExpressionTree>,
+        count: Box<ExpressionTree>,
+    },
+    Tuple {
+        span: SpanTree,
+        elements: Vec<ExpressionTree>,
+    },
+    Index {
+        span: SpanTree,
+        base: Box<ExpressionTree>,
+        index: Box<ExpressionTree>,
+    },
+    TupleField {
+        span: SpanTree,
+        base: Box<ExpressionTree>,
+        field: SpannedTree<u32>,
+    },
+    Call {
+        span: SpanTree,
+        callee: Box<ExpressionTree>,
+        arguments: Vec<ExpressionTree>,
+    },
+    Return {
+        span: SpanTree,
+        value: Option<Box<ExpressionTree>>,
+    },
+    Break {
+        span: SpanTree,
+        value: Option<Box<ExpressionTree>>,
+    },
+    Continue {
+        span: SpanTree,
+    },
+    Closure {
+        span: SpanTree,
+        params: Vec<ClosureParamTree>,
+        body: Box<ExpressionTree>,
+    },
+    Assign {
+        span: SpanTree,
+        target: Box<ExpressionTree>,
+        value: Box<ExpressionTree>,
+    },
+    /// With the operator written without its `=`
+    CompoundAssign {
+        span: SpanTree,
+        op: SpannedTree<String>,
+        target: Box<ExpressionTree>,
+        value: Box<ExpressionTree>,
+    },
+    Range {
+        span: SpanTree,
+        start: Box<ExpressionTree>,
+        end: Box<ExpressionTree>,
+        inclusive: bool,
+    },
+    Block {
+        span: SpanTree,
+        block: BlockTree,
+    },
+    If {
+        span: SpanTree,
+        condition: Box<ExpressionTree>,
+        then_branch: BlockTree,
+        else_branch: Option<Box<ExpressionTree>>,
+    },
+    While {
+        span: SpanTree,
+        condition: Box<ExpressionTree>,
+        body: BlockTree,
+    },
+    For {
+        span: SpanTree,
+        pattern: PatternTree,
+        iterable: Box<ExpressionTree>,
+        body: BlockTree,
+    },
+    Loop {
+        span: SpanTree,
+        body: BlockTree,
+    },
+    Match {
+        span: SpanTree,
+        scrutinee: Box<ExpressionTree>,
+        arms: Vec<MatchArmTree>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClosureParamTree {
+    pub pattern: PatternTree,
+    pub ty: Option<TypeTree>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchArmTree {
+    pub pattern: PatternTree,
+    pub body: ExpressionTree,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PatternTree {
+    Wildcard {
+        span: SpanTree,
+    },
+    Binding {
+        span: SpanTree,
+        name: String,
+    },
+    Tuple {
+        span: SpanTree,
+        elements: Vec<PatternTree>,
+    },
+    Literal {
+        span: SpanTree,
+        value: LiteralTree,
+    },
+    Range {
+        span: SpanTree,
+        start: Box<LiteralTree>,
+        end: Box<LiteralTree>,
+        inclusive: bool,
+    },
+    Variant {
+        span: SpanTree,
+        name: SpannedTree<String>,
+        fields: Vec<PatternTree>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LiteralTree {
+    Int {
+        value: u128,
+        negative: bool,
+        suffix: Option<String>,
+    },
+    Char(char),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TypeTree {
+    Named {
+        span: SpanTree,
+        name: String,
+    },
+    Generic {
+        span: SpanTree,
+        name: String,
+        arguments: Vec<TypeTree>,
+    },
+    Array {
+        span: SpanTree,
+        element: Box<TypeTree>,
+        length: SpannedTree<u128>,
+    },
+    Tuple {
+        span: SpanTree,
+        elements: Vec<TypeTree>,
+    },
+    Reference {
+        span: SpanTree,
+        mutable: bool,
+        inner: Box<TypeTree>,
+    },
+}
+
+/// Builds the tree of `items`
+pub fn to_trees(ast: &Ast, files: &FileArena, items: &[ItemId]) -> Vec<ItemTree> {
+    let lower = Lower { ast, files };
+    items.iter().map(|&item| lower.item(item)).collect()
+}
+
+/// Pretty-printed JSON array of `items`
+pub fn to_json(ast: &Ast, files: &FileArena, items: &[ItemId]) -> String {
+    serde_json::to_string_pretty(&to_trees(ast, files, items)).expect("trees always serialize")
+}
+
+/// One S-expression per item, each on its own line
+pub fn to_sexpr(ast: &Ast, files: &FileArena, items: &[ItemId]) -> String {
+    let mut out = String::new();
+    for tree in to_trees(ast, files, items) {
+        tree.write_sexpr(&mut out);
+        out.push('\n');
+    }
+    out
+}
+
+/// Allocates the items of a JSON array produced by [`to_json`] into `ast`
+pub fn from_json(json: &str, files: &FileArena, ast: &mut Ast) -> Result<Vec<ItemId>, TreeError> {
+    let trees: Vec<ItemTree> = serde_json::from_str(json)?;
+    let mut raise = Raise { files, ast };
+    trees.iter().map(|tree| raise.item(tree)).collect()
+}
+
+struct Lower<'a> {
+    ast: &'a Ast,
+    files: &'a FileArena,
+}
+
+impl Lower<'_> {
+    fn span(&self, span: Span) -> SpanTree {
+        SpanTree {
+            file: span.filename(self.files).to_string(),
+            start: span.start(),
+            end: span.end(),
+        }
+    }
+
+    fn spanned<T, U>(&self, spanned: &Spanned<T>, value: impl FnOnce(&T) -> U) -> SpannedTree<U> {
+        SpannedTree {
+            value: value(&spanned.data),
+            span: self.span(spanned.span),
+        }
+    }
+
+    fn name(&self, name: &Spanned<Identifier>) -> SpannedTree<String> {
+        self.spanned(name, ToString::to_string)
+    }
+
+    fn item(&self, id: ItemId) -> ItemTree {
+        let item = &self.ast[id];
+        match &item.kind {
+            ItemKind::Function {
+                name,
+                arguments,
+                return_type,
+                body,
+            } => ItemTree::Function {
+                span: self.span(item.span),
+                name: self.name(name),
+                arguments: arguments.iter().map(|param| self.param(param)).collect(),
+                return_type: self.ty(return_type),
+                body: self.block(body),
+            },
+        }
+    }
+
+    fn param(&self, param: &Param) -> ParamTree {
+        ParamTree {
+            span: self.span(self.ast.span(param.id)),
+            name: self.name(&param.name),
+            ty: self.ty(&param.ty),
+        }
+    }
+
+    fn block(&self, block: &Block) -> BlockTree {
+        BlockTree {
+            span: self.span(block.span),
+            statements: block
+                .statements
+                .iter()
+                .map(|&statement| self.statement(statement))
+                .collect(),
+        }
+    }
+
+    fn statement(&self, id: StmtId) -> StatementTree {
+        let statement = &self.ast[id];
+        let span = self.span(statement.span);
+        match &statement.kind {
+            StatementKind::Let { pattern, ty, value } => StatementTree::Let {
+                span,
+                pattern: Box::new(self.pattern(pattern)),
+                ty: ty.as_ref().map(|ty| self.ty(ty)),
+                value: value.map(|value| self.expression(value)),
+            },
+            StatementKind::Expr(expr) => StatementTree::Expr {
+                span,
+                expr: self.expression(*expr),
+            },
+            StatementKind::ReturnExpr(expr) => StatementTree::ReturnExpr {
+                span,
+                expr: self.expression(*expr),
+            },
+        }
+    }
+
+    fn boxed(&self, id: ExprId) -> Box<ExpressionTree> {
+        Box::new(self.expression(id))
+    }
+
+    fn expressions(&self, ids: &[ExprId]) -> Vec<ExpressionTree> {
+        ids.iter().map(|&id| self.expression(id)).collect()
+    }
+
+    fn expression(&self, id: ExprId) -> ExpressionTree {
+        let expression = &self.ast[id];
+        let span = self.span(expression.span);
+        match &expression.kind {
+            ExpressionKind::Name(name) => ExpressionTree::Name {
+                span,
+                name: self.name(name),
+            },
+            ExpressionKind::Path(path) => ExpressionTree::Path {
+                span,
+                segments: path.segments.iter().map(|name| self.name(name)).collect(),
+            },
+            ExpressionKind::Intrinsic(name) => ExpressionTree::Intrinsic {
+                span,
+                name: self.name(name),
+            },
+            &ExpressionKind::IntegerLiteral { value, suffix } => ExpressionTree::IntegerLiteral {
+                span,
+                value,
+                suffix: suffix.map(|suffix| suffix.to_string()),
+            },
+            ExpressionKind::FloatLiteral(value) => ExpressionTree::FloatLiteral {
+                span,
+                value: value.as_str().to_string(),
+            },
+            &ExpressionKind::CharLiteral(value) => ExpressionTree::CharLiteral { span, value },
+            ExpressionKind::StringLiteral(value) => ExpressionTree::StringLiteral {
+                span,
+                value: value.as_str().to_string(),
+            },
+            &ExpressionKind::BoolLiteral(value) => ExpressionTree::BoolLiteral { span, value },
+            ExpressionKind::Binary { op, lhs, rhs } => ExpressionTree::Binary {
+                span,
+                op: self.spanned(op, ToString::to_string),
+                lhs: self.boxed(*lhs),
+                rhs: self.boxed(*rhs),
+            },
+            ExpressionKind::Unary { op, operand } => ExpressionTree::Unary {
+                span,
+                op: self.spanned(op, ToString::to_string),
+                operand: self.boxed(*operand),
+            },
+            &ExpressionKind::Reference { mutable, operand } => ExpressionTree::Reference {
+                span,
+                mutable,
+                operand: self.boxed(operand),
+            },
+            ExpressionKind::Cast { expr, ty } => ExpressionTree::Cast {
+                span,
+                expr: self.boxed(*expr),
+                ty: self.ty(ty),
+            },
+            &ExpressionKind::Try(expr) => ExpressionTree::Try {
+                span,
+                expr: self.boxed(expr),
+            },
+            ExpressionKind::Array(elements) => ExpressionTree::Array {
+                span,
+                elements: self.expressions(elements),
+            },
+            &ExpressionKind::ArrayRepeat { value, count } => ExpressionTree::ArrayRepeat {
+                span,
+                value: self.boxed(value),
+                count: self.boxed(count),
+            },
+            ExpressionKind::Tuple(elements) => ExpressionTree::Tuple {
+                span,
+                elements: self.expressions(elements),
+            },
+            &ExpressionKind::Index { base, index } => ExpressionTree::Index {
+                span,
+                base: self.boxed(base),
+                index: self.boxed(index),
+            },
+            ExpressionKind::TupleField { base, field } => ExpressionTree::TupleField {
+                span,
+                base: self.boxed(*base),
+                field: self.spanned(field, |&field| field),
+            },
+            ExpressionKind::Call { callee, arguments } => ExpressionTree::Call {
+                span,
+                callee: self.boxed(*callee),
+                arguments: self.expressions(arguments),
+            },
+            ExpressionKind::Return(value) => ExpressionTree::Return {
+                span,
+                value: value.map(|id| self.boxed(id)),
+            },
+            ExpressionKind::Break(value) => ExpressionTree::Break {
+                span,
+                value: value.map(|id| self.boxed(id)),
+            },
+            ExpressionKind::Continue => ExpressionTree::Continue { span },
+            ExpressionKind::Closure { params, body } => ExpressionTree::Closure {
+                span,
+                params: params
+                    .iter()
+                    .map(|param| ClosureParamTree {
+                        pattern: self.pattern(&param.pattern),
+                        ty: param.ty.as_ref().map(|ty| self.ty(ty)),
+                    })
+                    .collect(),
+                body: self.boxed(*body),
+            },
+            &ExpressionKind::Assign { target, value } => ExpressionTree::Assign {
+                span,
+                target: self.boxed(target),
+                value: self.boxed(value),
+            },
+            ExpressionKind::CompoundAssign { op, target, value } => {
+                ExpressionTree::CompoundAssign {
+                    span,
+                    op: self.spanned(op, ToString::to_string),
+                    target: self.boxed(*target),
+                    value: self.boxed(*value),
+                }
+            }
+            &ExpressionKind::Range {
+                start,
+                end,
+                inclusive,
+            } => ExpressionTree::Range {
+                span,
+                start: self.boxed(start),
+                end: self.boxed(end),
+                inclusive,
+            },
+            ExpressionKind::Block(block) => ExpressionTree::Block {
+                span,
+                block: self.block(block),
+            },
+            ExpressionKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => ExpressionTree::If {
+                span,
+                condition: self.boxed(*condition),
+                then_branch: self.block(then_branch),
+                else_branch: else_branch.map(|id| self.boxed(id)),
+            },
+            ExpressionKind::While { condition, body } => ExpressionTree::While {
+                span,
+                condition: self.boxed(*condition),
+                body: self.block(body),
+            },
+            ExpressionKind::For {
+                pattern,
+                iterable,
+                body,
+            } => ExpressionTree::For {
+                span,
+                pattern: self.pattern(pattern),
+                iterable: self.boxed(*iterable),
+                body: self.block(body),
+            },
+            ExpressionKind::Loop(body) => ExpressionTree::Loop {
+                span,
+                body: self.block(body),
+            },
+            ExpressionKind::Match { scrutinee, arms } => ExpressionTree::Match {
+                span,
+                scrutinee: self.boxed(*scrutinee),
+                arms: arms
+                    .iter()
+                    .map(|arm| MatchArmTree {
+                        pattern: self.pattern(&arm.pattern),
+                        body: self.expression(arm.body),
+                    })
+                    .collect(),
+            },
+        }
+    }
+
+    fn pattern(&self, pattern: &Pattern) -> PatternTree {
+        let span = self.span(pattern.span);
+        match &pattern.kind {
+            PatternKind::Wildcard => PatternTree::Wildcard { span },
+            PatternKind::Binding(name) => PatternTree::Binding {
+                span,
+                name: name.to_string(),
+            },
+            PatternKind::Tuple(elements) => PatternTree::Tuple {
+                span,
+                elements: elements
+                    .iter()
+                    .map(|element| self.pattern(element))
+                    .collect(),
+            },
+            PatternKind::Literal(literal) => PatternTree::Literal {
+                span,
+                value: Self::literal(literal),
+            },
+            &PatternKind::Range {
+                ref start,
+                ref end,
+                inclusive,
+            } => PatternTree::Range {
+                span,
+                start: Box::new(Self::literal(start)),
+                end: Box::new(Self::literal(end)),
+                inclusive,
+            },
+            PatternKind::Variant { name, fields } => PatternTree::Variant {
+                span,
+                name: self.name(name),
+                fields: fields.iter().map(|field| self.pattern(field)).collect(),
+            },
+        }
+    }
+
+    fn literal(literal: &LiteralPattern) -> LiteralTree {
+        match *literal {
+            LiteralPattern::Int {
+                value,
+                negative,
+                suffix,
+            } => LiteralTree::Int {
+                value,
+                negative,
+                suffix: suffix.map(|suffix| suffix.to_string()),
+            },
+            LiteralPattern::Char(value) => LiteralTree::Char(value),
+            LiteralPattern::Bool(value) => LiteralTree::Bool(value),
+        }
+    }
+
+    fn ty(&self, ty: &Spanned<Type>) -> TypeTree {
+        let span = self.span(ty.span);
+        match &ty.data {
+            Type::Named(name) => TypeTree::Named {
+                span,
+                name: name.to_string(),
+            },
+            Type::Generic { name, arguments } => TypeTree::Generic {
+                span,
+                name: name.to_string(),
+                arguments: arguments.iter().map(|argument| self.ty(argument)).collect(),
+            },
+            Type::Array { element, length } => TypeTree::Array {
+                span,
+                element: Box::new(self.ty(element)),
+                length: self.spanned(length, |&length| length),
+            },
+            Type::Tuple(elements) => TypeTree::Tuple {
+                span,
+                elements: elements.iter().map(|element| self.ty(element)).collect(),
+            },
+            &Type::Reference { mutable, ref inner } => TypeTree::Reference {
+                span,
+                mutable,
+                inner: Box::new(self.ty(inner)),
+            },
+        }
+    }
+}
+
+struct Raise<'a> {
+    files: &'a FileArena,
+    ast: &'a mut Ast,
+}
+
+impl Raise<'_> {
+    fn file(&self, name: &str) -> Result<FileId, TreeError> {
+        self.files
+            .iter()
+            .find(|(_, file)| &*file.name == name)
+            .map(|(id, _)| id)
+            .ok_or_else(|| TreeError::UnknownFile(name.to_string()))
+    }
+
+    fn span(&self, span: &SpanTree) -> Result<Span, TreeError> {
+        let file = self.file(&span.file)?;
+        let contents = &self.files[file].contents;
+        if span.start > span.end
+            || span.end > contents.len()
+            || !contents.is_char_boundary(span.start)
+            || !contents.is_char_boundary(span.end)
+        {
+            return Err(TreeError::InvalidSpan {
+                file: span.file.clone(),
+                start: span.start,
+                end: span.end,
+            });
+        }
+        Ok(Span::new(file, span.start..span.end))
+    }
+
+    fn spanned<T, U>(
+        &self,
+        spanned: &SpannedTree<T>,
+        value: impl FnOnce(&T) -> Result<U, TreeError>,
+    ) -> Result<Spanned<U>, TreeError> {
+        Ok(self.span(&spanned.span)?.spanned(value(&spanned.value)?))
+    }
+
+    fn name(&self, name: &SpannedTree<String>) -> Result<Spanned<Identifier>, TreeError> {
+        self.spanned(name, |name| Ok(Identifier::from(name.as_str())))
+    }
+
+    fn item(&mut self, tree: &ItemTree) -> Result<ItemId, TreeError> {
+        match tree {
+            ItemTree::Function {
+                span,
+                name,
+                arguments,
+                return_type,
+                body,
+            } => {
+                let span = self.span(span)?;
+                let kind = ItemKind::Function {
+                    name: self.name(name)?,
+                    arguments: arguments
+                        .iter()
+                        .map(|param| self.param(param))
+                        .collect::<Result<_, _>>()?,
+                    return_type: self.ty(return_type)?,
+                    body: self.block(body)?,
+                };
+                Ok(self.ast.alloc_item(span, kind))
+            }
+        }
+    }
+
+    fn param(&mut self, tree: &ParamTree) -> Result<Param, TreeError> {
+        let name = self.name(&tree.name)?;
+        let ty = self.ty(&tree.ty)?;
+        Ok(self.ast.new_param(name, ty))
+    }
+
+    fn block(&mut self, tree: &BlockTree) -> Result<Block, TreeError> {
+        let span = self.span(&tree.span)?;
+        let statements = tree
+            .statements
+            .iter()
+            .map(|statement| self.statement(statement))
+            .collect::<Result<_, _>>()?;
+        Ok(self.ast.new_block(span, statements))
+    }
+
+    fn statement(&mut self, tree: &StatementTree) -> Result<StmtId, TreeError> {
+        let (span, kind) = match tree {
+            StatementTree::Let {
+                span,
+                pattern,
+                ty,
+                value,
+            } => (
+                span,
+                StatementKind::Let {
+                    pattern: Box::new(self.pattern(pattern)?),
+                    ty: ty.as_ref().map(|ty| self.ty(ty)).transpose()?,
+                    value: value
+                        .as_ref()
+                        .map(|value| self.expression(value))
+                        .transpose()?,
+                },
+            ),
+            StatementTree::Expr { span, expr } => {
+                (span, StatementKind::Expr(self.expression(expr)?))
+            }
+            StatementTree::ReturnExpr { span, expr } => {
+                (span, StatementKind::ReturnExpr(self.expression(expr)?))
+            }
+        };
+        let span = self.span(span)?;
+        Ok(self.ast.alloc_statement(span, kind))
+    }
+
+    fn expressions(&mut self, trees: &[ExpressionTree]) -> Result<Vec<ExprId>, TreeError> {
+        trees.iter().map(|tree| self.expression(tree)).collect()
+    }
+
+    fn expression(&mut self, tree: &ExpressionTree) -> Result<ExprId, TreeError> {
+        let (span, kind) = match tree {
+            ExpressionTree::Name { span, name } => (span, ExpressionKind::Name(self.name(name)?)),
+            ExpressionTree::Path { span, segments } => (
+                span,
+                ExpressionKind::Path(Path {
+                    segments: segments
+                        .iter()
+                        .map(|name| self.name(name))
+                        .collect::<Result<_, _>>()?,
+                }),
+            ),
+            ExpressionTree::Intrinsic { span, name } => {
+                (span, ExpressionKind::Intrinsic(self.name(name)?))
+            }
+            ExpressionTree::IntegerLiteral {
+                span,
+                value,
+                suffix,
+            } => (
+                span,
+                ExpressionKind::IntegerLiteral {
+                    value: *value,
+                    suffix: suffix.as_deref().map(Into::into),
+                },
+            ),
+            ExpressionTree::FloatLiteral { span, value } => {
+                (span, ExpressionKind::FloatLiteral(value.as_str().into()))
+            }
+            &ExpressionTree::CharLiteral { ref span, value } => {
+                (span, ExpressionKind::CharLiteral(value))
+            }
+            ExpressionTree::StringLiteral { span, value } => {
+                (span, ExpressionKind::StringLiteral(value.as_str().into()))
+            }
+            &ExpressionTree::BoolLiteral { ref span, value } => {
+                (span, ExpressionKind::BoolLiteral(value))
+            }
+            ExpressionTree::Binary { span, op, lhs, rhs } => (
+                span,
+                ExpressionKind::Binary {
+                    op: self.spanned(op, |op| {
+                        BinaryOp::from_operator(op)
+                            .ok_or_else(|| TreeError::UnknownOperator(op.clone()))
+                    })?,
+                    lhs: self.expression(lhs)?,
+                    rhs: self.expression(rhs)?,
+                },
+            ),
+            ExpressionTree::Unary { span, op, operand } => (
+                span,
+                ExpressionKind::Unary {
+                    op: self.spanned(op, |op| {
+                        [UnaryOp::Neg, UnaryOp::Not, UnaryOp::Deref]
+                            .into_iter()
+                            .find(|unary| unary.as_str() == op)
+                            .ok_or_else(|| TreeError::UnknownOperator(op.clone()))
+                    })?,
+                    operand: self.expression(operand)?,
+                },
+            ),
+            ExpressionTree::Reference {
+                span,
+                mutable,
+                operand,
+            } => (
+                span,
+                ExpressionKind::Reference {
+                    mutable: *mutable,
+                    operand: self.expression(operand)?,
+                },
+            ),
+            ExpressionTree::Cast { span, expr, ty } => (
+                span,
+                ExpressionKind::Cast {
+                    expr: self.expression(expr)?,
+                    ty: self.ty(ty)?,
+                },
+            ),
+            ExpressionTree::Try { span, expr } => {
+                (span, ExpressionKind::Try(self.expression(expr)?))
+            }
+            ExpressionTree::Array { span, elements } => {
+                (span, ExpressionKind::Array(self.expressions(elements)?))
+            }
+            ExpressionTree::ArrayRepeat { span, value, count } => (
+                span,
+                ExpressionKind::ArrayRepeat {
+                    value: self.expression(value)?,
+                    count: self.expression(count)?,
+                },
+            ),
+            ExpressionTree::Tuple { span, elements } => {
+                (span, ExpressionKind::Tuple(self.expressions(elements)?))
+            }
+            ExpressionTree::Index { span, base, index } => (
+                span,
+                ExpressionKind::Index {
+                    base: self.expression(base)?,
+                    index: self.expression(index)?,
+                },
+            ),
+            ExpressionTree::TupleField { span, base, field } => (
+                span,
+                ExpressionKind::TupleField {
+                    base: self.expression(base)?,
+                    field: self.spanned(field, |&field| Ok(field))?,
+                },
+            ),
+            ExpressionTree::Call {
+                span,
+                callee,
+                arguments,
+            } => (
+                span,
+                ExpressionKind::Call {
+                    callee: self.expression(callee)?,
+                    arguments: self.expressions(arguments)?,
+                },
+            ),
+            ExpressionTree::Return { span, value } => (
+                span,
+                ExpressionKind::Return(
+                    value
+                        .as_deref()
+                        .map(|tree| self.expression(tree))
+                        .transpose()?,
+                ),
+            ),
+            ExpressionTree::Break { span, value } => (
+                span,
+                ExpressionKind::Break(
+                    value
+                        .as_deref()
+                        .map(|tree| self.expression(tree))
+                        .transpose()?,
+                ),
+            ),
+            ExpressionTree::Continue { span } => (span, ExpressionKind::Continue),
+            ExpressionTree::Closure { span, params, body } => (
+                span,
+                ExpressionKind::Closure {
+                    params: params
+                        .iter()
+                        .map(|param| {
+                            Ok(ClosureParam {
+                                pattern: self.pattern(&param.pattern)?,
+                                ty: param.ty.as_ref().map(|ty| self.ty(ty)).transpose()?,
+                            })
+                        })
+                        .collect::<Result<_, TreeError>>()?,
+                    body: self.expression(body)?,
+                },
+            ),
+            ExpressionTree::Assign {
+                span,
+                target,
+                value,
+            } => (
+                span,
+                ExpressionKind::Assign {
+                    target: self.expression(target)?,
+                    value: self.expression(value)?,
+                },
+            ),
+            ExpressionTree::CompoundAssign {
+                span,
+                op,
+                target,
+                value,
+            } => (
+                span,
+                ExpressionKind::CompoundAssign {
+                    op: self.spanned(op, |op| {
+                        BinaryOp::from_operator(op)
+                            .ok_or_else(|| TreeError::UnknownOperator(op.clone()))
+                    })?,
+                    target: self.expression(target)?,
+                    value: self.expression(value)?,
+                },
+            ),
+            ExpressionTree::Range {
+                span,
+                start,
+                end,
+                inclusive,
+            } => (
+                span,
+                ExpressionKind::Range {
+                    start: self.expression(start)?,
+                    end: self.expression(end)?,
+                    inclusive: *inclusive,
+                },
+            ),
+            ExpressionTree::Block { span, block } => {
+                (span, ExpressionKind::Block(self.block(block)?))
+            }
+            ExpressionTree::If {
+                span,
+                condition,
+                then_branch,
+                else_branch,
+            } => (
+                span,
+                ExpressionKind::If {
+                    condition: self.expression(condition)?,
+                    then_branch: self.block(then_branch)?,
+                    else_branch: else_branch
+                        .as_deref()
+                        .map(|tree| self.expression(tree))
+                        .transpose()?,
+                },
+            ),
+            ExpressionTree::While {
+                span,
+                condition,
+                body,
+            } => (
+                span,
+                ExpressionKind::While {
+                    condition: self.expression(condition)?,
+                    body: self.block(body)?,
+                },
+            ),
+            ExpressionTree::For {
+                span,
+                pattern,
+                iterable,
+                body,
+            } => (
+                span,
+                ExpressionKind::For {
+                    pattern: self.pattern(pattern)?,
+                    iterable: self.expression(iterable)?,
+                    body: self.block(body)?,
+                },
+            ),
+            ExpressionTree::Loop { span, body } => (span, ExpressionKind::Loop(self.block(body)?)),
+            ExpressionTree::Match {
+                span,
+                scrutinee,
+                arms,
+            } => (
+                span,
+                ExpressionKind::Match {
+                    scrutinee: self.expression(scrutinee)?,
+                    arms: arms
+                        .iter()
+                        .map(|arm| {
+                            Ok(MatchArm {
+                                pattern: self.pattern(&arm.pattern)?,
+                                body: self.expression(&arm.body)?,
+                            })
+                        })
+                        .collect::<Result<_, TreeError>>()?,
+                },
+            ),
+        };
+        let span = self.span(span)?;
+        Ok(self.ast.alloc_expression(span, kind))
+    }
+
+    fn pattern(&mut self, tree: &PatternTree) -> Result<Pattern, TreeError> {
+        let (span, kind) = match tree {
+            PatternTree::Wildcard { span } => (span, PatternKind::Wildcard),
+            PatternTree::Binding { span, name } => {
+                (span, PatternKind::Binding(name.as_str().into()))
+            }
+            PatternTree::Tuple { span, elements } => (
+                span,
+                PatternKind::Tuple(
+                    elements
+                        .iter()
+                        .map(|element| self.pattern(element))
+                        .collect::<Result<_, _>>()?,
+                ),
+            ),
+            PatternTree::Literal { span, value } => (span, PatternKind::Literal(literal(value))),
+            PatternTree::Range {
+                span,
+                start,
+                end,
+                inclusive,
+            } => (
+                span,
+                PatternKind::Range {
+                    start: Box::new(literal(start)),
+                    end: Box::new(literal(end)),
+                    inclusive: *inclusive,
+                },
+            ),
+            PatternTree::Variant { span, name, fields } => (
+                span,
+                PatternKind::Variant {
+                    name: self.name(name)?,
+                    fields: fields
+                        .iter()
+                        .map(|field| self.pattern(field))
+                        .collect::<Result<_, _>>()?,
+                },
+            ),
+        };
+        let span = self.span(span)?;
+        Ok(self.ast.new_pattern(span, kind))
+    }
+
+    fn ty(&self, tree: &TypeTree) -> Result<Spanned<Type>, TreeError> {
+        let (span, ty) = match tree {
+            TypeTree::Named { span, name } => (span, Type::from(name.as_str())),
+            TypeTree::Generic {
+                span,
+                name,
+                arguments,
+            } => (
+                span,
+                Type::Generic {
+                    name: name.as_str().into(),
+                    arguments: arguments
+                        .iter()
+                        .map(|argument| self.ty(argument))
+                        .collect::<Result<_, _>>()?,
+                },
+            ),
+            TypeTree::Array {
+                span,
+                element,
+                length,
+            } => (
+                span,
+                Type::Array {
+                    element: Box::new(self.ty(element)?),
+                    length: self.spanned(length, |&length| Ok(length))?,
+                },
+            ),
+            TypeTree::Tuple { span, elements } => (
+                span,
+                Type::Tuple(
+                    elements
+                        .iter()
+                        .map(|element| self.ty(element))
+                        .collect::<Result<_, _>>()?,
+                ),
+            ),
+            TypeTree::Reference {
+                span,
+                mutable,
+                inner,
+            } => (
+                span,
+                Type::Reference {
+                    mutable: *mutable,
+                    inner: Box::new(self.ty(inner)?),
+                },
+            ),
+        };
+        Ok(self.span(span)?.spanned(ty))
+    }
+}
+
+fn literal(tree: &LiteralTree) -> LiteralPattern {
+    match *tree {
+        LiteralTree::Int {
+            value,
+            negative,
+            ref suffix,
+        } => LiteralPattern::Int {
+            value,
+            negative,
+            suffix: suffix.as_deref().map(Into::into),
+        },
+        LiteralTree::Char(value) => LiteralPattern::Char(value),
+        LiteralTree::Bool(value) => LiteralPattern::Bool(value),
+    }
+}
+
+/// Writes a node as `(Kind "file"@start..end :field value ..)`
+trait Sexpr {
+    fn write_sexpr(&self, out: &mut String);
+}
+
+fn node(out: &mut String, kind: &str, span: &SpanTree, fields: &[(&str, &dyn Sexpr)]) {
+    write!(out, "({kind} ").unwrap();
+    span.write_sexpr(out);
+    for (name, value) in fields {
+        write!(out, " :{name} ").unwrap();
+        value.write_sexpr(out);
+    }
+    out.push(')');
+}
+
+impl Sexpr for SpanTree {
+    fn write_sexpr(&self, out: &mut String) {
+        write!(out, "{:?}@{}..{}", self.file, self.start, self.end).unwrap();
+    }
+}
+
+impl<T: Sexpr> Sexpr for SpannedTree<T> {
+    fn write_sexpr(&self, out: &mut String) {
+        out.push('(');
+        self.value.write_sexpr(out);
+        out.push(' ');
+        self.span.write_sexpr(out);
+        out.push(')');
+    }
+}
+
+/// Identifiers and operators, which never need quoting
+impl Sexpr for String {
+    fn write_sexpr(&self, out: &mut String) {
+        out.push_str(self);
+    }
+}
+
+macro_rules! display_sexpr {
+    ($($ty:ty),*) => {
+        $(impl Sexpr for $ty {
+            fn write_sexpr(&self, out: &mut String) {
+                write!(out, "{self}").unwrap();
+            }
+        })*
+    };
+}
+
+display_sexpr!(bool, u32, u128);
+
+impl<T: Sexpr> Sexpr for Box<T> {
+    fn write_sexpr(&self, out: &mut String) {
+        (**self).write_sexpr(out);
+    }
+}
+
+impl<T: Sexpr> Sexpr for Option<T> {
+    fn write_sexpr(&self, out: &mut String) {
+        match self {
+            Some(value) => value.write_sexpr(out),
+            None => out.push_str("nil"),
+        }
+    }
+}
+
+impl<T: Sexpr> Sexpr for Vec<T> {
+    fn write_sexpr(&self, out: &mut String) {
+        out.push('(');
+        for (i, value) in self.iter().enumerate() {
+            if i != 0 {
+                out.push(' ');
+            }
+            value.write_sexpr(out);
+        }
+        out.push(')');
+    }
+}
+
+impl Sexpr for ItemTree {
+    fn write_sexpr(&self, out: &mut String) {
+        match self {
+            Self::Function {
+                span,
+                name,
+                arguments,
+                return_type,
+                body,
+            } => node(
+                out,
+                "Function",
+                span,
+                &[
+                    ("name", name),
+                    ("arguments", arguments),
+                    ("return_type", return_type),
+                    ("body", body),
+                ],
+            ),
+        }
+    }
+}
+
+impl Sexpr for ParamTree {
+    fn write_sexpr(&self, out: &mut String) {
+        node(
+            out,
+            "Param",
+            &self.span,
+            &[("name", &self.name), ("ty", &self.ty)],
+        )
+    }
+}
+
+/// Closure parameters have no span of their own, so they're written as `(pattern ty)`
+impl Sexpr for ClosureParamTree {
+    fn write_sexpr(&self, out: &mut String) {
+        out.push('(');
+        self.pattern.write_sexpr(out);
+        out.push(' ');
+        self.ty.write_sexpr(out);
+        out.push(')');
+    }
+}
+
+/// Arms have no span of their own either, so they're written as `(pattern body)`
+impl Sexpr for MatchArmTree {
+    fn write_sexpr(&self, out: &mut String) {
+        out.push('(');
+        self.pattern.write_sexpr(out);
+        out.push(' ');
+        self.body.write_sexpr(out);
+        out.push(')');
+    }
+}
+
+/// Literals are written as they would be in a pattern
+impl Sexpr for LiteralTree {
+    fn write_sexpr(&self, out: &mut String) {
+        match self {
+            Self::Int {
+                value,
+                negative,
+                suffix,
+            } => {
+                if *negative {
+                    out.push('-');
+                }
+                write!(out, "{value}{}", suffix.as_deref().unwrap_or("")).unwrap();
+            }
+            Self::Char(value) => write!(out, "{value:?}").unwrap(),
+            Self::Bool(value) => write!(out, "{value}").unwrap(),
+        }
+    }
+}
+
+impl Sexpr for BlockTree {
+    fn write_sexpr(&self, out: &mut String) {
+        node(
+            out,
+            "Block",
+            &self.span,
+            &[("statements", &self.statements)],
+        )
+    }
+}
+
+impl Sexpr for StatementTree {
+    fn write_sexpr(&self, out: &mut String) {
+        match self {
+            Self::Let {
+                span,
+                pattern,
+                ty,
+                value,
+            } => node(
+                out,
+                "Let",
+                span,
+                &[("pattern", pattern), ("ty", ty), ("value", value)],
+            ),
+            Self::Expr { span, expr } => node(out, "Expr", span, &[("expr", expr)]),
+            Self::ReturnExpr { span, expr } => node(out, "ReturnExpr", span, &[("expr", expr)]),
+        }
+    }
+}
+
+impl Sexpr for ExpressionTree {
+    fn write_sexpr(&self, out: &mut String) {
+        match self {
+            Self::Name { span, name } => node(out, "Name", span, &[("name", name)]),
+            Self::Path { span, segments } => node(out, "Path", span, &[("segments", segments)]),
+            Self::Intrinsic { span, name } => node(out, "Intrinsic", span, &[("name", name)]),
+            Self::IntegerLiteral {
+                span,
+                value,
+                suffix,
+            } => node(
+                out,
+                "IntegerLiteral",
+                span,
+                &[("value", value), ("suffix", suffix)],
+            ),
+            Self::FloatLiteral { span, value } => {
+                node(out, "FloatLiteral", span, &[("value", value)])
+            }
+            Self::CharLiteral { span, value } => node(
+                out,
+                "CharLiteral",
+                span,
+                &[("value", &format!("{value:?}"))],
+            ),
+            Self::StringLiteral { span, value } => node(
+                out,
+                "StringLiteral",
+                span,
+                &[("value", &format!("{value:?}"))],
+            ),
+            Self::BoolLiteral { span, value } => {
+                node(out, "BoolLiteral", span, &[("value", value)])
+            }
+            Self::Binary { span, op, lhs, rhs } => node(
+                out,
+                "Binary",
+                span,
+                &[("op", op), ("lhs", lhs), ("rhs", rhs)],
+            ),
+            Self::Unary { span, op, operand } => {
+                node(out, "Unary", span, &[("op", op), ("operand", operand)])
+            }
+            Self::Reference {
+                span,
+                mutable,
+                operand,
+            } => node(
+                out,
+                "Reference",
+                span,
+                &[("mutable", mutable), ("operand", operand)],
+            ),
+            Self::Cast { span, expr, ty } => node(out, "Cast", span, &[("expr", expr), ("ty", ty)]),
+            Self::Try { span, expr } => node(out, "Try", span, &[("expr", expr)]),
+            Self::Array { span, elements } => node(out, "Array", span, &[("elements", elements)]),
+            Self::ArrayRepeat { span, value, count } => node(
+                out,
+                "ArrayRepeat",
+                span,
+                &[("value", value), ("count", count)],
+            ),
+            Self::Tuple { span, elements } => node(out, "Tuple", span, &[("elements", elements)]),
+            Self::Index { span, base, index } => {
+                node(out, "Index", span, &[("base", base), ("index", index)])
+            }
+            Self::TupleField { span, base, field } => {
+                node(out, "TupleField", span, &[("base", base), ("field", field)])
+            }
+            Self::Call {
+                span,
+                callee,
+                arguments,
+            } => node(
+                out,
+                "Call",
+                span,
+                &[("callee", callee), ("arguments", arguments)],
+            ),
+            Self::Return { span, value } => node(out, "Return", span, &[("value", value)]),
+            Self::Break { span, value } => node(out, "Break", span, &[("value", value)]),
+            Self::Continue { span } => node(out, "Continue", span, &[]),
+            Self::Closure { span, params, body } => {
+                node(out, "Closure", span, &[("params", params), ("body", body)])
+            }
+            Self::Assign {
+                span,
+                target,
+                value,
+            } => node(out, "Assign", span, &[("target", target), ("value", value)]),
+            Self::CompoundAssign {
+                span,
+                op,
+                target,
+                value,
+            } => node(
+                out,
+                "CompoundAssign",
+                span,
+                &[("op", op), ("target", target), ("value", value)],
+            ),
+            Self::Range {
+                span,
+                start,
+                end,
+                inclusive,
+            } => node(
+                out,
+                "Range",
+                span,
+                &[("start", start), ("end", end), ("inclusive", inclusive)],
+            ),
+            Self::Block { span, block } => node(out, "BlockExpr", span, &[("block", block)]),
+            Self::If {
+                span,
+                condition,
+                then_branch,
+                else_branch,
+            } => node(
+                out,
+                "If",
+                span,
+                &[
+                    ("condition", condition),
+                    ("then_branch", then_branch),
+                    ("else_branch", else_branch),
+                ],
+            ),
+            Self::While {
+                span,
+                condition,
+                body,
+            } => node(
+                out,
+                "While",
+                span,
+                &[("condition", condition), ("body", body)],
+            ),
+            Self::For {
+                span,
+                pattern,
+                iterable,
+                body,
+            } => node(
+                out,
+                "For",
+                span,
+                &[("pattern", pattern), ("iterable", iterable), ("body", body)],
+            ),
+            Self::Loop { span, body } => node(out, "Loop", span, &[("body", body)]),
+            Self::Match {
+                span,
+                scrutinee,
+                arms,
+            } => node(
+                out,
+                "Match",
+                span,
+                &[("scrutinee", scrutinee), ("arms", arms)],
+            ),
+        }
+    }
+}
+
+impl Sexpr for PatternTree {
+    fn write_sexpr(&self, out: &mut String) {
+        match self {
+            Self::Wildcard { span } => node(out, "Wildcard", span, &[]),
+            Self::Binding { span, name } => node(out, "Binding", span, &[("name", name)]),
+            Self::Tuple { span, elements } => {
+                node(out, "TuplePattern", span, &[("elements", elements)])
+            }
+            Self::Literal { span, value } => node(out, "LiteralPattern", span, &[("value", value)]),
+            Self::Range {
+                span,
+                start,
+                end,
+                inclusive,
+            } => node(
+                out,
+                "RangePattern",
+                span,
+                &[("start", start), ("end", end), ("inclusive", inclusive)],
+            ),
+            Self::Variant { span, name, fields } => node(
+                out,
+                "VariantPattern",
+                span,
+                &[("name", name), ("fields", fields)],
+            ),
+        }
+    }
+}
+
+impl Sexpr for TypeTree {
+    fn write_sexpr(&self, out: &mut String) {
+        match self {
+            Self::Named { span, name } => node(out, "Named", span, &[("name", name)]),
+            Self::Generic {
+                span,
+                name,
+                arguments,
+            } => node(
+                out,
+                "Generic",
+                span,
+                &[("name", name), ("arguments", arguments)],
+            ),
+            Self::Array {
+                span,
+                element,
+                length,
+            } => node(
+                out,
+                "ArrayType",
+                span,
+                &[("element", element), ("length", length)],
+            ),
+            Self::Tuple { span, elements } => {
+                node(out, "TupleType", span, &[("elements", elements)])
+            }
+            Self::Reference {
+                span,
+                mutable,
+                inner,
+            } => node(
+                out,
+                "ReferenceType",
+                span,
+                &[("mutable", mutable), ("inner", inner)],
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use id_arena::Arena;
+
+    use crate::{
+        ast::Ast,
+        error::CollectingContext,
+        fs::File,
+        lexer::{tokens::Tokens, Token},
+        parser::parse_file,
+        transaction::ParserState,
+    };
+
+    use super::{from_json, to_json, to_sexpr, TreeError};
+
+    const SOURCE: &str = "fn f(a: i32, b: &mut [u8; 4]): (i32, bool) {
+        let (x, _): (i32, bool) = (a, b);
+        -x; !x; *x; &x; &mut x; x as u8; x?;
+        [1, 2]; [0; 3]; xs[0]; t.0; 0..1; 0..=1;
+        a + b; std::x; #line; 1.5; 'c'; \"s\\n\"; true; g(x, 1u8)?; return;
+        |y, (z, _): i32| || y + z;
+        while x { if x { x; } else if b { { b } } else { return x } }
+        x = 1; x -= 2; for (i, _) in 0..x { loop { break 1; } continue; }
+        match x { 0 => x, -1..=5i8 => { x } Some(_) => x, 'a'..'z' => x, (true, _) => x };
+        (x,)
+    }
+    fn h(o: Option<&Result<i32, bool>>): () {}
+    fn g(): () { 170141183460469231731687303715884105727 }";
+
+    #[test]
+    fn json_round_trip() {
+        let mut arena = Arena::new();
+        let file = arena.alloc(File::new("main.coral", SOURCE));
+        let tokens = Tokens::from(Token::lexer_from_file(&arena, file));
+        let mut state = ParserState::new(CollectingContext::new(), tokens);
+        let items = parse_file(&mut state);
+        let (ctx, _, ast) = state.into_parts();
+        assert_eq!(ctx.error_count(), 0, "{:?}", ctx.diagnostics());
+        let json = to_json(&ast, &arena, &items);
+        assert!(json.contains(r#""file": "main.coral""#));
+
+        let mut loaded = Ast::new();
+        let loaded_items = from_json(&json, &arena, &mut loaded).unwrap();
+        assert_eq!(loaded_items.len(), 3);
+        assert_eq!(to_json(&loaded, &arena, &loaded_items), json);
+        assert_eq!(
+            to_sexpr(&loaded, &arena, &loaded_items),
+            to_sexpr(&ast, &arena, &items)
+        );
+    }
+
+    #[test]
+    fn sexpr() {
+        let mut arena = Arena::new();
+        let file = arena.alloc(File::new("a.coral", "fn f(): i32 { 1 + x }"));
+        let tokens = Tokens::from(Token::lexer_from_file(&arena, file));
+        let mut state = ParserState::new(CollectingContext::new(), tokens);
+        let items = parse_file(&mut state);
+        let (ctx, _, ast) = state.into_parts();
+        assert_eq!(ctx.error_count(), 0, "{:?}", ctx.diagnostics());
+        assert_eq!(
+            to_sexpr(&ast, &arena, &items),
+            concat!(
+                r#"(Function "a.coral"@0..21 :name (f "a.coral"@3..4) :arguments () "#,
+                r#":return_type (Named "a.coral"@8..11 :name i32) :body (Block "a.coral"@12..21 "#,
+                r#":statements ((ReturnExpr "a.coral"@14..19 :expr (Binary "a.coral"@14..19 "#,
+                r#":op (+ "a.coral"@16..17) :lhs (IntegerLiteral "a.coral"@14..15 :value 1 :suffix nil) "#,
+                r#":rhs (Name "a.coral"@18..19 :name (x "a.coral"@18..19)))))))"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_files() {
+        let arena = Arena::new();
+        let json = r#"[{"Function":{"span":{"file":"gone.coral","start":0,"end":1}}}]"#;
+        assert!(matches!(
+            from_json(json, &arena, &mut Ast::new()),
+            Err(TreeError::Json(_))
+        ));
+        let json = r#"[{"Function":{"span":{"file":"gone.coral","start":0,"end":1},
+            "name":{"value":"f","span":{"file":"gone.coral","start":0,"end":1}},
+            "arguments":[],"return_type":{"Tuple":{"span":{"file":"gone.coral","start":0,"end":1},
+            "elements":[]}},"body":{"span":{"file":"gone.coral","start":0,"end":1},"statements":[]}}}]"#;
+        assert!(matches!(
+            from_json(json, &arena, &mut Ast::new()),
+            Err(TreeError::UnknownFile(name)) if name == "gone.coral"
+        ));
+    }
+}
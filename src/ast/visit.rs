@@ -0,0 +1,714 @@
+//! Traversal of an [`Ast`].
+//!
+//! Every `visit_*` method defaults to the matching `walk_*` function, which visits the children
+//! of the node. Passes override the methods they care about and call `walk_*` themselves when
+//! they still want to recurse.
+
+use crate::span::{Span, Spanned};
+
+use super::{
+    Ast, Block, ExprId, ExpressionKind, Identifier, ItemId, ItemKind, Param, Path, Pattern,
+    PatternKind, StatementKind, StmtId, Type,
+};
+
+pub trait Visitor<'ast>: Sized {
+    fn visit_item(&mut self, ast: &'ast Ast, id: ItemId) {
+        walk_item(self, ast, id)
+    }
+
+    fn visit_param(&mut self, ast: &'ast Ast, param: &'ast Param) {
+        walk_param(self, ast, param)
+    }
+
+    fn visit_block(&mut self, ast: &'ast Ast, block: &'ast Block) {
+        walk_block(self, ast, block)
+    }
+
+    fn visit_statement(&mut self, ast: &'ast Ast, id: StmtId) {
+        walk_statement(self, ast, id)
+    }
+
+    fn visit_expression(&mut self, ast: &'ast Ast, id: ExprId) {
+        walk_expression(self, ast, id)
+    }
+
+    fn visit_pattern(&mut self, ast: &'ast Ast, pattern: &'ast Pattern) {
+        walk_pattern(self, ast, pattern)
+    }
+
+    fn visit_type(&mut self, ast: &'ast Ast, ty: &'ast Spanned<Type>) {
+        walk_type(self, ast, ty)
+    }
+
+    fn visit_path(&mut self, ast: &'ast Ast, path: &'ast Path) {
+        walk_path(self, ast, path)
+    }
+
+    fn visit_identifier(&mut self, _ast: &'ast Ast, _ident: Identifier, _span: Span) {}
+}
+
+pub fn walk_item<'ast, V: Visitor<'ast>>(visitor: &mut V, ast: &'ast Ast, id: ItemId) {
+    match &ast[id].kind {
+        ItemKind::Function {
+            name,
+            arguments,
+            return_type,
+            body,
+        } => {
+            visitor.visit_identifier(ast, name.data, name.span);
+            for param in arguments {
+                visitor.visit_param(ast, param);
+            }
+            visitor.visit_type(ast, return_type);
+            visitor.visit_block(ast, body);
+        }
+    }
+}
+
+pub fn walk_param<'ast, V: Visitor<'ast>>(visitor: &mut V, ast: &'ast Ast, param: &'ast Param) {
+    visitor.visit_identifier(ast, param.name.data, param.name.span);
+    visitor.visit_type(ast, &param.ty);
+}
+
+pub fn walk_block<'ast, V: Visitor<'ast>>(visitor: &mut V, ast: &'ast Ast, block: &'ast Block) {
+    for &statement in &block.statements {
+        visitor.visit_statement(ast, statement);
+    }
+}
+
+pub fn walk_statement<'ast, V: Visitor<'ast>>(visitor: &mut V, ast: &'ast Ast, id: StmtId) {
+    match &ast[id].kind {
+        StatementKind::Let { pattern, ty, value } => {
+            visitor.visit_pattern(ast, pattern);
+            if let Some(ty) = ty {
+                visitor.visit_type(ast, ty);
+            }
+            if let Some(value) = value {
+                visitor.visit_expression(ast, *value);
+            }
+        }
+        StatementKind::Expr(expr) | StatementKind::ReturnExpr(expr) => {
+            visitor.visit_expression(ast, *expr)
+        }
+    }
+}
+
+pub fn walk_expression<'ast, V: Visitor<'ast>>(visitor: &mut V, ast: &'ast Ast, id: ExprId) {
+    match &ast[id].kind {
+        ExpressionKind::Name(ident) | ExpressionKind::Intrinsic(ident) => {
+            visitor.visit_identifier(ast, ident.data, ident.span)
+        }
+        ExpressionKind::Path(path) => visitor.visit_path(ast, path),
+        ExpressionKind::IntegerLiteral { .. }
+        | ExpressionKind::FloatLiteral(_)
+        | ExpressionKind::CharLiteral(_)
+        | ExpressionKind::StringLiteral(_)
+        | ExpressionKind::BoolLiteral(_)
+        | ExpressionKind::Return(None)
+        | ExpressionKind::Break(None)
+        | ExpressionKind::Continue => {}
+        ExpressionKind::Binary { lhs, rhs, .. }
+        | ExpressionKind::Assign {
+            target: lhs,
+            value: rhs,
+        }
+        | ExpressionKind::CompoundAssign {
+            target: lhs,
+            value: rhs,
+            ..
+        } => {
+            visitor.visit_expression(ast, *lhs);
+            visitor.visit_expression(ast, *rhs);
+        }
+        ExpressionKind::Unary { operand, .. }
+        | ExpressionKind::Reference { operand, .. }
+        | ExpressionKind::Try(operand)
+        | ExpressionKind::TupleField { base: operand, .. }
+        | ExpressionKind::Return(Some(operand))
+        | ExpressionKind::Break(Some(operand)) => visitor.visit_expression(ast, *operand),
+        ExpressionKind::Cast { expr, ty } => {
+            visitor.visit_expression(ast, *expr);
+            visitor.visit_type(ast, ty);
+        }
+        ExpressionKind::Closure { params, body } => {
+            for param in params {
+                visitor.visit_pattern(ast, &param.pattern);
+                if let Some(ty) = &param.ty {
+                    visitor.visit_type(ast, ty);
+                }
+            }
+            visitor.visit_expression(ast, *body);
+        }
+        ExpressionKind::Array(elements) | ExpressionKind::Tuple(elements) => {
+            for &element in elements {
+                visitor.visit_expression(ast, element);
+            }
+        }
+        ExpressionKind::Call { callee, arguments } => {
+            visitor.visit_expression(ast, *callee);
+            for &argument in arguments {
+                visitor.visit_expression(ast, argument);
+            }
+        }
+        ExpressionKind::ArrayRepeat { value, count } => {
+            visitor.visit_expression(ast, *value);
+            visitor.visit_expression(ast, *count);
+        }
+        ExpressionKind::Index { base, index } => {
+            visitor.visit_expression(ast, *base);
+            visitor.visit_expression(ast, *index);
+        }
+        ExpressionKind::Range { start, end, .. } => {
+            visitor.visit_expression(ast, *start);
+            visitor.visit_expression(ast, *end);
+        }
+        ExpressionKind::Block(block) => visitor.visit_block(ast, block),
+        ExpressionKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            visitor.visit_expression(ast, *condition);
+            visitor.visit_block(ast, then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_expression(ast, *else_branch);
+            }
+        }
+        ExpressionKind::While { condition, body } => {
+            visitor.visit_expression(ast, *condition);
+            visitor.visit_block(ast, body);
+        }
+        ExpressionKind::For {
+            pattern,
+            iterable,
+            body,
+        } => {
+            visitor.visit_pattern(ast, pattern);
+            visitor.visit_expression(ast, *iterable);
+            visitor.visit_block(ast, body);
+        }
+        ExpressionKind::Loop(body) => visitor.visit_block(ast, body),
+        ExpressionKind::Match { scrutinee, arms } => {
+            visitor.visit_expression(ast, *scrutinee);
+            for arm in arms {
+                visitor.visit_pattern(ast, &arm.pattern);
+                visitor.visit_expression(ast, arm.body);
+            }
+        }
+    }
+}
+
+pub fn walk_pattern<'ast, V: Visitor<'ast>>(
+    visitor: &mut V,
+    ast: &'ast Ast,
+    pattern: &'ast Pattern,
+) {
+    match &pattern.kind {
+        PatternKind::Wildcard | PatternKind::Literal(_) | PatternKind::Range { .. } => {}
+        PatternKind::Binding(ident) => visitor.visit_identifier(ast, *ident, pattern.span),
+        PatternKind::Tuple(elements) => {
+            for element in elements {
+                visitor.visit_pattern(ast, element);
+            }
+        }
+        PatternKind::Variant { name, fields } => {
+            visitor.visit_identifier(ast, name.data, name.span);
+            for field in fields {
+                visitor.visit_pattern(ast, field);
+            }
+        }
+    }
+}
+
+pub fn walk_type<'ast, V: Visitor<'ast>>(visitor: &mut V, ast: &'ast Ast, ty: &'ast Spanned<Type>) {
+    match &ty.data {
+        Type::Named(ident) => visitor.visit_identifier(ast, *ident, ty.span),
+        Type::Array { element, .. } => visitor.visit_type(ast, element),
+        Type::Tuple(elements) => {
+            for element in elements {
+                visitor.visit_type(ast, element);
+            }
+        }
+        Type::Reference { inner, .. } => visitor.visit_type(ast, inner),
+        Type::Generic { name, arguments } => {
+            visitor.visit_identifier(ast, *name, ty.span);
+            for argument in arguments {
+                visitor.visit_type(ast, argument);
+            }
+        }
+    }
+}
+
+pub fn walk_path<'ast, V: Visitor<'ast>>(visitor: &mut V, ast: &'ast Ast, path: &'ast Path) {
+    for segment in &path.segments {
+        visitor.visit_identifier(ast, segment.data, segment.span);
+    }
+}
+
+/// In-place counterpart of [`Visitor`].
+///
+/// Nodes stored in the arenas are visited by id so implementors can replace them or allocate new
+/// ones through the `&mut Ast`. Inline nodes (patterns, types, identifiers) never contain arena ids,
+/// so they are handed out directly.
+pub trait VisitorMut: Sized {
+    fn visit_item_mut(&mut self, ast: &mut Ast, id: ItemId) {
+        walk_item_mut(self, ast, id)
+    }
+
+    fn visit_param_mut(&mut self, param: &mut Param) {
+        walk_param_mut(self, param)
+    }
+
+    fn visit_block_mut(&mut self, ast: &mut Ast, block: &mut Block) {
+        walk_block_mut(self, ast, block)
+    }
+
+    fn visit_statement_mut(&mut self, ast: &mut Ast, id: StmtId) {
+        walk_statement_mut(self, ast, id)
+    }
+
+    fn visit_expression_mut(&mut self, ast: &mut Ast, id: ExprId) {
+        walk_expression_mut(self, ast, id)
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        walk_pattern_mut(self, pattern)
+    }
+
+    fn visit_type_mut(&mut self, ty: &mut Spanned<Type>) {
+        walk_type_mut(self, ty)
+    }
+
+    fn visit_path_mut(&mut self, path: &mut Path) {
+        walk_path_mut(self, path)
+    }
+
+    fn visit_identifier_mut(&mut self, _ident: &mut Identifier, _span: Span) {}
+}
+
+pub fn walk_item_mut<V: VisitorMut>(visitor: &mut V, ast: &mut Ast, id: ItemId) {
+    match &mut ast[id].kind {
+        ItemKind::Function {
+            name,
+            arguments,
+            return_type,
+            body,
+        } => {
+            visitor.visit_identifier_mut(&mut name.data, name.span);
+            for param in arguments {
+                visitor.visit_param_mut(param);
+            }
+            visitor.visit_type_mut(return_type);
+            // The body is moved out while it's visited, since visiting it needs the whole arena
+            let mut block = take_block(body);
+            visitor.visit_block_mut(ast, &mut block);
+            let ItemKind::Function { body, .. } = &mut ast[id].kind;
+            *body = block;
+        }
+    }
+}
+
+pub fn walk_param_mut<V: VisitorMut>(visitor: &mut V, param: &mut Param) {
+    visitor.visit_identifier_mut(&mut param.name.data, param.name.span);
+    visitor.visit_type_mut(&mut param.ty);
+}
+
+pub fn walk_block_mut<V: VisitorMut>(visitor: &mut V, ast: &mut Ast, block: &mut Block) {
+    for &statement in &block.statements {
+        visitor.visit_statement_mut(ast, statement);
+    }
+}
+
+pub fn walk_statement_mut<V: VisitorMut>(visitor: &mut V, ast: &mut Ast, id: StmtId) {
+    match &mut ast[id].kind {
+        StatementKind::Let { pattern, ty, value } => {
+            let value = *value;
+            visitor.visit_pattern_mut(pattern);
+            if let Some(ty) = ty {
+                visitor.visit_type_mut(ty);
+            }
+            if let Some(value) = value {
+                visitor.visit_expression_mut(ast, value);
+            }
+        }
+        StatementKind::Expr(expr) | StatementKind::ReturnExpr(expr) => {
+            let expr = *expr;
+            visitor.visit_expression_mut(ast, expr)
+        }
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut>(visitor: &mut V, ast: &mut Ast, id: ExprId) {
+    let children = match &mut ast[id].kind {
+        ExpressionKind::Name(ident) | ExpressionKind::Intrinsic(ident) => {
+            visitor.visit_identifier_mut(&mut ident.data, ident.span);
+            Vec::new()
+        }
+        ExpressionKind::Path(path) => {
+            visitor.visit_path_mut(path);
+            Vec::new()
+        }
+        ExpressionKind::IntegerLiteral { .. }
+        | ExpressionKind::FloatLiteral(_)
+        | ExpressionKind::CharLiteral(_)
+        | ExpressionKind::StringLiteral(_)
+        | ExpressionKind::BoolLiteral(_)
+        | ExpressionKind::Return(None)
+        | ExpressionKind::Break(None)
+        | ExpressionKind::Continue => Vec::new(),
+        ExpressionKind::Cast { expr, .. } => {
+            let expr = *expr;
+            visitor.visit_expression_mut(ast, expr);
+            if let ExpressionKind::Cast { ty, .. } = &mut ast[id].kind {
+                visitor.visit_type_mut(ty);
+            }
+            return;
+        }
+        ExpressionKind::Closure { params, body } => {
+            for param in params {
+                visitor.visit_pattern_mut(&mut param.pattern);
+                if let Some(ty) = &mut param.ty {
+                    visitor.visit_type_mut(ty);
+                }
+            }
+            vec![*body]
+        }
+        ExpressionKind::Binary { lhs, rhs, .. }
+        | ExpressionKind::Assign {
+            target: lhs,
+            value: rhs,
+        }
+        | ExpressionKind::CompoundAssign {
+            target: lhs,
+            value: rhs,
+            ..
+        } => vec![*lhs, *rhs],
+        ExpressionKind::Unary { operand, .. }
+        | ExpressionKind::Reference { operand, .. }
+        | ExpressionKind::Try(operand)
+        | ExpressionKind::TupleField { base: operand, .. }
+        | ExpressionKind::Return(Some(operand))
+        | ExpressionKind::Break(Some(operand)) => vec![*operand],
+        ExpressionKind::Array(elements) | ExpressionKind::Tuple(elements) => elements.clone(),
+        ExpressionKind::Call { callee, arguments } => std::iter::once(*callee)
+            .chain(arguments.iter().copied())
+            .collect(),
+        ExpressionKind::ArrayRepeat { value, count } => vec![*value, *count],
+        ExpressionKind::Index { base, index } => vec![*base, *index],
+        ExpressionKind::Range { start, end, .. } => vec![*start, *end],
+        ExpressionKind::Match { scrutinee, arms } => {
+            for arm in arms.iter_mut() {
+                visitor.visit_pattern_mut(&mut arm.pattern);
+            }
+            std::iter::once(*scrutinee)
+                .chain(arms.iter().map(|arm| arm.body))
+                .collect()
+        }
+        ExpressionKind::For { pattern, .. } => {
+            visitor.visit_pattern_mut(pattern);
+            return walk_block_like_mut(visitor, ast, id);
+        }
+        ExpressionKind::Block(_)
+        | ExpressionKind::If { .. }
+        | ExpressionKind::While { .. }
+        | ExpressionKind::Loop(_) => {
+            return walk_block_like_mut(visitor, ast, id);
+        }
+    };
+    for child in children {
+        visitor.visit_expression_mut(ast, child);
+    }
+}
+
+/// Blocks are stored inline in their expression, so they're moved out while being visited
+fn take_block(block: &mut Block) -> Block {
+    Block {
+        statements: std::mem::take(&mut block.statements),
+        ..*block
+    }
+}
+
+fn walk_block_like_mut<V: VisitorMut>(visitor: &mut V, ast: &mut Ast, id: ExprId) {
+    let (condition, mut block, else_branch) = match &mut ast[id].kind {
+        ExpressionKind::Block(block) | ExpressionKind::Loop(block) => {
+            (None, take_block(block), None)
+        }
+        ExpressionKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => (Some(*condition), take_block(then_branch), *else_branch),
+        ExpressionKind::While { condition, body }
+        | ExpressionKind::For {
+            iterable: condition,
+            body,
+            ..
+        } => (Some(*condition), take_block(body), None),
+        _ => unreachable!("not a block-like expression"),
+    };
+    if let Some(condition) = condition {
+        visitor.visit_expression_mut(ast, condition);
+    }
+    visitor.visit_block_mut(ast, &mut block);
+    match &mut ast[id].kind {
+        ExpressionKind::Block(original)
+        | ExpressionKind::Loop(original)
+        | ExpressionKind::For { body: original, .. }
+        | ExpressionKind::If {
+            then_branch: original,
+            ..
+        }
+        | ExpressionKind::While { body: original, .. } => *original = block,
+        _ => unreachable!("not a block-like expression"),
+    }
+    if let Some(else_branch) = else_branch {
+        visitor.visit_expression_mut(ast, else_branch);
+    }
+}
+
+pub fn walk_pattern_mut<V: VisitorMut>(visitor: &mut V, pattern: &mut Pattern) {
+    match &mut pattern.kind {
+        PatternKind::Wildcard | PatternKind::Literal(_) | PatternKind::Range { .. } => {}
+        PatternKind::Binding(ident) => visitor.visit_identifier_mut(ident, pattern.span),
+        PatternKind::Tuple(elements) => {
+            for element in elements {
+                visitor.visit_pattern_mut(element);
+            }
+        }
+        PatternKind::Variant { name, fields } => {
+            visitor.visit_identifier_mut(&mut name.data, name.span);
+            for field in fields {
+                visitor.visit_pattern_mut(field);
+            }
+        }
+    }
+}
+
+pub fn walk_type_mut<V: VisitorMut>(visitor: &mut V, ty: &mut Spanned<Type>) {
+    match &mut ty.data {
+        Type::Named(ident) => visitor.visit_identifier_mut(ident, ty.span),
+        Type::Array { element, .. } => visitor.visit_type_mut(element),
+        Type::Tuple(elements) => {
+            for element in elements {
+                visitor.visit_type_mut(element);
+            }
+        }
+        Type::Reference { inner, .. } => visitor.visit_type_mut(inner),
+        Type::Generic { name, arguments } => {
+            visitor.visit_identifier_mut(name, ty.span);
+            for argument in arguments {
+                visitor.visit_type_mut(argument);
+            }
+        }
+    }
+}
+
+pub fn walk_path_mut<V: VisitorMut>(visitor: &mut V, path: &mut Path) {
+    for segment in &mut path.segments {
+        visitor.visit_identifier_mut(&mut segment.data, segment.span);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use id_arena::Arena;
+
+    use crate::{
+        ast::{
+            Ast, ExprId, ExpressionKind, Identifier, ItemId, Pattern, PatternKind, StatementKind,
+            StmtId, Type,
+        },
+        error::PrintingContext,
+        fs::File,
+        lexer::{tokens::Tokens, Token},
+        parser::parse_item,
+        span::{Span, Spanned},
+        symbol::Symbol,
+        transaction::ParserState,
+    };
+
+    use super::{walk_expression, walk_pattern, walk_statement, walk_type, Visitor, VisitorMut};
+
+    /// Uses every kind of node at least once
+    const SOURCE: &str = "fn f(a: i32, b: &mut [u8; 4]): Result<(i32, bool), str> {
+        let (x, _) = (a, b);
+        -x; !x; *x; &x; &mut x; x as u8; x?; g(x, 1);
+        [1, 2]; [0; 3]; xs[0]; t.0; 0..1; 0..=1;
+        a + b; std::x; #line; 1.5; 'c'; \"s\"; true; |y: u8, _| x;
+        while x { if x { x; } else if b { { b } } else { return x; } }
+        x = a; x += 1; for (y, _) in x { loop { break x; } continue; }
+        match x { 0 => a, -1..=5 => b, Some(_) => c, true => d, _ => e };
+        (x,)
+    }";
+
+    // Exhaustive matches: adding a variant fails to compile until it is listed here,
+    // and the assertions below fail until `SOURCE` exercises it
+    fn expression_kind(kind: &ExpressionKind) -> &'static str {
+        match kind {
+            ExpressionKind::Name(_) => "Name",
+            ExpressionKind::Path(_) => "Path",
+            ExpressionKind::Intrinsic(_) => "Intrinsic",
+            ExpressionKind::IntegerLiteral { .. } => "IntegerLiteral",
+            ExpressionKind::FloatLiteral(_) => "FloatLiteral",
+            ExpressionKind::CharLiteral(_) => "CharLiteral",
+            ExpressionKind::StringLiteral(_) => "StringLiteral",
+            ExpressionKind::BoolLiteral(_) => "BoolLiteral",
+            ExpressionKind::Binary { .. } => "Binary",
+            ExpressionKind::Unary { .. } => "Unary",
+            ExpressionKind::Reference { .. } => "Reference",
+            ExpressionKind::Cast { .. } => "Cast",
+            ExpressionKind::Try(_) => "Try",
+            ExpressionKind::Call { .. } => "Call",
+            ExpressionKind::Array(_) => "Array",
+            ExpressionKind::ArrayRepeat { .. } => "ArrayRepeat",
+            ExpressionKind::Tuple(_) => "Tuple",
+            ExpressionKind::Index { .. } => "Index",
+            ExpressionKind::TupleField { .. } => "TupleField",
+            ExpressionKind::Range { .. } => "Range",
+            ExpressionKind::Block(_) => "Block",
+            ExpressionKind::If { .. } => "If",
+            ExpressionKind::While { .. } => "While",
+            ExpressionKind::For { .. } => "For",
+            ExpressionKind::Loop(_) => "Loop",
+            ExpressionKind::Break(_) => "Break",
+            ExpressionKind::Continue => "Continue",
+            ExpressionKind::Return(_) => "Return",
+            ExpressionKind::Closure { .. } => "Closure",
+            ExpressionKind::Assign { .. } => "Assign",
+            ExpressionKind::CompoundAssign { .. } => "CompoundAssign",
+            ExpressionKind::Match { .. } => "Match",
+        }
+    }
+    const EXPRESSION_KINDS: usize = 32;
+
+    fn statement_kind(kind: &StatementKind) -> &'static str {
+        match kind {
+            StatementKind::Let { .. } => "Let",
+            StatementKind::Expr(_) => "Expr",
+            StatementKind::ReturnExpr(_) => "ReturnExpr",
+        }
+    }
+    const STATEMENT_KINDS: usize = 3;
+
+    fn pattern_kind(kind: &PatternKind) -> &'static str {
+        match kind {
+            PatternKind::Wildcard => "Wildcard",
+            PatternKind::Binding(_) => "Binding",
+            PatternKind::Tuple(_) => "Tuple",
+            PatternKind::Literal(_) => "Literal",
+            PatternKind::Range { .. } => "Range",
+            PatternKind::Variant { .. } => "Variant",
+        }
+    }
+    const PATTERN_KINDS: usize = 6;
+
+    fn type_kind(ty: &Type) -> &'static str {
+        match ty {
+            Type::Named(_) => "Named",
+            Type::Array { .. } => "Array",
+            Type::Tuple(_) => "Tuple",
+            Type::Reference { .. } => "Reference",
+            Type::Generic { .. } => "Generic",
+        }
+    }
+    const TYPE_KINDS: usize = 5;
+
+    fn parse() -> (Ast, ItemId) {
+        let mut arena: Arena<File> = Arena::new();
+        let file = arena.alloc(File::new("test", SOURCE));
+        let mut state = ParserState::new(
+            PrintingContext::new(&arena),
+            Tokens::from(Token::lexer_from_file(&arena, file)),
+        );
+        let item = parse_item(&mut state).expect("item should parse");
+        (state.into_ast(), item)
+    }
+
+    #[derive(Default)]
+    struct Kinds {
+        expressions: BTreeSet<&'static str>,
+        statements: BTreeSet<&'static str>,
+        patterns: BTreeSet<&'static str>,
+        types: BTreeSet<&'static str>,
+        identifiers: Vec<Symbol>,
+        visited: usize,
+    }
+
+    impl<'ast> Visitor<'ast> for Kinds {
+        fn visit_statement(&mut self, ast: &'ast Ast, id: StmtId) {
+            self.statements.insert(statement_kind(&ast[id].kind));
+            walk_statement(self, ast, id)
+        }
+
+        fn visit_expression(&mut self, ast: &'ast Ast, id: ExprId) {
+            self.visited += 1;
+            self.expressions.insert(expression_kind(&ast[id].kind));
+            walk_expression(self, ast, id)
+        }
+
+        fn visit_pattern(&mut self, ast: &'ast Ast, pattern: &'ast Pattern) {
+            self.patterns.insert(pattern_kind(&pattern.kind));
+            walk_pattern(self, ast, pattern)
+        }
+
+        fn visit_type(&mut self, ast: &'ast Ast, ty: &'ast Spanned<Type>) {
+            self.types.insert(type_kind(&ty.data));
+            walk_type(self, ast, ty)
+        }
+
+        fn visit_identifier(&mut self, _ast: &'ast Ast, ident: Identifier, _span: Span) {
+            self.identifiers.push(ident.symbol());
+        }
+    }
+
+    #[test]
+    fn visits_every_kind() {
+        let (ast, item) = parse();
+        let mut kinds = Kinds::default();
+        kinds.visit_item(&ast, item);
+        assert_eq!(kinds.expressions.len(), EXPRESSION_KINDS);
+        assert_eq!(kinds.statements.len(), STATEMENT_KINDS);
+        assert_eq!(kinds.patterns.len(), PATTERN_KINDS);
+        assert_eq!(kinds.types.len(), TYPE_KINDS);
+        // Every allocated expression is reachable from the item
+        assert_eq!(kinds.visited, ast.expressions.len());
+    }
+
+    struct Rename {
+        from: Symbol,
+        to: Symbol,
+    }
+
+    impl VisitorMut for Rename {
+        fn visit_identifier_mut(&mut self, ident: &mut Identifier, _span: Span) {
+            if ident.symbol() == self.from {
+                *ident = self.to.into();
+            }
+        }
+    }
+
+    #[test]
+    fn mutable_visitor() {
+        let (mut ast, item) = parse();
+        let x = Symbol::intern("x");
+        let renamed = Symbol::intern("renamed");
+        Rename {
+            from: x,
+            to: renamed,
+        }
+        .visit_item_mut(&mut ast, item);
+
+        let mut kinds = Kinds::default();
+        kinds.visit_item(&ast, item);
+        assert!(!kinds.identifiers.contains(&x));
+        // the binding in the `let` pattern, nineteen uses and one path segment,
+        // five of the uses being inside blocks nested in expressions
+        // and one in a closure body
+        assert_eq!(
+            kinds.identifiers.iter().filter(|&&s| s == renamed).count(),
+            21
+        );
+    }
+}
@@ -0,0 +1,485 @@
+//! Command line interface of the `coral` binary.
+//!
+//! Exit codes: 0 when no errors were reported, 1 when some were,
+//! 2 for invalid usage and files that couldn't be loaded.
+
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use miette::{GraphicalReportHandler, GraphicalTheme};
+
+use coral::{
+    ast::{tree, Ast, ItemId},
+    error::{
+        codes,
+        output::{json_line, sarif, ErrorFormat},
+        render_with, CollectingContext, Context,
+    },
+    fix, flow,
+    fmt::{self, Config},
+    fs::{ColumnMode, DiskLoader, FileId, SourceMap, StdinLoader},
+    hir,
+    lexer::{tokens::Tokens, Token},
+    mir,
+    parser::{parse_file, ContextName, ParseError},
+    resolve::resolve,
+    span::Span,
+    transaction::ParserState,
+    typeck::typeck,
+};
+
+const ERRORS: u8 = 1;
+const USAGE: u8 = 2;
+
+#[derive(Debug, Parser)]
+#[command(name = "coral", version, about = "The Coral compiler")]
+#[command(arg_required_else_help = true)]
+struct Cli {
+    #[command(flatten)]
+    options: Options,
+    /// Print the long-form explanation of an error code, such as `E0001`
+    #[arg(long, value_name = "CODE", exclusive = true)]
+    explain: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Args)]
+struct Options {
+    /// When to color diagnostics
+    #[arg(long, global = true, value_enum, default_value_t = Color::Auto)]
+    color: Color,
+    /// How to print diagnostics: `human`, `json` or `sarif`
+    #[arg(long, global = true, value_name = "FORMAT", default_value = "human")]
+    error_format: ErrorFormat,
+    /// Stop reporting errors after this many
+    #[arg(long, global = true, value_name = "N")]
+    max_errors: Option<usize>,
+    /// Print more details, such as what was being parsed when an error occurred
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+    /// Only print diagnostics, without the final summary
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Emit {
+    /// The syntax tree as a JSON array of items
+    AstJson,
+    /// The syntax tree as one S-expression per item
+    AstSexpr,
+    /// The desugared and typed functions
+    Hir,
+    /// The control-flow graphs of the functions as text
+    Mir,
+    /// The control-flow graphs of the functions in graphviz's dot format
+    CfgDot,
+}
+
+/// Files are read from standard input when given as `-`
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the tokens of a file
+    Lex { file: PathBuf },
+    /// Print the syntax tree of a file
+    Parse { file: PathBuf },
+    /// Report errors without building anything
+    Check {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Print an intermediate representation to stdout, the syntax tree once parsing
+        /// succeeds and the others once checking does
+        #[arg(long, value_enum)]
+        emit: Option<Emit>,
+    },
+    /// Format files in place, or standard input to standard output when none are given
+    Fmt {
+        files: Vec<PathBuf>,
+        /// Print a diff and fail instead of writing when a file isn't formatted
+        #[arg(long)]
+        check: bool,
+    },
+    /// Apply machine-applicable suggestions to files, then check them
+    Fix {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Build a program and run it
+    Run { file: PathBuf },
+    /// Build an executable
+    Build {
+        file: PathBuf,
+        /// Where to write the executable
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+pub fn run() -> ExitCode {
+    let cli = Cli::parse();
+    if let Some(code) = cli.explain {
+        return match codes::explain(&code) {
+            Some(explanation) => {
+                print!("{explanation}");
+                ExitCode::SUCCESS
+            }
+            None => {
+                eprintln!("error: `{code}` is not a known error code");
+                ExitCode::from(USAGE)
+            }
+        };
+    }
+    let Some(command) = cli.command else {
+        return ExitCode::from(USAGE);
+    };
+    let mut session = Session::new(cli.options);
+    let result = match command {
+        Command::Lex { file } => session.lex(&file),
+        Command::Parse { file } => session.parse(&file),
+        Command::Check { files, emit } => session.check(&files, emit),
+        Command::Fix { files } => session.fix(&files),
+        Command::Fmt { files, check } => session.fmt(&files, check),
+        Command::Run { file } | Command::Build { file, .. } => {
+            session.unsupported(&[file], "code generation")
+        }
+    };
+    match result {
+        Ok(()) => session.finish(),
+        Err(code) => code,
+    }
+}
+
+/// State shared by the subcommands: the loaded files and the diagnostics reported so far
+struct Session {
+    options: Options,
+    sources: SourceMap,
+    ctx: CollectingContext<ContextName>,
+    ast: Ast,
+}
+
+impl Session {
+    fn new(options: Options) -> Self {
+        let mut ctx = CollectingContext::new();
+        if let Some(max) = options.max_errors {
+            ctx = ctx.with_max_errors(max);
+        }
+        Self {
+            options,
+            sources: SourceMap::new(),
+            ctx,
+            ast: Ast::new(),
+        }
+    }
+
+    fn load(&mut self, path: &Path) -> Result<FileId, ExitCode> {
+        let loaded = if path == Path::new("-") {
+            self.sources.load(&mut StdinLoader, path)
+        } else {
+            self.sources.load(&mut DiskLoader, path)
+        };
+        loaded.map_err(|err| {
+            eprintln!("error: {err}");
+            ExitCode::from(USAGE)
+        })
+    }
+
+    fn parse_files(&mut self, paths: &[PathBuf]) -> Result<Vec<ItemId>, ExitCode> {
+        let mut files = Vec::new();
+        for path in paths {
+            let file = self.load(path)?;
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+        let mut items = Vec::new();
+        for file in files {
+            let tokens = Tokens::from(Token::lexer_from_file(self.sources.files(), file));
+            let ctx = std::mem::take(&mut self.ctx);
+            let ast = std::mem::take(&mut self.ast);
+            let mut state = ParserState::with_ast(ctx, tokens, ast);
+            items.extend(parse_file(&mut state));
+            (self.ctx, _, self.ast) = state.into_parts();
+        }
+        if self.options.verbose > 0 {
+            eprintln!(
+                "parsed {} item{} from {} file{}",
+                items.len(),
+                plural(items.len()),
+                paths.len(),
+                plural(paths.len())
+            );
+        }
+        Ok(items)
+    }
+
+    fn lex(&mut self, path: &Path) -> Result<(), ExitCode> {
+        let file = self.load(path)?;
+        let files = self.sources.files();
+        for (token, range) in Token::lexer_from_file(files, file).spanned() {
+            let span = Span::new(file, range);
+            // Code points, as in the JSON and SARIF diagnostics
+            let start = span.start_position(files, ColumnMode::Utf32);
+            let slice = span.get_slice(files);
+            match token {
+                Ok(token) => println!(
+                    "{}:{}\t{token:?}\t{slice:?}",
+                    start.line + 1,
+                    start.column + 1
+                ),
+                Err(err) => {
+                    self.ctx.message(ParseError::lex(err, span));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn parse(&mut self, path: &Path) -> Result<(), ExitCode> {
+        for item in self.parse_files(&[path.to_path_buf()])? {
+            println!("{:#?}", self.ast[item]);
+        }
+        Ok(())
+    }
+
+    fn check(&mut self, paths: &[PathBuf], emit: Option<Emit>) -> Result<(), ExitCode> {
+        let items = self.parse_files(paths)?;
+        if self.ctx.error_count() != 0 {
+            return Ok(());
+        }
+        // The syntax tree is complete once parsing succeeds, even if checking it fails
+        let files = self.sources.files();
+        match emit {
+            Some(Emit::AstJson) => println!("{}", tree::to_json(&self.ast, files, &items)),
+            Some(Emit::AstSexpr) => print!("{}", tree::to_sexpr(&self.ast, files, &items)),
+            _ => {}
+        }
+        let resolutions = resolve(&mut self.ctx, &self.ast, &items);
+        if self.ctx.error_count() != 0 {
+            return Ok(());
+        }
+        let results = typeck(&mut self.ctx, &self.ast, &items, &resolutions);
+        if self.ctx.error_count() != 0 {
+            return Ok(());
+        }
+        let hir = hir::lower(&self.ast, &items, &resolutions, &results, files);
+        let mir = mir::build(&hir);
+        flow::check(&mut self.ctx, &hir, &mir);
+        if self.ctx.error_count() != 0 {
+            return Ok(());
+        }
+        match emit {
+            None | Some(Emit::AstJson | Emit::AstSexpr) => {}
+            Some(Emit::Hir) => print!("{hir}"),
+            Some(Emit::Mir) => print!("{mir}"),
+            Some(Emit::CfgDot) => print!("{}", mir::dot::to_dot(&mir)),
+        }
+        Ok(())
+    }
+
+    /// Applies the machine-applicable suggestions, then checks the fixed files so what's left
+    /// is reported like any other diagnostics
+    fn fix(&mut self, paths: &[PathBuf]) -> Result<(), ExitCode> {
+        let mut done = Vec::new();
+        for path in paths {
+            let file = self.load(path)?;
+            if done.contains(&file) {
+                continue;
+            }
+            done.push(file);
+            let files = self.sources.files();
+            let suggestions = fix::suggestions(files, file);
+            if suggestions.is_empty() {
+                continue;
+            }
+            let fixed = fix::apply(&files[file].contents, &suggestions);
+            for conflict in &fixed.conflicts {
+                eprintln!(
+                    "warning: {}: not applying `{}` at byte {}, it overlaps `{}` at byte {}",
+                    path.display(),
+                    conflict.skipped.replacement,
+                    conflict.skipped.span.start(),
+                    conflict.applied.replacement,
+                    conflict.applied.span.start(),
+                );
+            }
+            if let Err(err) = std::fs::write(path, files[file].encode(&fixed.source)) {
+                eprintln!("error: couldn't write `{}`: {err}", path.display());
+                return Err(ExitCode::from(ERRORS));
+            }
+            if !self.options.quiet {
+                eprintln!(
+                    "fixed {} issue{} in {}",
+                    fixed.applied,
+                    plural(fixed.applied),
+                    path.display()
+                );
+            }
+        }
+        // The spans of everything loaded so far point into the files as they were
+        self.sources = SourceMap::new();
+        self.check(paths, None)
+    }
+
+    fn fmt(&mut self, paths: &[PathBuf], check: bool) -> Result<(), ExitCode> {
+        let stdin = [PathBuf::from("-")];
+        let paths = if paths.is_empty() { &stdin[..] } else { paths };
+        let items = self.parse_files(paths)?;
+        if self.ctx.error_count() != 0 {
+            return Ok(());
+        }
+        let mut unformatted = 0;
+        let mut done = Vec::new();
+        for path in paths {
+            // Already loaded, so this only looks the file up
+            let file = self.load(path)?;
+            if done.contains(&file) {
+                continue;
+            }
+            done.push(file);
+            let is_stdin = path == Path::new("-");
+            // Made absolute so the search goes on above the working directory, the parent
+            // of `m.coral` is the empty path
+            let cwd = std::env::current_dir().unwrap_or_default();
+            let dir = match path.parent() {
+                Some(parent) if !is_stdin => cwd.join(parent),
+                _ => cwd,
+            };
+            let config = Config::find(&dir).map_err(|err| {
+                eprintln!("error: {err}");
+                ExitCode::from(USAGE)
+            })?;
+            let file_items: Vec<_> = items
+                .iter()
+                .copied()
+                .filter(|&item| self.ast[item].span.file() == file)
+                .collect();
+            let files = self.sources.files();
+            let original = &files[file].contents;
+            let formatted = fmt::format(&self.ast, &file_items, files, file, &config);
+            if check {
+                if formatted != *original {
+                    print!("{}", fmt::diff(&files[file].name, original, &formatted));
+                    unformatted += 1;
+                }
+            } else if is_stdin {
+                print!("{formatted}");
+            } else if formatted != *original {
+                if let Err(err) = std::fs::write(path, files[file].encode(&formatted)) {
+                    eprintln!("error: couldn't write `{}`: {err}", path.display());
+                    return Err(ExitCode::from(ERRORS));
+                }
+                if self.options.verbose > 0 {
+                    eprintln!("formatted {}", path.display());
+                }
+            }
+        }
+        if unformatted != 0 {
+            if !self.options.quiet {
+                eprintln!(
+                    "{unformatted} file{} would be reformatted",
+                    plural(unformatted)
+                );
+            }
+            return Err(ExitCode::from(ERRORS));
+        }
+        Ok(())
+    }
+
+    /// Checks the files, then reports that the rest of the command doesn't exist yet
+    fn unsupported(&mut self, paths: &[PathBuf], what: &str) -> Result<(), ExitCode> {
+        self.check(paths, None)?;
+        if self.ctx.error_count() == 0 {
+            eprintln!("error: {what} isn't implemented yet");
+            return Err(ExitCode::from(ERRORS));
+        }
+        Ok(())
+    }
+
+    /// Prints every collected diagnostic, returning the exit code for them
+    fn finish(self) -> ExitCode {
+        let Self {
+            options,
+            sources,
+            mut ctx,
+            ..
+        } = self;
+        let files = sources.files();
+        ctx.sort();
+        ctx.dedup();
+        let summary = ctx.finish();
+        let (Ok(summary) | Err(summary)) = &summary;
+        match options.error_format {
+            ErrorFormat::Human => {
+                // https://no-color.org: any non-empty value turns colors off
+                let color = match options.color {
+                    Color::Auto => {
+                        std::io::stderr().is_terminal()
+                            && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                    }
+                    Color::Always => true,
+                    Color::Never => false,
+                };
+                let theme = if color {
+                    GraphicalTheme::unicode()
+                } else {
+                    GraphicalTheme::unicode_nocolor()
+                };
+                let handler = GraphicalReportHandler::new_themed(theme);
+                for diagnostic in &summary.diagnostics {
+                    eprint!("{}", render_with(&handler, &diagnostic.error, files));
+                    if options.verbose > 0 && !diagnostic.context.is_empty() {
+                        let context: Vec<_> =
+                            diagnostic.context.iter().map(ToString::to_string).collect();
+                        eprintln!("  while parsing {}\n", context.join(" > "));
+                    }
+                }
+                if !options.quiet && summary.errors + summary.warnings != 0 {
+                    eprintln!("{summary}");
+                }
+            }
+            ErrorFormat::Json => {
+                for diagnostic in &summary.diagnostics {
+                    println!("{}", json_line(diagnostic, files));
+                }
+            }
+            ErrorFormat::Sarif => println!("{:#}", sarif(&summary.diagnostics, files)),
+        }
+        if summary.errors == 0 {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::from(ERRORS)
+        }
+    }
+}
+
+const fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::CommandFactory;
+
+    use super::Cli;
+
+    #[test]
+    fn cli_is_consistent() {
+        Cli::command().debug_assert();
+    }
+}
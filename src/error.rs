@@ -1,6 +1,11 @@
+use miette::{Diagnostic, LabeledSpan};
 use thiserror::Error;
 
-use crate::parser::ParseError;
+use crate::{
+    flow::FlowError, parser::ParseError, resolve::ResolveError, span::Span, typeck::TypeError,
+};
+
+use self::suggestion::Suggestion;
 
 pub trait ContextName {}
 
@@ -19,11 +24,71 @@ pub trait Context<C: ContextName> {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[derive(Debug, Clone, PartialEq, Eq, Error, Diagnostic)]
 pub enum CoralError {
     #[error(transparent)]
+    #[diagnostic(transparent)]
     ParserError(#[from] ParseError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    ResolveError(#[from] ResolveError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    TypeError(#[from] TypeError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    FlowError(#[from] FlowError),
+}
+
+impl CoralError {
+    /// The span the primary label points at, which decides which file gets attached
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::ParserError(err) => err.span(),
+            Self::ResolveError(err) => err.span(),
+            Self::TypeError(err) => err.span(),
+            Self::FlowError(err) => err.span(),
+        }
+    }
+
+    /// The labels with the spans they point at, primary first. Unlike the labels miette gets,
+    /// these can be in another file than the primary one.
+    pub fn labelled_spans(&self) -> Vec<(Span, String)> {
+        match self {
+            Self::ParserError(err) => err.labelled_spans(),
+            Self::ResolveError(err) => err.labelled_spans(),
+            Self::TypeError(err) => err.labelled_spans(),
+            Self::FlowError(err) => err.labelled_spans(),
+        }
+    }
+
+    pub fn suggestions(&self) -> Vec<Suggestion> {
+        match self {
+            Self::ParserError(err) => err.suggestions(),
+            Self::ResolveError(err) => err.suggestions(),
+            Self::TypeError(_) | Self::FlowError(_) => Vec::new(),
+        }
+    }
+}
+
+/// The labels of `spans` that are in the file of the first, primary one, which are all miette
+/// can show in one snippet. [`WithSource`] shows the others as notes of their own.
+pub(crate) fn snippet_labels(spans: Vec<(Span, String)>) -> Box<dyn Iterator<Item = LabeledSpan>> {
+    let file = spans.first().map(|(span, _)| span.file());
+    Box::new(
+        spans
+            .into_iter()
+            .filter(move |(span, _)| Some(span.file()) == file)
+            .map(|(span, label)| LabeledSpan::new_with_span(Some(label), span)),
+    )
 }
 
+pub mod codes;
+mod collecting_context;
+mod diagnostic;
+pub mod output;
 mod printing_context;
-pub use printing_context::PrintingContext;
+pub mod suggestion;
+pub use collecting_context::{Collected, CollectedError, CollectingContext, Severity, Summary};
+pub use diagnostic::{render, render_with, WithSource};
+pub use printing_context::{PCError, PrintingContext};
@@ -0,0 +1,95 @@
+//! Stable error codes and their long-form explanations, as printed by `--explain`
+
+macro_rules! register_codes {
+    ($($code:ident),* $(,)?) => {
+        /// Every code handed out so far, with its explanation.
+        /// Codes are never reused, even once the error they belong to is gone.
+        pub const REGISTRY: &[(&str, &str)] = &[
+            $((stringify!($code), include_str!(concat!("explanations/", stringify!($code), ".md")))),*
+        ];
+    };
+}
+
+register_codes! {
+    E0001,
+    E0002,
+    E0003,
+    E0004,
+    E0005,
+    E0006,
+    E0007,
+    E0008,
+    E0009,
+    E0010,
+    E0011,
+    E0012,
+    E0013,
+    E0014,
+    E0015,
+    E0016,
+    E0017,
+    E0018,
+    E0019,
+    E0020,
+    E0021,
+    E0022,
+    E0023,
+    E0024,
+    E0025,
+    E0026,
+    E0027,
+    E0028,
+    E0029,
+    E0030,
+    E0031,
+    E0032,
+    E0033,
+    E0034,
+    E0035,
+    E0036,
+}
+
+/// Looks up the explanation of `code`, accepting `E0001`, `e0001` and `0001`
+pub fn explain(code: &str) -> Option<&'static str> {
+    let code = code.trim().to_ascii_uppercase();
+    let code = if code.starts_with('E') {
+        code
+    } else {
+        format!("E{code}")
+    };
+    REGISTRY
+        .iter()
+        .find(|(registered, _)| *registered == code)
+        .map(|(_, explanation)| *explanation)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use super::{explain, REGISTRY};
+
+    #[test]
+    fn registry_is_well_formed() {
+        let mut seen = HashSet::new();
+        for (code, explanation) in REGISTRY {
+            assert!(seen.insert(code), "{code} is registered twice");
+            assert!(
+                explanation.starts_with(&format!("# {code}: ")),
+                "the explanation of {code} should start with its title"
+            );
+            assert!(
+                explanation.contains("```coral"),
+                "the explanation of {code} should have an example"
+            );
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        assert_eq!(explain("E0002"), explain("e0002"));
+        assert_eq!(explain("0002"), explain("E0002"));
+        assert!(explain("E0002").unwrap().contains("unexpected token"));
+        assert_eq!(explain("E9999"), None);
+    }
+}
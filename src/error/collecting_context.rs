@@ -0,0 +1,350 @@
+use crate::span::Span;
+
+use super::{Context, ContextName, CoralError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl Severity {
+    /// What a diagnostic reported through [`Context::message`] counts as
+    pub fn of(error: &CoralError) -> Self {
+        match miette::Diagnostic::severity(error) {
+            None | Some(miette::Severity::Error) => Self::Error,
+            Some(miette::Severity::Warning) => Self::Warning,
+            Some(miette::Severity::Advice) => Self::Note,
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Note => "note",
+            Self::Help => "help",
+        })
+    }
+}
+
+/// A diagnostic along with the contexts that were entered when it was reported,
+/// innermost last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collected<CName> {
+    pub severity: Severity,
+    pub error: CoralError,
+    pub context: Vec<CName>,
+}
+
+impl<CName> Collected<CName> {
+    pub const fn span(&self) -> Span {
+        self.error.span()
+    }
+}
+
+/// Returned to the parser in place of the diagnostic, which stays in the context
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollectedError {
+    _p: (),
+}
+
+/// Stores every diagnostic instead of printing it,
+/// so the driver decides how and when to render them.
+#[derive(Debug)]
+pub struct CollectingContext<CName> {
+    stack: Vec<CName>,
+    diagnostics: Vec<Collected<CName>>,
+    max_errors: Option<usize>,
+    errors: usize,
+    warnings: usize,
+    suppressed: usize,
+}
+
+impl<CName> Default for CollectingContext<CName> {
+    fn default() -> Self {
+        Self {
+            stack: Vec::new(),
+            diagnostics: Vec::new(),
+            max_errors: None,
+            errors: 0,
+            warnings: 0,
+            suppressed: 0,
+        }
+    }
+}
+
+impl<CName: Clone> CollectingContext<CName> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Errors past the `max`th are counted but not stored, as with `--max-errors`
+    #[must_use]
+    pub fn with_max_errors(mut self, max: usize) -> Self {
+        self.max_errors = Some(max);
+        self
+    }
+
+    pub fn emit(&mut self, severity: Severity, error: CoralError) {
+        match severity {
+            Severity::Error => {
+                let dropped = self.limit_reached();
+                self.errors += 1;
+                if dropped {
+                    self.suppressed += 1;
+                    return;
+                }
+            }
+            Severity::Warning => self.warnings += 1,
+            Severity::Note | Severity::Help => {}
+        }
+        self.diagnostics.push(Collected {
+            severity,
+            error,
+            context: self.stack.clone(),
+        });
+    }
+
+    /// Whether further errors will be dropped, callers can use it to bail out early
+    pub fn limit_reached(&self) -> bool {
+        self.max_errors.is_some_and(|max| self.errors >= max)
+    }
+
+    pub fn diagnostics(&self) -> &[Collected<CName>] {
+        &self.diagnostics
+    }
+
+    pub const fn error_count(&self) -> usize {
+        self.errors
+    }
+
+    pub const fn warning_count(&self) -> usize {
+        self.warnings
+    }
+
+    /// Orders diagnostics by file, then position. Ties keep the order they were reported in.
+    pub fn sort(&mut self) {
+        self.diagnostics.sort_by_key(|diagnostic| {
+            let span = diagnostic.span();
+            (span.file().index(), span.start(), span.end())
+        });
+    }
+
+    /// Drops diagnostics that repeat an earlier one: the same code and message, on the same
+    /// span with the same severity. The counts go down with them, so the summary matches what's
+    /// shown. Only adjacent entries are compared, so this is meant to run after [`Self::sort`].
+    pub fn dedup(&mut self) {
+        let (mut errors, mut warnings) = (0, 0);
+        self.diagnostics.dedup_by(|b, a| {
+            let duplicate = a.severity == b.severity && a.span() == b.span() && {
+                let code = |error: &CoralError| {
+                    miette::Diagnostic::code(error).map(|code| code.to_string())
+                };
+                code(&a.error) == code(&b.error) && a.error.to_string() == b.error.to_string()
+            };
+            if duplicate {
+                match b.severity {
+                    Severity::Error => errors += 1,
+                    Severity::Warning => warnings += 1,
+                    Severity::Note | Severity::Help => {}
+                }
+            }
+            duplicate
+        });
+        self.errors -= errors;
+        self.warnings -= warnings;
+    }
+
+    /// Fails if any error was reported, either way handing back everything collected
+    pub fn finish(self) -> Result<Summary<CName>, Summary<CName>> {
+        let summary = Summary {
+            diagnostics: self.diagnostics,
+            errors: self.errors,
+            warnings: self.warnings,
+            suppressed: self.suppressed,
+        };
+        if summary.errors == 0 {
+            Ok(summary)
+        } else {
+            Err(summary)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Summary<CName> {
+    pub diagnostics: Vec<Collected<CName>>,
+    pub errors: usize,
+    pub warnings: usize,
+    /// Errors that were counted but not stored because of the limit
+    pub suppressed: usize,
+}
+
+impl<CName> std::fmt::Display for Summary<CName> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        write!(
+            f,
+            "{} error{}, {} warning{}",
+            self.errors,
+            plural(self.errors),
+            self.warnings,
+            plural(self.warnings)
+        )?;
+        if self.suppressed != 0 {
+            write!(f, " ({} not shown)", self.suppressed)?;
+        }
+        Ok(())
+    }
+}
+
+impl<CName: ContextName + Clone> Context<CName> for CollectingContext<CName> {
+    type Error = CollectedError;
+
+    fn enter_context(&mut self, name: CName) {
+        self.stack.push(name);
+    }
+
+    fn exit_ctx(&mut self) {
+        self.stack.pop();
+    }
+
+    fn message<T: Into<CoralError>>(&mut self, msg: T) -> Self::Error {
+        let error = msg.into();
+        self.emit(Severity::of(&error), error);
+        CollectedError { _p: () }
+    }
+
+    fn report<T, E: Into<CoralError>>(&mut self, res: Result<T, E>) -> Result<T, Self::Error> {
+        res.map_err(|err| self.message(err))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use id_arena::Arena;
+
+    use crate::{
+        error::{Context, CoralError},
+        fs::{File, FileId},
+        lexer::{tokens::Tokens, Token},
+        parser::{parse_item, ContextName, ParseError},
+        span::{Span, Spanned},
+        transaction::ParserState,
+    };
+
+    use super::{CollectingContext, Severity};
+
+    fn eoi(file: FileId, at: usize) -> CoralError {
+        ParseError::UnexpectedEOI(Span::new(file, at..at)).into()
+    }
+
+    fn files() -> (Arena<File>, FileId, FileId) {
+        let mut arena = Arena::new();
+        let a = arena.alloc(File::new("a", "fn main(): void { let = 1; }"));
+        let b = arena.alloc(File::new("b", ""));
+        (arena, a, b)
+    }
+
+    #[test]
+    fn records_context_stack() {
+        let (arena, file, _) = files();
+        let mut state = ParserState::new(
+            CollectingContext::new(),
+            Tokens::from(Token::lexer_from_file(&arena, file)),
+        );
+        // The block skips the broken statement, so only the context knows something went wrong
+        assert!(parse_item(&mut state).is_ok());
+        let (ctx, _, _) = state.into_parts();
+
+        let [diagnostic] = ctx.diagnostics() else {
+            panic!("expected one diagnostic, got {:?}", ctx.diagnostics());
+        };
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(
+            diagnostic.error,
+            CoralError::from(ParseError::UnexpectedToken(Spanned {
+                span: Span::new(file, 22..23),
+                data: Token::Assignment,
+            }))
+        );
+        assert_eq!(
+            diagnostic.context,
+            [
+                ContextName::Item,
+                ContextName::Fn,
+                ContextName::FnName("main".into()),
+                ContextName::Block,
+                ContextName::Statement,
+                ContextName::Let,
+                ContextName::Pattern,
+            ]
+        );
+        assert_eq!(ctx.error_count(), 1);
+    }
+
+    #[test]
+    fn sort_and_dedup() {
+        let (_, a, b) = files();
+        let mut ctx = CollectingContext::<ContextName>::new();
+        ctx.message(eoi(b, 0));
+        ctx.message(eoi(a, 5));
+        ctx.emit(Severity::Warning, eoi(a, 2));
+        ctx.message(eoi(a, 5));
+        ctx.emit(Severity::Note, eoi(a, 5));
+        // A different error on the same span is kept
+        ctx.message(ParseError::InvalidToken(Span::new(a, 5..5)));
+        ctx.sort();
+        ctx.dedup();
+
+        let order: Vec<_> = ctx
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| (diagnostic.span(), diagnostic.severity))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (Span::new(a, 2..2), Severity::Warning),
+                (Span::new(a, 5..5), Severity::Error),
+                (Span::new(a, 5..5), Severity::Note),
+                (Span::new(a, 5..5), Severity::Error),
+                (Span::new(b, 0..0), Severity::Error),
+            ]
+        );
+        // Duplicates aren't counted, so the summary matches what's shown
+        let summary = ctx.finish().unwrap_err();
+        assert_eq!((summary.errors, summary.warnings), (3, 1));
+        assert_eq!(summary.to_string(), "3 errors, 1 warning");
+    }
+
+    #[test]
+    fn max_errors() {
+        let (_, a, _) = files();
+        let mut ctx = CollectingContext::<ContextName>::new().with_max_errors(2);
+        for at in 0..4 {
+            ctx.message(eoi(a, at));
+        }
+        ctx.emit(Severity::Warning, eoi(a, 9));
+        assert!(ctx.limit_reached());
+        assert_eq!(ctx.diagnostics().len(), 3);
+
+        let summary = ctx.finish().unwrap_err();
+        assert_eq!((summary.errors, summary.suppressed), (4, 2));
+        assert_eq!(summary.to_string(), "4 errors, 1 warning (2 not shown)");
+    }
+
+    #[test]
+    fn warnings_only_succeed() {
+        let (_, a, _) = files();
+        let mut ctx = CollectingContext::<ContextName>::new();
+        ctx.emit(Severity::Warning, eoi(a, 0));
+        let summary = ctx.finish().unwrap();
+        assert_eq!(summary.to_string(), "0 errors, 1 warning");
+    }
+}
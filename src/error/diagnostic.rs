@@ -0,0 +1,236 @@
+use miette::{Diagnostic, GraphicalReportHandler, LabeledSpan, Severity, SourceCode};
+
+use crate::{
+    fs::{ColumnMode, File},
+    span::Span,
+    FileArena,
+};
+
+use super::CoralError;
+
+/// A [`CoralError`] together with the file its spans point into,
+/// which is all miette needs to render a snippet.
+/// Labels in other files are shown as notes after it.
+#[derive(Debug)]
+pub struct WithSource<'a> {
+    error: &'a CoralError,
+    file: &'a File,
+    notes: Vec<Note<'a>>,
+}
+
+impl<'a> WithSource<'a> {
+    pub fn new(error: &'a CoralError, files: &'a FileArena) -> Self {
+        let primary = error.span().file();
+        let notes = error
+            .labelled_spans()
+            .into_iter()
+            .filter(|(span, _)| span.file() != primary)
+            .map(|(span, label)| Note {
+                label,
+                span,
+                file: &files[span.file()],
+            })
+            .collect();
+        Self {
+            error,
+            file: &files[primary],
+            notes,
+        }
+    }
+}
+
+/// A label of a [`WithSource`] in another file, with a snippet of that file
+#[derive(Debug)]
+struct Note<'a> {
+    label: String,
+    span: Span,
+    file: &'a File,
+}
+
+impl std::fmt::Display for Note<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.label)
+    }
+}
+
+impl std::error::Error for Note<'_> {}
+
+impl Diagnostic for Note<'_> {
+    fn severity(&self) -> Option<Severity> {
+        Some(Severity::Advice)
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        Some(self.file)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        Some(Box::new(std::iter::once(LabeledSpan::new_with_span(
+            None, self.span,
+        ))))
+    }
+}
+
+/// Renders `error` with a snippet of the file it points into,
+/// falling back to the bare message if the snippet can't be read.
+pub fn render(error: &CoralError, files: &FileArena) -> String {
+    render_with(&GraphicalReportHandler::new(), error, files)
+}
+
+/// [`render`] with a handler set up by the caller, for instance without colors
+pub fn render_with(
+    handler: &GraphicalReportHandler,
+    error: &CoralError,
+    files: &FileArena,
+) -> String {
+    let with_source = WithSource::new(error, files);
+    let mut rendered = String::new();
+    if handler.render_report(&mut rendered, &with_source).is_err() {
+        return format!("error: {error}\n");
+    }
+    // The snippets come in the same order as their spans
+    let spans = std::iter::once(error.span()).chain(with_source.notes.iter().map(|note| note.span));
+    let mut from = 0;
+    for span in spans {
+        from = point_header_at(&mut rendered, from, span, files);
+    }
+    rendered
+}
+
+/// miette heads a snippet with the line and column its context starts at, a line above
+/// the label, so the first header after `from` is rewritten to where `span` starts.
+/// Returns where to look for the next header.
+fn point_header_at(rendered: &mut String, from: usize, span: Span, files: &FileArena) -> usize {
+    let file = &files[span.file()];
+    let Some(name) = rendered[from..].find(&*file.name) else {
+        return from;
+    };
+    let mut at = from + name + file.name.len();
+    // The name may be followed by the escape sequence that ends its color
+    while rendered[at..].starts_with("\x1b[") {
+        match rendered[at..].find('m') {
+            Some(end) => at += end + 1,
+            None => return at,
+        }
+    }
+    let Some(end) = rendered[at..].find(']') else {
+        return at;
+    };
+    // Code points, as in the JSON and SARIF diagnostics
+    let position = file.offset_to_line_col(span.start(), ColumnMode::Utf32);
+    let location = format!(":{}:{}", position.line + 1, position.column + 1);
+    rendered.replace_range(at..at + end, &location);
+    at + location.len()
+}
+
+impl std::fmt::Display for WithSource<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl std::error::Error for WithSource<'_> {}
+
+impl Diagnostic for WithSource<'_> {
+    fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        self.error.code()
+    }
+
+    fn severity(&self) -> Option<Severity> {
+        self.error.severity()
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        self.error.help()
+    }
+
+    fn url<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        self.error.url()
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        Some(self.file)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        self.error.labels()
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        if self.notes.is_empty() {
+            return None;
+        }
+        Some(Box::new(
+            self.notes.iter().map(|note| note as &dyn Diagnostic),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use id_arena::Arena;
+    use miette::{Diagnostic, GraphicalReportHandler, GraphicalTheme};
+
+    use crate::{
+        error::CoralError,
+        fs::File,
+        lexer::Token,
+        parser::ParseError,
+        resolve::{DeclarationKind, ResolveError},
+        span::{Span, Spanned},
+    };
+
+    use super::render_with;
+
+    #[test]
+    fn renders_snippet_with_location() {
+        let mut arena = Arena::new();
+        let file = arena.alloc(File::new(
+            "main.coral",
+            "fn main(): void {\n    let x = ;\n}",
+        ));
+        let error: CoralError = ParseError::UnexpectedToken(Spanned {
+            span: Span::new(file, 30..31),
+            data: Token::Semicolon,
+        })
+        .into();
+
+        assert_eq!(
+            error.code().map(|code| code.to_string()).as_deref(),
+            Some("E0002")
+        );
+        let labels: Vec<_> = error.labels().unwrap().collect();
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels[0].label(), Some("unexpected `;`"));
+        assert_eq!((labels[0].offset(), labels[0].len()), (30, 1));
+
+        let handler = GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor());
+        let rendered = render_with(&handler, &error, &arena);
+        // Where the error is, rather than where the snippet starts
+        assert!(rendered.contains("[main.coral:2:13]"), "{rendered}");
+        assert!(rendered.contains(" 2 │     let x = ;"), "{rendered}");
+        assert!(rendered.contains("╰── unexpected `;`"), "{rendered}");
+    }
+
+    #[test]
+    fn labels_in_other_files_are_notes() {
+        let mut arena = Arena::new();
+        let a = arena.alloc(File::new("a.coral", "fn main(): void {}"));
+        let b = arena.alloc(File::new("b.coral", "\nfn main(): void {}"));
+        let error: CoralError = ResolveError::Duplicate {
+            name: "main".into(),
+            kind: DeclarationKind::Param,
+            first: Span::new(a, 3..7),
+            second: Span::new(b, 4..8),
+        }
+        .into();
+        let handler = GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor());
+        let rendered = render_with(&handler, &error, &arena);
+        assert!(rendered.contains("[b.coral:2:4]"), "{rendered}");
+        assert!(
+            rendered.contains("Advice:   ☞ first definition of `main`"),
+            "{rendered}"
+        );
+        assert!(rendered.contains("[a.coral:1:4]"), "{rendered}");
+    }
+}
@@ -0,0 +1,363 @@
+//! Diagnostics as data, for editors and CI rather than people
+
+use miette::Diagnostic;
+use serde::Serialize;
+
+use crate::{
+    fs::{ColumnMode, LineCol},
+    span::Span,
+    FileArena,
+};
+
+use super::{codes, suggestion::Applicability, Collected, CoralError, Severity};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    /// Rendered snippets on stderr
+    #[default]
+    Human,
+    /// One JSON object per diagnostic per line
+    Json,
+    /// A single SARIF 2.1.0 log once everything is collected
+    Sarif,
+}
+
+impl std::str::FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            "sarif" => Ok(Self::Sarif),
+            _ => Err(format!(
+                "unknown error format `{s}`, expected `human`, `json` or `sarif`"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonDiagnostic {
+    pub code: Option<String>,
+    pub severity: Severity,
+    pub message: String,
+    pub help: Option<String>,
+    /// The first label is the primary span, the rest are secondary
+    pub spans: Vec<JsonSpan>,
+    /// Outermost first
+    pub context: Vec<String>,
+    pub fixes: Vec<JsonFix>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonSpan {
+    pub file: String,
+    pub byte_start: usize,
+    pub byte_end: usize,
+    pub line_start: usize,
+    pub column_start: usize,
+    pub line_end: usize,
+    pub column_end: usize,
+    pub primary: bool,
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonFix {
+    pub span: JsonSpan,
+    pub replacement: String,
+    pub applicability: Applicability,
+}
+
+impl Serialize for Applicability {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Serialize for Severity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// 1-based line and column in code points, as both formats expect
+fn position(position: LineCol) -> LineCol {
+    LineCol {
+        line: position.line + 1,
+        column: position.column + 1,
+    }
+}
+
+impl JsonSpan {
+    fn new(span: Span, files: &FileArena, primary: bool, label: Option<String>) -> Self {
+        let start = position(span.start_position(files, ColumnMode::Utf32));
+        let end = position(span.end_position(files, ColumnMode::Utf32));
+        Self {
+            file: files[span.file()].name.to_string(),
+            byte_start: span.start(),
+            byte_end: span.end(),
+            line_start: start.line,
+            column_start: start.column,
+            line_end: end.line,
+            column_end: end.column,
+            primary,
+            label,
+        }
+    }
+}
+
+/// Labels of `error` along with the spans they point into, which can be in any file,
+/// primary first
+fn labelled_spans(error: &CoralError) -> Vec<(Span, Option<String>)> {
+    error
+        .labelled_spans()
+        .into_iter()
+        .map(|(span, label)| (span, Some(label)))
+        .collect()
+}
+
+impl JsonDiagnostic {
+    pub fn new<CName: std::fmt::Display>(diagnostic: &Collected<CName>, files: &FileArena) -> Self {
+        let error = &diagnostic.error;
+        Self {
+            code: error.code().map(|code| code.to_string()),
+            severity: diagnostic.severity,
+            message: error.to_string(),
+            help: error.help().map(|help| help.to_string()),
+            spans: labelled_spans(error)
+                .into_iter()
+                .enumerate()
+                .map(|(i, (span, label))| JsonSpan::new(span, files, i == 0, label))
+                .collect(),
+            context: diagnostic.context.iter().map(ToString::to_string).collect(),
+            fixes: error
+                .suggestions()
+                .into_iter()
+                .map(|suggestion| JsonFix {
+                    span: JsonSpan::new(suggestion.span, files, false, None),
+                    replacement: suggestion.replacement,
+                    applicability: suggestion.applicability,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// One line of JSON, without the trailing newline
+pub fn json_line<CName: std::fmt::Display>(
+    diagnostic: &Collected<CName>,
+    files: &FileArena,
+) -> String {
+    serde_json::to_string(&JsonDiagnostic::new(diagnostic, files))
+        .expect("diagnostics always serialize")
+}
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note | Severity::Help => "note",
+    }
+}
+
+fn sarif_region(span: Span, files: &FileArena) -> serde_json::Value {
+    let start = position(span.start_position(files, ColumnMode::Utf32));
+    let end = position(span.end_position(files, ColumnMode::Utf32));
+    serde_json::json!({
+        "startLine": start.line,
+        "startColumn": start.column,
+        "endLine": end.line,
+        "endColumn": end.column,
+        "byteOffset": span.start(),
+        "byteLength": span.end() - span.start(),
+    })
+}
+
+fn sarif_location(span: Span, files: &FileArena, label: Option<String>) -> serde_json::Value {
+    let mut location = serde_json::json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": files[span.file()].name },
+            "region": sarif_region(span, files),
+        }
+    });
+    if let Some(label) = label {
+        location["message"] = serde_json::json!({ "text": label });
+    }
+    location
+}
+
+/// A SARIF 2.1.0 log with a single run holding every diagnostic
+pub fn sarif<CName>(diagnostics: &[Collected<CName>], files: &FileArena) -> serde_json::Value {
+    let mut rules: Vec<String> = Vec::new();
+    let results: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let error = &diagnostic.error;
+            let rule_id = error
+                .code()
+                .map_or_else(|| "coral".to_string(), |code| code.to_string());
+            if !rules.contains(&rule_id) {
+                rules.push(rule_id.clone());
+            }
+            let mut spans = labelled_spans(error).into_iter();
+            let (primary, _) = spans.next().expect("there is always a primary span");
+            let mut text = error.to_string();
+            if let Some(help) = error.help() {
+                text = format!("{text}\n{help}");
+            }
+            serde_json::json!({
+                "ruleId": rule_id,
+                "level": sarif_level(diagnostic.severity),
+                "message": { "text": text },
+                "locations": [sarif_location(primary, files, None)],
+                "relatedLocations": spans
+                    .map(|(span, label)| sarif_location(span, files, label))
+                    .collect::<Vec<_>>(),
+                "fixes": error
+                    .suggestions()
+                    .iter()
+                    .map(|suggestion| serde_json::json!({
+                        "description": { "text": suggestion.applicability.to_string() },
+                        "artifactChanges": [{
+                            "artifactLocation": { "uri": files[suggestion.span.file()].name },
+                            "replacements": [{
+                                "deletedRegion": sarif_region(suggestion.span, files),
+                                "insertedContent": { "text": suggestion.replacement },
+                            }],
+                        }],
+                    }))
+                    .collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules
+                        .iter()
+                        .map(|id| match codes::explain(id) {
+                            Some(explanation) => serde_json::json!({
+                                "id": id,
+                                "help": { "text": explanation, "markdown": explanation },
+                            }),
+                            None => serde_json::json!({ "id": id }),
+                        })
+                        .collect::<Vec<_>>(),
+                }
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }]
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use id_arena::Arena;
+
+    use crate::{
+        error::{Collected, Severity},
+        fs::File,
+        lexer::Token,
+        parser::{ContextName, ParseError},
+        resolve::{DeclarationKind, ResolveError},
+        span::{Span, Spanned},
+    };
+
+    use super::{json_line, sarif};
+
+    fn collected() -> (Arena<File>, Collected<ContextName>) {
+        let mut arena = Arena::new();
+        let file = arena.alloc(File::new("main.coral", "fn main(): void {\n    ä = ;\n}"));
+        let error = ParseError::UnexpectedToken(Spanned {
+            span: Span::new(file, 27..28),
+            data: Token::Semicolon,
+        });
+        let diagnostic = Collected {
+            severity: Severity::Error,
+            error: error.into(),
+            context: vec![ContextName::Item, ContextName::Block],
+        };
+        (arena, diagnostic)
+    }
+
+    #[test]
+    fn json_lines() {
+        let (arena, diagnostic) = collected();
+        assert_eq!(
+            json_line(&diagnostic, &arena),
+            concat!(
+                r#"{"code":"E0002","severity":"error","message":"unexpected `;`","#,
+                r#""help":null,"spans":[{"file":"main.coral","byte_start":27,"byte_end":28,"#,
+                r#""line_start":2,"column_start":9,"line_end":2,"column_end":10,"primary":true,"#,
+                r#""label":"unexpected `;`"}],"context":["item","block"],"fixes":[]}"#
+            )
+        );
+    }
+
+    #[test]
+    fn secondary_spans_keep_their_file() {
+        let mut arena = Arena::new();
+        let a = arena.alloc(File::new("a.coral", "fn main(): void {}"));
+        let b = arena.alloc(File::new("b.coral", "\nfn main(): void {}"));
+        let diagnostic: Collected<ContextName> = Collected {
+            severity: Severity::Error,
+            error: ResolveError::Duplicate {
+                name: "main".into(),
+                kind: DeclarationKind::Param,
+                first: Span::new(a, 3..7),
+                second: Span::new(b, 4..8),
+            }
+            .into(),
+            context: Vec::new(),
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&json_line(&diagnostic, &arena)).unwrap();
+        let spans = &json["spans"];
+        assert_eq!(
+            (&spans[0]["file"], &spans[0]["line_start"]),
+            (&"b.coral".into(), &2.into())
+        );
+        assert_eq!(
+            (&spans[1]["file"], &spans[1]["line_start"]),
+            (&"a.coral".into(), &1.into())
+        );
+        let log = sarif(&[diagnostic], &arena);
+        let related = &log["runs"][0]["results"][0]["relatedLocations"][0];
+        assert_eq!(
+            related["physicalLocation"]["artifactLocation"]["uri"],
+            "a.coral"
+        );
+    }
+
+    #[test]
+    fn sarif_log() {
+        let (arena, diagnostic) = collected();
+        let log = sarif(&[diagnostic], &arena);
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "E0002");
+        assert!(run["tool"]["driver"]["rules"][0]["help"]["markdown"]
+            .as_str()
+            .unwrap()
+            .starts_with("# E0002"));
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "E0002");
+        assert_eq!(result["level"], "error");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "main.coral");
+        assert_eq!(location["region"]["startLine"], 2);
+        assert_eq!(location["region"]["startColumn"], 9);
+        assert_eq!(location["region"]["byteOffset"], 27);
+        assert_eq!(location["region"]["byteLength"], 1);
+    }
+}
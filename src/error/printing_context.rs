@@ -1,14 +1,29 @@
-use super::{Context, ContextName};
+use crate::FileArena;
 
+use super::{diagnostic::render, Context, ContextName, CoralError};
+
+/// Renders every error to stderr as soon as it's reported
 #[derive(Debug)]
-pub struct PrintingContext<CName> {
+pub struct PrintingContext<'a, CName> {
+    files: &'a FileArena,
     stack: Vec<CName>,
 }
 
-impl<CName> Default for PrintingContext<CName> {
-    fn default() -> Self {
+impl<'a, CName> PrintingContext<'a, CName> {
+    pub const fn new(files: &'a FileArena) -> Self {
         Self {
-            stack: Default::default(),
+            files,
+            stack: Vec::new(),
+        }
+    }
+
+    fn print(&self, error: &CoralError)
+    where
+        CName: std::fmt::Display,
+    {
+        eprint!("{}", render(error, self.files));
+        if !self.stack.is_empty() {
+            eprintln!("  while parsing {}", PCStackPrinter(&self.stack));
         }
     }
 }
@@ -29,7 +44,7 @@ pub struct PCError {
     _p: (),
 }
 
-impl<CName: std::fmt::Display + ContextName> Context<CName> for PrintingContext<CName> {
+impl<CName: std::fmt::Display + ContextName> Context<CName> for PrintingContext<'_, CName> {
     type Error = PCError;
 
     fn enter_context(&mut self, name: CName) {
@@ -40,17 +55,14 @@ impl<CName: std::fmt::Display + ContextName> Context<CName> for PrintingContext<
         self.stack.pop();
     }
 
-    fn message<T: Into<super::CoralError>>(&mut self, msg: T) -> Self::Error {
-        eprintln!("{}|[MSG]| {:?}", PCStackPrinter(&self.stack), msg.into());
+    fn message<T: Into<CoralError>>(&mut self, msg: T) -> Self::Error {
+        self.print(&msg.into());
         PCError { _p: () }
     }
 
-    fn report<T, E: Into<super::CoralError>>(
-        &mut self,
-        res: Result<T, E>,
-    ) -> Result<T, Self::Error> {
+    fn report<T, E: Into<CoralError>>(&mut self, res: Result<T, E>) -> Result<T, Self::Error> {
         res.map_err(|err| {
-            eprintln!("{}|[REP]| {:?}", PCStackPrinter(&self.stack), err.into());
+            self.print(&err.into());
             PCError { _p: () }
         })
     }
@@ -0,0 +1,50 @@
+use crate::span::Span;
+
+/// How sure we are that applying a [`Suggestion`] gives the code the user meant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Applicability {
+    /// Safe to apply without looking, `coral fix` applies these
+    MachineApplicable,
+    /// Probably right, but should be reviewed
+    MaybeIncorrect,
+    /// The replacement contains placeholders the user has to fill in
+    HasPlaceholders,
+}
+
+impl std::fmt::Display for Applicability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::MachineApplicable => "machine-applicable",
+            Self::MaybeIncorrect => "maybe-incorrect",
+            Self::HasPlaceholders => "has-placeholders",
+        })
+    }
+}
+
+/// Replace the text under `span` with `replacement`.
+/// An empty span is an insertion, an empty replacement a deletion.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Suggestion {
+    pub span: Span,
+    pub replacement: String,
+    pub applicability: Applicability,
+}
+
+impl Suggestion {
+    pub fn new(span: Span, replacement: impl Into<String>, applicability: Applicability) -> Self {
+        Self {
+            span,
+            replacement: replacement.into(),
+            applicability,
+        }
+    }
+
+    /// Inserts `text` right after `span`
+    pub fn insert_after(span: Span, text: impl Into<String>, applicability: Applicability) -> Self {
+        Self::new(
+            Span::new(span.file(), span.end()..span.end()),
+            text,
+            applicability,
+        )
+    }
+}
@@ -0,0 +1,161 @@
+//! `coral fix`: finds the machine-applicable suggestions for a file and applies them to its text
+
+use crate::{
+    error::{
+        suggestion::{Applicability, Suggestion},
+        CollectingContext,
+    },
+    fs::FileId,
+    lexer::{tokens::Tokens, Token},
+    parser::parse_file,
+    transaction::ParserState,
+    FileArena,
+};
+
+/// Two suggestions that touch the same text, of which only `applied` was
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub applied: Suggestion,
+    pub skipped: Suggestion,
+}
+
+#[derive(Debug)]
+pub struct Fixed {
+    pub source: String,
+    pub applied: usize,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Applies `suggestions` to `source`, which their spans point into.
+/// Suggestions are applied front to back; one that overlaps an already applied one,
+/// or inserts different text at the same position, is skipped and reported.
+pub fn apply(source: &str, suggestions: &[Suggestion]) -> Fixed {
+    let mut suggestions = suggestions.to_vec();
+    suggestions.sort_by_key(|suggestion| (suggestion.span.start(), suggestion.span.end()));
+    suggestions.dedup();
+
+    let mut fixed = String::with_capacity(source.len());
+    let mut applied = 0;
+    let mut conflicts = Vec::new();
+    let mut copied_up_to = 0;
+    let mut last: Option<Suggestion> = None;
+    for suggestion in suggestions {
+        if let Some(last) = &last {
+            let overlaps = suggestion.span.start() < last.span.end()
+                || suggestion.span.start() == last.span.start();
+            if overlaps {
+                conflicts.push(Conflict {
+                    applied: last.clone(),
+                    skipped: suggestion,
+                });
+                continue;
+            }
+        }
+        fixed.push_str(&source[copied_up_to..suggestion.span.start()]);
+        fixed.push_str(&suggestion.replacement);
+        copied_up_to = suggestion.span.end();
+        applied += 1;
+        last = Some(suggestion);
+    }
+    fixed.push_str(&source[copied_up_to..]);
+    Fixed {
+        source: fixed,
+        applied,
+        conflicts,
+    }
+}
+
+/// Machine-applicable suggestions for `file`, found by parsing it
+pub fn suggestions(files: &FileArena, file: FileId) -> Vec<Suggestion> {
+    let mut state = ParserState::new(
+        CollectingContext::new(),
+        Tokens::from(Token::lexer_from_file(files, file)),
+    );
+    parse_file(&mut state);
+    let (ctx, _, _) = state.into_parts();
+    ctx.diagnostics()
+        .iter()
+        .flat_map(|diagnostic| diagnostic.error.suggestions())
+        .filter(|suggestion| suggestion.applicability == Applicability::MachineApplicable)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use id_arena::Arena;
+
+    use crate::{
+        error::suggestion::{Applicability, Suggestion},
+        fs::File,
+        span::Span,
+        FileArena,
+    };
+
+    use super::{apply, suggestions, Conflict};
+
+    fn suggestion(range: std::ops::Range<usize>, replacement: &str) -> Suggestion {
+        let mut arena: FileArena = Arena::new();
+        let file = arena.alloc(File::new("test", ""));
+        Suggestion::new(
+            Span::new(file, range),
+            replacement,
+            Applicability::MachineApplicable,
+        )
+    }
+
+    #[test]
+    fn applies_in_order() {
+        let fixed = apply(
+            "let x = a:b",
+            &[suggestion(11..11, ";"), suggestion(9..10, "::")],
+        );
+        assert_eq!(fixed.source, "let x = a::b;");
+        assert_eq!(fixed.applied, 2);
+        assert!(fixed.conflicts.is_empty());
+    }
+
+    #[test]
+    fn overlapping_edits_conflict() {
+        let first = suggestion(4..9, "y");
+        let overlapping = suggestion(8..10, "z");
+        let semicolon = suggestion(13..13, ";");
+        let bang = suggestion(13..13, "!");
+        let fixed = apply(
+            "let xxxxx = 1",
+            &[
+                overlapping.clone(),
+                first.clone(),
+                first.clone(),
+                semicolon.clone(),
+                bang.clone(),
+            ],
+        );
+        assert_eq!(fixed.source, "let y = 1;");
+        assert_eq!(fixed.applied, 2);
+        assert_eq!(
+            fixed.conflicts,
+            [
+                Conflict {
+                    applied: first,
+                    skipped: overlapping,
+                },
+                Conflict {
+                    applied: semicolon,
+                    skipped: bang,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fixes_source() {
+        let source = "fn main(): void {\n    let x = a:b\n    if x = 1 { (1, 2 }\n    x\n}";
+        let mut arena: FileArena = Arena::new();
+        let file = arena.alloc(File::new("test", source));
+        let fixed = apply(source, &suggestions(&arena, file));
+        assert_eq!(
+            fixed.source,
+            "fn main(): void {\n    let x = a::b;\n    if x == 1 { (1, 2) }\n    x\n}"
+        );
+    }
+}
@@ -0,0 +1,581 @@
+//! Checks on how control flows through checked functions: that every way through a function
+//! that returns a value does, that locals are assigned before they're read, and that no code
+//! comes after something that never finishes.
+//!
+//! Whether the end of a body is reached comes from the [`Mir`], where it's a question of which
+//! blocks can be gotten to, and so does whether a local is assigned, see [`init`]. Code after a
+//! `return`, `break`, `continue` or `loop` without a `break` is found in the [`Hir`], where those
+//! are the statements of type `never`.
+//!
+//! Patterns are checked in the [`Hir`] as well: every `match` has to have an arm for every value,
+//! and `let`s and closure parameters a pattern that matches every value, while an arm that
+//! only matches what the arms before it do is never taken, see [`usefulness`].
+
+use miette::{Diagnostic, LabeledSpan};
+use thiserror::Error;
+
+use crate::{
+    ast::Identifier,
+    error::{snippet_labels, Context, ContextName},
+    hir::{self, Arm, ExprKind, Function, Hir, Pat, Stmt},
+    mir::{Body, Mir, TerminatorKind},
+    span::Span,
+    typeck::ty::Ty,
+};
+
+mod init;
+mod usefulness;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum FlowError {
+    #[error("not all paths return a value")]
+    MissingReturn {
+        ret: Ty,
+        /// The closing `}` of the function body
+        span: Span,
+    },
+    #[error("unreachable code")]
+    UnreachableCode {
+        span: Span,
+        /// The statement that never finishes
+        cause: Span,
+    },
+    #[error("`{name}` is used before it's assigned on every path")]
+    Uninitialized {
+        name: Identifier,
+        span: Span,
+        /// The binding of the local, in a `let` without a value
+        declared: Span,
+    },
+    #[error("non-exhaustive patterns: {} not covered", list(.missing))]
+    NonExhaustive {
+        /// The value being matched
+        span: Span,
+        /// The patterns of the values without an arm
+        missing: Vec<String>,
+    },
+    #[error("unreachable pattern")]
+    UnreachableArm {
+        /// The pattern of the arm
+        span: Span,
+    },
+    #[error("refutable pattern: {} not covered", list(.missing))]
+    RefutablePattern {
+        span: Span,
+        /// The patterns of the values it doesn't match
+        missing: Vec<String>,
+    },
+}
+
+/// Up to three `patterns`, with how many more there are
+fn list(patterns: &[String]) -> String {
+    let shown: Vec<_> = patterns
+        .iter()
+        .take(3)
+        .map(|pattern| format!("`{pattern}`"))
+        .collect();
+    match (shown.split_last(), patterns.len()) {
+        (None, _) => String::new(),
+        (Some((last, [])), _) => last.clone(),
+        (Some((last, rest)), 0..=3) => format!("{} and {last}", rest.join(", ")),
+        (Some(_), len) => format!("{} and {} more", shown.join(", "), len - 3),
+    }
+}
+
+impl FlowError {
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::MissingReturn { span, .. }
+            | Self::UnreachableCode { span, .. }
+            | Self::Uninitialized { span, .. }
+            | Self::NonExhaustive { span, .. }
+            | Self::UnreachableArm { span }
+            | Self::RefutablePattern { span, .. } => *span,
+        }
+    }
+
+    /// Code of the error, registered with its explanation in [`crate::error::codes`]
+    pub const fn error_code(&self) -> &'static str {
+        match self {
+            Self::MissingReturn { .. } => "E0029",
+            Self::UnreachableCode { .. } => "E0030",
+            Self::Uninitialized { .. } => "E0031",
+            Self::NonExhaustive { .. } => "E0033",
+            Self::UnreachableArm { .. } => "E0034",
+            Self::RefutablePattern { .. } => "E0035",
+        }
+    }
+}
+
+impl Diagnostic for FlowError {
+    fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        Some(Box::new(self.error_code()))
+    }
+
+    fn severity(&self) -> Option<miette::Severity> {
+        match self {
+            Self::MissingReturn { .. }
+            | Self::Uninitialized { .. }
+            | Self::NonExhaustive { .. }
+            | Self::RefutablePattern { .. } => None,
+            Self::UnreachableCode { .. } | Self::UnreachableArm { .. } => {
+                Some(miette::Severity::Warning)
+            }
+        }
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        match self {
+            Self::MissingReturn { ret, .. } => Some(Box::new(format!(
+                "end the body with a value of type `{ret}`, or `return` one on every path"
+            ))),
+            Self::UnreachableCode { .. } | Self::UnreachableArm { .. } => None,
+            Self::NonExhaustive { missing, .. } => Some(Box::new(match &missing[..] {
+                [pattern] => format!("add an arm for `{pattern}`"),
+                _ => "add arms for the missing patterns, or one with `_` for everything else"
+                    .to_string(),
+            })),
+            Self::RefutablePattern { .. } => Some(Box::new(
+                "use a `match` to handle the values the pattern doesn't match",
+            )),
+            Self::Uninitialized { name, .. } => Some(Box::new(format!(
+                "give `{name}` a value where it's declared, or assign it on every path before this"
+            ))),
+        }
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        Some(snippet_labels(self.labelled_spans()))
+    }
+}
+
+impl FlowError {
+    /// The labels with the spans they point at, primary first
+    pub fn labelled_spans(&self) -> Vec<(Span, String)> {
+        match self {
+            Self::MissingReturn { ret, span } => vec![(
+                *span,
+                format!("the function can get here without returning `{ret}`"),
+            )],
+            Self::UnreachableCode { span, cause } => vec![
+                (*span, "this is never run".to_string()),
+                (*cause, "because control never gets past this".to_string()),
+            ],
+            Self::NonExhaustive { span, .. } => {
+                vec![(*span, "not every value of this is matched".to_string())]
+            }
+            Self::UnreachableArm { span } => vec![(
+                *span,
+                "the arms before this one already match everything it does".to_string(),
+            )],
+            Self::RefutablePattern { span, .. } => {
+                vec![(*span, "this has to match every value".to_string())]
+            }
+            Self::Uninitialized {
+                name,
+                span,
+                declared,
+            } => vec![
+                (*span, format!("`{name}` might not have a value here")),
+                (*declared, "declared here without a value".to_string()),
+            ],
+        }
+    }
+}
+
+/// Checks the functions of `hir`, built into `mir`, reporting to `ctx`
+pub fn check<C: ContextName>(ctx: &mut impl Context<C>, hir: &Hir, mir: &Mir) {
+    for function in &hir.functions {
+        function.body.walk(&mut |expr| match &expr.kind {
+            ExprKind::Block(block) | ExprKind::Loop(block) => {
+                if let Some(err) = unreachable_code(block) {
+                    ctx.message(err);
+                }
+                for stmt in &block.stmts {
+                    if let Stmt::Let { pat, .. } = stmt {
+                        check_irrefutable(ctx, pat);
+                    }
+                }
+            }
+            ExprKind::Closure { params, .. } => {
+                for param in params {
+                    check_irrefutable(ctx, param);
+                }
+            }
+            ExprKind::Match { scrutinee, arms } => check_match(ctx, scrutinee, arms),
+            _ => {}
+        });
+        let body = mir
+            .body(function.def)
+            .expect("every function is built into a body");
+        if function.ret != Ty::Void && falls_off_end(&function.body, body) {
+            let end = function.body.span;
+            ctx.message(FlowError::MissingReturn {
+                ret: function.ret.clone(),
+                span: Span::new(end.file(), end.end() - 1..end.end()),
+            });
+        }
+    }
+    for body in &mir.bodies {
+        // The closures of a function are in its span
+        let Some(function) = hir.functions.iter().find(|function| {
+            function.span.file() == body.span.file()
+                && function.span.start() <= body.span.start()
+                && body.span.end() <= function.span.end()
+        }) else {
+            continue;
+        };
+        for read in init::uninitialized_reads(body) {
+            let decl = &body[read.local];
+            let name = decl.name.expect("only named locals are reported");
+            ctx.message(FlowError::Uninitialized {
+                name,
+                span: read_span(function, name, decl.span, read.span),
+                declared: decl.span,
+            });
+        }
+    }
+}
+
+/// Reports the arms that are never taken and the values without an arm. Nothing is matched
+/// if the scrutinee never has a value, which is reported as unreachable code instead.
+fn check_match<C: ContextName>(ctx: &mut impl Context<C>, scrutinee: &hir::Expr, arms: &[Arm]) {
+    if scrutinee.ty == Ty::Never {
+        return;
+    }
+    let pats: Vec<_> = arms.iter().map(|arm| &arm.pat).collect();
+    for i in usefulness::unreachable_patterns(&scrutinee.ty, &pats) {
+        ctx.message(FlowError::UnreachableArm { span: pats[i].span });
+    }
+    let missing = usefulness::missing_patterns(&scrutinee.ty, &pats);
+    if !missing.is_empty() {
+        ctx.message(FlowError::NonExhaustive {
+            span: scrutinee.span,
+            missing,
+        });
+    }
+}
+
+/// Reports `pat` of a `let` or a closure parameter if there are values it doesn't match
+fn check_irrefutable<C: ContextName>(ctx: &mut impl Context<C>, pat: &Pat) {
+    let missing = usefulness::missing_patterns(&pat.ty, &[pat]);
+    if !missing.is_empty() {
+        ctx.message(FlowError::RefutablePattern {
+            span: pat.span,
+            missing,
+        });
+    }
+}
+
+/// Where in the code that `at` is the span of the local declared at `declared` is read.
+/// Statements of the [`Mir`] have the span of the expression they come from, and those that
+/// test patterns the span of the pattern, so this is the first read in the smallest expression
+/// around `at` that has one.
+fn read_span(function: &Function, name: Identifier, declared: Span, at: Span) -> Span {
+    let contains = |outer: Span, inner: Span| {
+        outer.file() == inner.file() && outer.start() <= inner.start() && inner.end() <= outer.end()
+    };
+    // Assigning to the whole local doesn't read it
+    let mut targets = Vec::new();
+    function.body.walk(&mut |expr| {
+        if let ExprKind::Assign { place, .. } = &expr.kind {
+            targets.push(place.def);
+        }
+    });
+    let first_read = |expr: &hir::Expr| {
+        let mut first = None;
+        expr.walk(&mut |expr| {
+            if let ExprKind::Local(local) = expr.kind {
+                let local = &function[local];
+                if first.is_none()
+                    && local.name == Some(name)
+                    && local.span == declared
+                    && !targets.contains(&expr.def)
+                {
+                    first = Some(expr.span);
+                }
+            }
+        });
+        first
+    };
+    let mut smallest: Option<(Span, Span)> = None;
+    function.body.walk(&mut |expr| {
+        if !contains(expr.span, at)
+            || smallest.is_some_and(|(around, _)| !contains(around, expr.span))
+        {
+            return;
+        }
+        if let Some(read) = first_read(expr) {
+            smallest = Some((expr.span, read));
+        }
+    });
+    smallest.map_or(at, |(_, read)| read)
+}
+
+/// Whether control can get to the end of `function_body`, built into `body`, without a value
+fn falls_off_end(function_body: &hir::Expr, body: &Body) -> bool {
+    // A tail like an `if` without an `else` is `void` and doesn't give a value either
+    let ExprKind::Block(block) = &function_body.kind else {
+        unreachable!("function bodies are blocks")
+    };
+    if block.expr.as_ref().is_some_and(|tail| tail.ty != Ty::Void) {
+        return false;
+    }
+    // The `return` at the end of the body is the only one with the span of the whole body
+    let reachable = body.reachable();
+    body.basic_blocks().any(|(block, data)| {
+        let terminator = data.terminator();
+        reachable[block.index()]
+            && matches!(terminator.kind, TerminatorKind::Return)
+            && terminator.span == function_body.span
+    })
+}
+
+/// The code in `block` after the first statement that never finishes, if there's any
+fn unreachable_code(block: &hir::Block) -> Option<FlowError> {
+    let diverges = block.stmts.iter().position(|stmt| match stmt {
+        Stmt::Let {
+            init: Some(expr), ..
+        }
+        | Stmt::Expr(expr) => expr.ty == Ty::Never,
+        Stmt::Let { init: None, .. } => false,
+    })?;
+    let first = match block.stmts.get(diverges + 1) {
+        Some(stmt) => stmt.span(),
+        None => block.expr.as_ref()?.span,
+    };
+    let last = match &block.expr {
+        Some(expr) => expr.span,
+        None => block.stmts.last()?.span(),
+    };
+    let cause = match &block.stmts[diverges] {
+        Stmt::Let {
+            init: Some(expr), ..
+        }
+        | Stmt::Expr(expr) => expr.span,
+        Stmt::Let { init: None, .. } => unreachable!("only statements with a value diverge"),
+    };
+    Some(FlowError::UnreachableCode {
+        span: Span::new(first.file(), first.start()..last.end()),
+        cause,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        error::{CollectingContext, CoralError, Severity},
+        mir,
+        parser::ContextName,
+        testing::Source,
+    };
+
+    use super::check;
+
+    /// The context with what the flow analysis of `source` reported
+    fn check_source(source: &str) -> CollectingContext<ContextName> {
+        let mut source = Source::parse(source);
+        let hir = source.lower();
+        check(&mut source.ctx, &hir, &mir::build(&hir));
+        source.ctx
+    }
+
+    /// The code, severity and span of every diagnostic, as "Exxxx@start..end" for errors and
+    /// "warning Exxxx@start..end" for warnings
+    fn diagnostics(source: &str) -> Vec<String> {
+        check_source(source)
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| {
+                let CoralError::FlowError(err) = &diagnostic.error else {
+                    panic!("{:?} isn't from the flow analysis", diagnostic.error)
+                };
+                let span = err.span();
+                let severity = match diagnostic.severity {
+                    Severity::Warning => "warning ",
+                    _ => "",
+                };
+                format!(
+                    "{severity}{}@{}..{}",
+                    err.error_code(),
+                    span.start(),
+                    span.end()
+                )
+            })
+            .collect()
+    }
+
+    /// The messages of every diagnostic
+    fn messages(source: &str) -> Vec<String> {
+        check_source(source)
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.error.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn every_path_returns_a_value() {
+        assert_eq!(diagnostics("fn f(): u8 { }"), ["E0029@13..14"]);
+        assert_eq!(
+            diagnostics("fn f(c: bool): u8 {\n    if c { return 1; }\n}"),
+            ["E0029@43..44"]
+        );
+        // Loops that check a condition can always end
+        assert_eq!(
+            diagnostics("fn f(): u8 { while true { return 1; } }"),
+            ["E0029@38..39"]
+        );
+        for source in [
+            "fn f(c: bool): u8 { if c { return 1; } else { return 2; } }",
+            "fn f(c: bool): u8 { loop { if c { return 1; } } }",
+            "fn f(): u8 { return 1; }",
+            "fn f(c: bool): u8 { if c { 1 } else { 2 } }",
+            "fn f(): void { }",
+            // The closure returning is not the function returning, but the closure doesn't
+            // need to produce a value
+            "fn f(): u8 { let g = || { return; }; return 1; }",
+        ] {
+            assert_eq!(diagnostics(source), [] as [String; 0], "{source}");
+        }
+
+        let ctx = check_source("fn f(): i32 { }");
+        let CoralError::FlowError(err) = &ctx.diagnostics()[0].error else {
+            panic!(
+                "{:?} isn't from the flow analysis",
+                ctx.diagnostics()[0].error
+            )
+        };
+        assert_eq!(
+            err.labelled_spans()[0].1,
+            "the function can get here without returning `i32`"
+        );
+    }
+
+    #[test]
+    fn code_after_diverging_statements_is_unreachable() {
+        assert_eq!(
+            diagnostics("fn f(): u8 { return 1; let x = 2; x }"),
+            ["warning E0030@23..35"]
+        );
+        assert_eq!(
+            diagnostics("fn f(): void { loop { break; 1; } }"),
+            ["warning E0030@29..30"]
+        );
+        assert_eq!(
+            diagnostics("fn f(): void { loop {} f(); }"),
+            ["warning E0030@23..26"]
+        );
+        // A loop that can be broken out of finishes
+        assert_eq!(
+            diagnostics("fn f(c: bool): void { loop { if c { break; } } f(c); }"),
+            [] as [String; 0]
+        );
+        // Still every path returns
+        assert_eq!(
+            diagnostics("fn f(c: bool): u8 { if c { return 1; } else { return 2; }; 3 }"),
+            ["warning E0030@59..60"]
+        );
+    }
+
+    #[test]
+    fn locals_are_assigned_before_they_are_read() {
+        assert_eq!(
+            diagnostics("fn f(c: bool): u8 { let x; if c { x = 1; } x }"),
+            ["E0031@43..44"]
+        );
+        // The body of a `while` might never run
+        assert_eq!(
+            diagnostics("fn f(c: bool): u8 { let x; while c { x = 1; } x }"),
+            ["E0031@46..47"]
+        );
+        // Assigning doesn't read the local, but what's assigned can
+        assert_eq!(
+            diagnostics("fn f(): u8 { let x: u8; x = x + 1; x }"),
+            ["E0031@28..29"]
+        );
+        // Nor is a closure assigned the captured local
+        assert_eq!(
+            diagnostics("fn f(): u8 { let x: u8; let g = || x; x = 1; g() }"),
+            ["E0031@35..36"]
+        );
+        for source in [
+            "fn f(c: bool): u8 { let x; if c { x = 1; } else { x = 2; } x }",
+            "fn f(c: bool): u8 { let x; loop { if c { x = 1; break; } } x }",
+            "fn f(c: bool): u8 { let x; if c { return 0; } else { x = 2; } x }",
+            "fn f(): u8 { let (a, b); a = 1; b = 2; a + b }",
+            "fn f(): void { let x: u8; }",
+        ] {
+            assert_eq!(diagnostics(source), [] as [String; 0], "{source}");
+        }
+    }
+
+    #[test]
+    fn matches_cover_every_value() {
+        assert_eq!(
+            diagnostics("fn f(x: Option<u8>): u8 { match x { Some(0) => 0, None => 1 } }"),
+            ["E0033@32..33"]
+        );
+        assert_eq!(
+            messages(
+                "fn f(x: (bool, i8)): u8 { match x { (true, -128..=0) => 0, (false, _) => 1 } }"
+            ),
+            ["non-exhaustive patterns: `(true, 1..=127)` not covered"]
+        );
+        assert_eq!(
+            messages("fn f(c: char, r: Result<u8, bool>): u8 {
+                match c { 'a'..='z' => 0 };
+                match r { Ok(1) => 0, Ok(3) => 0, Err(true) => 1 }
+            }"),
+            [
+                "non-exhaustive patterns: `'\\0'..='`'`, `'{'..='\\u{d7ff}'` and `'\\u{e000}'..='\\u{10ffff}'` not covered",
+                "non-exhaustive patterns: `Ok(0)`, `Ok(2)`, `Ok(4..=255)` and 1 more not covered",
+            ]
+        );
+        for source in [
+            "fn f(x: Option<bool>): u8 { match x { Some(true) => 0, Some(false) => 1, None => 2 } }",
+            "fn f(x: i8): u8 { match x { -128..0 => 0, 0 => 1, 1..=127 => 2 } }",
+            "fn f(x: (u8, bool)): u8 { match x { (0, _) => 0, (_, true) => 1, (1..=255, false) => 2 } }",
+            // Nothing is matched when there's never a value
+            "fn f(): u8 { match return 1 {} }",
+        ] {
+            assert_eq!(diagnostics(source), [] as [String; 0], "{source}");
+        }
+    }
+
+    #[test]
+    fn arms_after_others_matching_everything_are_unreachable() {
+        assert_eq!(
+            diagnostics("fn f(x: u8): u8 { match x { 0..=9 => 0, _ => 1, 5 => 2 } }"),
+            ["warning E0034@48..49"]
+        );
+        assert_eq!(
+            diagnostics(
+                "fn f(x: (bool, bool)): u8 {
+                match x { (true, _) => 0, (_, false) => 1, (true, true) => 2, (false, true) => 3 }
+            }"
+            ),
+            ["warning E0034@87..99"]
+        );
+        // A range that ends where it starts matches nothing
+        assert_eq!(
+            diagnostics("fn f(x: u8): u8 { match x { 3..3 => 0, _ => 1 } }"),
+            ["warning E0034@28..32"]
+        );
+    }
+
+    #[test]
+    fn bindings_match_every_value() {
+        assert_eq!(
+            diagnostics("fn f(x: Option<u8>): u8 { let Some(y) = x; y }"),
+            ["E0035@30..37"]
+        );
+        assert_eq!(
+            messages("fn f(a: [(u8, bool); 2]): void { for (0, b) in a {} let g = |true| 1; }"),
+            [
+                "refutable pattern: `(1..=255, _)` not covered",
+                "refutable pattern: `false` not covered",
+            ]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Definite initialization: which locals have been assigned on every path to a point of a
+//! [`Body`], worked out forwards over its blocks until nothing changes.
+//!
+//! A local is initialized once it's assigned as a whole, and stays that way. Where paths join,
+//! a local is initialized if it is on all of them. Blocks control can't get to count as having
+//! everything initialized, so nothing is reported in them and they don't hold back the blocks
+//! they go to.
+
+use crate::{
+    mir::{
+        BasicBlock, Body, Local, Operand, Place, Projection, Rvalue, StatementKind, Terminator,
+        TerminatorKind,
+    },
+    span::Span,
+    typeck::ty::Ty,
+};
+
+/// A read of a local that might not have been assigned yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UninitializedRead {
+    pub local: Local,
+    /// Of the statement or terminator that reads it
+    pub span: Span,
+}
+
+/// The first read, in source order, of each named local that can happen before it's assigned
+pub fn uninitialized_reads(body: &Body) -> Vec<UninitializedRead> {
+    let entry = initialized_on_entry(body);
+    let mut reads: Vec<UninitializedRead> = Vec::new();
+    let mut report = |local: Local, span: Span| {
+        let decl = &body[local];
+        if local == Local::RETURN || decl.name.is_none() || matches!(decl.ty, Ty::Void) {
+            return;
+        }
+        match reads.iter_mut().find(|read| read.local == local) {
+            Some(read) if span.start() < read.span.start() => read.span = span,
+            Some(_) => {}
+            None => reads.push(UninitializedRead { local, span }),
+        }
+    };
+    for (block, data) in body.basic_blocks() {
+        let mut initialized = entry[block.index()].clone();
+        for statement in &data.statements {
+            let mut check = |local: Local| {
+                if !initialized[local.index()] {
+                    report(local, statement.span);
+                }
+            };
+            let StatementKind::Assign(place, rvalue) = &statement.kind;
+            rvalue_reads(rvalue, &mut check);
+            destination_reads(place, &mut check);
+            assign(place, &mut initialized);
+        }
+        let terminator = data.terminator();
+        terminator_reads(terminator, &mut |local| {
+            if !initialized[local.index()] {
+                report(local, terminator.span);
+            }
+        });
+    }
+    reads.sort_by_key(|read| read.span.start());
+    reads
+}
+
+/// Which locals are initialized when control enters each block
+fn initialized_on_entry(body: &Body) -> Vec<Vec<bool>> {
+    let mut entry = vec![vec![true; body.locals.len()]; body.blocks.len()];
+    entry[BasicBlock::START.index()] = (0..body.locals.len())
+        .map(|index| (1..=body.arg_count).contains(&index))
+        .collect();
+    let mut visited = vec![false; body.blocks.len()];
+    let mut worklist = vec![BasicBlock::START];
+    while let Some(block) = worklist.pop() {
+        visited[block.index()] = true;
+        let data = &body[block];
+        let mut initialized = entry[block.index()].clone();
+        for statement in &data.statements {
+            let StatementKind::Assign(place, _) = &statement.kind;
+            assign(place, &mut initialized);
+        }
+        if let TerminatorKind::Call { destination, .. } = &data.terminator().kind {
+            assign(destination, &mut initialized);
+        }
+        for successor in body.successors(block) {
+            let mut changed = !visited[successor.index()];
+            for (entry, &initialized) in entry[successor.index()].iter_mut().zip(&initialized) {
+                if *entry && !initialized {
+                    *entry = false;
+                    changed = true;
+                }
+            }
+            if changed && !worklist.contains(&successor) {
+                worklist.push(successor);
+            }
+        }
+    }
+    entry
+}
+
+fn assign(place: &Place, initialized: &mut [bool]) {
+    if place.projection.is_empty() {
+        initialized[place.local.index()] = true;
+    }
+}
+
+/// Calls `f` on the locals that reading `place` reads
+fn place_reads(place: &Place, f: &mut impl FnMut(Local)) {
+    f(place.local);
+    index_reads(place, f);
+}
+
+/// Calls `f` on the locals that hold indices in `place`
+fn index_reads(place: &Place, f: &mut impl FnMut(Local)) {
+    for projection in &place.projection {
+        if let &Projection::Index(index) = projection {
+            f(index);
+        }
+    }
+}
+
+/// Calls `f` on the locals that assigning to `place` reads: all of them but a whole local
+/// being assigned, as a part of a local can only be assigned once it has a value
+fn destination_reads(place: &Place, f: &mut impl FnMut(Local)) {
+    if place.projection.is_empty() {
+        return;
+    }
+    place_reads(place, f);
+}
+
+fn operand_reads(operand: &Operand, f: &mut impl FnMut(Local)) {
+    if let Operand::Copy(place) = operand {
+        place_reads(place, f);
+    }
+}
+
+fn rvalue_reads(rvalue: &Rvalue, f: &mut impl FnMut(Local)) {
+    match rvalue {
+        Rvalue::Use(operand)
+        | Rvalue::Unary(_, operand)
+        | Rvalue::Cast(operand, _)
+        | Rvalue::Repeat(operand, _) => operand_reads(operand, f),
+        Rvalue::Binary(_, lhs, rhs) => {
+            operand_reads(lhs, f);
+            operand_reads(rhs, f);
+        }
+        // A reference to something without a value could be read through
+        Rvalue::Ref { place, .. } | Rvalue::Discriminant(place) => place_reads(place, f),
+        Rvalue::Aggregate(_, operands) => {
+            for operand in operands {
+                operand_reads(operand, f);
+            }
+        }
+    }
+}
+
+fn terminator_reads(terminator: &Terminator, f: &mut impl FnMut(Local)) {
+    match &terminator.kind {
+        TerminatorKind::SwitchInt { discr, .. } => operand_reads(discr, f),
+        TerminatorKind::Call {
+            func,
+            args,
+            destination,
+            ..
+        } => {
+            operand_reads(func, f);
+            for arg in args {
+                operand_reads(arg, f);
+            }
+            destination_reads(destination, f);
+        }
+        TerminatorKind::Goto { .. } | TerminatorKind::Return | TerminatorKind::Unreachable => {}
+    }
+}
@@ -0,0 +1,372 @@
+//! Usefulness of patterns, after Maranget's "Warnings for pattern matching": a pattern is
+//! useful after some others if there's a value it matches that none of them do. An arm of a
+//! `match` whose pattern isn't useful after those of the arms before it is never taken, and a
+//! `match` is exhaustive if `_` isn't useful after all of its arms.
+//!
+//! Patterns are boiled down to constructors applied to the patterns of their fields, and the
+//! rows of patterns are compared column by column. Integers and characters are ranges of
+//! values, which are cut at the ends of the ranges in the column so that every piece is either
+//! wholly in a range or wholly out of it. Where `_` is useful, the values it gets to are kept
+//! as witnesses, which are the missing patterns that get reported.
+
+use crate::{
+    hir::{self, Literal, PatKind, Variant},
+    typeck::ty::Ty,
+};
+
+/// The patterns of the values of type `ty` that none of `pats` match, as they'd be written
+pub fn missing_patterns(ty: &Ty, pats: &[&hir::Pat]) -> Vec<String> {
+    let rows: Vec<_> = pats.iter().map(|pat| vec![lower(pat)]).collect();
+    useful(&rows, &[Pat::Wild], std::slice::from_ref(ty), true)
+        .iter()
+        .map(|witness| print(&witness[0], ty))
+        .collect()
+}
+
+/// The indices of the patterns in `pats` that only match values the ones before them do
+pub fn unreachable_patterns(ty: &Ty, pats: &[&hir::Pat]) -> Vec<usize> {
+    let mut rows = Vec::new();
+    let mut unreachable = Vec::new();
+    for (i, pat) in pats.iter().enumerate() {
+        let row = vec![lower(pat)];
+        if useful(&rows, &row, std::slice::from_ref(ty), false).is_empty() {
+            unreachable.push(i);
+        }
+        rows.push(row);
+    }
+    unreachable
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    Variant(Variant),
+    Bool(bool),
+    /// The integers or characters from the first value up to the second, and none if the first
+    /// is greater. Signed integers have their sign bit flipped, see [`flip_sign`].
+    Range(u128, u128),
+    /// A tuple of the length, with `void` as the tuple of none
+    Tuple(usize),
+    /// A float or a string, which there are too many of to list
+    Literal(Literal),
+}
+
+#[derive(Debug, Clone)]
+enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+}
+
+/// Every value of a type, by what makes them
+enum Values {
+    Ctors(Vec<Ctor>),
+    /// Integers or characters, in these ranges
+    Ranges(Vec<(u128, u128)>),
+    /// Too many to list
+    Unlisted,
+}
+
+fn values(ty: &Ty) -> Values {
+    match *ty {
+        Ty::Bool => Values::Ctors(vec![Ctor::Bool(false), Ctor::Bool(true)]),
+        Ty::Option(_) => Values::Ctors(vec![
+            Ctor::Variant(Variant::None),
+            Ctor::Variant(Variant::Some),
+        ]),
+        Ty::Result(..) => Values::Ctors(vec![
+            Ctor::Variant(Variant::Ok),
+            Ctor::Variant(Variant::Err),
+        ]),
+        Ty::Void => Values::Ctors(vec![Ctor::Tuple(0)]),
+        Ty::Tuple(ref tys) => Values::Ctors(vec![Ctor::Tuple(tys.len())]),
+        // There are none
+        Ty::Never => Values::Ctors(Vec::new()),
+        Ty::Int(ty) => Values::Ranges(vec![(0, ty.truncate(u128::MAX))]),
+        Ty::Char => Values::Ranges(vec![(0, 0xD7FF), (0xE000, char::MAX as u128)]),
+        _ => Values::Unlisted,
+    }
+}
+
+/// `value` of type `ty` flipped between two's complement and the order of [`Ctor::Range`],
+/// where the smallest signed integer is `0` so that signed ranges compare like unsigned ones
+fn flip_sign(ty: &Ty, value: u128) -> u128 {
+    match *ty {
+        Ty::Int(ty) if ty.is_signed() => value ^ (1 << (ty.bits() - 1)),
+        _ => value,
+    }
+}
+
+fn lower(pat: &hir::Pat) -> Pat {
+    let value = |literal| match literal {
+        Literal::Int(value) => flip_sign(&pat.ty, value),
+        Literal::Char(value) => value as u128,
+        _ => unreachable!("only integers and characters are in ranges"),
+    };
+    let ctor = match pat.kind {
+        PatKind::Wild | PatKind::Binding(_) => return Pat::Wild,
+        PatKind::Literal(Literal::Bool(value)) => Ctor::Bool(value),
+        PatKind::Literal(literal @ (Literal::Int(_) | Literal::Char(_))) => {
+            Ctor::Range(value(literal), value(literal))
+        }
+        PatKind::Literal(literal) => Ctor::Literal(literal),
+        PatKind::Range {
+            start,
+            end,
+            inclusive,
+        } => match value(end).checked_sub(u128::from(!inclusive)) {
+            Some(end) => Ctor::Range(value(start), end),
+            None => Ctor::Range(1, 0),
+        },
+        PatKind::Tuple(ref elements) => {
+            return Pat::Ctor(
+                Ctor::Tuple(elements.len()),
+                elements.iter().map(lower).collect(),
+            )
+        }
+        PatKind::Variant { variant, ref inner } => {
+            return Pat::Ctor(
+                Ctor::Variant(variant),
+                inner.iter().map(|inner| lower(inner)).collect(),
+            )
+        }
+    };
+    Pat::Ctor(ctor, Vec::new())
+}
+
+/// The types of the fields of `ctor`, which makes values of type `ty`
+fn fields(ctor: &Ctor, ty: &Ty) -> Vec<Ty> {
+    match (ctor, ty) {
+        (Ctor::Tuple(_), Ty::Tuple(tys)) => tys.clone(),
+        (Ctor::Variant(Variant::Some), Ty::Option(value))
+        | (Ctor::Variant(Variant::Ok), Ty::Result(value, _))
+        | (Ctor::Variant(Variant::Err), Ty::Result(_, value)) => vec![(**value).clone()],
+        (Ctor::Variant(Variant::None), _) | (Ctor::Tuple(0), _) => Vec::new(),
+        // Only patterns that are never reached are of another type
+        (&Ctor::Tuple(len), _) => vec![Ty::Never; len],
+        (Ctor::Variant(_), _) => vec![Ty::Never],
+        (Ctor::Bool(_) | Ctor::Range(..) | Ctor::Literal(_), _) => Vec::new(),
+    }
+}
+
+/// Whether every value `inner` makes is made by `outer`, where a range `inner` is either in
+/// `outer` or apart from it
+fn covers(outer: &Ctor, inner: &Ctor) -> bool {
+    match (outer, inner) {
+        (&Ctor::Range(start, end), &Ctor::Range(inner_start, inner_end)) => {
+            start <= inner_start && inner_end <= end
+        }
+        _ => outer == inner,
+    }
+}
+
+/// `ranges` cut at the ends of the ranges in `heads`, so that every piece is either wholly in
+/// one of them or wholly out of it
+fn split(ranges: &[(u128, u128)], heads: &[&Ctor]) -> Vec<Ctor> {
+    let mut cuts: Vec<u128> = heads
+        .iter()
+        .filter_map(|head| match **head {
+            Ctor::Range(start, end) if start <= end => Some([Some(start), end.checked_add(1)]),
+            _ => None,
+        })
+        .flatten()
+        .flatten()
+        .collect();
+    cuts.sort_unstable();
+    cuts.dedup();
+    let mut pieces = Vec::new();
+    for &(start, end) in ranges.iter().filter(|(start, end)| start <= end) {
+        let mut piece = start;
+        for &cut in cuts.iter().filter(|&&cut| start < cut && cut <= end) {
+            pieces.push(Ctor::Range(piece, cut - 1));
+            piece = cut;
+        }
+        pieces.push(Ctor::Range(piece, end));
+    }
+    pieces
+}
+
+/// The rows that match the values `ctor` makes, with the patterns of the fields of `ctor` in
+/// place of their first pattern
+fn specialize(rows: &[Vec<Pat>], ctor: &Ctor, arity: usize) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| {
+            let fields = match &row[0] {
+                Pat::Wild => vec![Pat::Wild; arity],
+                Pat::Ctor(head, fields) if covers(head, ctor) => fields.clone(),
+                Pat::Ctor(..) => return None,
+            };
+            Some(fields.into_iter().chain(row[1..].iter().cloned()).collect())
+        })
+        .collect()
+}
+
+/// The values that `v`, whose columns are of types `tys`, matches and none of `rows` do, as
+/// patterns. Without `all`, that's just the first one found.
+fn useful(rows: &[Vec<Pat>], v: &[Pat], tys: &[Ty], all: bool) -> Vec<Vec<Pat>> {
+    let Some((head, tail)) = v.split_first() else {
+        return if rows.is_empty() {
+            vec![Vec::new()]
+        } else {
+            Vec::new()
+        };
+    };
+    let heads: Vec<&Ctor> = rows
+        .iter()
+        .filter_map(|row| match &row[0] {
+            Pat::Ctor(ctor, _) => Some(ctor),
+            Pat::Wild => None,
+        })
+        .collect();
+    let mut witnesses = Vec::new();
+    // Adds the witnesses of `v` starting with `ctor` instead, with `fields` unless they're all
+    // `_`, returning whether it's done looking
+    let specialized = |ctor: &Ctor, fields: Vec<Pat>, witnesses: &mut Vec<Vec<Pat>>| {
+        let field_tys = self::fields(ctor, &tys[0]);
+        let arity = field_tys.len();
+        let fields = if fields.is_empty() {
+            vec![Pat::Wild; arity]
+        } else {
+            fields
+        };
+        let rows = specialize(rows, ctor, arity);
+        let v: Vec<_> = fields.into_iter().chain(tail.iter().cloned()).collect();
+        let tys: Vec<_> = field_tys
+            .into_iter()
+            .chain(tys[1..].iter().cloned())
+            .collect();
+        for mut witness in useful(&rows, &v, &tys, all) {
+            let rest = witness.split_off(arity);
+            witnesses.push(
+                std::iter::once(Pat::Ctor(ctor.clone(), witness))
+                    .chain(rest)
+                    .collect(),
+            );
+        }
+        !all && !witnesses.is_empty()
+    };
+    match head {
+        &Pat::Ctor(Ctor::Range(start, end), _) => {
+            for piece in split(&[(start, end)], &heads) {
+                if specialized(&piece, Vec::new(), &mut witnesses) {
+                    break;
+                }
+            }
+            return witnesses;
+        }
+        Pat::Ctor(ctor, fields) => {
+            specialized(ctor, fields.clone(), &mut witnesses);
+            return witnesses;
+        }
+        Pat::Wild => {}
+    }
+    // Without a list of every value, the constructors in the column are all there is to split
+    let (ctors, listed) = match values(&tys[0]) {
+        Values::Ctors(ctors) => (ctors, true),
+        Values::Ranges(ranges) => (split(&ranges, &heads), true),
+        Values::Unlisted => {
+            let mut ctors: Vec<Ctor> = Vec::new();
+            for &head in &heads {
+                if !ctors.contains(head) {
+                    ctors.push(head.clone());
+                }
+            }
+            (ctors, false)
+        }
+    };
+    let (present, missing): (Vec<Ctor>, Vec<Ctor>) = ctors
+        .into_iter()
+        .partition(|ctor| heads.iter().any(|head| covers(head, ctor)));
+    let complete = listed && missing.is_empty();
+    // What the column misses is enough to know `v` is useful, but not to find every witness
+    if complete || all {
+        for ctor in &present {
+            if specialized(ctor, Vec::new(), &mut witnesses) {
+                return witnesses;
+            }
+        }
+    }
+    if complete {
+        return witnesses;
+    }
+    // Only the rows starting with `_` match what the column misses
+    let rows: Vec<Vec<Pat>> = rows
+        .iter()
+        .filter(|row| matches!(row[0], Pat::Wild))
+        .map(|row| row[1..].to_vec())
+        .collect();
+    let rest = useful(&rows, tail, &tys[1..], all);
+    let firsts = if heads.is_empty() || !listed {
+        vec![Pat::Wild]
+    } else {
+        merge(missing)
+            .into_iter()
+            .map(|ctor| {
+                let arity = fields(&ctor, &tys[0]).len();
+                Pat::Ctor(ctor, vec![Pat::Wild; arity])
+            })
+            .collect()
+    };
+    for first in &firsts {
+        for witness in &rest {
+            witnesses.push(
+                std::iter::once(first.clone())
+                    .chain(witness.iter().cloned())
+                    .collect(),
+            );
+            if !all {
+                return witnesses;
+            }
+        }
+    }
+    witnesses
+}
+
+/// `ctors` with the ranges that follow each other joined
+fn merge(ctors: Vec<Ctor>) -> Vec<Ctor> {
+    let mut merged: Vec<Ctor> = Vec::new();
+    for ctor in ctors {
+        if let (Some(Ctor::Range(_, end)), Ctor::Range(start, next_end)) =
+            (merged.last_mut(), &ctor)
+        {
+            if end.checked_add(1) == Some(*start) {
+                *end = *next_end;
+                continue;
+            }
+        }
+        merged.push(ctor);
+    }
+    merged
+}
+
+/// A witness matching values of type `ty`, as it'd be written
+fn print(pat: &Pat, ty: &Ty) -> String {
+    let Pat::Ctor(ctor, fields) = pat else {
+        return "_".to_string();
+    };
+    let field_tys = self::fields(ctor, ty);
+    let fields: Vec<_> = fields
+        .iter()
+        .zip(&field_tys)
+        .map(|(field, ty)| print(field, ty))
+        .collect();
+    match *ctor {
+        Ctor::Variant(variant) if fields.is_empty() => variant.as_str().to_string(),
+        Ctor::Variant(variant) => format!("{}({})", variant.as_str(), fields.join(", ")),
+        Ctor::Bool(value) => value.to_string(),
+        Ctor::Tuple(1) => format!("({},)", fields[0]),
+        Ctor::Tuple(_) => format!("({})", fields.join(", ")),
+        Ctor::Range(start, end) if start == end => print_value(ty, start),
+        Ctor::Range(start, end) => format!("{}..={}", print_value(ty, start), print_value(ty, end)),
+        Ctor::Literal(_) => unreachable!("what's missing of an unlisted type is `_`"),
+    }
+}
+
+fn print_value(ty: &Ty, value: u128) -> String {
+    match *ty {
+        Ty::Int(int) => int.format_bits(flip_sign(ty, value)),
+        Ty::Char => format!(
+            "{:?}",
+            char::from_u32(value as u32).expect("ranges of chars skip surrogates")
+        ),
+        _ => value.to_string(),
+    }
+}
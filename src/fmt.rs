@@ -0,0 +1,234 @@
+//! `coral fmt`: reprints files with [`print`], keeping their comments and blank lines.
+//!
+//! The width and indentation come from the closest `.coralfmt` or `coral.toml` in the
+//! directory of the file or one of its ancestors. A `.coralfmt` holds the settings at the top
+//! level, a `coral.toml` in its `[fmt]` table:
+//!
+//! ```toml
+//! [fmt]
+//! width = 80
+//! indent = 2
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    ast::{print, Ast, ItemId},
+    fs::FileId,
+    lexer::trivia::Trivia,
+    FileArena,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Lines are broken to fit in this many columns where possible
+    pub width: usize,
+    /// Spaces per indentation level
+    pub indent: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            width: print::WIDTH,
+            indent: print::INDENT,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("couldn't read `{}`: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid formatter configuration in `{}`: {source}", path.display())]
+    Toml {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+}
+
+/// The part of `coral.toml` the formatter cares about
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    fmt: Config,
+}
+
+impl Config {
+    pub const FILE: &'static str = ".coralfmt";
+    pub const MANIFEST: &'static str = "coral.toml";
+
+    /// The configuration for files in `dir`. A `.coralfmt` wins over a `coral.toml` next to
+    /// it, and either wins over those further up. Without any, it's the default.
+    pub fn find(dir: &Path) -> Result<Self, ConfigError> {
+        for dir in dir.ancestors() {
+            let path = dir.join(Self::FILE);
+            if path.is_file() {
+                return Self::parse(&read(&path)?)
+                    .map_err(|source| ConfigError::Toml { path, source });
+            }
+            let path = dir.join(Self::MANIFEST);
+            if path.is_file() {
+                return toml::from_str::<Manifest>(&read(&path)?)
+                    .map(|manifest| manifest.fmt)
+                    .map_err(|source| ConfigError::Toml { path, source });
+            }
+        }
+        Ok(Self::default())
+    }
+
+    /// Parses the contents of a `.coralfmt`
+    pub fn parse(source: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(source)
+    }
+}
+
+fn read(path: &Path) -> Result<String, ConfigError> {
+    std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// The formatted source of `file`, given its `items`. `file` must have parsed without errors,
+/// since anything the parser skipped would be lost. Lines end in `\r\n` if the file's first one does.
+pub fn format(
+    ast: &Ast,
+    items: &[ItemId],
+    files: &FileArena,
+    file: FileId,
+    config: &Config,
+) -> String {
+    let trivia = Trivia::new(files, file);
+    if items.is_empty() && trivia.comments().is_empty() {
+        return String::new();
+    }
+    let mut out = print::document(ast, items, Some(trivia)).render(config.width, config.indent);
+    out.push('\n');
+    // The printer breaks lines with `\n`, block comments keep the breaks they were written with
+    let out = out.replace("\r\n", "\n");
+    let contents = &files[file].contents;
+    let crlf = contents
+        .find('\n')
+        .is_some_and(|end| contents[..end].ends_with('\r'));
+    if crlf {
+        out.replace('\n', "\r\n")
+    } else {
+        out
+    }
+}
+
+/// A unified diff from `original` to `formatted`, or nothing if they're the same
+pub fn diff(name: &str, original: &str, formatted: &str) -> String {
+    similar::TextDiff::from_lines(original, formatted)
+        .unified_diff()
+        .header(name, name)
+        .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use id_arena::Arena;
+
+    use crate::{
+        ast::ItemId,
+        error::CollectingContext,
+        fs::File,
+        lexer::{tokens::Tokens, Token},
+        parser::parse_file,
+        transaction::ParserState,
+        FileArena,
+    };
+
+    use super::{diff, format, Config};
+
+    fn fmt(source: &str, config: &Config) -> String {
+        let mut arena: FileArena = Arena::new();
+        let file = arena.alloc(File::new("test", source.to_string()));
+        let mut state = ParserState::new(
+            CollectingContext::new(),
+            Tokens::from(Token::lexer_from_file(&arena, file)),
+        );
+        let items: Vec<ItemId> = parse_file(&mut state);
+        let (ctx, _, ast) = state.into_parts();
+        assert_eq!(ctx.error_count(), 0, "{source:?} should parse");
+        format(&ast, &items, &arena, file, config)
+    }
+
+    #[test]
+    fn keeps_comments_and_blank_lines() {
+        let source = "// The entry point\nfn main(): void {\n  let x = 1;   // one\n\n\n\n  /* then */\n  let y = x+1;\n  // nothing left\n}\n// the end\n";
+        let formatted = fmt(source, &Config::default());
+        assert_eq!(
+            formatted,
+            "// The entry point\nfn main(): void {\n    let x = 1; // one\n\n    /* then */\n    let y = x + 1;\n    // nothing left\n}\n// the end\n"
+        );
+        assert_eq!(fmt(&formatted, &Config::default()), formatted);
+        assert_eq!(fmt("", &Config::default()), "");
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        let source = "// The entry point\r\nfn main(): void {\r\n  let x = 1;   // one\r\n\r\n\r\n  /* then\r\n  more */\r\n  x+1\r\n}\r\n";
+        let formatted = fmt(source, &Config::default());
+        assert_eq!(
+            formatted,
+            "// The entry point\r\nfn main(): void {\r\n    let x = 1; // one\r\n\r\n    /* then\r\n  more */\r\n    x + 1\r\n}\r\n"
+        );
+        assert_eq!(fmt(&formatted, &Config::default()), formatted);
+    }
+
+    #[test]
+    fn breaks_to_the_configured_width() {
+        let source = "fn f(): void { let t = (first, second, third); }";
+        let config = Config {
+            width: 24,
+            indent: 2,
+        };
+        let formatted = fmt(source, &config);
+        assert_eq!(
+            formatted,
+            "fn f(): void {\n  let t = (\n    first,\n    second,\n    third,\n  );\n}\n"
+        );
+        assert_eq!(fmt(&formatted, &config), formatted);
+    }
+
+    #[test]
+    fn reads_config() {
+        assert_eq!(
+            Config::parse("width = 80").unwrap(),
+            Config {
+                width: 80,
+                ..Config::default()
+            }
+        );
+        assert!(Config::parse("widht = 80").is_err());
+
+        let dir = std::env::temp_dir().join(format!("coral-fmt-{}", std::process::id()));
+        let nested = dir.join("src");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(dir.join("coral.toml"), "name = \"x\"\n[fmt]\nindent = 2\n").unwrap();
+        assert_eq!(Config::find(&nested).unwrap().indent, 2);
+        std::fs::write(dir.join(".coralfmt"), "indent = 8\n").unwrap();
+        assert_eq!(Config::find(&nested).unwrap().indent, 8);
+        std::fs::write(nested.join(".coralfmt"), "indent =").unwrap();
+        assert!(Config::find(&nested).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn diffs_lines() {
+        assert_eq!(diff("f", "a\n", "a\n"), "");
+        assert_eq!(
+            diff("f", "a\nb\n", "a\nc\n"),
+            "--- f\n+++ f\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n"
+        );
+    }
+}
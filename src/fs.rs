@@ -1,11 +1,131 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, sync::OnceLock};
 
 use id_arena::Id;
 use miette::{SourceCode, SpanContents};
 
+mod loader;
+pub use loader::{DiskLoader, LoadError, MemoryLoader, SourceLoader, SourceMap, StdinLoader};
+
+#[derive(Debug)]
 pub struct File {
     pub name: Cow<'static, str>,
     pub contents: Cow<'static, str>,
+    /// Whether the file started with a UTF-8 byte order mark, which isn't part of `contents`
+    pub bom: bool,
+    /// Byte offset of the start of every line, built the first time it's needed
+    line_starts: OnceLock<Vec<usize>>,
+}
+
+/// Unit columns are counted in. LSP clients pick one of these, miette and SARIF use code points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColumnMode {
+    /// Bytes
+    #[default]
+    Utf8,
+    /// UTF-16 code units, where characters outside the BMP count twice
+    Utf16,
+    /// Code points
+    Utf32,
+}
+
+impl ColumnMode {
+    fn width(self, c: char) -> usize {
+        match self {
+            Self::Utf8 => c.len_utf8(),
+            Self::Utf16 => c.len_utf16(),
+            Self::Utf32 => 1,
+        }
+    }
+}
+
+/// 0-based line and column, as used by LSP. Add one to both before showing them to people.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineCol {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl File {
+    pub fn new(name: impl Into<Cow<'static, str>>, contents: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            name: name.into(),
+            contents: contents.into(),
+            bom: false,
+            line_starts: OnceLock::new(),
+        }
+    }
+
+    /// The bytes to write back in place of the file for `contents`, which start with the byte
+    /// order mark if the file did
+    pub fn encode(&self, contents: &str) -> Vec<u8> {
+        let bom = if self.bom { loader::BOM } else { &[] };
+        [bom, contents.as_bytes()].concat()
+    }
+
+    fn line_starts(&self) -> &[usize] {
+        self.line_starts.get_or_init(|| {
+            std::iter::once(0)
+                .chain(self.contents.match_indices('\n').map(|(i, _)| i + 1))
+                .collect()
+        })
+    }
+
+    /// Number of lines, counting the empty line after a trailing newline
+    pub fn line_count(&self) -> usize {
+        self.line_starts().len()
+    }
+
+    /// Text of the 0-based `line` without its `\n` or `\r\n`
+    pub fn line(&self, line: usize) -> Option<&str> {
+        let starts = self.line_starts();
+        let start = *starts.get(line)?;
+        let end = starts
+            .get(line + 1)
+            .map_or(self.contents.len(), |&next| next - 1);
+        let text = &self.contents[start..end];
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+
+    /// Position of a byte offset. Offsets inside a character count as its start,
+    /// offsets inside a line terminator as the end of the line.
+    ///
+    /// # Panics
+    /// If `offset` is past the end of the file.
+    pub fn offset_to_line_col(&self, offset: usize, mode: ColumnMode) -> LineCol {
+        assert!(
+            offset <= self.contents.len(),
+            "offset {offset} is past the end of `{}`",
+            self.name
+        );
+        let starts = self.line_starts();
+        let line = starts.partition_point(|&start| start <= offset) - 1;
+        let text = self.line(line).unwrap();
+        let within = (offset - starts[line]).min(text.len());
+        let column = text
+            .char_indices()
+            .take_while(|&(i, c)| i + c.len_utf8() <= within)
+            .map(|(_, c)| mode.width(c))
+            .sum();
+        LineCol { line, column }
+    }
+
+    /// Byte offset of a position, `None` if the line doesn't exist, the column is past
+    /// the end of the line or falls inside a character.
+    pub fn line_col_to_offset(&self, position: LineCol, mode: ColumnMode) -> Option<usize> {
+        let start = *self.line_starts().get(position.line)?;
+        let text = self.line(position.line)?;
+        let mut column = 0;
+        for (i, c) in text.char_indices() {
+            if column == position.column {
+                return Some(start + i);
+            }
+            column += mode.width(c);
+            if column > position.column {
+                return None;
+            }
+        }
+        (column == position.column).then_some(start + text.len())
+    }
 }
 
 impl SourceCode for File {
@@ -59,3 +179,62 @@ impl<'a> SpanContents<'a> for NamedSpan<'a> {
 }
 
 pub type FileId = Id<File>;
+
+#[cfg(test)]
+mod test {
+    use super::{ColumnMode, File, LineCol};
+
+    const fn at(line: usize, column: usize) -> LineCol {
+        LineCol { line, column }
+    }
+
+    #[test]
+    fn lines() {
+        let file = File::new("test", "ab\r\ncd\n\nef\n");
+        assert_eq!(file.line_count(), 5);
+        assert_eq!(file.line(0), Some("ab"));
+        assert_eq!(file.line(1), Some("cd"));
+        assert_eq!(file.line(2), Some(""));
+        assert_eq!(file.line(4), Some(""));
+        assert_eq!(file.line(5), None);
+    }
+
+    #[test]
+    fn crlf_and_trailing_newline() {
+        let file = File::new("test", "ab\r\ncd\n");
+        let utf8 = ColumnMode::Utf8;
+        assert_eq!(file.offset_to_line_col(1, utf8), at(0, 1));
+        // `\r` and `\n` both belong to the end of the line
+        assert_eq!(file.offset_to_line_col(2, utf8), at(0, 2));
+        assert_eq!(file.offset_to_line_col(3, utf8), at(0, 2));
+        assert_eq!(file.offset_to_line_col(4, utf8), at(1, 0));
+        assert_eq!(file.offset_to_line_col(7, utf8), at(2, 0));
+
+        assert_eq!(file.line_col_to_offset(at(0, 2), utf8), Some(2));
+        assert_eq!(file.line_col_to_offset(at(0, 3), utf8), None);
+        assert_eq!(file.line_col_to_offset(at(1, 0), utf8), Some(4));
+        assert_eq!(file.line_col_to_offset(at(2, 0), utf8), Some(7));
+        assert_eq!(file.line_col_to_offset(at(3, 0), utf8), None);
+    }
+
+    #[test]
+    fn column_modes() {
+        // `é` is 2 bytes and 1 UTF-16 unit, `𝕏` 4 bytes and 2 UTF-16 units
+        let file = File::new("test", "x\né𝕏y");
+        let y = 2 + 2 + 4;
+        assert_eq!(file.offset_to_line_col(y, ColumnMode::Utf8), at(1, 6));
+        assert_eq!(file.offset_to_line_col(y, ColumnMode::Utf16), at(1, 3));
+        assert_eq!(file.offset_to_line_col(y, ColumnMode::Utf32), at(1, 2));
+        // inside `𝕏`
+        assert_eq!(file.offset_to_line_col(y - 1, ColumnMode::Utf32), at(1, 1));
+
+        for mode in [ColumnMode::Utf8, ColumnMode::Utf16, ColumnMode::Utf32] {
+            for offset in (0..=file.contents.len()).filter(|&i| file.contents.is_char_boundary(i)) {
+                let position = file.offset_to_line_col(offset, mode);
+                assert_eq!(file.line_col_to_offset(position, mode), Some(offset));
+            }
+        }
+        // the middle of a surrogate pair
+        assert_eq!(file.line_col_to_offset(at(1, 2), ColumnMode::Utf16), None);
+    }
+}
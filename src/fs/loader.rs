@@ -0,0 +1,300 @@
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::FileArena;
+
+use super::{File, FileId};
+
+/// Where source files come from.
+/// [`SourceMap`] takes care of decoding, loaders only hand out bytes.
+pub trait SourceLoader {
+    /// Identity of `path`, the same for every way of spelling it
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, LoadError>;
+    fn read(&mut self, canonical: &Path) -> Result<Vec<u8>, LoadError>;
+}
+
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("couldn't read `{}`: {source}", .path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("`{}` doesn't exist", .path.display())]
+    NotFound { path: PathBuf },
+    #[error("`{}` isn't valid UTF-8: invalid byte at offset {offset}", .path.display())]
+    InvalidUtf8 { path: PathBuf, offset: usize },
+}
+
+impl LoadError {
+    fn io(path: &Path, source: std::io::Error) -> Self {
+        if source.kind() == std::io::ErrorKind::NotFound {
+            Self::NotFound {
+                path: path.to_path_buf(),
+            }
+        } else {
+            Self::Io {
+                path: path.to_path_buf(),
+                source,
+            }
+        }
+    }
+}
+
+/// Files on the real filesystem, identified by their canonical path so symlinks
+/// and `..` don't load a file twice
+#[derive(Debug, Default)]
+pub struct DiskLoader;
+
+impl SourceLoader for DiskLoader {
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, LoadError> {
+        std::fs::canonicalize(path).map_err(|err| LoadError::io(path, err))
+    }
+
+    fn read(&mut self, canonical: &Path) -> Result<Vec<u8>, LoadError> {
+        std::fs::read(canonical).map_err(|err| LoadError::io(canonical, err))
+    }
+}
+
+/// Standard input, whatever path it's asked for. It can only be read once,
+/// which is fine since [`SourceMap`] never reads a path twice.
+#[derive(Debug, Default)]
+pub struct StdinLoader;
+
+impl StdinLoader {
+    pub const PATH: &'static str = "<stdin>";
+}
+
+impl SourceLoader for StdinLoader {
+    fn canonicalize(&self, _path: &Path) -> Result<PathBuf, LoadError> {
+        Ok(PathBuf::from(Self::PATH))
+    }
+
+    fn read(&mut self, canonical: &Path) -> Result<Vec<u8>, LoadError> {
+        let mut contents = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut contents)
+            .map_err(|err| LoadError::io(canonical, err))?;
+        Ok(contents)
+    }
+}
+
+/// A virtual filesystem for tests. Paths are normalized lexically.
+#[derive(Debug, Default)]
+pub struct MemoryLoader {
+    files: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_file(mut self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) -> Self {
+        self.insert(path, contents);
+        self
+    }
+
+    pub fn insert(&mut self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) {
+        self.files.insert(normalize(path.as_ref()), contents.into());
+    }
+}
+
+/// Resolves `.` and `..` without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+impl SourceLoader for MemoryLoader {
+    fn canonicalize(&self, path: &Path) -> Result<PathBuf, LoadError> {
+        let normalized = normalize(path);
+        if self.files.contains_key(&normalized) {
+            Ok(normalized)
+        } else {
+            Err(LoadError::NotFound {
+                path: path.to_path_buf(),
+            })
+        }
+    }
+
+    fn read(&mut self, canonical: &Path) -> Result<Vec<u8>, LoadError> {
+        self.files
+            .get(canonical)
+            .cloned()
+            .ok_or_else(|| LoadError::NotFound {
+                path: canonical.to_path_buf(),
+            })
+    }
+}
+
+pub(super) const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// The [`FileArena`] along with the path every file was loaded from,
+/// so loading a path twice gives back the same [`FileId`]
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: FileArena,
+    by_path: HashMap<PathBuf, FileId>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads `path` through `loader` unless it was already loaded.
+    /// The file is named after `path` as given, so diagnostics show what the user typed.
+    pub fn load(
+        &mut self,
+        loader: &mut impl SourceLoader,
+        path: impl AsRef<Path>,
+    ) -> Result<FileId, LoadError> {
+        let path = path.as_ref();
+        let canonical = loader.canonicalize(path)?;
+        if let Some(&id) = self.by_path.get(&canonical) {
+            return Ok(id);
+        }
+        let bytes = loader.read(&canonical)?;
+        let (skipped, bytes) = match bytes.strip_prefix(BOM) {
+            Some(rest) => (BOM.len(), rest.to_vec()),
+            None => (0, bytes),
+        };
+        let contents = String::from_utf8(bytes).map_err(|err| LoadError::InvalidUtf8 {
+            path: path.to_path_buf(),
+            offset: skipped + err.utf8_error().valid_up_to(),
+        })?;
+        let mut file = File::new(path.display().to_string(), contents);
+        file.bom = skipped != 0;
+        let id = self.files.alloc(file);
+        self.by_path.insert(canonical, id);
+        Ok(id)
+    }
+
+    /// Adds a file that doesn't come from any loader, such as a REPL line
+    pub fn add(&mut self, file: File) -> FileId {
+        self.files.alloc(file)
+    }
+
+    pub const fn files(&self) -> &FileArena {
+        &self.files
+    }
+
+    pub fn into_files(self) -> FileArena {
+        self.files
+    }
+}
+
+impl std::ops::Index<FileId> for SourceMap {
+    type Output = File;
+
+    fn index(&self, index: FileId) -> &Self::Output {
+        &self.files[index]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use crate::{fs::ColumnMode, lexer::Token, span::Span};
+
+    use super::{DiskLoader, LoadError, MemoryLoader, SourceMap};
+
+    #[test]
+    fn dedupes_by_path() {
+        let mut loader = MemoryLoader::new()
+            .with_file("src/main.coral", "fn main(): void {}")
+            .with_file("src/other.coral", "");
+        let mut sources = SourceMap::new();
+        let main = sources.load(&mut loader, "src/main.coral").unwrap();
+        let again = sources
+            .load(&mut loader, "src/../src/./main.coral")
+            .unwrap();
+        let other = sources.load(&mut loader, "src/other.coral").unwrap();
+        assert_eq!(main, again);
+        assert_ne!(main, other);
+        assert_eq!(sources.files().len(), 2);
+        assert_eq!(sources[main].name, "src/main.coral");
+    }
+
+    #[test]
+    fn decoding() {
+        let mut loader = MemoryLoader::new()
+            .with_file("bom.coral", b"\xEF\xBB\xBFfn".to_vec())
+            .with_file("latin1.coral", b"fn \xE9".to_vec());
+        let mut sources = SourceMap::new();
+        let bom = sources.load(&mut loader, "bom.coral").unwrap();
+        assert_eq!(sources[bom].contents, "fn");
+        assert!(sources[bom].bom);
+        assert_eq!(sources[bom].encode("fn f"), b"\xEF\xBB\xBFfn f");
+
+        let err = sources.load(&mut loader, "latin1.coral").unwrap_err();
+        assert!(matches!(err, LoadError::InvalidUtf8 { offset: 3, .. }));
+        assert_eq!(
+            err.to_string(),
+            "`latin1.coral` isn't valid UTF-8: invalid byte at offset 3"
+        );
+
+        let err = sources.load(&mut loader, "missing.coral").unwrap_err();
+        assert!(matches!(err, LoadError::NotFound { .. }));
+    }
+
+    #[test]
+    fn disk() {
+        let dir = std::env::temp_dir().join(format!("coral-loader-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.coral"), "fn a(): void {}").unwrap();
+
+        let mut sources = SourceMap::new();
+        let a = sources.load(&mut DiskLoader, dir.join("a.coral")).unwrap();
+        let again = sources
+            .load(&mut DiskLoader, dir.join(".").join("a.coral"))
+            .unwrap();
+        assert_eq!(a, again);
+        assert_eq!(sources[a].contents, "fn a(): void {}");
+        let missing = sources.load(&mut DiskLoader, Path::new(&dir).join("b.coral"));
+        assert!(matches!(missing, Err(LoadError::NotFound { .. })));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn disk_crlf() {
+        let dir = std::env::temp_dir().join(format!("coral-loader-crlf-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let text = "fn a(): void {\r\n    let x = 1;\r\n}\r\n";
+        std::fs::write(dir.join("crlf.coral"), text).unwrap();
+
+        let mut sources = SourceMap::new();
+        let file = sources
+            .load(&mut DiskLoader, dir.join("crlf.coral"))
+            .unwrap();
+        // Line endings are left alone, the lexer and the line index both handle them
+        assert_eq!(sources[file].contents, text);
+        let files = sources.files();
+        let tokens: Vec<_> = Token::lexer_from_file(files, file).spanned().collect();
+        assert!(tokens.iter().all(|(token, _)| token.is_ok()), "{tokens:?}");
+        let (_, range) = &tokens[7];
+        let position = Span::new(file, range.clone()).start_position(files, ColumnMode::Utf8);
+        assert_eq!((position.line, position.column), (1, 4));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,646 @@
+//! The high-level IR that checked functions are lowered to, see [`lower()`].
+//!
+//! It's a tree like the [`crate::ast`] is, but names are resolved, every expression has its
+//! type, and the sugar of the surface syntax is gone: `while` and `for` are `loop`s, `&&` and
+//! `||` are `if`s, `a += b` is `a = a + b`, `?` is a `match`, intrinsics are literals, and
+//! the references that field accesses and indexing see through are dereferenced explicitly.
+//! Every expression gets a [`DefId`] of its own and keeps the span of the source it comes
+//! from, which for desugared code is the span of the sugar, so nothing after this needs the
+//! AST.
+
+use std::fmt::Write as _;
+
+use crate::{ast::Identifier, span::Span, symbol::Symbol, typeck::ty::Ty};
+
+pub mod lower;
+
+pub use crate::ast::{BinaryOp, UnaryOp, Variant};
+pub use lower::lower;
+
+/// Identity of a function or an expression, unique across everything lowered together
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DefId(u32);
+
+impl DefId {
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl std::fmt::Display for DefId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "d{}", self.0)
+    }
+}
+
+/// A local of a [`Function`]: a parameter, a binding, or a temporary of a desugaring
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LocalId(u32);
+
+impl LocalId {
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Hir {
+    pub functions: Vec<Function>,
+    /// Where every definition comes from, indexed by [`DefId`]
+    spans: Vec<Span>,
+}
+
+impl Hir {
+    pub fn span(&self, def: DefId) -> Span {
+        self.spans[def.index()]
+    }
+
+    pub fn function(&self, def: DefId) -> Option<&Function> {
+        self.functions.iter().find(|function| function.def == def)
+    }
+
+    /// Number of [`DefId`]s handed out
+    pub fn def_count(&self) -> usize {
+        self.spans.len()
+    }
+}
+
+#[derive(Debug)]
+pub struct Function {
+    pub def: DefId,
+    pub name: Identifier,
+    pub span: Span,
+    pub params: Vec<LocalId>,
+    pub ret: Ty,
+    /// Every local of the function, including those of the closures in it
+    pub locals: Vec<Local>,
+    /// Always an [`ExprKind::Block`]
+    pub body: Expr,
+}
+
+impl std::ops::Index<LocalId> for Function {
+    type Output = Local;
+
+    fn index(&self, index: LocalId) -> &Self::Output {
+        &self.locals[index.index()]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Local {
+    /// `None` for temporaries
+    pub name: Option<Identifier>,
+    pub ty: Ty,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    /// The value of the block, without which it's `void` or never finishes
+    pub expr: Option<Box<Expr>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    /// Without an `init`, the bindings are assigned later
+    Let {
+        pat: Pat,
+        init: Option<Expr>,
+        span: Span,
+    },
+    Expr(Expr),
+}
+
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Let { span, .. } => *span,
+            Self::Expr(expr) => expr.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub def: DefId,
+    pub ty: Ty,
+    pub span: Span,
+    pub kind: ExprKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+    Literal(Literal),
+    Local(LocalId),
+    /// A function item
+    Fn(DefId),
+    /// Never `&&` or `||`, which are [`ExprKind::If`]s
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
+    Ref {
+        mutable: bool,
+        operand: Box<Expr>,
+    },
+    /// To the type of the expression
+    Cast(Box<Expr>),
+    Call {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    Array(Vec<Expr>),
+    ArrayRepeat {
+        value: Box<Expr>,
+        count: u64,
+    },
+    /// Never empty, that's a `void` [`ExprKind::Block`]
+    Tuple(Vec<Expr>),
+    /// The base is an array, not a reference to one
+    Index {
+        base: Box<Expr>,
+        index: Box<Expr>,
+    },
+    /// A field of a tuple, or of a range: `0` is its start, `1` its end and `2` whether it's
+    /// inclusive
+    Field {
+        base: Box<Expr>,
+        index: u32,
+    },
+    Range {
+        start: Box<Expr>,
+        end: Box<Expr>,
+        inclusive: bool,
+    },
+    /// A value of an `Option` or a `Result`
+    Variant {
+        variant: Variant,
+        value: Option<Box<Expr>>,
+    },
+    Closure {
+        params: Vec<Pat>,
+        body: Box<Expr>,
+    },
+    Block(Block),
+    /// Both branches are [`ExprKind::Block`]s, or the `else` one is another `if`
+    If {
+        condition: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Option<Box<Expr>>,
+    },
+    Loop(Block),
+    Break(Option<Box<Expr>>),
+    Continue,
+    Return(Option<Box<Expr>>),
+    /// The first arm whose pattern matches is taken
+    Match {
+        scrutinee: Box<Expr>,
+        arms: Vec<Arm>,
+    },
+    Assign {
+        place: Box<Expr>,
+        value: Box<Expr>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Literal {
+    Int(u128),
+    /// As written, the type decides how it's read
+    Float(Symbol),
+    Char(char),
+    Str(Symbol),
+    Bool(bool),
+}
+
+impl Expr {
+    /// Calls `f` on this expression and every one inside it, parents first, including the
+    /// bodies of closures
+    pub fn walk<'a>(&'a self, f: &mut impl FnMut(&'a Self)) {
+        f(self);
+        match &self.kind {
+            ExprKind::Literal(_) | ExprKind::Local(_) | ExprKind::Fn(_) | ExprKind::Continue => {}
+            ExprKind::Unary { operand, .. }
+            | ExprKind::Ref { operand, .. }
+            | ExprKind::Cast(operand)
+            | ExprKind::ArrayRepeat { value: operand, .. }
+            | ExprKind::Field { base: operand, .. }
+            | ExprKind::Closure { body: operand, .. } => operand.walk(f),
+            ExprKind::Binary { lhs, rhs, .. }
+            | ExprKind::Index {
+                base: lhs,
+                index: rhs,
+            }
+            | ExprKind::Range {
+                start: lhs,
+                end: rhs,
+                ..
+            }
+            | ExprKind::Assign {
+                place: lhs,
+                value: rhs,
+            } => {
+                lhs.walk(f);
+                rhs.walk(f);
+            }
+            ExprKind::Call { callee, args } => {
+                callee.walk(f);
+                args.iter().for_each(|arg| arg.walk(f));
+            }
+            ExprKind::Array(elements) | ExprKind::Tuple(elements) => {
+                elements.iter().for_each(|element| element.walk(f));
+            }
+            ExprKind::Variant { value, .. } | ExprKind::Break(value) | ExprKind::Return(value) => {
+                value.iter().for_each(|value| value.walk(f));
+            }
+            ExprKind::Block(block) | ExprKind::Loop(block) => block.walk(f),
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                condition.walk(f);
+                then_branch.walk(f);
+                else_branch.iter().for_each(|branch| branch.walk(f));
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                scrutinee.walk(f);
+                arms.iter().for_each(|arm| arm.body.walk(f));
+            }
+        }
+    }
+}
+
+impl Block {
+    /// Like [`Expr::walk`] for every expression in the block
+    pub fn walk<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+        for stmt in &self.stmts {
+            match stmt {
+                Stmt::Let { init: None, .. } => {}
+                Stmt::Let {
+                    init: Some(expr), ..
+                }
+                | Stmt::Expr(expr) => expr.walk(f),
+            }
+        }
+        if let Some(expr) = &self.expr {
+            expr.walk(f);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Arm {
+    pub pat: Pat,
+    pub body: Expr,
+}
+
+#[derive(Debug, Clone)]
+pub struct Pat {
+    pub ty: Ty,
+    pub span: Span,
+    pub kind: PatKind,
+}
+
+impl Pat {
+    /// Calls `f` on every local the pattern binds, in order
+    pub fn bindings(&self, f: &mut impl FnMut(LocalId)) {
+        match &self.kind {
+            PatKind::Wild | PatKind::Literal(_) | PatKind::Range { .. } => {}
+            &PatKind::Binding(local) => f(local),
+            PatKind::Tuple(elements) => elements.iter().for_each(|element| element.bindings(f)),
+            PatKind::Variant { inner, .. } => inner.iter().for_each(|inner| inner.bindings(f)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PatKind {
+    Wild,
+    Binding(LocalId),
+    /// Negative integers are in two's complement, at the width of the type
+    Literal(Literal),
+    /// The integers or characters from `start` up to `end`
+    Range {
+        start: Literal,
+        end: Literal,
+        inclusive: bool,
+    },
+    Tuple(Vec<Pat>),
+    Variant {
+        variant: Variant,
+        inner: Option<Box<Pat>>,
+    },
+}
+
+/// Prints the functions as Coral-like source, with locals numbered after their name and
+/// every binary operation parenthesized
+impl std::fmt::Display for Hir {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, function) in self.functions.iter().enumerate() {
+            if i != 0 {
+                f.write_str("\n")?;
+            }
+            let mut printer = Printer {
+                hir: self,
+                function,
+                out: String::new(),
+                indent: 0,
+            };
+            printer.function();
+            f.write_str(&printer.out)?;
+        }
+        Ok(())
+    }
+}
+
+struct Printer<'a> {
+    hir: &'a Hir,
+    function: &'a Function,
+    out: String,
+    indent: usize,
+}
+
+impl Printer<'_> {
+    fn function(&mut self) {
+        let function = self.function;
+        write!(self.out, "fn {}(", function.name).unwrap();
+        for (i, &param) in function.params.iter().enumerate() {
+            if i != 0 {
+                self.out.push_str(", ");
+            }
+            self.local(param);
+            write!(self.out, ": {}", function[param].ty).unwrap();
+        }
+        write!(self.out, "): {} ", function.ret).unwrap();
+        self.expr(&function.body);
+        self.out.push('\n');
+    }
+
+    fn local(&mut self, id: LocalId) {
+        match self.function[id].name {
+            Some(name) => write!(self.out, "{name}_{}", id.index()),
+            None => write!(self.out, "_{}", id.index()),
+        }
+        .unwrap();
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        if block.stmts.is_empty() && block.expr.is_none() {
+            self.out.push_str("{}");
+            return;
+        }
+        self.out.push('{');
+        self.indent += 1;
+        for stmt in &block.stmts {
+            self.newline();
+            match stmt {
+                Stmt::Let { pat, init, .. } => {
+                    self.out.push_str("let ");
+                    self.pat(pat);
+                    write!(self.out, ": {}", pat.ty).unwrap();
+                    if let Some(init) = init {
+                        self.out.push_str(" = ");
+                        self.expr(init);
+                    }
+                }
+                Stmt::Expr(expr) => self.expr(expr),
+            }
+            self.out.push(';');
+        }
+        if let Some(expr) = &block.expr {
+            self.newline();
+            self.expr(expr);
+        }
+        self.indent -= 1;
+        self.newline();
+        self.out.push('}');
+    }
+
+    fn list(&mut self, exprs: &[Expr]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i != 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expr);
+        }
+    }
+
+    fn literal(&mut self, literal: Literal) {
+        match literal {
+            Literal::Int(value) => write!(self.out, "{value}"),
+            Literal::Float(value) => write!(self.out, "{value}"),
+            Literal::Char(value) => write!(self.out, "{value:?}"),
+            Literal::Str(value) => write!(self.out, "{:?}", value.as_str()),
+            Literal::Bool(value) => write!(self.out, "{value}"),
+        }
+        .unwrap();
+    }
+
+    /// A literal in a pattern, whose negative integers are in two's complement
+    fn pat_literal(&mut self, literal: Literal, ty: &Ty) {
+        match (literal, ty) {
+            (Literal::Int(value), &Ty::Int(ty)) => self.out.push_str(&ty.format_bits(value)),
+            _ => self.literal(literal),
+        }
+    }
+
+    fn pat(&mut self, pat: &Pat) {
+        match &pat.kind {
+            PatKind::Wild => self.out.push('_'),
+            &PatKind::Binding(local) => self.local(local),
+            &PatKind::Literal(literal) => self.pat_literal(literal, &pat.ty),
+            &PatKind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                self.pat_literal(start, &pat.ty);
+                self.out.push_str(if inclusive { "..=" } else { ".." });
+                self.pat_literal(end, &pat.ty);
+            }
+            PatKind::Tuple(elements) => {
+                self.out.push('(');
+                for (i, element) in elements.iter().enumerate() {
+                    if i != 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.pat(element);
+                }
+                if elements.len() == 1 {
+                    self.out.push(',');
+                }
+                self.out.push(')');
+            }
+            PatKind::Variant { variant, inner } => {
+                self.out.push_str(variant.as_str());
+                if let Some(inner) = inner {
+                    self.out.push('(');
+                    self.pat(inner);
+                    self.out.push(')');
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            &ExprKind::Literal(literal) => self.literal(literal),
+            &ExprKind::Local(local) => self.local(local),
+            &ExprKind::Fn(def) => match self.hir.function(def) {
+                Some(function) => write!(self.out, "{}", function.name).unwrap(),
+                None => write!(self.out, "{def}").unwrap(),
+            },
+            ExprKind::Binary { op, lhs, rhs } => {
+                self.out.push('(');
+                self.expr(lhs);
+                write!(self.out, " {op} ").unwrap();
+                self.expr(rhs);
+                self.out.push(')');
+            }
+            ExprKind::Unary { op, operand } => {
+                write!(self.out, "({op}").unwrap();
+                self.expr(operand);
+                self.out.push(')');
+            }
+            ExprKind::Ref { mutable, operand } => {
+                self.out.push_str(if *mutable { "(&mut " } else { "(&" });
+                self.expr(operand);
+                self.out.push(')');
+            }
+            ExprKind::Cast(operand) => {
+                self.out.push('(');
+                self.expr(operand);
+                write!(self.out, " as {})", expr.ty).unwrap();
+            }
+            ExprKind::Call { callee, args } => {
+                self.expr(callee);
+                self.out.push('(');
+                self.list(args);
+                self.out.push(')');
+            }
+            ExprKind::Array(elements) => {
+                self.out.push('[');
+                self.list(elements);
+                self.out.push(']');
+            }
+            ExprKind::ArrayRepeat { value, count } => {
+                self.out.push('[');
+                self.expr(value);
+                write!(self.out, "; {count}]").unwrap();
+            }
+            ExprKind::Tuple(elements) => {
+                self.out.push('(');
+                self.list(elements);
+                if elements.len() == 1 {
+                    self.out.push(',');
+                }
+                self.out.push(')');
+            }
+            ExprKind::Index { base, index } => {
+                self.expr(base);
+                self.out.push('[');
+                self.expr(index);
+                self.out.push(']');
+            }
+            ExprKind::Field { base, index } => {
+                self.expr(base);
+                write!(self.out, ".{index}").unwrap();
+            }
+            ExprKind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                self.out.push('(');
+                self.expr(start);
+                self.out.push_str(if *inclusive { "..=" } else { ".." });
+                self.expr(end);
+                self.out.push(')');
+            }
+            ExprKind::Variant { variant, value } => {
+                self.out.push_str(variant.as_str());
+                if let Some(value) = value {
+                    self.out.push('(');
+                    self.expr(value);
+                    self.out.push(')');
+                }
+            }
+            ExprKind::Closure { params, body } => {
+                self.out.push('|');
+                for (i, param) in params.iter().enumerate() {
+                    if i != 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.pat(param);
+                }
+                self.out.push_str("| ");
+                self.expr(body);
+            }
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.out.push_str("if ");
+                self.expr(condition);
+                self.out.push(' ');
+                self.expr(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.out.push_str(" else ");
+                    self.expr(else_branch);
+                }
+            }
+            ExprKind::Loop(body) => {
+                self.out.push_str("loop ");
+                self.block(body);
+            }
+            ExprKind::Break(value) | ExprKind::Return(value) => {
+                self.out.push_str(match expr.kind {
+                    ExprKind::Break(_) => "break",
+                    _ => "return",
+                });
+                if let Some(value) = value {
+                    self.out.push(' ');
+                    self.expr(value);
+                }
+            }
+            ExprKind::Continue => self.out.push_str("continue"),
+            ExprKind::Match { scrutinee, arms } => {
+                self.out.push_str("match ");
+                self.expr(scrutinee);
+                self.out.push_str(" {");
+                self.indent += 1;
+                for arm in arms {
+                    self.newline();
+                    self.pat(&arm.pat);
+                    self.out.push_str(" => ");
+                    self.expr(&arm.body);
+                    self.out.push(',');
+                }
+                self.indent -= 1;
+                self.newline();
+                self.out.push('}');
+            }
+            ExprKind::Assign { place, value } => {
+                self.expr(place);
+                self.out.push_str(" = ");
+                self.expr(value);
+            }
+        }
+    }
+}
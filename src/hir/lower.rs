@@ -0,0 +1,1207 @@
+//! Lowering of checked functions from the [`Ast`] to the [`Hir`].
+//!
+//! The desugarings produce code that could have been written by hand, with the temporaries
+//! it needs as unnamed locals:
+//!
+//! - `for pattern in start..end { body }` counts `next` up to `end`, with `more` saying whether
+//!   there's another element so that an inclusive range ending at the largest value of its
+//!   type doesn't overflow:
+//!   `{ let next = start; let end = end; let more = next < end;
+//!   loop { match more { true => { let pattern = next; if next < end { next = next + 1;
+//!   more = next < end } else { more = false }; body } false => break } } }`, where both
+//!   `next < end` that decide `more` are `if inclusive { .. } else { next < end }`, with
+//!   `next <= end` and `true` for an inclusive range.
+//! - `for pattern in array { body }` indexes the array with a counter up to its length, and
+//!   takes references to the elements of a reference to an array.
+//! - `while condition { body }` is `loop { if condition { body } else { break } }`.
+//! - `place op= value` is `place = place op value`, where the operands of indexing and
+//!   dereferences in `place` are evaluated once, into temporaries, beforehand.
+//! - `operand?` is `match operand { Ok(value) => value, Err(err) => return Err(err) }`, or
+//!   the same with `Some` and `None`.
+//! - `Some`, `Ok` and `Err` other than in a call are `|value| Some(value)`, the function that
+//!   calling them calls.
+
+use crate::{
+    ast::{
+        self, Ast, ExprId, ExpressionKind, ItemId, ItemKind, LiteralPattern, NodeMap, PatternKind,
+        StatementKind,
+    },
+    fs::ColumnMode,
+    resolve::{DeclarationKind, Resolutions},
+    span::Span,
+    symbol::{sym, Symbol},
+    typeck::{
+        ty::{IntTy, Ty},
+        TypeckResults,
+    },
+    FileArena,
+};
+
+use super::{
+    Arm, BinaryOp, Block, DefId, Expr, ExprKind, Function, Hir, Literal, Local, LocalId, Pat,
+    PatKind, Stmt, UnaryOp, Variant,
+};
+
+/// Lowers the functions in `items`, which have to have been checked without errors into
+/// `results`
+pub fn lower(
+    ast: &Ast,
+    items: &[ItemId],
+    resolutions: &Resolutions,
+    results: &TypeckResults,
+    files: &FileArena,
+) -> Hir {
+    let mut hir = Hir::default();
+    // Functions get their ids first, so that calls can refer to functions defined later
+    let mut functions = NodeMap::default();
+    for &item in items {
+        let def = DefId(hir.spans.len() as u32);
+        hir.spans.push(ast[item].span);
+        functions.insert(ast[item].id, def);
+    }
+    for &item in items {
+        let mut lowerer = Lowerer {
+            ast,
+            resolutions,
+            results,
+            files,
+            functions: &functions,
+            spans: &mut hir.spans,
+            locals: Vec::new(),
+            local_ids: NodeMap::default(),
+            return_ty: Ty::Void,
+        };
+        let function = lowerer.function(item);
+        hir.functions.push(function);
+    }
+    hir
+}
+
+struct Lowerer<'a, 'h> {
+    ast: &'a Ast,
+    resolutions: &'a Resolutions,
+    results: &'a TypeckResults,
+    files: &'a FileArena,
+    functions: &'a NodeMap<DefId>,
+    spans: &'h mut Vec<Span>,
+    /// The locals of the function being lowered
+    locals: Vec<Local>,
+    /// The locals of the parameters and bindings, keyed by their declaration
+    local_ids: NodeMap<LocalId>,
+    /// The return type of the function or closure being lowered, for desugaring `?`
+    return_ty: Ty,
+}
+
+impl Lowerer<'_, '_> {
+    fn function(&mut self, item: ItemId) -> Function {
+        let item = &self.ast[item];
+        let ItemKind::Function {
+            name,
+            arguments,
+            body,
+            ..
+        } = &item.kind;
+        let Some(Ty::Fn { ret, .. }) = self.results.declarations.get(item.id) else {
+            unreachable!("functions are declared before they're checked")
+        };
+        self.return_ty = (**ret).clone();
+        let params = arguments
+            .iter()
+            .map(|param| {
+                let ty = self.results.declarations[param.id].clone();
+                let local = self.new_local(Some(param.name.data), ty, param.name.span);
+                self.local_ids.insert(param.id, local);
+                local
+            })
+            .collect();
+        let body = self.block(body);
+        Function {
+            def: self.functions[item.id],
+            name: name.data,
+            span: item.span,
+            params,
+            ret: (**ret).clone(),
+            locals: std::mem::take(&mut self.locals),
+            body,
+        }
+    }
+
+    fn expr(&mut self, id: ExprId) -> Expr {
+        let ast = self.ast;
+        let expression = &ast[id];
+        let span = expression.span;
+        let ty = self.results.expressions[expression.id].clone();
+        let kind = match &expression.kind {
+            ExpressionKind::Name(_) => {
+                if let Some(&variant) = self.resolutions.variants.get(expression.id) {
+                    return self.variant(span, ty, variant);
+                }
+                let Some((id, declaration)) = self.resolutions.declaration_of(expression.id) else {
+                    unreachable!("names are resolved before lowering")
+                };
+                match declaration.kind {
+                    DeclarationKind::Function(item) => ExprKind::Fn(self.functions[ast[item].id]),
+                    DeclarationKind::Param | DeclarationKind::Local => {
+                        ExprKind::Local(self.local_ids[id])
+                    }
+                }
+            }
+            ExpressionKind::Path(_) => unreachable!("paths are reported by the checker"),
+            ExpressionKind::Intrinsic(name) => {
+                let start = span.start_position(self.files, ColumnMode::Utf32);
+                ExprKind::Literal(match name.data.symbol() {
+                    sym::line => Literal::Int(start.line as u128 + 1),
+                    sym::column => Literal::Int(start.column as u128 + 1),
+                    sym::file => Literal::Str(Symbol::intern(span.filename(self.files))),
+                    _ => unreachable!("unknown intrinsics are reported by the checker"),
+                })
+            }
+            &ExpressionKind::IntegerLiteral { value, .. } => ExprKind::Literal(Literal::Int(value)),
+            &ExpressionKind::FloatLiteral(value) => ExprKind::Literal(Literal::Float(value)),
+            &ExpressionKind::CharLiteral(value) => ExprKind::Literal(Literal::Char(value)),
+            &ExpressionKind::StringLiteral(value) => ExprKind::Literal(Literal::Str(value)),
+            &ExpressionKind::BoolLiteral(value) => ExprKind::Literal(Literal::Bool(value)),
+            &ExpressionKind::Binary { op, lhs, rhs } => {
+                let lhs = self.expr(lhs);
+                let rhs = self.expr(rhs);
+                match op.data {
+                    BinaryOp::And => {
+                        let otherwise = self.bool(span, false);
+                        self.if_else(lhs, rhs, otherwise)
+                    }
+                    BinaryOp::Or => {
+                        let then = self.bool(span, true);
+                        self.if_else(lhs, then, rhs)
+                    }
+                    op => ExprKind::Binary {
+                        op,
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(rhs),
+                    },
+                }
+            }
+            &ExpressionKind::Unary { op, operand } => ExprKind::Unary {
+                op: op.data,
+                operand: Box::new(self.expr(operand)),
+            },
+            &ExpressionKind::Reference { mutable, operand } => ExprKind::Ref {
+                mutable,
+                operand: Box::new(self.expr(operand)),
+            },
+            ExpressionKind::Cast { expr, .. } => ExprKind::Cast(Box::new(self.expr(*expr))),
+            &ExpressionKind::Try(operand) => return self.try_operator(span, ty, operand),
+            ExpressionKind::Call { callee, arguments } => match (
+                self.resolutions.variants.get(ast[*callee].id),
+                &arguments[..],
+            ) {
+                (Some(&variant), &[value]) => ExprKind::Variant {
+                    variant,
+                    value: Some(Box::new(self.expr(value))),
+                },
+                _ => ExprKind::Call {
+                    callee: Box::new(self.expr(*callee)),
+                    args: arguments
+                        .iter()
+                        .map(|&argument| self.expr(argument))
+                        .collect(),
+                },
+            },
+            ExpressionKind::Array(elements) => {
+                ExprKind::Array(elements.iter().map(|&element| self.expr(element)).collect())
+            }
+            &ExpressionKind::ArrayRepeat { value, .. } => {
+                let Ty::Array(_, count) = ty else {
+                    unreachable!("a repeat expression is an array")
+                };
+                ExprKind::ArrayRepeat {
+                    value: Box::new(self.expr(value)),
+                    count,
+                }
+            }
+            ExpressionKind::Tuple(elements) if elements.is_empty() => ExprKind::Block(Block {
+                stmts: Vec::new(),
+                expr: None,
+            }),
+            ExpressionKind::Tuple(elements) => {
+                ExprKind::Tuple(elements.iter().map(|&element| self.expr(element)).collect())
+            }
+            &ExpressionKind::Index { base, index } => {
+                let base = self.expr(base);
+                ExprKind::Index {
+                    base: Box::new(self.deref_all(base)),
+                    index: Box::new(self.expr(index)),
+                }
+            }
+            &ExpressionKind::TupleField { base, field } => {
+                let base = self.expr(base);
+                ExprKind::Field {
+                    base: Box::new(self.deref_all(base)),
+                    index: field.data,
+                }
+            }
+            &ExpressionKind::Range {
+                start,
+                end,
+                inclusive,
+            } => ExprKind::Range {
+                start: Box::new(self.expr(start)),
+                end: Box::new(self.expr(end)),
+                inclusive,
+            },
+            ExpressionKind::Block(block) => ExprKind::Block(self.block_contents(block)),
+            ExpressionKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => ExprKind::If {
+                condition: Box::new(self.expr(*condition)),
+                then_branch: Box::new(self.block(then_branch)),
+                else_branch: else_branch.map(|else_branch| Box::new(self.expr(else_branch))),
+            },
+            ExpressionKind::While { condition, body } => {
+                let condition = self.expr(*condition);
+                let body = self.block(body);
+                let exit = self.mk(span, Ty::Never, ExprKind::Break(None));
+                let kind = self.if_else(condition, body, exit);
+                let check = self.mk(span, Ty::Void, kind);
+                ExprKind::Loop(Block {
+                    stmts: vec![Stmt::Expr(check)],
+                    expr: None,
+                })
+            }
+            ExpressionKind::For {
+                pattern,
+                iterable,
+                body,
+            } => return self.for_loop(span, pattern, *iterable, body),
+            ExpressionKind::Match { scrutinee, arms } => {
+                let scrutinee = self.expr(*scrutinee);
+                let arms = arms
+                    .iter()
+                    .map(|arm| Arm {
+                        pat: self.pattern(&arm.pattern, scrutinee.ty.clone()),
+                        body: self.expr(arm.body),
+                    })
+                    .collect();
+                ExprKind::Match {
+                    scrutinee: Box::new(scrutinee),
+                    arms,
+                }
+            }
+            ExpressionKind::Loop(body) => ExprKind::Loop(self.block_contents(body)),
+            ExpressionKind::Break(value) => {
+                ExprKind::Break(value.map(|value| Box::new(self.expr(value))))
+            }
+            ExpressionKind::Continue => ExprKind::Continue,
+            ExpressionKind::Return(value) => {
+                ExprKind::Return(value.map(|value| Box::new(self.expr(value))))
+            }
+            &ExpressionKind::Assign { target, value } => ExprKind::Assign {
+                place: Box::new(self.expr(target)),
+                value: Box::new(self.expr(value)),
+            },
+            &ExpressionKind::CompoundAssign { op, target, value } => {
+                return self.compound_assign(span, op.data, target, value)
+            }
+            ExpressionKind::Closure { params, body } => {
+                let Ty::Fn { params: tys, ret } = &ty else {
+                    unreachable!("a closure is a function")
+                };
+                let params = params
+                    .iter()
+                    .zip(tys)
+                    .map(|(param, ty)| self.pattern(&param.pattern, ty.clone()))
+                    .collect();
+                let outer = std::mem::replace(&mut self.return_ty, (**ret).clone());
+                let body = self.expr(*body);
+                self.return_ty = outer;
+                ExprKind::Closure {
+                    params,
+                    body: Box::new(body),
+                }
+            }
+        };
+        self.mk(span, ty, kind)
+    }
+
+    /// A block that isn't an expression of its own, like the body of a function or a loop
+    fn block(&mut self, block: &ast::Block) -> Expr {
+        let contents = self.block_contents(block);
+        self.block_expr(block.span, contents)
+    }
+
+    fn block_contents(&mut self, block: &ast::Block) -> Block {
+        let mut stmts = Vec::new();
+        let mut expr = None;
+        for &statement in &block.statements {
+            match &self.ast[statement].kind {
+                StatementKind::Let { pattern, value, .. } => {
+                    let init = value.map(|value| self.expr(value));
+                    let ty = match (self.pattern_ty(pattern), &init) {
+                        (Some(ty), _) => ty,
+                        (None, Some(init)) => init.ty.clone(),
+                        (None, None) => self.unassigned_pattern_ty(pattern),
+                    };
+                    let pat = self.pattern(pattern, ty);
+                    let span = self.ast[statement].span;
+                    stmts.push(Stmt::Let { pat, init, span });
+                }
+                &StatementKind::Expr(value) => stmts.push(Stmt::Expr(self.expr(value))),
+                &StatementKind::ReturnExpr(value) => expr = Some(Box::new(self.expr(value))),
+            }
+        }
+        Block { stmts, expr }
+    }
+
+    /// The type of `pattern` as far as its bindings tell, which is all of it without `_`s
+    fn pattern_ty(&self, pattern: &ast::Pattern) -> Option<Ty> {
+        match &pattern.kind {
+            PatternKind::Wildcard => None,
+            PatternKind::Binding(_) if self.resolutions.variants.contains(pattern.id) => {
+                self.results.patterns.get(pattern.id).cloned()
+            }
+            PatternKind::Binding(_) => self.results.declarations.get(pattern.id).cloned(),
+            PatternKind::Literal(_) | PatternKind::Range { .. } | PatternKind::Variant { .. } => {
+                self.results.patterns.get(pattern.id).cloned()
+            }
+            PatternKind::Tuple(elements) => elements
+                .iter()
+                .map(|element| self.pattern_ty(element))
+                .collect::<Option<_>>()
+                .map(Ty::tuple),
+        }
+    }
+
+    /// The type of the pattern of a `let` without a value, which nothing is ever read through
+    /// the `_`s of
+    fn unassigned_pattern_ty(&self, pattern: &ast::Pattern) -> Ty {
+        match &pattern.kind {
+            PatternKind::Wildcard => Ty::Void,
+            PatternKind::Binding(_) if self.resolutions.variants.contains(pattern.id) => {
+                self.results.patterns[pattern.id].clone()
+            }
+            PatternKind::Binding(_) => self.results.declarations[pattern.id].clone(),
+            PatternKind::Literal(_) | PatternKind::Range { .. } | PatternKind::Variant { .. } => {
+                self.results.patterns[pattern.id].clone()
+            }
+            PatternKind::Tuple(elements) => Ty::tuple(
+                elements
+                    .iter()
+                    .map(|element| self.unassigned_pattern_ty(element))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Lowers `pattern`, which matches values of type `ty`, declaring a local for each binding
+    fn pattern(&mut self, pattern: &ast::Pattern, ty: Ty) -> Pat {
+        let kind = match &pattern.kind {
+            PatternKind::Wildcard => PatKind::Wild,
+            PatternKind::Binding(_) if self.resolutions.variants.contains(pattern.id) => {
+                PatKind::Variant {
+                    variant: self.resolutions.variants[pattern.id],
+                    inner: None,
+                }
+            }
+            &PatternKind::Binding(name) => {
+                let ty = self.results.declarations[pattern.id].clone();
+                let local = self.new_local(Some(name), ty, pattern.span);
+                self.local_ids.insert(pattern.id, local);
+                PatKind::Binding(local)
+            }
+            PatternKind::Tuple(elements) => {
+                let tys = match &ty {
+                    Ty::Tuple(tys) if tys.len() == elements.len() => tys.clone(),
+                    // Only code that's never reached matches something else
+                    _ => vec![Ty::Never; elements.len()],
+                };
+                PatKind::Tuple(
+                    elements
+                        .iter()
+                        .zip(tys)
+                        .map(|(element, ty)| self.pattern(element, ty))
+                        .collect(),
+                )
+            }
+            PatternKind::Literal(literal) => PatKind::Literal(literal_pattern(literal, &ty)),
+            PatternKind::Range {
+                start,
+                end,
+                inclusive,
+            } => PatKind::Range {
+                start: literal_pattern(start, &ty),
+                end: literal_pattern(end, &ty),
+                inclusive: *inclusive,
+            },
+            PatternKind::Variant { name: _, fields } => {
+                let variant = self.resolutions.variants[pattern.id];
+                let value = match (&ty, variant) {
+                    (Ty::Option(value), _) | (Ty::Result(value, _), Variant::Ok) => {
+                        (**value).clone()
+                    }
+                    (Ty::Result(_, value), _) => (**value).clone(),
+                    // Only code that's never reached matches something else
+                    _ => Ty::Never,
+                };
+                PatKind::Variant {
+                    variant,
+                    inner: fields
+                        .first()
+                        .map(|field| Box::new(self.pattern(field, value))),
+                }
+            }
+        };
+        Pat {
+            ty,
+            span: pattern.span,
+            kind,
+        }
+    }
+
+    /// A variant named on its own, which is either `None` or a function that wraps its
+    /// argument in the variant: `|value| Some(value)`
+    fn variant(&mut self, span: Span, ty: Ty, variant: Variant) -> Expr {
+        let Ty::Fn { params, ret } = &ty else {
+            let kind = ExprKind::Variant {
+                variant,
+                value: None,
+            };
+            return self.mk(span, ty, kind);
+        };
+        let value = self.temp(params[0].clone(), span);
+        let param = self.binding(value, span);
+        let local = self.local(value, span);
+        let body = self.mk(
+            span,
+            (**ret).clone(),
+            ExprKind::Variant {
+                variant,
+                value: Some(Box::new(local)),
+            },
+        );
+        let kind = ExprKind::Closure {
+            params: vec![param],
+            body: Box::new(body),
+        };
+        self.mk(span, ty, kind)
+    }
+
+    /// `operand?`, whose value is of type `ty`
+    fn try_operator(&mut self, span: Span, ty: Ty, operand: ExprId) -> Expr {
+        let operand = self.expr(operand);
+        let (ok, err) = match &operand.ty {
+            Ty::Option(_) => (Variant::Some, None),
+            Ty::Result(_, err) => (Variant::Ok, Some((**err).clone())),
+            // Neither arm would ever be taken
+            _ => return operand,
+        };
+        let value = self.temp(ty.clone(), span);
+        let ok_pat = Pat {
+            ty: operand.ty.clone(),
+            span,
+            kind: PatKind::Variant {
+                variant: ok,
+                inner: Some(Box::new(self.binding(value, span))),
+            },
+        };
+        let ok_body = self.local(value, span);
+        let (err_pat, returned) = match err {
+            Some(err) => {
+                let err = self.temp(err, span);
+                let pat = PatKind::Variant {
+                    variant: Variant::Err,
+                    inner: Some(Box::new(self.binding(err, span))),
+                };
+                let value = self.local(err, span);
+                let kind = ExprKind::Variant {
+                    variant: Variant::Err,
+                    value: Some(Box::new(value)),
+                };
+                (pat, kind)
+            }
+            None => (
+                PatKind::Variant {
+                    variant: Variant::None,
+                    inner: None,
+                },
+                ExprKind::Variant {
+                    variant: Variant::None,
+                    value: None,
+                },
+            ),
+        };
+        let err_pat = Pat {
+            ty: operand.ty.clone(),
+            span,
+            kind: err_pat,
+        };
+        let returned = self.mk(span, self.return_ty.clone(), returned);
+        let err_body = self.mk(span, Ty::Never, ExprKind::Return(Some(Box::new(returned))));
+        let kind = ExprKind::Match {
+            scrutinee: Box::new(operand),
+            arms: vec![
+                Arm {
+                    pat: ok_pat,
+                    body: ok_body,
+                },
+                Arm {
+                    pat: err_pat,
+                    body: err_body,
+                },
+            ],
+        };
+        self.mk(span, ty, kind)
+    }
+
+    fn for_loop(
+        &mut self,
+        span: Span,
+        pattern: &ast::Pattern,
+        iterable: ExprId,
+        body: &ast::Block,
+    ) -> Expr {
+        let mut stmts = Vec::new();
+        let (more, element, advance) = match &self.results.expressions[self.ast[iterable].id] {
+            Ty::Range(element) => {
+                let element = (**element).clone();
+                self.range_loop(span, iterable, element, &mut stmts)
+            }
+            Ty::Array(..) | Ty::Ref { .. } => {
+                let iterable = self.expr(iterable);
+                self.array_loop(span, iterable, &mut stmts)
+            }
+            // The loop is never reached
+            _ => return self.expr(iterable),
+        };
+        let pat = self.pattern(pattern, element.ty.clone());
+        let mut arm = vec![Stmt::Let {
+            pat,
+            init: Some(element),
+            span,
+        }];
+        arm.extend(advance);
+        arm.push(Stmt::Expr(self.block(body)));
+        let arm = Block {
+            stmts: arm,
+            expr: None,
+        };
+        let arm = self.block_expr(span, arm);
+        let exit = self.mk(span, Ty::Never, ExprKind::Break(None));
+        let exit = self.wrap(exit);
+        let arms = vec![
+            Arm {
+                pat: self.bool_pat(span, true),
+                body: arm,
+            },
+            Arm {
+                pat: self.bool_pat(span, false),
+                body: exit,
+            },
+        ];
+        let dispatch = self.mk(
+            span,
+            Ty::Void,
+            ExprKind::Match {
+                scrutinee: Box::new(more),
+                arms,
+            },
+        );
+        let body = Block {
+            stmts: vec![Stmt::Expr(dispatch)],
+            expr: None,
+        };
+        let kind = ExprKind::Loop(body);
+        stmts.push(Stmt::Expr(self.mk(span, Ty::Void, kind)));
+        let kind = ExprKind::Block(Block { stmts, expr: None });
+        self.mk(span, Ty::Void, kind)
+    }
+
+    /// Declares the counters of a `for` over `range`, returning what the loop matches on,
+    /// the current element, and what moves on to the next one
+    fn range_loop(
+        &mut self,
+        span: Span,
+        range: ExprId,
+        element: Ty,
+        stmts: &mut Vec<Stmt>,
+    ) -> (Expr, Expr, Vec<Stmt>) {
+        // A range that isn't written out in the loop is taken apart by its fields
+        let (start, end, inclusive, range) = match self.ast[range].kind {
+            ExpressionKind::Range {
+                start,
+                end,
+                inclusive,
+            } => (self.expr(start), self.expr(end), inclusive, None),
+            _ => {
+                let range = self.expr(range);
+                let range = self.let_temp(range, stmts);
+                let start = self.field(span, range, 0, element.clone());
+                let end = self.field(span, range, 1, element.clone());
+                (start, end, false, Some(range))
+            }
+        };
+        let next = self.let_temp(start, stmts);
+        let end = self.let_temp(end, stmts);
+        let inclusive_check = {
+            let is_inclusive = self.inclusive(span, inclusive, range);
+            let le = self.compare(span, BinaryOp::Le, next, end);
+            let lt = self.compare(span, BinaryOp::Lt, next, end);
+            let kind = self.if_else(is_inclusive, le, lt);
+            self.mk(span, Ty::Bool, kind)
+        };
+        let more = self.let_temp(inclusive_check, stmts);
+
+        // `if next < end { next = next + 1; more = inclusive || next < end } else { more = false }`
+        let condition = self.compare(span, BinaryOp::Lt, next, end);
+        let step = self.step(span, next);
+        let stepped = self.assign_local(span, next, step);
+        let is_inclusive = self.inclusive(span, inclusive, range);
+        let yes = self.bool(span, true);
+        let lt = self.compare(span, BinaryOp::Lt, next, end);
+        let kind = self.if_else(is_inclusive, yes, lt);
+        let still = self.mk(span, Ty::Bool, kind);
+        let still = self.assign_local(span, more, still);
+        let then = Block {
+            stmts: vec![Stmt::Expr(stepped), Stmt::Expr(still)],
+            expr: None,
+        };
+        let then = self.block_expr(span, then);
+        let no = self.bool(span, false);
+        let done = self.assign_local(span, more, no);
+        let kind = self.if_else(condition, then, done);
+        let advance = self.mk(span, Ty::Void, kind);
+
+        let current = self.local(next, span);
+        let more = self.local(more, span);
+        (more, current, vec![Stmt::Expr(advance)])
+    }
+
+    /// Like [`Self::range_loop`] for an array or a reference to one
+    fn array_loop(
+        &mut self,
+        span: Span,
+        array: Expr,
+        stmts: &mut Vec<Stmt>,
+    ) -> (Expr, Expr, Vec<Stmt>) {
+        let (length, reference) = match array.ty {
+            Ty::Array(_, length) => (length, None),
+            Ty::Ref { mutable, ref inner } => match **inner {
+                Ty::Array(_, length) => (length, Some(mutable)),
+                _ => unreachable!("only arrays and references to them are iterated over"),
+            },
+            _ => unreachable!("only arrays and references to them are iterated over"),
+        };
+        let array = self.let_temp(array, stmts);
+        let zero = self.usize(span, 0);
+        let index = self.let_temp(zero, stmts);
+
+        let base = self.local(array, span);
+        let base = self.deref_all(base);
+        let Ty::Array(element_ty, _) = base.ty.clone() else {
+            unreachable!("the base is an array once dereferenced")
+        };
+        let position = self.local(index, span);
+        let kind = ExprKind::Index {
+            base: Box::new(base),
+            index: Box::new(position),
+        };
+        let mut element = self.mk(span, *element_ty, kind);
+        if let Some(mutable) = reference {
+            let ty = Ty::Ref {
+                mutable,
+                inner: Box::new(element.ty.clone()),
+            };
+            let kind = ExprKind::Ref {
+                mutable,
+                operand: Box::new(element),
+            };
+            element = self.mk(span, ty, kind);
+        }
+
+        let current = self.local(index, span);
+        let one = self.usize(span, 1);
+        let next = self.arithmetic(span, BinaryOp::Add, current, one);
+        let advance = self.assign_local(span, index, next);
+        let length = self.usize(span, length);
+        let position = self.local(index, span);
+        let more = self.mk(
+            span,
+            Ty::Bool,
+            ExprKind::Binary {
+                op: BinaryOp::Lt,
+                lhs: Box::new(position),
+                rhs: Box::new(length),
+            },
+        );
+        (more, element, vec![Stmt::Expr(advance)])
+    }
+
+    /// Whether the range a `for` goes through is inclusive, known from the syntax or read
+    /// from `range`
+    fn inclusive(&mut self, span: Span, known: bool, range: Option<LocalId>) -> Expr {
+        match range {
+            Some(range) => self.field(span, range, 2, Ty::Bool),
+            None => self.bool(span, known),
+        }
+    }
+
+    /// The element after the one in `local`, which is an integer or a character
+    fn step(&mut self, span: Span, local: LocalId) -> Expr {
+        let current = self.local(local, span);
+        match current.ty.clone() {
+            Ty::Char => {
+                let code = self.mk(span, Ty::Int(IntTy::U32), ExprKind::Cast(Box::new(current)));
+                let one = self.literal(span, Ty::Int(IntTy::U32), Literal::Int(1));
+                let next = self.arithmetic(span, BinaryOp::Add, code, one);
+                self.mk(span, Ty::Char, ExprKind::Cast(Box::new(next)))
+            }
+            ty => {
+                let one = self.literal(span, ty, Literal::Int(1));
+                self.arithmetic(span, BinaryOp::Add, current, one)
+            }
+        }
+    }
+
+    fn compound_assign(&mut self, span: Span, op: BinaryOp, target: ExprId, value: ExprId) -> Expr {
+        let mut stmts = Vec::new();
+        let place = self.expr(target);
+        let place = self.stable_place(place, &mut stmts);
+        let value = self.expr(value);
+        let current = self.copy_place(&place);
+        let value = self.arithmetic(span, op, current, value);
+        let assign = self.mk(
+            span,
+            Ty::Void,
+            ExprKind::Assign {
+                place: Box::new(place),
+                value: Box::new(value),
+            },
+        );
+        if stmts.is_empty() {
+            return assign;
+        }
+        let block = Block {
+            stmts,
+            expr: Some(Box::new(assign)),
+        };
+        self.mk(span, Ty::Void, ExprKind::Block(block))
+    }
+
+    /// `place` with the operands it evaluates moved into temporaries declared in `stmts`,
+    /// so that it can be read and then written without evaluating them twice
+    fn stable_place(&mut self, place: Expr, stmts: &mut Vec<Stmt>) -> Expr {
+        let Expr {
+            def,
+            ty,
+            span,
+            kind,
+        } = place;
+        let kind = match kind {
+            ExprKind::Local(local) => ExprKind::Local(local),
+            ExprKind::Field { base, index } => ExprKind::Field {
+                base: Box::new(self.stable_place(*base, stmts)),
+                index,
+            },
+            ExprKind::Index { base, index } => ExprKind::Index {
+                base: Box::new(self.stable_place(*base, stmts)),
+                index: Box::new(self.stable_value(*index, stmts)),
+            },
+            ExprKind::Unary {
+                op: UnaryOp::Deref,
+                operand,
+            } => ExprKind::Unary {
+                op: UnaryOp::Deref,
+                operand: Box::new(self.stable_value(*operand, stmts)),
+            },
+            // Not a place, so what's assigned to is a temporary anyway
+            kind => {
+                let value = Expr {
+                    def,
+                    ty,
+                    span,
+                    kind,
+                };
+                return self.stable_value(value, stmts);
+            }
+        };
+        Expr {
+            def,
+            ty,
+            span,
+            kind,
+        }
+    }
+
+    fn stable_value(&mut self, value: Expr, stmts: &mut Vec<Stmt>) -> Expr {
+        match value.kind {
+            ExprKind::Local(_) | ExprKind::Literal(_) | ExprKind::Fn(_) => value,
+            _ => {
+                let span = value.span;
+                let temp = self.let_temp(value, stmts);
+                self.local(temp, span)
+            }
+        }
+    }
+
+    /// A copy of the place made by [`Self::stable_place`], with ids of its own
+    fn copy_place(&mut self, place: &Expr) -> Expr {
+        let kind = match &place.kind {
+            kind @ (ExprKind::Local(_) | ExprKind::Literal(_) | ExprKind::Fn(_)) => kind.clone(),
+            ExprKind::Field { base, index } => ExprKind::Field {
+                base: Box::new(self.copy_place(base)),
+                index: *index,
+            },
+            ExprKind::Index { base, index } => ExprKind::Index {
+                base: Box::new(self.copy_place(base)),
+                index: Box::new(self.copy_place(index)),
+            },
+            ExprKind::Unary { op, operand } => ExprKind::Unary {
+                op: *op,
+                operand: Box::new(self.copy_place(operand)),
+            },
+            _ => unreachable!("stable places only contain locals, fields and indexing"),
+        };
+        self.mk(place.span, place.ty.clone(), kind)
+    }
+
+    /// `expr` with the references it's behind dereferenced, for indexing and fields
+    fn deref_all(&mut self, mut expr: Expr) -> Expr {
+        while let Ty::Ref { inner, .. } = &expr.ty {
+            let ty = (**inner).clone();
+            let kind = ExprKind::Unary {
+                op: UnaryOp::Deref,
+                operand: Box::new(expr.clone()),
+            };
+            expr = self.mk(expr.span, ty, kind);
+        }
+        expr
+    }
+
+    fn mk(&mut self, span: Span, ty: Ty, kind: ExprKind) -> Expr {
+        let def = DefId(self.spans.len() as u32);
+        self.spans.push(span);
+        Expr {
+            def,
+            ty,
+            span,
+            kind,
+        }
+    }
+
+    fn new_local(&mut self, name: Option<ast::Identifier>, ty: Ty, span: Span) -> LocalId {
+        let id = LocalId(self.locals.len() as u32);
+        self.locals.push(Local { name, ty, span });
+        id
+    }
+
+    fn temp(&mut self, ty: Ty, span: Span) -> LocalId {
+        self.new_local(None, ty, span)
+    }
+
+    /// Evaluates `init` into a new temporary, declared in `stmts`
+    fn let_temp(&mut self, init: Expr, stmts: &mut Vec<Stmt>) -> LocalId {
+        let temp = self.temp(init.ty.clone(), init.span);
+        let span = init.span;
+        let pat = self.binding(temp, span);
+        stmts.push(Stmt::Let {
+            pat,
+            init: Some(init),
+            span,
+        });
+        temp
+    }
+
+    fn local(&mut self, local: LocalId, span: Span) -> Expr {
+        let ty = self.locals[local.index()].ty.clone();
+        self.mk(span, ty, ExprKind::Local(local))
+    }
+
+    fn binding(&self, local: LocalId, span: Span) -> Pat {
+        Pat {
+            ty: self.locals[local.index()].ty.clone(),
+            span,
+            kind: PatKind::Binding(local),
+        }
+    }
+
+    fn field(&mut self, span: Span, local: LocalId, index: u32, ty: Ty) -> Expr {
+        let base = self.local(local, span);
+        let kind = ExprKind::Field {
+            base: Box::new(base),
+            index,
+        };
+        self.mk(span, ty, kind)
+    }
+
+    fn literal(&mut self, span: Span, ty: Ty, literal: Literal) -> Expr {
+        self.mk(span, ty, ExprKind::Literal(literal))
+    }
+
+    fn bool(&mut self, span: Span, value: bool) -> Expr {
+        self.literal(span, Ty::Bool, Literal::Bool(value))
+    }
+
+    fn usize(&mut self, span: Span, value: u64) -> Expr {
+        self.literal(span, Ty::Int(IntTy::Usize), Literal::Int(value.into()))
+    }
+
+    fn bool_pat(&self, span: Span, value: bool) -> Pat {
+        Pat {
+            ty: Ty::Bool,
+            span,
+            kind: PatKind::Literal(Literal::Bool(value)),
+        }
+    }
+
+    fn compare(&mut self, span: Span, op: BinaryOp, lhs: LocalId, rhs: LocalId) -> Expr {
+        let lhs = self.local(lhs, span);
+        let rhs = self.local(rhs, span);
+        let kind = ExprKind::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        };
+        self.mk(span, Ty::Bool, kind)
+    }
+
+    fn arithmetic(&mut self, span: Span, op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+        let ty = if lhs.ty == Ty::Never {
+            rhs.ty.clone()
+        } else {
+            lhs.ty.clone()
+        };
+        let kind = ExprKind::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        };
+        self.mk(span, ty, kind)
+    }
+
+    fn assign_local(&mut self, span: Span, local: LocalId, value: Expr) -> Expr {
+        let place = self.local(local, span);
+        let kind = ExprKind::Assign {
+            place: Box::new(place),
+            value: Box::new(value),
+        };
+        self.mk(span, Ty::Void, kind)
+    }
+
+    /// `if condition { then } else { otherwise }`, with the branches made blocks
+    fn if_else(&mut self, condition: Expr, then: Expr, otherwise: Expr) -> ExprKind {
+        ExprKind::If {
+            condition: Box::new(condition),
+            then_branch: Box::new(self.wrap(then)),
+            else_branch: Some(Box::new(self.wrap(otherwise))),
+        }
+    }
+
+    /// `expr` as a block, if it isn't one already
+    fn wrap(&mut self, expr: Expr) -> Expr {
+        if let ExprKind::Block(_) = expr.kind {
+            return expr;
+        }
+        let block = Block {
+            stmts: Vec::new(),
+            expr: Some(Box::new(expr)),
+        };
+        self.block_expr(block.expr.as_ref().unwrap().span, block)
+    }
+
+    fn block_expr(&mut self, span: Span, block: Block) -> Expr {
+        let diverges = block.stmts.iter().any(|stmt| match stmt {
+            Stmt::Let {
+                init: Some(expr), ..
+            }
+            | Stmt::Expr(expr) => expr.ty == Ty::Never,
+            Stmt::Let { init: None, .. } => false,
+        });
+        let ty = match &block.expr {
+            Some(expr) => expr.ty.clone(),
+            None if diverges => Ty::Never,
+            None => Ty::Void,
+        };
+        self.mk(span, ty, ExprKind::Block(block))
+    }
+}
+
+/// The value of `literal` in a pattern matching values of type `ty`, with negative integers in
+/// two's complement at the width of `ty`
+fn literal_pattern(literal: &LiteralPattern, ty: &Ty) -> Literal {
+    match *literal {
+        LiteralPattern::Int {
+            value, negative, ..
+        } => {
+            let value = if negative {
+                value.wrapping_neg()
+            } else {
+                value
+            };
+            match ty {
+                Ty::Int(ty) => Literal::Int(ty.truncate(value)),
+                _ => Literal::Int(value),
+            }
+        }
+        LiteralPattern::Char(value) => Literal::Char(value),
+        LiteralPattern::Bool(value) => Literal::Bool(value),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        hir::{ExprKind, Hir, Literal, PatKind},
+        testing::Source,
+        typeck::ty::{InferTy, Ty},
+    };
+
+    fn lower_source(source: &str) -> Hir {
+        Source::parse(source).lower()
+    }
+
+    #[test]
+    fn desugars_compound_assignment_and_try() {
+        let hir = lower_source(
+            "fn f(a: [i32; 2], r: Result<i32, u8>): Result<i32, u8> {
+                a[g()] += r?; a[0] -= 1; r
+            }
+            fn g(): usize { 1 }",
+        );
+        assert_eq!(
+            hir.to_string(),
+            "fn f(a_0: [i32; 2], r_1: Result<i32, u8>): Result<i32, u8> {
+    {
+        let _2: usize = g();
+        a_0[_2] = (a_0[_2] + match r_1 {
+            Ok(_3) => _3,
+            Err(_4) => return Err(_4),
+        })
+    };
+    a_0[0] = (a_0[0] - 1);
+    r_1
+}
+
+fn g(): usize {
+    1
+}
+"
+        );
+    }
+
+    #[test]
+    fn lowers_matches_and_variants() {
+        let hir = lower_source(
+            "fn f(x: Option<i8>): Result<i8, bool> {
+                let wrap = Ok; let none: Option<u8> = None;
+                match x { Some(-128..-1) => wrap(-1), Some(n) => Ok(n), None => Err(true) }
+            }",
+        );
+        // Negative integers in patterns are stored in two's complement
+        assert_eq!(
+            hir.to_string(),
+            "fn f(x_0: Option<i8>): Result<i8, bool> {
+    let wrap_2: fn(i8): Result<i8, bool> = |_1| Ok(_1);
+    let none_3: Option<u8> = None;
+    match x_0 {
+        Some(-128..-1) => wrap_2((-1)),
+        Some(n_4) => Ok(n_4),
+        None => Err(true),
+    }
+}
+"
+        );
+        let ExprKind::Block(body) = &hir.functions[0].body.kind else {
+            panic!("function bodies are blocks")
+        };
+        let Some(ExprKind::Match { arms, .. }) = body.expr.as_ref().map(|expr| &expr.kind) else {
+            panic!("expected the body to end with the match")
+        };
+        assert!(matches!(
+            arms[0].pat.kind,
+            PatKind::Variant { inner: Some(ref inner), .. } if matches!(
+                inner.kind,
+                PatKind::Range { start: Literal::Int(128), end: Literal::Int(255), inclusive: false }
+            )
+        ));
+    }
+
+    #[test]
+    fn desugars_loops_and_logic() {
+        let hir = lower_source(
+            "fn f(p: &(bool, [u8; 2])): bool {
+                while p.0 || false { for x in p.1 { break; } }
+                for (i, _) in [(1, 2)] { continue; }
+                p.0 && #line == 4
+            }",
+        );
+        let text = hir.to_string();
+        // Field accesses go through the reference explicitly
+        assert!(text.contains("let _1: [u8; 2] = (*p_0).1;"), "{text}");
+        assert!(
+            text.contains(
+                "if (*p_0).0 {\n            true\n        } else {\n            false\n        }"
+            ),
+            "{text}"
+        );
+        assert!(
+            text.contains("let (i_6, _): (i32, i32) = _4[_5];"),
+            "{text}"
+        );
+        assert!(
+            text.ends_with(
+                "    if (*p_0).0 {\n        (4 == 4)\n    } else {\n        false\n    }\n}\n"
+            ),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn every_expression_is_typed_and_has_its_own_def() {
+        let hir = lower_source(
+            "fn f(n: u32): u32 {
+                let add = |a, b| a + b; let total = 0;
+                for i in 0..n { total += add(i, 1); }
+                let v = loop { break 'a'; };
+                total
+            }",
+        );
+        let function = &hir.functions[0];
+        let mut exprs = Vec::new();
+        function.body.walk(&mut |expr| exprs.push(expr));
+        let mut defs: Vec<_> = exprs.iter().map(|expr| expr.def).collect();
+        defs.sort();
+        defs.dedup();
+        assert_eq!(defs.len(), exprs.len());
+        // The function and every expression, with nothing lowered and left out
+        assert_eq!(hir.def_count(), exprs.len() + 1);
+        for expr in exprs {
+            assert_eq!(hir.span(expr.def), expr.span);
+            assert!(
+                !matches!(expr.ty, Ty::Error | Ty::Infer(InferTy::Var(_))),
+                "{expr:?}"
+            );
+            assert!(
+                function.span.start() <= expr.span.start()
+                    && expr.span.end() <= function.span.end(),
+                "{expr:?}"
+            );
+        }
+        let types: Vec<_> = function
+            .locals
+            .iter()
+            .filter_map(|local| Some(format!("{}: {}", local.name?, local.ty)))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                "n: u32",
+                "a: u32",
+                "b: u32",
+                "add: fn(u32, u32): u32",
+                "total: u32",
+                "i: u32",
+                "v: char"
+            ]
+        );
+    }
+}
@@ -1,17 +1,49 @@
 use std::ops::Index;
 
 use crate::fs::{File, FileId};
-use logos::{Lexer, Logos};
+use logos::{FilterResult, Lexer, Logos};
 
 pub mod tokens;
+pub mod trivia;
 
 #[derive(Logos, Clone, Copy, Debug, PartialEq, Eq)]
-#[logos(skip r"[ \t\n\f]+", extras = FileId)] // Ignore this regex pattern between tokens
+// Ignore this regex pattern between tokens
+#[logos(skip r"[ \t\r\n\f]+", extras = FileId, error = LexError)]
+// Comments are trivia too, see [`trivia`] for getting them back
+#[logos(skip r"//[^\n]*")]
 pub enum Token {
     #[token("fn")]
     Fn,
     #[token("let")]
     Let,
+    #[token("as")]
+    As,
+    #[token("mut")]
+    Mut,
+    #[token("if")]
+    If,
+    #[token("else")]
+    Else,
+    #[token("while")]
+    While,
+    #[token("for")]
+    For,
+    #[token("in")]
+    In,
+    #[token("loop")]
+    Loop,
+    #[token("match")]
+    Match,
+    #[token("break")]
+    Break,
+    #[token("continue")]
+    Continue,
+    #[token("return")]
+    Return,
+    #[token("true")]
+    True,
+    #[token("false")]
+    False,
 
     #[token("(")]
     OpeningParen,
@@ -28,9 +60,29 @@ pub enum Token {
 
     #[token("=")]
     Assignment,
+    /// Between the pattern and the body of a `match` arm
+    #[token("=>")]
+    FatArrow,
+    /// `+=`, `-=`, `*=`, `/=` or `%=`, the parser reads the operator from the slice
+    #[regex("\\+=|-=|\\*=|/=|%=")]
+    CompoundAssignment,
 
     #[regex("\\+|-|\\*|/|%|&&|\\|\\||==|!=|>|<|>=|<=")]
     Operator,
+    /// Never produced: a closed `/* .. */` is skipped like a `//` comment,
+    /// and one that isn't closed is a [`LexError::UnterminatedBlockComment`]
+    #[token("/*", block_comment)]
+    BlockComment,
+
+    /// Opens and closes closure parameters, `||` is an [`Token::Operator`]
+    #[token("|")]
+    Pipe,
+    #[token("&")]
+    Ampersand,
+    #[token("!")]
+    Bang,
+    #[token("?")]
+    Question,
 
     #[token(";")]
     Semicolon,
@@ -40,17 +92,138 @@ pub enum Token {
     PathSep,
     #[token(",")]
     Comma,
+    #[token(".")]
+    Dot,
+    #[token("..")]
+    DotDot,
+    #[token("..=")]
+    DotDotEq,
 
-    #[regex("[_a-zA-Z][_0-9a-zA-z]*")]
+    #[regex("[_a-zA-Z][_0-9a-zA-Z]*")]
     Identifier,
-    #[regex("#[_a-zA-Z][_0-9a-zA-z]*")]
+    #[regex("#[_a-zA-Z][_0-9a-zA-Z]*")]
     IntrinsicIdentifier,
 
-    #[regex("-?[0-9]+", |lex| lex.slice().parse().ok())]
-    IntegerLiteral(i128),
+    /// `42` or `42u8`, the parser reads the suffix from the slice. The value is `None` when it
+    /// doesn't fit in a `u128`, which the parser reports.
+    #[regex("[0-9]+", integer)]
+    #[regex("[0-9]+(i8|i16|i32|i64|i128|isize|u8|u16|u32|u64|u128|usize)", integer)]
+    IntegerLiteral(Option<u128>),
+    /// `1.5`, `1e9` or `2.5e-3`. The parser reads the value from the slice,
+    /// and splits `0.1` back up after a `.` as in `t.0.1`.
+    #[regex(r"[0-9]+\.[0-9]+([eE][+-]?[0-9]+)?")]
+    #[regex(r"[0-9]+[eE][+-]?[0-9]+")]
+    FloatLiteral,
+    /// `'c'`, with the escapes `\n`, `\r`, `\t`, `\0`, `\\`, `\'` and `\"`
+    #[regex(r#"'([^'\\\n]|\\[nrt0\\'"])'"#)]
+    CharLiteral,
+    /// `"text"`, with the same escapes as characters
+    #[regex(r#""([^"\\]|\\[nrt0\\'"])*""#)]
+    StringLiteral,
+}
+
+/// Why some source text isn't a token, reported by the parser
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LexError {
+    /// A character that doesn't start any token
+    #[default]
+    InvalidToken,
+    /// A `/*` without a `*/` after it, the error spans the rest of the file
+    UnterminatedBlockComment,
+}
+
+/// Skips a `/* .. */` comment, which logos can't match with a regex since it doesn't backtrack
+fn block_comment(lex: &mut Lexer<'_, Token>) -> FilterResult<(), LexError> {
+    match lex.remainder().find("*/") {
+        Some(end) => {
+            lex.bump(end + 2);
+            FilterResult::Skip
+        }
+        None => {
+            lex.bump(lex.remainder().len());
+            FilterResult::Error(LexError::UnterminatedBlockComment)
+        }
+    }
+}
+
+/// The value of an integer literal, leaving out its suffix. The type checker checks it against
+/// the type the literal gets.
+fn integer(lex: &mut Lexer<'_, Token>) -> Option<u128> {
+    let slice = lex.slice();
+    let digits = slice
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(slice.len());
+    slice[..digits].parse().ok()
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Operator => write!(f, "operator"),
+            Self::BlockComment => write!(f, "block comment"),
+            Self::CompoundAssignment => write!(f, "compound assignment"),
+            Self::Identifier => write!(f, "identifier"),
+            Self::IntrinsicIdentifier => write!(f, "intrinsic identifier"),
+            Self::IntegerLiteral(_) => write!(f, "integer literal"),
+            Self::FloatLiteral => write!(f, "float literal"),
+            Self::CharLiteral => write!(f, "character literal"),
+            Self::StringLiteral => write!(f, "string literal"),
+            _ => write!(f, "`{}`", self.text().unwrap()),
+        }
+    }
 }
 
 impl Token {
+    /// Source text of keywords and punctuation, `None` for tokens that carry their text
+    pub const fn text(self) -> Option<&'static str> {
+        Some(match self {
+            Self::Fn => "fn",
+            Self::Let => "let",
+            Self::As => "as",
+            Self::Mut => "mut",
+            Self::If => "if",
+            Self::Else => "else",
+            Self::While => "while",
+            Self::For => "for",
+            Self::In => "in",
+            Self::Loop => "loop",
+            Self::Match => "match",
+            Self::Break => "break",
+            Self::Continue => "continue",
+            Self::Return => "return",
+            Self::True => "true",
+            Self::False => "false",
+            Self::OpeningParen => "(",
+            Self::ClosingParen => ")",
+            Self::OpeningBracket => "{",
+            Self::ClosingBracket => "}",
+            Self::OpeningSqBracket => "[",
+            Self::ClosingSqBracket => "]",
+            Self::Assignment => "=",
+            Self::FatArrow => "=>",
+            Self::Pipe => "|",
+            Self::Ampersand => "&",
+            Self::Bang => "!",
+            Self::Question => "?",
+            Self::Semicolon => ";",
+            Self::Colon => ":",
+            Self::PathSep => "::",
+            Self::Comma => ",",
+            Self::Dot => ".",
+            Self::DotDot => "..",
+            Self::DotDotEq => "..=",
+            Self::Operator
+            | Self::BlockComment
+            | Self::CompoundAssignment
+            | Self::Identifier
+            | Self::IntrinsicIdentifier
+            | Self::IntegerLiteral(_)
+            | Self::FloatLiteral
+            | Self::CharLiteral
+            | Self::StringLiteral => return None,
+        })
+    }
+
     pub fn lexer_from_file<A>(arena: &A, id: FileId) -> Lexer<'_, Self>
     where
         A: Index<FileId, Output = File>,
@@ -65,7 +238,7 @@ mod test {
 
     use crate::fs::File;
 
-    use super::Token;
+    use super::{LexError, Token};
 
     macro_rules! ok_or_err {
         (Ok) => {};
@@ -94,10 +267,10 @@ mod test {
     fn test_all() {
         use Token::*;
         let mut arena: Arena<File> = Arena::new();
-        let f_b = arena.alloc(File {
-            name: "b".into(),
-            contents: "fn main() {hello == b && a >= c; #a != 1; let d = 2; std::yes() }".into(),
-        });
+        let f_b = arena.alloc(File::new(
+            "b",
+            "fn main() {hello == b && a >= c; #a != 1; let d = 2; std::yes() }",
+        ));
         let lexer = Token::lexer_from_file(&arena, f_b);
         let mut spanned = lexer.spanned();
         assert_tokens!(
@@ -117,12 +290,12 @@ mod test {
             Ok(Semicolon, 31..32),
             Ok(IntrinsicIdentifier, 33..35),
             Ok(Operator, 36..38),
-            Ok(IntegerLiteral(1), 39..40),
+            Ok(IntegerLiteral(Some(1)), 39..40),
             Ok(Semicolon, 40..41),
             Ok(Let, 42..45),
             Ok(Identifier, 46..47),
             Ok(Assignment, 48..49),
-            Ok(IntegerLiteral(2), 50..51),
+            Ok(IntegerLiteral(Some(2)), 50..51),
             Ok(Semicolon, 51..52),
             Ok(Identifier, 53..56),
             Ok(PathSep, 56..58),
@@ -132,6 +305,179 @@ mod test {
             Ok(ClosingBracket, 64..65)
         );
     }
+
+    #[test]
+    fn test_ranges_and_fields() {
+        use Token::*;
+        let mut arena: Arena<File> = Arena::new();
+        let f = arena.alloc(File::new("f", "xs[0..n-1] t.0 a..=b"));
+        let lexer = Token::lexer_from_file(&arena, f);
+        let mut spanned = lexer.spanned();
+        assert_tokens!(
+            spanned,
+            Ok(Identifier, 0..2),
+            Ok(OpeningSqBracket, 2..3),
+            Ok(IntegerLiteral(Some(0)), 3..4),
+            Ok(DotDot, 4..6),
+            Ok(Identifier, 6..7),
+            Ok(Operator, 7..8),
+            Ok(IntegerLiteral(Some(1)), 8..9),
+            Ok(ClosingSqBracket, 9..10),
+            Ok(Identifier, 11..12),
+            Ok(Dot, 12..13),
+            Ok(IntegerLiteral(Some(0)), 13..14),
+            Ok(Identifier, 15..16),
+            Ok(DotDotEq, 16..19),
+            Ok(Identifier, 19..20)
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        use Token::*;
+        let mut arena: Arena<File> = Arena::new();
+        let f = arena.alloc(File::new(
+            "f",
+            r#"1.5 2e-3 t.0.1 1..2 'a' '\n' "a\"b // c" true return '\q'"#,
+        ));
+        let lexer = Token::lexer_from_file(&arena, f);
+        let mut spanned = lexer.spanned();
+        assert_tokens!(
+            spanned,
+            Ok(FloatLiteral, 0..3),
+            Ok(FloatLiteral, 4..8),
+            Ok(Identifier, 9..10),
+            Ok(Dot, 10..11),
+            Ok(FloatLiteral, 11..14),
+            Ok(IntegerLiteral(Some(1)), 15..16),
+            Ok(DotDot, 16..18),
+            Ok(IntegerLiteral(Some(2)), 18..19),
+            Ok(CharLiteral, 20..23),
+            Ok(CharLiteral, 24..28),
+            Ok(StringLiteral, 29..40),
+            Ok(True, 41..45),
+            Ok(Return, 46..52),
+            Err(LexError::InvalidToken, 53..54),
+            Err(LexError::InvalidToken, 54..55),
+            Ok(Identifier, 55..56),
+            Err(LexError::InvalidToken, 56..57)
+        );
+    }
+
+    #[test]
+    fn test_suffixes_and_closures() {
+        use Token::*;
+        let mut arena: Arena<File> = Arena::new();
+        let f = arena.alloc(File::new("f", "1u8 20usize 3i1 |x| || a | b"));
+        let lexer = Token::lexer_from_file(&arena, f);
+        let mut spanned = lexer.spanned();
+        assert_tokens!(
+            spanned,
+            Ok(IntegerLiteral(Some(1)), 0..3),
+            Ok(IntegerLiteral(Some(20)), 4..11),
+            Ok(IntegerLiteral(Some(3)), 12..13),
+            Ok(Identifier, 13..15),
+            Ok(Pipe, 16..17),
+            Ok(Identifier, 17..18),
+            Ok(Pipe, 18..19),
+            Ok(Operator, 20..22),
+            Ok(Identifier, 23..24),
+            Ok(Pipe, 25..26),
+            Ok(Identifier, 27..28)
+        );
+    }
+
+    #[test]
+    fn test_large_integers() {
+        use Token::*;
+        let mut arena: Arena<File> = Arena::new();
+        let max = u128::MAX.to_string();
+        let f = arena.alloc(File::new("f", format!("{max}u128 {max}0")));
+        let lexer = Token::lexer_from_file(&arena, f);
+        let mut spanned = lexer.spanned();
+        assert_tokens!(
+            spanned,
+            Ok(IntegerLiteral(Some(u128::MAX)), 0..43),
+            Ok(IntegerLiteral(None), 44..84)
+        );
+    }
+
+    #[test]
+    fn test_loops() {
+        use Token::*;
+        let mut arena: Arena<File> = Arena::new();
+        let f = arena.alloc(File::new(
+            "f",
+            "for x in 0..n { x += 1; break } loop continue y=-1",
+        ));
+        let lexer = Token::lexer_from_file(&arena, f);
+        let mut spanned = lexer.spanned();
+        assert_tokens!(
+            spanned,
+            Ok(For, 0..3),
+            Ok(Identifier, 4..5),
+            Ok(In, 6..8),
+            Ok(IntegerLiteral(Some(0)), 9..10),
+            Ok(DotDot, 10..12),
+            Ok(Identifier, 12..13),
+            Ok(OpeningBracket, 14..15),
+            Ok(Identifier, 16..17),
+            Ok(CompoundAssignment, 18..20),
+            Ok(IntegerLiteral(Some(1)), 21..22),
+            Ok(Semicolon, 22..23),
+            Ok(Break, 24..29),
+            Ok(ClosingBracket, 30..31),
+            Ok(Loop, 32..36),
+            Ok(Continue, 37..45),
+            Ok(Identifier, 46..47),
+            Ok(Assignment, 47..48),
+            Ok(Operator, 48..49),
+            Ok(IntegerLiteral(Some(1)), 49..50)
+        );
+    }
+
+    #[test]
+    fn test_match() {
+        use Token::*;
+        let mut arena: Arena<File> = Arena::new();
+        let f = arena.alloc(File::new("f", "match x { 0 => a, _=>b } x >= y"));
+        let lexer = Token::lexer_from_file(&arena, f);
+        let mut spanned = lexer.spanned();
+        assert_tokens!(
+            spanned,
+            Ok(Match, 0..5),
+            Ok(Identifier, 6..7),
+            Ok(OpeningBracket, 8..9),
+            Ok(IntegerLiteral(Some(0)), 10..11),
+            Ok(FatArrow, 12..14),
+            Ok(Identifier, 15..16),
+            Ok(Comma, 16..17),
+            Ok(Identifier, 18..19),
+            Ok(FatArrow, 19..21),
+            Ok(Identifier, 21..22),
+            Ok(ClosingBracket, 23..24),
+            Ok(Identifier, 25..26),
+            Ok(Operator, 27..29),
+            Ok(Identifier, 30..31)
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        use Token::*;
+        let mut arena: Arena<File> = Arena::new();
+        let f = arena.alloc(File::new("f", "a // b\n/* c * / */ d / e /* f"));
+        let lexer = Token::lexer_from_file(&arena, f);
+        let mut spanned = lexer.spanned();
+        assert_tokens!(
+            spanned,
+            Ok(Identifier, 0..1),
+            Ok(Identifier, 19..20),
+            Ok(Operator, 21..22),
+            Ok(Identifier, 23..24),
+            Err(LexError::UnterminatedBlockComment, 25..29)
+        );
+    }
 }
 
 // pub struct SpannedIterExt<'source, Token: Logos<'source>>(SpannedIter<'source, Token>, FileId);
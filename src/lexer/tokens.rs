@@ -22,6 +22,10 @@ pub type RefData<'r, 'source, Token> = (
 pub trait TokenReader<'source, Token: Logos<'source>> {
     fn get_current_token(&self) -> Option<Result<&Token, &Token::Error>>;
     fn get_current_span(&self) -> Option<Span>;
+    /// Span of the token that was current before the last [`Self::advance`]
+    fn previous_span(&self) -> Option<Span>;
+    /// Empty span right after the last token
+    fn eoi_span(&self) -> Span;
     fn current(&self) -> Option<RefData<'_, 'source, Token>>;
     fn current_slice(&self) -> Option<&'source <Token::Source as Source>::Slice>;
     fn extras(&self) -> &Token::Extras;
@@ -33,6 +37,7 @@ pub struct Tokens<'source, Token: Logos<'source>> {
     file: FileId,
     lexer: Lexer<'source, Token>,
     current: Option<Data<'source, Token>>,
+    previous: Option<Span>,
     queue: VecDeque<Data<'source, Token>>,
 }
 
@@ -45,6 +50,15 @@ impl<'a, Token: Logos<'a>> TokenReader<'a, Token> for Tokens<'a, Token> {
         self.current.as_ref().map(|(_, a, _)| *a)
     }
 
+    fn previous_span(&self) -> Option<Span> {
+        self.previous
+    }
+
+    fn eoi_span(&self) -> Span {
+        let end = self.lexer.source().len();
+        Span::new(self.file, end..end)
+    }
+
     fn current(&self) -> Option<RefData<'_, 'a, Token>> {
         self.current.as_ref().map(|(a, b, c)| (a.as_ref(), *b, *c))
     }
@@ -58,6 +72,7 @@ impl<'a, Token: Logos<'a>> TokenReader<'a, Token> for Tokens<'a, Token> {
     }
 
     fn advance(&mut self) {
+        self.previous = self.get_current_span().or(self.previous);
         self.current = self.queue.pop_front().or_else(|| {
             self.lexer.next().map(|token| {
                 (
@@ -80,6 +95,7 @@ impl<'a, Token: Logos<'a>> Tokens<'a, Token> {
             file,
             lexer,
             current: None,
+            previous: None,
             queue: Default::default(),
         };
         s.advance();
@@ -87,7 +103,7 @@ impl<'a, Token: Logos<'a>> Tokens<'a, Token> {
     }
 
     fn add_to_queue(&mut self, tokens: VecDeque<Data<'a, Token>>) {
-        self.queue.extend(tokens.into_iter())
+        self.queue.extend(tokens)
     }
 }
 impl<'a, Token: Logos<'a, Extras = FileId>> Tokens<'a, Token> {
@@ -103,13 +119,15 @@ impl<'a, Token: Logos<'a, Extras = FileId>> From<Lexer<'a, Token>> for Tokens<'a
 }
 
 impl<'a, Token: Logos<'a> + Clone> Transactionable for Tokens<'a, Token> {
-    type Transaction<'t> = TokensTransaction<'a, 't, Token, Self>
+    type Transaction<'t>
+        = TokensTransaction<'a, 't, Token, Self>
     where
         Self: 't;
 
     fn transaction(&mut self) -> Self::Transaction<'_> {
         TokensTransaction {
             current: self.current.clone(),
+            previous: self.previous,
             parent: self,
             add_to_parent_queue: Self::add_to_queue,
             in_trans: false,
@@ -126,6 +144,7 @@ pub struct TokensTransaction<
 > {
     parent: &'parent mut Parent,
     current: Option<Data<'source, Token>>,
+    previous: Option<Span>,
     queue: VecDeque<Data<'source, Token>>,
     transaction: VecDeque<Data<'source, Token>>,
     add_to_parent_queue: fn(&'parent mut Parent, VecDeque<Data<'source, Token>>),
@@ -147,6 +166,14 @@ where
         self.current.as_ref().map(|(_, a, _)| *a)
     }
 
+    fn previous_span(&self) -> Option<Span> {
+        self.previous
+    }
+
+    fn eoi_span(&self) -> Span {
+        self.parent.eoi_span()
+    }
+
     fn current(&self) -> Option<RefData<'_, 'source, Token>> {
         self.current.as_ref().map(|(a, b, c)| (a.as_ref(), *b, *c))
     }
@@ -160,10 +187,11 @@ where
     }
 
     fn advance(&mut self) {
+        self.previous = self.get_current_span().or(self.previous);
         self.current = self.queue.pop_front().or_else(|| {
             self.parent.advance();
             self.parent.current().map(|(token, span, slice)| {
-                let data = (token.map(Clone::clone).map_err(Clone::clone), span, slice);
+                let data = (token.cloned().map_err(Clone::clone), span, slice);
                 if !self.in_trans {
                     self.transaction.push_back(data.clone());
                 }
@@ -183,7 +211,8 @@ where
     Token: Logos<'source> + Clone,
     Parent: TokenReader<'source, Token>,
 {
-    type Transaction<'t> = TokensTransaction<'source, 't, Token, Self>
+    type Transaction<'t>
+        = TokensTransaction<'source, 't, Token, Self>
     where
         Self: 't;
 
@@ -191,6 +220,7 @@ where
         self.in_trans = true;
         TokensTransaction {
             current: self.current.clone(),
+            previous: self.previous,
             parent: self,
             add_to_parent_queue: Self::add_to_queue,
             in_trans: false,
@@ -211,7 +241,7 @@ where
     }
 
     fn discard(mut self) {
-        self.transaction.extend(self.queue.into_iter());
+        self.transaction.extend(self.queue);
         (self.add_to_parent_queue)(self.parent, self.transaction)
     }
 }
@@ -223,6 +253,6 @@ where
 {
     fn add_to_queue(&mut self, tokens: VecDeque<Data<'source, Token>>) {
         self.in_trans = false;
-        self.queue.extend(tokens.into_iter())
+        self.queue.extend(tokens)
     }
 }
@@ -0,0 +1,190 @@
+//! Comments and blank lines, which [`Token`]s skip but the formatter has to keep.
+//!
+//! Whatever the lexer skips between two tokens is whitespace and comments, so comments are
+//! found by scanning those gaps rather than with a second lexer that could disagree.
+
+use std::ops::Index;
+
+use crate::{
+    fs::{File, FileId},
+    span::Span,
+};
+
+use super::Token;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentKind {
+    /// `// ..` up to the end of the line
+    Line,
+    /// `/* .. */`
+    Block,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Comment {
+    pub span: Span,
+    pub kind: CommentKind,
+}
+
+/// The comments of a file, handed out in source order as the formatter reaches them
+pub struct Trivia<'source> {
+    source: &'source str,
+    comments: Vec<Comment>,
+    next: usize,
+}
+
+impl<'source> Trivia<'source> {
+    pub fn new<A>(arena: &'source A, file: FileId) -> Self
+    where
+        A: Index<FileId, Output = File>,
+    {
+        let source: &str = &arena[file].contents;
+        let mut comments = Vec::new();
+        let mut gap_start = 0;
+        let mut lexer = Token::lexer_from_file(arena, file).spanned();
+        loop {
+            let next = lexer.next();
+            let gap_end = next.as_ref().map_or(source.len(), |(_, range)| range.start);
+            comments_in_gap(source, file, gap_start..gap_end, &mut comments);
+            match next {
+                Some((_, range)) => gap_start = range.end,
+                None => break,
+            }
+        }
+        Self {
+            source,
+            comments,
+            next: 0,
+        }
+    }
+
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    pub fn text(&self, comment: &Comment) -> &'source str {
+        &self.source[comment.span.range()]
+    }
+
+    /// Comments starting before `offset` that haven't been taken yet
+    pub fn take_before(&mut self, offset: usize) -> &[Comment] {
+        let start = self.next;
+        while self
+            .comments
+            .get(self.next)
+            .is_some_and(|comment| comment.span.start() < offset)
+        {
+            self.next += 1;
+        }
+        &self.comments[start..self.next]
+    }
+
+    /// The next comment, if it starts on the same line as `offset` with nothing but
+    /// whitespace in between
+    pub fn take_trailing(&mut self, offset: usize) -> Option<Comment> {
+        let comment = *self.comments.get(self.next)?;
+        let between = self.source.get(offset..comment.span.start())?;
+        if between.contains('\n') || !between.trim().is_empty() {
+            return None;
+        }
+        self.next += 1;
+        Some(comment)
+    }
+
+    /// Every comment that hasn't been taken yet
+    pub fn take_rest(&mut self) -> &[Comment] {
+        self.take_before(usize::MAX)
+    }
+
+    /// Whether the source between the two offsets has an empty line,
+    /// that is two line breaks with only whitespace between them
+    pub fn blank_line_between(&self, start: usize, end: usize) -> bool {
+        let Some(text) = self.source.get(start..end) else {
+            return false;
+        };
+        let lines: Vec<_> = text.split('\n').collect();
+        // The first and last lines are shared with whatever is before and after
+        lines.len() > 2
+            && lines[1..lines.len() - 1]
+                .iter()
+                .any(|line| line.trim().is_empty())
+    }
+}
+
+/// Scans text that is only whitespace and comments
+fn comments_in_gap(
+    source: &str,
+    file: FileId,
+    range: std::ops::Range<usize>,
+    comments: &mut Vec<Comment>,
+) {
+    let mut offset = range.start;
+    while offset < range.end {
+        let rest = &source[offset..range.end];
+        let (kind, len) = if rest.starts_with("//") {
+            // The `\r` of a `\r\n` is part of the line break, not of the comment
+            let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+            (
+                CommentKind::Line,
+                line.strip_suffix('\r').unwrap_or(line).len(),
+            )
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            (
+                CommentKind::Block,
+                body.find("*/").map_or(rest.len(), |end| end + 4),
+            )
+        } else {
+            offset += rest.chars().next().map_or(1, char::len_utf8);
+            continue;
+        };
+        comments.push(Comment {
+            span: Span::new(file, offset..offset + len),
+            kind,
+        });
+        offset += len;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use id_arena::Arena;
+
+    use crate::fs::File;
+
+    use super::{CommentKind, Trivia};
+
+    #[test]
+    fn finds_comments() {
+        let mut arena: Arena<File> = Arena::new();
+        let file = arena.alloc(File::new(
+            "f",
+            "// first\nlet x = 1; /* inline */\n\n\n  // after a gap\ny",
+        ));
+        let mut trivia = Trivia::new(&arena, file);
+        let texts: Vec<_> = trivia
+            .comments()
+            .iter()
+            .map(|comment| (trivia.text(comment), comment.kind))
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                ("// first", CommentKind::Line),
+                ("/* inline */", CommentKind::Block),
+                ("// after a gap", CommentKind::Line),
+            ]
+        );
+
+        assert_eq!(trivia.take_before(9).len(), 1);
+        assert!(trivia.take_trailing(9).is_none());
+        assert_eq!(trivia.take_before(19).len(), 0);
+        let inline = trivia.take_trailing(19).unwrap();
+        assert_eq!(trivia.text(&inline), "/* inline */");
+        assert!(trivia.take_trailing(inline.span.end()).is_none());
+        assert_eq!(trivia.take_rest().len(), 1);
+
+        assert!(!trivia.blank_line_between(0, 9));
+        assert!(trivia.blank_line_between(inline.span.end(), 39));
+        assert!(!trivia.blank_line_between(8, 12));
+    }
+}
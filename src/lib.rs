@@ -0,0 +1,24 @@
+use id_arena::Arena;
+
+use crate::fs::File;
+
+pub mod ast;
+pub mod error;
+pub mod fix;
+pub mod flow;
+pub mod fmt;
+pub mod fs;
+pub mod hir;
+pub mod lexer;
+pub mod mir;
+pub mod parser;
+pub mod pretty;
+pub mod resolve;
+pub mod span;
+pub mod symbol;
+#[cfg(test)]
+mod testing;
+pub mod transaction;
+pub mod typeck;
+
+pub type FileArena = Arena<File>;
@@ -0,0 +1,494 @@
+//! The mid-level IR: every function as a control-flow graph, see [`build()`].
+//!
+//! A [`Body`] is a list of basic blocks, each a run of [`Statement`]s that assign to places
+//! followed by one [`Terminator`] that says where control goes next. Every value lives in an
+//! explicit [`Local`]: `_0` is the return value, the parameters come next, and then the
+//! bindings and temporaries of the function. Operands are constants or copies of places, so
+//! evaluation order and control flow are all spelled out, which is what the analyses of
+//! initialization, reachability and the like work on. Statements and terminators keep the
+//! span of the expression they come from.
+//!
+//! Closures get bodies of their own, which take the locals they capture by copy as
+//! parameters after their own.
+
+use std::fmt::Write as _;
+
+use crate::{
+    ast::Identifier,
+    hir::{BinaryOp, DefId, Literal, UnaryOp, Variant},
+    span::Span,
+    typeck::ty::Ty,
+};
+
+mod build;
+pub mod dot;
+
+pub use build::build;
+
+#[derive(Debug, Default)]
+pub struct Mir {
+    /// The functions in the order they were defined, each followed by its closures
+    pub bodies: Vec<Body>,
+}
+
+impl Mir {
+    pub fn body(&self, def: DefId) -> Option<&Body> {
+        self.bodies.iter().find(|body| body.def == def)
+    }
+}
+
+#[derive(Debug)]
+pub struct Body {
+    /// The function or the closure expression
+    pub def: DefId,
+    pub name: String,
+    pub span: Span,
+    /// The number of parameters, which are `_1` to `_{arg_count}`
+    pub arg_count: usize,
+    pub locals: Vec<LocalDecl>,
+    /// Control starts in [`BasicBlock::START`]
+    pub blocks: Vec<BasicBlockData>,
+}
+
+impl Body {
+    pub fn args(&self) -> impl Iterator<Item = Local> {
+        (1..=self.arg_count).map(|index| Local(index as u32))
+    }
+
+    /// The blocks control can go to from `block`
+    pub fn successors(&self, block: BasicBlock) -> Vec<BasicBlock> {
+        self[block].terminator().kind.successors()
+    }
+
+    /// The blocks that control can go to `block` from, for every block
+    pub fn predecessors(&self) -> Vec<Vec<BasicBlock>> {
+        let mut predecessors = vec![Vec::new(); self.blocks.len()];
+        for (index, data) in self.blocks.iter().enumerate() {
+            for successor in data.terminator().kind.successors() {
+                predecessors[successor.index()].push(BasicBlock(index as u32));
+            }
+        }
+        predecessors
+    }
+
+    /// Whether control can get to each block from the start
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut stack = vec![BasicBlock::START];
+        while let Some(block) = stack.pop() {
+            if !std::mem::replace(&mut reachable[block.index()], true) {
+                stack.extend(self.successors(block));
+            }
+        }
+        reachable
+    }
+
+    pub fn basic_blocks(&self) -> impl Iterator<Item = (BasicBlock, &BasicBlockData)> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .map(|(index, data)| (BasicBlock(index as u32), data))
+    }
+}
+
+impl std::ops::Index<BasicBlock> for Body {
+    type Output = BasicBlockData;
+
+    fn index(&self, index: BasicBlock) -> &Self::Output {
+        &self.blocks[index.index()]
+    }
+}
+
+impl std::ops::Index<Local> for Body {
+    type Output = LocalDecl;
+
+    fn index(&self, index: Local) -> &Self::Output {
+        &self.locals[index.index()]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Local(u32);
+
+impl Local {
+    /// Where the return value goes
+    pub const RETURN: Self = Self(0);
+
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl std::fmt::Display for Local {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "_{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalDecl {
+    /// `None` for the return value and temporaries
+    pub name: Option<Identifier>,
+    pub ty: Ty,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BasicBlock(u32);
+
+impl BasicBlock {
+    pub const START: Self = Self(0);
+
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl std::fmt::Display for BasicBlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BasicBlockData {
+    pub statements: Vec<Statement>,
+    /// Only `None` while the block is being built
+    pub terminator: Option<Terminator>,
+}
+
+impl BasicBlockData {
+    pub fn terminator(&self) -> &Terminator {
+        self.terminator
+            .as_ref()
+            .expect("every block has a terminator once built")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub span: Span,
+    pub kind: StatementKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum StatementKind {
+    Assign(Place, Rvalue),
+}
+
+/// A local, or a part of what it holds or points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Place {
+    pub local: Local,
+    pub projection: Vec<Projection>,
+}
+
+impl Place {
+    /// The part `projection` picks out of this place
+    pub fn project(&self, projection: Projection) -> Self {
+        let mut place = self.clone();
+        place.projection.push(projection);
+        place
+    }
+}
+
+impl From<Local> for Place {
+    fn from(local: Local) -> Self {
+        Self {
+            local,
+            projection: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Projection {
+    /// What a reference points to
+    Deref,
+    /// A field of a tuple, or of a range as described for [`crate::hir::ExprKind::Field`]
+    Field(u32),
+    /// The element of an array at the index held by the local
+    Index(Local),
+    /// The value inside an `Option` or a `Result`, which has to be the variant
+    Payload(Variant),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Copy(Place),
+    Constant(Constant),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constant {
+    pub ty: Ty,
+    pub kind: ConstKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstKind {
+    /// The value of type `void`
+    Void,
+    Literal(Literal),
+    Fn(DefId),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rvalue {
+    Use(Operand),
+    /// Never `&&` or `||`, which are branches
+    Binary(BinaryOp, Operand, Operand),
+    /// `-` or `!`, dereferencing is a [`Projection::Deref`]
+    Unary(UnaryOp, Operand),
+    Ref {
+        mutable: bool,
+        place: Place,
+    },
+    Cast(Operand, Ty),
+    Aggregate(AggregateKind, Vec<Operand>),
+    /// `[operand; count]`
+    Repeat(Operand, u64),
+    /// Which variant of an `Option` or a `Result` the place holds, see
+    /// [`Variant::discriminant`]
+    Discriminant(Place),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AggregateKind {
+    Tuple,
+    Array,
+    Range {
+        inclusive: bool,
+    },
+    /// With the value inside as the only operand, if there's one
+    Variant(Variant),
+    /// With the locals the closure captures as operands
+    Closure(DefId),
+}
+
+#[derive(Debug, Clone)]
+pub struct Terminator {
+    pub span: Span,
+    pub kind: TerminatorKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum TerminatorKind {
+    Goto {
+        target: BasicBlock,
+    },
+    /// Goes to the target of the value of `discr`, read as an integer, or to `otherwise`
+    SwitchInt {
+        discr: Operand,
+        targets: Vec<(u128, BasicBlock)>,
+        otherwise: BasicBlock,
+    },
+    Return,
+    /// Calls a function or a closure, storing what it returns in `destination` before going
+    /// to `target`
+    Call {
+        func: Operand,
+        args: Vec<Operand>,
+        destination: Place,
+        target: BasicBlock,
+    },
+    /// Marks what control can never get to, like the end of a `match` none of whose arms
+    /// matched
+    Unreachable,
+}
+
+impl TerminatorKind {
+    pub fn successors(&self) -> Vec<BasicBlock> {
+        match self {
+            &Self::Goto { target } | &Self::Call { target, .. } => vec![target],
+            Self::SwitchInt {
+                targets, otherwise, ..
+            } => targets
+                .iter()
+                .map(|&(_, target)| target)
+                .chain([*otherwise])
+                .collect(),
+            Self::Return | Self::Unreachable => Vec::new(),
+        }
+    }
+}
+
+impl std::fmt::Display for Mir {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let printer = Printer { mir: self };
+        for (i, body) in self.bodies.iter().enumerate() {
+            if i != 0 {
+                f.write_str("\n")?;
+            }
+            f.write_str(&printer.body(body))?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the parts of a [`Mir`] in the syntax of its dump, which refers to other bodies by
+/// name
+struct Printer<'a> {
+    mir: &'a Mir,
+}
+
+impl Printer<'_> {
+    fn body(&self, body: &Body) -> String {
+        let mut out = format!("fn {}(", body.name);
+        for (i, arg) in body.args().enumerate() {
+            if i != 0 {
+                out.push_str(", ");
+            }
+            write!(out, "{arg}: {}", body[arg].ty).unwrap();
+        }
+        writeln!(out, "): {} {{", body[Local::RETURN].ty).unwrap();
+        for arg in body.args() {
+            if let Some(name) = body[arg].name {
+                writeln!(out, "    debug {name} => {arg};").unwrap();
+            }
+        }
+        for (index, decl) in body.locals.iter().enumerate() {
+            if (1..=body.arg_count).contains(&index) {
+                continue;
+            }
+            write!(out, "    let _{index}: {};", decl.ty).unwrap();
+            if let Some(name) = decl.name {
+                write!(out, " // {name}").unwrap();
+            }
+            out.push('\n');
+        }
+        for (block, data) in body.basic_blocks() {
+            writeln!(out, "\n    {block}: {{").unwrap();
+            for statement in &data.statements {
+                writeln!(out, "        {};", self.statement(statement)).unwrap();
+            }
+            writeln!(out, "        {};", self.terminator(data.terminator())).unwrap();
+            out.push_str("    }\n");
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn statement(&self, statement: &Statement) -> String {
+        match &statement.kind {
+            StatementKind::Assign(place, rvalue) => {
+                format!("{} = {}", self.place(place), self.rvalue(rvalue))
+            }
+        }
+    }
+
+    fn terminator(&self, terminator: &Terminator) -> String {
+        match &terminator.kind {
+            TerminatorKind::Goto { target } => format!("goto -> {target}"),
+            TerminatorKind::SwitchInt {
+                discr,
+                targets,
+                otherwise,
+            } => {
+                let mut out = format!("switchInt({}) -> [", self.operand(discr));
+                for (value, target) in targets {
+                    write!(out, "{value}: {target}, ").unwrap();
+                }
+                write!(out, "otherwise: {otherwise}]").unwrap();
+                out
+            }
+            TerminatorKind::Return => "return".to_string(),
+            TerminatorKind::Call {
+                func,
+                args,
+                destination,
+                target,
+            } => format!(
+                "{} = {}({}) -> {target}",
+                self.place(destination),
+                self.operand(func),
+                self.operands(args)
+            ),
+            TerminatorKind::Unreachable => "unreachable".to_string(),
+        }
+    }
+
+    fn place(&self, place: &Place) -> String {
+        let mut out = place.local.to_string();
+        for projection in &place.projection {
+            out = match projection {
+                Projection::Deref => format!("(*{out})"),
+                Projection::Field(index) => format!("{out}.{index}"),
+                Projection::Index(local) => format!("{out}[{local}]"),
+                Projection::Payload(variant) => format!("({out} as {})", variant.as_str()),
+            };
+        }
+        out
+    }
+
+    fn operand(&self, operand: &Operand) -> String {
+        match operand {
+            Operand::Copy(place) => format!("copy {}", self.place(place)),
+            Operand::Constant(constant) => format!("const {}", self.constant(constant)),
+        }
+    }
+
+    fn operands(&self, operands: &[Operand]) -> String {
+        operands
+            .iter()
+            .map(|operand| self.operand(operand))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn constant(&self, constant: &Constant) -> String {
+        match constant.kind {
+            ConstKind::Void => "()".to_string(),
+            ConstKind::Literal(Literal::Int(value)) => match constant.ty {
+                Ty::Int(ty) => format!("{}_{}", ty.format_bits(value), constant.ty),
+                _ => format!("{value}_{}", constant.ty),
+            },
+            ConstKind::Literal(Literal::Float(value)) => format!("{value}_{}", constant.ty),
+            ConstKind::Literal(Literal::Char(value)) => format!("{value:?}"),
+            ConstKind::Literal(Literal::Str(value)) => format!("{:?}", value.as_str()),
+            ConstKind::Literal(Literal::Bool(value)) => value.to_string(),
+            ConstKind::Fn(def) => self.name(def),
+        }
+    }
+
+    fn name(&self, def: DefId) -> String {
+        self.mir
+            .body(def)
+            .map_or_else(|| def.to_string(), |body| body.name.clone())
+    }
+
+    fn rvalue(&self, rvalue: &Rvalue) -> String {
+        match rvalue {
+            Rvalue::Use(operand) => self.operand(operand),
+            Rvalue::Binary(op, lhs, rhs) => {
+                format!("{} {op} {}", self.operand(lhs), self.operand(rhs))
+            }
+            Rvalue::Unary(op, operand) => format!("{op}{}", self.operand(operand)),
+            Rvalue::Ref { mutable, place } => format!(
+                "&{}{}",
+                if *mutable { "mut " } else { "" },
+                self.place(place)
+            ),
+            Rvalue::Cast(operand, ty) => format!("{} as {ty}", self.operand(operand)),
+            Rvalue::Aggregate(kind, operands) => {
+                let operands = self.operands(operands);
+                match kind {
+                    AggregateKind::Tuple if operands.contains(", ") => format!("({operands})"),
+                    AggregateKind::Tuple => format!("({operands},)"),
+                    AggregateKind::Array => format!("[{operands}]"),
+                    AggregateKind::Range { inclusive } => {
+                        let (start, end) = operands.split_once(", ").unwrap_or_default();
+                        format!("{start}{}{end}", if *inclusive { "..=" } else { ".." })
+                    }
+                    AggregateKind::Variant(variant) if operands.is_empty() => {
+                        variant.as_str().to_string()
+                    }
+                    AggregateKind::Variant(variant) => format!("{}({operands})", variant.as_str()),
+                    AggregateKind::Closure(def) => format!("{} [{operands}]", self.name(*def)),
+                }
+            }
+            Rvalue::Repeat(operand, count) => format!("[{}; {count}]", self.operand(operand)),
+            Rvalue::Discriminant(place) => format!("discriminant({})", self.place(place)),
+        }
+    }
+}
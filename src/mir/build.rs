@@ -0,0 +1,931 @@
+//! Building the [`Mir`] from the [`Hir`].
+//!
+//! Expressions are lowered by what's wanted of them: [`Builder::expr_into`] stores the value
+//! of an expression in a place and handles everything that branches or calls,
+//! [`Builder::rvalue`] computes a value with a single statement, [`Builder::operand`] and
+//! [`Builder::place`] give something to read from or to project, evaluating anything else
+//! into a temporary first. Each returns the block that control continues in.
+//!
+//! Values of type `void` are never stored or read: an expression of that type is only
+//! evaluated for its effects, and stands for `const ()` wherever it's used. After a `break`,
+//! `continue` or `return`, lowering carries on in a block nothing goes to.
+
+use crate::{
+    ast::Identifier,
+    hir::{self, DefId, ExprKind, Function, Hir, Literal, LocalId, PatKind, Stmt, UnaryOp},
+    span::Span,
+    typeck::ty::{IntTy, Ty},
+};
+
+use super::{
+    AggregateKind, BasicBlock, BasicBlockData, Body, ConstKind, Constant, Local, LocalDecl, Mir,
+    Operand, Place, Projection, Rvalue, Statement, StatementKind, Terminator, TerminatorKind,
+};
+
+/// Builds the bodies of the functions in `hir` and of the closures in them
+pub fn build(hir: &Hir) -> Mir {
+    let mut mir = Mir::default();
+    for function in &hir.functions {
+        let mut closures = Vec::new();
+        let mut builder = Builder::new(function, &mut closures);
+        let params: Vec<_> = function
+            .params
+            .iter()
+            .map(|&param| builder.declare(param))
+            .collect();
+        let body = builder.finish(
+            BasicBlock::START,
+            function.def,
+            function.name.to_string(),
+            function.span,
+            params.len(),
+            &function.body,
+        );
+        mir.bodies.push(body);
+        // Closures can add closures of their own, which come after them
+        let mut index = 0;
+        while let Some(closure) = closures.get(index).cloned() {
+            mir.bodies
+                .push(build_closure(function, closure, index, &mut closures));
+            index += 1;
+        }
+    }
+    mir
+}
+
+/// A closure expression found while building a body, to get a body of its own
+#[derive(Clone)]
+struct Closure<'a> {
+    expr: &'a hir::Expr,
+    /// The locals from outside the closure that it uses, in order
+    captures: Vec<LocalId>,
+}
+
+fn build_closure<'a>(
+    function: &'a Function,
+    closure: Closure<'a>,
+    index: usize,
+    closures: &mut Vec<Closure<'a>>,
+) -> Body {
+    let ExprKind::Closure { params, body } = &closure.expr.kind else {
+        unreachable!("only closure expressions are queued")
+    };
+    let Ty::Fn { ret, .. } = &closure.expr.ty else {
+        unreachable!("closures have function types")
+    };
+    let mut builder = Builder::new(function, closures);
+    builder.locals[Local::RETURN.index()].ty = (**ret).clone();
+    let args: Vec<_> = params
+        .iter()
+        .map(|param| match param.kind {
+            PatKind::Binding(local) => builder.declare(local),
+            _ => builder.new_local(None, param.ty.clone(), param.span),
+        })
+        .collect();
+    for &capture in &closure.captures {
+        builder.declare(capture);
+    }
+    let mut block = BasicBlock::START;
+    for (param, arg) in params.iter().zip(args) {
+        if !matches!(param.kind, PatKind::Binding(_)) {
+            block = builder.pattern(param, arg.into(), block, None);
+        }
+    }
+    builder.finish(
+        block,
+        closure.expr.def,
+        format!("{}::{{closure#{index}}}", function.name),
+        closure.expr.span,
+        params.len() + closure.captures.len(),
+        body,
+    )
+}
+
+/// The locals `closure` uses that are bound outside of it
+fn captures(closure: &hir::Expr) -> Vec<LocalId> {
+    let mut bound = Vec::new();
+    let mut used = Vec::new();
+    closure.walk(&mut |expr| match &expr.kind {
+        &ExprKind::Local(local) => used.push(local),
+        ExprKind::Closure { params, .. } => {
+            for param in params {
+                param.bindings(&mut |local| bound.push(local));
+            }
+        }
+        ExprKind::Block(block) | ExprKind::Loop(block) => {
+            for stmt in &block.stmts {
+                if let Stmt::Let { pat, .. } = stmt {
+                    pat.bindings(&mut |local| bound.push(local));
+                }
+            }
+        }
+        ExprKind::Match { arms, .. } => {
+            for arm in arms {
+                arm.pat.bindings(&mut |local| bound.push(local));
+            }
+        }
+        _ => {}
+    });
+    used.sort();
+    used.dedup();
+    used.retain(|local| !bound.contains(local));
+    used
+}
+
+/// Where `break` and `continue` go in a loop, and where `break` puts its value
+struct LoopScope {
+    head: BasicBlock,
+    exit: BasicBlock,
+    dest: Place,
+}
+
+struct Builder<'a, 'c> {
+    function: &'a Function,
+    closures: &'c mut Vec<Closure<'a>>,
+    locals: Vec<LocalDecl>,
+    blocks: Vec<BasicBlockData>,
+    /// The local of every local of the function in the body, once it's been declared
+    local_map: Vec<Option<Local>>,
+    loops: Vec<LoopScope>,
+    /// Where the values of expressions of type `void` would go, created when first needed
+    void: Option<Local>,
+}
+
+impl<'a, 'c> Builder<'a, 'c> {
+    fn new(function: &'a Function, closures: &'c mut Vec<Closure<'a>>) -> Self {
+        Self {
+            function,
+            closures,
+            locals: vec![LocalDecl {
+                name: None,
+                ty: function.ret.clone(),
+                span: function.span,
+            }],
+            blocks: vec![BasicBlockData::default()],
+            local_map: vec![None; function.locals.len()],
+            loops: Vec::new(),
+            void: None,
+        }
+    }
+
+    /// Builds `body` from `block` on into the return value, and returns after it
+    fn finish(
+        mut self,
+        block: BasicBlock,
+        def: DefId,
+        name: String,
+        span: Span,
+        arg_count: usize,
+        body: &'a hir::Expr,
+    ) -> Body {
+        let block = self.expr_into(&Local::RETURN.into(), body, block);
+        self.terminate(block, body.span, TerminatorKind::Return);
+        Body {
+            def,
+            name,
+            span,
+            arg_count,
+            locals: self.locals,
+            blocks: self.blocks,
+        }
+    }
+
+    fn new_local(&mut self, name: Option<Identifier>, ty: Ty, span: Span) -> Local {
+        self.locals.push(LocalDecl { name, ty, span });
+        Local(self.locals.len() as u32 - 1)
+    }
+
+    fn temp(&mut self, ty: Ty, span: Span) -> Local {
+        self.new_local(None, ty, span)
+    }
+
+    /// The local for a local of the function, declaring it if this is its binding
+    fn declare(&mut self, id: LocalId) -> Local {
+        if let Some(local) = self.local_map[id.index()] {
+            return local;
+        }
+        let hir::Local { name, ty, span } = self.function[id].clone();
+        let local = self.new_local(name, ty, span);
+        self.local_map[id.index()] = Some(local);
+        local
+    }
+
+    /// Where to evaluate an expression of type `ty` whose value isn't used
+    fn scratch(&mut self, ty: &Ty, span: Span) -> Place {
+        if is_void(ty) {
+            if self.void.is_none() {
+                self.void = Some(self.temp(Ty::Void, span));
+            }
+            self.void.unwrap().into()
+        } else {
+            self.temp(ty.clone(), span).into()
+        }
+    }
+
+    fn new_block(&mut self) -> BasicBlock {
+        self.blocks.push(BasicBlockData::default());
+        BasicBlock(self.blocks.len() as u32 - 1)
+    }
+
+    fn push(&mut self, block: BasicBlock, span: Span, place: Place, rvalue: Rvalue) {
+        self.blocks[block.index()].statements.push(Statement {
+            span,
+            kind: StatementKind::Assign(place, rvalue),
+        });
+    }
+
+    fn terminate(&mut self, block: BasicBlock, span: Span, kind: TerminatorKind) {
+        let data = &mut self.blocks[block.index()];
+        debug_assert!(data.terminator.is_none(), "{block} is already terminated");
+        data.terminator = Some(Terminator { span, kind });
+    }
+
+    fn goto(&mut self, block: BasicBlock, span: Span, target: BasicBlock) {
+        self.terminate(block, span, TerminatorKind::Goto { target });
+    }
+
+    /// Ends `block` with a jump that doesn't come back, and gives the block that what comes
+    /// after it is lowered into
+    fn diverge(&mut self, block: BasicBlock, span: Span, kind: TerminatorKind) -> BasicBlock {
+        self.terminate(block, span, kind);
+        self.new_block()
+    }
+
+    /// Evaluates `expr` into `dest`, which isn't written to if it's of type `void`
+    fn expr_into(
+        &mut self,
+        dest: &Place,
+        expr: &'a hir::Expr,
+        mut block: BasicBlock,
+    ) -> BasicBlock {
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Block(body) => self.block_into(dest, body, block),
+            ExprKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let (block, condition) = self.operand(condition, block);
+                let then_block = self.new_block();
+                let else_block = self.new_block();
+                self.terminate(
+                    block,
+                    span,
+                    TerminatorKind::SwitchInt {
+                        discr: condition,
+                        targets: vec![(0, else_block)],
+                        otherwise: then_block,
+                    },
+                );
+                let join = self.new_block();
+                let then_end = self.expr_into(dest, then_branch, then_block);
+                self.goto(then_end, span, join);
+                let else_end = match else_branch {
+                    Some(else_branch) => self.expr_into(dest, else_branch, else_block),
+                    None => else_block,
+                };
+                self.goto(else_end, span, join);
+                join
+            }
+            ExprKind::Loop(body) => {
+                let head = self.new_block();
+                self.goto(block, span, head);
+                let exit = self.new_block();
+                self.loops.push(LoopScope {
+                    head,
+                    exit,
+                    dest: dest.clone(),
+                });
+                let void = self.scratch(&Ty::Void, span);
+                let end = self.block_into(&void, body, head);
+                self.goto(end, span, head);
+                self.loops.pop();
+                exit
+            }
+            ExprKind::Break(value) => {
+                let scope = self
+                    .loops
+                    .last()
+                    .expect("`break` is checked to be in a loop");
+                let (dest, exit) = (scope.dest.clone(), scope.exit);
+                if let Some(value) = value {
+                    block = self.expr_into(&dest, value, block);
+                }
+                self.diverge(block, span, TerminatorKind::Goto { target: exit })
+            }
+            ExprKind::Continue => {
+                let scope = self
+                    .loops
+                    .last()
+                    .expect("`continue` is checked to be in a loop");
+                let head = scope.head;
+                self.diverge(block, span, TerminatorKind::Goto { target: head })
+            }
+            ExprKind::Return(value) => {
+                if let Some(value) = value {
+                    block = self.expr_into(&Local::RETURN.into(), value, block);
+                }
+                self.diverge(block, span, TerminatorKind::Return)
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                let (mut block, scrutinee) = self.place(scrutinee, block);
+                let join = self.new_block();
+                for arm in arms {
+                    let fail = self.new_block();
+                    let matched = self.pattern(&arm.pat, scrutinee.clone(), block, Some(fail));
+                    let end = self.expr_into(dest, &arm.body, matched);
+                    self.goto(end, arm.body.span, join);
+                    block = fail;
+                }
+                self.terminate(block, span, TerminatorKind::Unreachable);
+                join
+            }
+            ExprKind::Assign { place, value } => {
+                let (block, rvalue) = self.rvalue(value, block);
+                let (block, place) = self.place(place, block);
+                if !is_void(&value.ty) {
+                    self.push(block, span, place, rvalue);
+                }
+                block
+            }
+            ExprKind::Call { callee, args } => {
+                let (mut block, func) = self.operand(callee, block);
+                let args = self.operands(args, &mut block);
+                let target = self.new_block();
+                self.terminate(
+                    block,
+                    span,
+                    TerminatorKind::Call {
+                        func,
+                        args,
+                        destination: dest.clone(),
+                        target,
+                    },
+                );
+                target
+            }
+            _ => {
+                let (block, rvalue) = self.rvalue(expr, block);
+                if !is_void(&expr.ty) {
+                    self.push(block, span, dest.clone(), rvalue);
+                }
+                block
+            }
+        }
+    }
+
+    fn block_into(
+        &mut self,
+        dest: &Place,
+        body: &'a hir::Block,
+        mut block: BasicBlock,
+    ) -> BasicBlock {
+        for stmt in &body.stmts {
+            block = self.stmt(stmt, block);
+        }
+        match &body.expr {
+            Some(tail) => self.expr_into(dest, tail, block),
+            None => block,
+        }
+    }
+
+    fn stmt(&mut self, stmt: &'a Stmt, block: BasicBlock) -> BasicBlock {
+        match stmt {
+            Stmt::Let {
+                pat, init: None, ..
+            } => {
+                // Assigned later, if at all, see `crate::flow`
+                pat.bindings(&mut |local| {
+                    self.declare(local);
+                });
+                block
+            }
+            Stmt::Let {
+                pat,
+                init: Some(init),
+                ..
+            } => {
+                if let PatKind::Binding(local) = pat.kind {
+                    let local = self.declare(local);
+                    return self.expr_into(&local.into(), init, block);
+                }
+                let (block, place) = self.place(init, block);
+                self.pattern(pat, place, block, None)
+            }
+            Stmt::Expr(expr) => {
+                let dest = self.scratch(&expr.ty, expr.span);
+                self.expr_into(&dest, expr, block)
+            }
+        }
+    }
+
+    /// Computes the value of `expr` as one right-hand side
+    fn rvalue(&mut self, expr: &'a hir::Expr, block: BasicBlock) -> (BasicBlock, Rvalue) {
+        let mut block = block;
+        let rvalue = match &expr.kind {
+            ExprKind::Binary { op, lhs, rhs } => {
+                let (next, lhs) = self.operand(lhs, block);
+                let (next, rhs) = self.operand(rhs, next);
+                block = next;
+                Rvalue::Binary(*op, lhs, rhs)
+            }
+            &ExprKind::Unary {
+                op: op @ (UnaryOp::Neg | UnaryOp::Not),
+                ref operand,
+            } => {
+                let (next, operand) = self.operand(operand, block);
+                block = next;
+                Rvalue::Unary(op, operand)
+            }
+            &ExprKind::Ref {
+                mutable,
+                ref operand,
+            } => {
+                let (next, place) = self.place(operand, block);
+                block = next;
+                Rvalue::Ref { mutable, place }
+            }
+            ExprKind::Cast(operand) => {
+                let (next, operand) = self.operand(operand, block);
+                block = next;
+                Rvalue::Cast(operand, expr.ty.clone())
+            }
+            ExprKind::Array(elements) => {
+                Rvalue::Aggregate(AggregateKind::Array, self.operands(elements, &mut block))
+            }
+            ExprKind::Tuple(elements) => {
+                Rvalue::Aggregate(AggregateKind::Tuple, self.operands(elements, &mut block))
+            }
+            &ExprKind::ArrayRepeat { ref value, count } => {
+                let (next, value) = self.operand(value, block);
+                block = next;
+                Rvalue::Repeat(value, count)
+            }
+            &ExprKind::Range {
+                ref start,
+                ref end,
+                inclusive,
+            } => {
+                let (next, start) = self.operand(start, block);
+                let (next, end) = self.operand(end, next);
+                block = next;
+                Rvalue::Aggregate(AggregateKind::Range { inclusive }, vec![start, end])
+            }
+            &ExprKind::Variant { variant, ref value } => {
+                let value = match value {
+                    Some(value) => {
+                        let (next, value) = self.operand(value, block);
+                        block = next;
+                        vec![value]
+                    }
+                    None => Vec::new(),
+                };
+                Rvalue::Aggregate(AggregateKind::Variant(variant), value)
+            }
+            ExprKind::Closure { .. } => {
+                let captures = captures(expr);
+                let operands = captures
+                    .iter()
+                    .map(|&local| Operand::Copy(self.declare(local).into()))
+                    .collect();
+                self.closures.push(Closure { expr, captures });
+                Rvalue::Aggregate(AggregateKind::Closure(expr.def), operands)
+            }
+            _ => {
+                let (next, operand) = self.operand(expr, block);
+                block = next;
+                Rvalue::Use(operand)
+            }
+        };
+        (block, rvalue)
+    }
+
+    /// Gives something that reads the value of `expr`
+    fn operand(&mut self, expr: &'a hir::Expr, block: BasicBlock) -> (BasicBlock, Operand) {
+        let constant = |kind| {
+            Operand::Constant(Constant {
+                ty: expr.ty.clone(),
+                kind,
+            })
+        };
+        if is_void(&expr.ty) {
+            let void = self.scratch(&expr.ty, expr.span);
+            let block = self.expr_into(&void, expr, block);
+            return (block, constant(ConstKind::Void));
+        }
+        match expr.kind {
+            ExprKind::Literal(literal) => (block, constant(ConstKind::Literal(literal))),
+            ExprKind::Fn(def) => (block, constant(ConstKind::Fn(def))),
+            _ => {
+                let (block, place) = self.place(expr, block);
+                (block, Operand::Copy(place))
+            }
+        }
+    }
+
+    /// Gives operands for `exprs`, evaluated in order from `block` on
+    fn operands(&mut self, exprs: &'a [hir::Expr], block: &mut BasicBlock) -> Vec<Operand> {
+        exprs
+            .iter()
+            .map(|expr| {
+                let (next, operand) = self.operand(expr, *block);
+                *block = next;
+                operand
+            })
+            .collect()
+    }
+
+    /// Gives the place that `expr` stands for, or a temporary holding its value
+    fn place(&mut self, expr: &'a hir::Expr, block: BasicBlock) -> (BasicBlock, Place) {
+        match &expr.kind {
+            &ExprKind::Local(local) => (block, self.declare(local).into()),
+            ExprKind::Unary {
+                op: UnaryOp::Deref,
+                operand,
+            } => {
+                let (block, place) = self.place(operand, block);
+                (block, place.project(Projection::Deref))
+            }
+            &ExprKind::Field { ref base, index } => {
+                let (block, place) = self.place(base, block);
+                (block, place.project(Projection::Field(index)))
+            }
+            ExprKind::Index { base, index } => {
+                let (block, place) = self.place(base, block);
+                // Into a temporary of its own, so that the index can't change before it's used
+                let temp = self.temp(index.ty.clone(), index.span);
+                let block = self.expr_into(&temp.into(), index, block);
+                (block, place.project(Projection::Index(temp)))
+            }
+            _ => {
+                let temp = self.temp(expr.ty.clone(), expr.span);
+                let block = self.expr_into(&temp.into(), expr, block);
+                (block, temp.into())
+            }
+        }
+    }
+
+    /// Tests whether the value in `place` matches `pat`, and assigns its bindings if it does,
+    /// going on in the block this returns or in `fail` otherwise. Patterns without a `fail`
+    /// can't be refuted.
+    fn pattern(
+        &mut self,
+        pat: &'a hir::Pat,
+        place: Place,
+        block: BasicBlock,
+        mut fail: Option<BasicBlock>,
+    ) -> BasicBlock {
+        let span = pat.span;
+        let mut fail_block = |this: &mut Self| {
+            *fail.get_or_insert_with(|| {
+                let unreachable = this.new_block();
+                this.terminate(unreachable, span, TerminatorKind::Unreachable);
+                unreachable
+            })
+        };
+        match &pat.kind {
+            PatKind::Wild => block,
+            &PatKind::Binding(local) => {
+                let local = self.declare(local);
+                if !is_void(&pat.ty) {
+                    self.push(block, span, local.into(), Rvalue::Use(Operand::Copy(place)));
+                }
+                block
+            }
+            &PatKind::Literal(literal) => {
+                let value = match literal {
+                    Literal::Int(value) => Some(value),
+                    Literal::Char(value) => Some(value as u128),
+                    Literal::Bool(value) => Some(value as u128),
+                    Literal::Float(_) | Literal::Str(_) => None,
+                };
+                let matched = self.new_block();
+                let fail = fail_block(self);
+                let (discr, targets, otherwise) = match value {
+                    Some(value) => (Operand::Copy(place), vec![(value, matched)], fail),
+                    None => {
+                        // Compared with `==`, which gives `0` if they're different
+                        let equal = self.temp(Ty::Bool, span);
+                        let literal = Operand::Constant(Constant {
+                            ty: pat.ty.clone(),
+                            kind: ConstKind::Literal(literal),
+                        });
+                        self.push(
+                            block,
+                            span,
+                            equal.into(),
+                            Rvalue::Binary(hir::BinaryOp::Eq, Operand::Copy(place), literal),
+                        );
+                        (Operand::Copy(equal.into()), vec![(0, fail)], matched)
+                    }
+                };
+                self.terminate(
+                    block,
+                    span,
+                    TerminatorKind::SwitchInt {
+                        discr,
+                        targets,
+                        otherwise,
+                    },
+                );
+                matched
+            }
+            &PatKind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                // Each end is compared on its own, going to `fail` as soon as one doesn't hold
+                let end_op = if inclusive {
+                    hir::BinaryOp::Le
+                } else {
+                    hir::BinaryOp::Lt
+                };
+                let fail = fail_block(self);
+                let mut block = block;
+                for (op, bound) in [(hir::BinaryOp::Ge, start), (end_op, end)] {
+                    let holds = self.temp(Ty::Bool, span);
+                    let bound = Operand::Constant(Constant {
+                        ty: pat.ty.clone(),
+                        kind: ConstKind::Literal(bound),
+                    });
+                    self.push(
+                        block,
+                        span,
+                        holds.into(),
+                        Rvalue::Binary(op, Operand::Copy(place.clone()), bound),
+                    );
+                    let next = self.new_block();
+                    self.terminate(
+                        block,
+                        span,
+                        TerminatorKind::SwitchInt {
+                            discr: Operand::Copy(holds.into()),
+                            targets: vec![(0, fail)],
+                            otherwise: next,
+                        },
+                    );
+                    block = next;
+                }
+                block
+            }
+            PatKind::Tuple(elements) => {
+                let mut block = block;
+                for (index, element) in elements.iter().enumerate() {
+                    let fail = if is_refutable(element) {
+                        Some(fail_block(self))
+                    } else {
+                        None
+                    };
+                    block = self.pattern(
+                        element,
+                        place.project(Projection::Field(index as u32)),
+                        block,
+                        fail,
+                    );
+                }
+                block
+            }
+            &PatKind::Variant { variant, ref inner } => {
+                let discriminant = self.temp(Ty::Int(IntTy::U8), span);
+                self.push(
+                    block,
+                    span,
+                    discriminant.into(),
+                    Rvalue::Discriminant(place.clone()),
+                );
+                let matched = self.new_block();
+                let fail = fail_block(self);
+                self.terminate(
+                    block,
+                    span,
+                    TerminatorKind::SwitchInt {
+                        discr: Operand::Copy(discriminant.into()),
+                        targets: vec![(variant.discriminant(), matched)],
+                        otherwise: fail,
+                    },
+                );
+                match inner {
+                    Some(inner) => self.pattern(
+                        inner,
+                        place.project(Projection::Payload(variant)),
+                        matched,
+                        Some(fail),
+                    ),
+                    None => matched,
+                }
+            }
+        }
+    }
+}
+
+/// Whether a value of the type is never stored, because there's nothing to it or there's
+/// never one
+fn is_void(ty: &Ty) -> bool {
+    matches!(ty, Ty::Void | Ty::Never)
+}
+
+fn is_refutable(pat: &hir::Pat) -> bool {
+    match &pat.kind {
+        PatKind::Wild | PatKind::Binding(_) => false,
+        PatKind::Literal(_) | PatKind::Range { .. } | PatKind::Variant { .. } => true,
+        PatKind::Tuple(elements) => elements.iter().any(is_refutable),
+    }
+}
+
+#[cfg(test)]
+pub(super) mod test {
+    use crate::{
+        mir::{BasicBlock, Mir, TerminatorKind},
+        testing::Source,
+    };
+
+    use super::build;
+
+    pub fn build_source(source: &str) -> Mir {
+        build(&Source::parse(source).lower())
+    }
+
+    #[test]
+    fn branches_and_loops() {
+        let mir = build_source(
+            "fn f(a: bool, n: u8): u8 {
+                let x = if a { n } else { 1 };
+                while x > 0 { x -= 1; }
+                x
+            }",
+        );
+        assert_eq!(
+            mir.to_string(),
+            "fn f(_1: bool, _2: u8): u8 {
+    debug a => _1;
+    debug n => _2;
+    let _0: u8;
+    let _3: u8; // x
+    let _4: void;
+    let _5: bool;
+
+    bb0: {
+        switchInt(copy _1) -> [0: bb2, otherwise: bb1];
+    }
+
+    bb1: {
+        _3 = copy _2;
+        goto -> bb3;
+    }
+
+    bb2: {
+        _3 = const 1_u8;
+        goto -> bb3;
+    }
+
+    bb3: {
+        goto -> bb4;
+    }
+
+    bb4: {
+        _5 = copy _3 > const 0_u8;
+        switchInt(copy _5) -> [0: bb7, otherwise: bb6];
+    }
+
+    bb5: {
+        _0 = copy _3;
+        return;
+    }
+
+    bb6: {
+        _3 = copy _3 - const 1_u8;
+        goto -> bb8;
+    }
+
+    bb7: {
+        goto -> bb5;
+    }
+
+    bb8: {
+        goto -> bb4;
+    }
+
+    bb9: {
+        goto -> bb8;
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn calls_patterns_and_closures() {
+        let mir = build_source(
+            "fn f(n: u32, o: Option<(u32, u32)>, p: Option<u32>): Option<u32> {
+                let add = |a, b| a + b + n;
+                let (x, _) = o?;
+                g(add(x, 1), p)
+            }
+            fn g(y: u32, p: Option<u32>): Option<u32> { p }",
+        );
+        let text = mir.to_string();
+        assert!(text.contains("_4 = f::{closure#0} [copy _1];"), "{text}");
+        assert!(
+            text.contains("switchInt(copy _6) -> [1: bb3, otherwise: bb2];"),
+            "{text}"
+        );
+        assert!(text.contains("_7 = copy (_2 as Some);"), "{text}");
+        assert!(
+            text.contains("_10 = copy _4(copy _9, const 1_u32) -> bb7;"),
+            "{text}"
+        );
+        assert!(
+            text.contains("_0 = const g(copy _10, copy _3) -> bb8;"),
+            "{text}"
+        );
+        // The closure takes what it captures after its own parameters
+        assert!(
+            text.contains("fn f::{closure#0}(_1: u32, _2: u32, _3: u32): u32 {"),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn range_patterns_test_each_end() {
+        let mir = build_source("fn f(x: i8): u8 { match x { -10..=-1 => 0, 0 => 1, _ => 2 } }");
+        assert_eq!(
+            mir.to_string(),
+            "fn f(_1: i8): u8 {
+    debug x => _1;
+    let _0: u8;
+    let _2: bool;
+    let _3: bool;
+
+    bb0: {
+        _2 = copy _1 >= const -10_i8;
+        switchInt(copy _2) -> [0: bb2, otherwise: bb3];
+    }
+
+    bb1: {
+        return;
+    }
+
+    bb2: {
+        switchInt(copy _1) -> [0: bb6, otherwise: bb5];
+    }
+
+    bb3: {
+        _3 = copy _1 <= const -1_i8;
+        switchInt(copy _3) -> [0: bb2, otherwise: bb4];
+    }
+
+    bb4: {
+        _0 = const 0_u8;
+        goto -> bb1;
+    }
+
+    bb5: {
+        _0 = const 2_u8;
+        goto -> bb1;
+    }
+
+    bb6: {
+        _0 = const 1_u8;
+        goto -> bb1;
+    }
+
+    bb7: {
+        unreachable;
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn every_block_is_terminated_with_existing_targets() {
+        let mir = build_source(
+            "fn f(p: &[(bool, char); 2], r: Result<u8, u8>): Result<u8, u8> {
+                let sum = 0;
+                for q in p { if q.0 && q.1 == 'x' { continue; } sum += 1; }
+                for i in 0..=9 { if i == 3 { break; } }
+                let f = |v: u8| { let w = v; loop { return w + sum; } };
+                let e = loop { if r? > 2 { break f(1); } };
+                sum += e;
+                r
+            }",
+        );
+        assert_eq!(mir.bodies.len(), 2);
+        for body in &mir.bodies {
+            let mut returns = 0;
+            for (block, data) in body.basic_blocks() {
+                let kind = &data.terminator().kind;
+                returns += matches!(kind, TerminatorKind::Return) as usize;
+                for successor in body.successors(block) {
+                    assert!(successor.index() < body.blocks.len(), "{mir}");
+                    assert_ne!(successor, BasicBlock::START, "{mir}");
+                }
+            }
+            assert!(returns >= 1, "{mir}");
+            let predecessors = body.predecessors();
+            assert!(predecessors[BasicBlock::START.index()].is_empty());
+        }
+    }
+}
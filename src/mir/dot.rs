@@ -0,0 +1,77 @@
+//! Graphviz export of the control-flow graphs of a [`Mir`].
+
+use std::fmt::Write as _;
+
+use super::{Mir, Printer, TerminatorKind};
+
+/// Writes every body as a `digraph` of its basic blocks, with the statements and terminator
+/// of each block as its label and the values a `switchInt` goes to a block for on its edges
+pub fn to_dot(mir: &Mir) -> String {
+    let printer = Printer { mir };
+    let mut out = String::new();
+    for body in &mir.bodies {
+        writeln!(out, "digraph {} {{", quote(&body.name)).unwrap();
+        out.push_str("    node [shape=box, fontname=monospace];\n");
+        for (block, data) in body.basic_blocks() {
+            let mut label = format!("{block}:\\l");
+            for statement in &data.statements {
+                write!(label, "{};\\l", escape(&printer.statement(statement))).unwrap();
+            }
+            write!(
+                label,
+                "{};\\l",
+                escape(&printer.terminator(data.terminator()))
+            )
+            .unwrap();
+            writeln!(out, "    {block} [label=\"{label}\"];").unwrap();
+            match &data.terminator().kind {
+                TerminatorKind::SwitchInt {
+                    targets, otherwise, ..
+                } => {
+                    for (value, target) in targets {
+                        writeln!(out, "    {block} -> {target} [label=\"{value}\"];").unwrap();
+                    }
+                    writeln!(out, "    {block} -> {otherwise} [label=\"otherwise\"];").unwrap();
+                }
+                kind => {
+                    for target in kind.successors() {
+                        writeln!(out, "    {block} -> {target};").unwrap();
+                    }
+                }
+            }
+        }
+        out.push_str("}\n");
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", escape(text))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mir::build::test::build_source;
+
+    use super::to_dot;
+
+    #[test]
+    fn blocks_are_nodes_and_switches_have_labelled_edges() {
+        let mir = build_source("fn f(a: bool): char { if a { 'a' } else { '\"' } }");
+        let dot = to_dot(&mir);
+        assert!(dot.starts_with("digraph \"f\" {\n"), "{dot}");
+        assert!(
+            dot.contains(
+                "    bb0 [label=\"bb0:\\lswitchInt(copy _1) -> [0: bb2, otherwise: bb1];\\l\"];\n    bb0 -> bb2 [label=\"0\"];\n    bb0 -> bb1 [label=\"otherwise\"];\n"
+            ),
+            "{dot}"
+        );
+        assert!(dot.contains("_0 = const '\\\"';"), "{dot}");
+        assert!(dot.contains("    bb1 -> bb3;\n"), "{dot}");
+        assert!(dot.ends_with("}\n"), "{dot}");
+    }
+}
@@ -1,14 +1,24 @@
+use miette::{Diagnostic, LabeledSpan};
 use thiserror::Error;
 
 use crate::{
-    ast::{Block, Expression, Identifier, Item, Statement, Type},
-    error::Context,
-    lexer::{tokens::TokenReader, Token},
+    ast::{
+        BinaryOp, Block, ClosureParam, ExprId, ExpressionKind, Identifier, ItemId, ItemKind,
+        LiteralPattern, MatchArm, Path, Pattern, PatternKind, Precedence, StatementKind, StmtId,
+        Type, UnaryOp,
+    },
+    error::{
+        snippet_labels,
+        suggestion::{Applicability, Suggestion},
+        Context,
+    },
+    lexer::{tokens::TokenReader, LexError, Token},
     span::{Span, Spanned},
+    symbol::Symbol,
     transaction::ParserState,
 };
 
-use self::context::ContextName;
+pub use self::context::ContextName;
 
 mod context;
 
@@ -17,14 +27,14 @@ fn parse_token<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>
     expected: Token,
 ) -> Result<Span, C::Error> {
     let r = match state.current() {
-        None => Err(state.message(ParseError::UnexpectedEOI)),
+        None => Err(state.message(ParseError::UnexpectedEOI(state.eoi_span()))),
         Some((token, span, _)) => match token {
             Ok(x) if x == &expected => Ok(span),
             Ok(&token) => Err(state.message(ParseError::UnexpectedTokenWithExpectation {
                 found: span.spanned(token),
                 expected,
             })),
-            Err(()) => Err(state.message(ParseError::InvalidToken(span))),
+            Err(&err) => Err(state.message(ParseError::lex(err, span))),
         },
     };
     state.advance();
@@ -36,24 +46,140 @@ fn maybe_parse_token<'source, C: Context<ContextName>, T: TokenReader<'source, T
     expected: Token,
 ) -> Result<Option<Span>, C::Error> {
     match state.current() {
-        None => Err(state.message(ParseError::UnexpectedEOI)),
+        None => Err(state.message(ParseError::UnexpectedEOI(state.eoi_span()))),
         Some((token, span, _)) => match token {
             Ok(x) if x == &expected => {
                 state.advance();
                 Ok(Some(span))
             }
             Ok(_) => Ok(None),
-            Err(()) => {
+            Err(&err) => {
                 state.advance();
-                Err(state.message(ParseError::InvalidToken(span)))
+                Err(state.message(ParseError::lex(err, span)))
             }
         },
     }
 }
 
+/// Like [`parse_token`] for tokens that close something, such as `;` or `)`.
+/// If the token is missing, reports where it should go and carries on as if it was there,
+/// returning an empty span right after the previous token.
+fn parse_closing<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+    expected: Token,
+) -> Result<Span, C::Error> {
+    match (state.current(), state.previous_span()) {
+        (Some((Ok(token), span, _)), _) if token == &expected => {
+            state.advance();
+            Ok(span)
+        }
+        (Some((Ok(&token), span, _)), Some(after)) => {
+            state.message(ParseError::Missing {
+                expected,
+                after,
+                found: Some(span.spanned(token)),
+            });
+            Ok(Span::new(after.file(), after.end()..after.end()))
+        }
+        (None, Some(after)) => {
+            state.message(ParseError::Missing {
+                expected,
+                after,
+                found: None,
+            });
+            Ok(Span::new(after.file(), after.end()..after.end()))
+        }
+        _ => parse_token(state, expected),
+    }
+}
+
+/// Parses `element, element, ... closing`, allowing a trailing comma.
+/// Returns the elements, whether the last one was followed by a comma and the span of `closing`.
+fn parse_comma_separated<'source, C, T, U, F>(
+    state: &mut ParserState<C, T>,
+    closing: Token,
+    mut parse_element: F,
+) -> Result<(Vec<U>, bool, Span), C::Error>
+where
+    C: Context<ContextName>,
+    T: TokenReader<'source, Token>,
+    F: FnMut(&mut ParserState<C, T>) -> Result<U, C::Error>,
+{
+    let mut elements = Vec::new();
+    let mut trailing_comma = false;
+    let end = loop {
+        if let Some(end) = maybe_parse_token(state, closing)? {
+            break end;
+        }
+        elements.push(parse_element(state)?);
+        trailing_comma = maybe_parse_token(state, Token::Comma)?.is_some();
+        if !trailing_comma {
+            break parse_closing(state, closing)?;
+        }
+    };
+    Ok((elements, trailing_comma, end))
+}
+
+fn alloc_expression<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+    span: Span,
+    kind: ExpressionKind,
+) -> ExprId {
+    state.ast_mut().alloc_expression(span, kind)
+}
+
+fn expression_span<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &ParserState<C, T>,
+    id: ExprId,
+) -> Span {
+    state.ast()[id].span
+}
+
+/// Parses items until the end of the input. Errors are left to the context,
+/// after one the parser silently skips ahead to the next `fn`.
+pub fn parse_file<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Vec<ItemId> {
+    let mut items = Vec::new();
+    while !state.is_eoi() {
+        match parse_item(state) {
+            Ok(item) => items.push(item),
+            Err(_) => {
+                while !state.is_eoi() && state.get_current_token() != Some(Ok(&Token::Fn)) {
+                    state.advance();
+                }
+            }
+        }
+    }
+    items
+}
+
+/// Skips the rest of a statement that failed to parse, up to and including its `;`,
+/// or up to the `}` closing the block it's in. Braces in between are skipped in pairs.
+/// Fails at the end of the input, where there's no block left to carry on with.
+fn skip_statement<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<(), ()> {
+    let mut depth = 0usize;
+    loop {
+        match state.get_current_token() {
+            None => return Err(()),
+            Some(Ok(Token::Semicolon)) if depth == 0 => {
+                state.advance();
+                return Ok(());
+            }
+            Some(Ok(Token::ClosingBracket)) if depth == 0 => return Ok(()),
+            Some(Ok(Token::OpeningBracket)) => depth += 1,
+            Some(Ok(Token::ClosingBracket)) => depth -= 1,
+            Some(_) => {}
+        }
+        state.advance();
+    }
+}
+
 pub fn parse_item<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
     state: &mut ParserState<C, T>,
-) -> Result<Spanned<Item<'source>>, C::Error> {
+) -> Result<ItemId, C::Error> {
     state.context(ContextName::Item, |state| match state.current() {
         Some((token, span, _)) => match token {
             Ok(&token) => match token {
@@ -61,100 +187,999 @@ pub fn parse_item<'source, C: Context<ContextName>, T: TokenReader<'source, Toke
                     state.advance();
                     state.enter_context(ContextName::Fn);
                     let name = parse_identifier(state)?;
-                    state.enter_context(ContextName::FnName(name.data.to_string()));
+                    state.enter_context(ContextName::FnName(name.data));
                     parse_token(state, Token::OpeningParen)?;
-                    let mut arguments = Vec::new();
-                    state.context(ContextName::Arguments, |state| -> Result<(), C::Error> {
-                        while {
-                            let closing = maybe_parse_token(state, Token::ClosingParen)?;
-                            closing.is_none()
-                        } {
+                    let (arguments, _, _) = state.context(ContextName::Arguments, |state| {
+                        parse_comma_separated(state, Token::ClosingParen, |state| {
                             let identifier = parse_identifier(state)?;
-
                             parse_token(state, Token::Colon)?;
                             let ty = parse_type(state)?;
-                            arguments.push((identifier, ty));
-                            if state.get_current_token() == Some(Ok(&Token::Comma)) {
-                                state.advance()
-                            } else {
-                                break;
-                            }
-                        }
-                        Ok(())
+                            Ok(state.ast_mut().new_param(identifier, ty))
+                        })
                     })?;
                     parse_token(state, Token::Colon)?;
                     let return_type = parse_type(state)?;
                     let body = parse_block(state)?;
                     state.exit_ctx();
                     state.exit_ctx();
-                    Ok(span.spanned(Item::Function {
-                        name,
-                        arguments,
-                        return_type,
-                        body,
-                    }))
+                    let span = Span::from_ends(span, body.span).unwrap();
+                    Ok(state.ast_mut().alloc_item(
+                        span,
+                        ItemKind::Function {
+                            name,
+                            arguments,
+                            return_type,
+                            body,
+                        },
+                    ))
                 }
                 _ => Err(state.message(ParseError::UnexpectedToken(span.spanned(token)))),
             },
-            Err(()) => Err(state.message(ParseError::InvalidToken(span))),
+            Err(&err) => Err(state.message(ParseError::lex(err, span))),
         },
-        None => Err(state.message(ParseError::UnexpectedEOI)),
+        None => Err(state.message(ParseError::UnexpectedEOI(state.eoi_span()))),
     })
 }
 
 pub fn parse_statement<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
     state: &mut ParserState<C, T>,
-) -> Result<Spanned<Statement<'source>>, C::Error> {
+) -> Result<StmtId, C::Error> {
     state.context(
         ContextName::Statement,
         |state: &mut ParserState<C, T>| match state.current() {
             Some((token, span, _)) => match token {
+                Ok(Token::Let) => parse_let(state),
                 Ok(_) => {
                     let expr = parse_expression(state)?;
+                    let expr_span = expression_span(state, expr);
                     let semicolon = maybe_parse_token(state, Token::Semicolon)?;
+                    let block_end = state.get_current_token() == Some(Ok(&Token::ClosingBracket));
                     if let Some(semicolon) = semicolon {
-                        Ok(Span::from_ends(expr.span, semicolon)
-                            .unwrap()
-                            .spanned(Statement::Expr(expr)))
+                        Ok(state.ast_mut().alloc_statement(
+                            Span::from_ends(expr_span, semicolon).unwrap(),
+                            StatementKind::Expr(expr),
+                        ))
+                    } else if block_end {
+                        Ok(state
+                            .ast_mut()
+                            .alloc_statement(expr_span, StatementKind::ReturnExpr(expr)))
                     } else {
-                        Ok(expr.span.spanned(Statement::ReturnExpr(expr)))
+                        if !state.ast()[expr].kind.is_block_like() {
+                            parse_closing(state, Token::Semicolon)?;
+                        }
+                        Ok(state
+                            .ast_mut()
+                            .alloc_statement(expr_span, StatementKind::Expr(expr)))
                     }
                 }
-                Err(()) => Err(state.message(ParseError::InvalidToken(span))),
+                Err(&err) => Err(state.message(ParseError::lex(err, span))),
             },
-            None => Err(state.message(ParseError::UnexpectedEOI)),
+            None => Err(state.message(ParseError::UnexpectedEOI(state.eoi_span()))),
         },
     )
 }
 
+fn parse_let<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<StmtId, C::Error> {
+    state.context(ContextName::Let, |state| {
+        let start = parse_token(state, Token::Let)?;
+        let pattern = Box::new(parse_pattern(state)?);
+        let ty = if maybe_parse_token(state, Token::Colon)?.is_some() {
+            Some(parse_type(state)?)
+        } else {
+            None
+        };
+        let value = if maybe_parse_token(state, Token::Assignment)?.is_some() {
+            Some(parse_expression(state)?)
+        } else {
+            None
+        };
+        let end = parse_closing(state, Token::Semicolon)?;
+        Ok(state.ast_mut().alloc_statement(
+            Span::from_ends(start, end).unwrap(),
+            StatementKind::Let { pattern, ty, value },
+        ))
+    })
+}
+
 pub fn parse_expression<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
     state: &mut ParserState<C, T>,
-) -> Result<Spanned<Expression<'source>>, C::Error> {
-    state.context(ContextName::Expression, |state| match state.current() {
+) -> Result<ExprId, C::Error> {
+    state.context(ContextName::Expression, |state| {
+        let target = parse_range(state)?;
+        let op = match state.current() {
+            Some((Ok(Token::Assignment), _, _)) => None,
+            Some((Ok(Token::CompoundAssignment), span, slice)) => {
+                let op = BinaryOp::from_operator(&slice[..slice.len() - 1])
+                    .expect("the lexer only allows arithmetic operators");
+                Some(span.spanned(op))
+            }
+            _ => return Ok(target),
+        };
+        state.context(ContextName::Assignment, |state| {
+            state.advance();
+            // Assignments group to the right, `a = b = c` assigns `b = c` to `a`
+            let value = parse_expression(state)?;
+            let span = Span::from_ends(
+                expression_span(state, target),
+                expression_span(state, value),
+            )
+            .unwrap();
+            let kind = match op {
+                Some(op) => ExpressionKind::CompoundAssign { op, target, value },
+                None => ExpressionKind::Assign { target, value },
+            };
+            Ok(alloc_expression(state, span, kind))
+        })
+    })
+}
+
+/// Parses an expression without assignments, as in the condition of an `if` or what a `for`
+/// loops over, where a `=` can only be a mistake
+fn parse_range<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<ExprId, C::Error> {
+    let start = parse_binary(state, Precedence::RANGE + 1)?;
+    let inclusive = match state.get_current_token() {
+        Some(Ok(Token::DotDot)) => false,
+        Some(Ok(Token::DotDotEq)) => true,
+        _ => return Ok(start),
+    };
+    state.context(ContextName::Range, |state| {
+        state.advance();
+        let end = parse_binary(state, Precedence::RANGE + 1)?;
+        let span =
+            Span::from_ends(expression_span(state, start), expression_span(state, end)).unwrap();
+        Ok(alloc_expression(
+            state,
+            span,
+            ExpressionKind::Range {
+                start,
+                end,
+                inclusive,
+            },
+        ))
+    })
+}
+
+/// Precedence climbing over [`Token::Operator`] and `as` casts, only consuming operators
+/// that bind at least as tight as `min_precedence`.
+fn parse_binary<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+    min_precedence: u8,
+) -> Result<ExprId, C::Error> {
+    let mut lhs = parse_unary(state)?;
+    loop {
+        lhs = match state.current() {
+            Some((Ok(Token::As), _, _)) if Precedence::CAST >= min_precedence => {
+                state.context(ContextName::Cast, |state| {
+                    state.advance();
+                    // `x as u8 < y` compares, so the type can't take generic arguments
+                    let ty = parse_type_with(state, false)?;
+                    let span = Span::from_ends(expression_span(state, lhs), ty.span).unwrap();
+                    Ok(alloc_expression(
+                        state,
+                        span,
+                        ExpressionKind::Cast { expr: lhs, ty },
+                    ))
+                })?
+            }
+            Some((Ok(Token::Operator), span, slice)) => {
+                let op = match BinaryOp::from_operator(slice) {
+                    Some(op) if op.precedence() >= min_precedence => span.spanned(op),
+                    _ => break,
+                };
+                state.advance();
+                let rhs = state.context(ContextName::BinaryOperation(op.data), |state| {
+                    parse_binary(state, op.data.precedence() + 1)
+                })?;
+                let span =
+                    Span::from_ends(expression_span(state, lhs), expression_span(state, rhs))
+                        .unwrap();
+                alloc_expression(state, span, ExpressionKind::Binary { op, lhs, rhs })
+            }
+            _ => break,
+        }
+    }
+    Ok(lhs)
+}
+
+/// Parses the prefix operators `-`, `!`, `*`, `&` and `&mut`.
+/// `&&` in prefix position is a reference to a reference.
+fn parse_unary<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<ExprId, C::Error> {
+    let (op, span, references) = match state.current() {
+        Some((Ok(Token::Operator), span, "-")) => (Some(UnaryOp::Neg), span, 0),
+        Some((Ok(Token::Operator), span, "*")) => (Some(UnaryOp::Deref), span, 0),
+        Some((Ok(Token::Operator), span, "&&")) => (None, span, 2),
+        Some((Ok(Token::Bang), span, _)) => (Some(UnaryOp::Not), span, 0),
+        Some((Ok(Token::Ampersand), span, _)) => (None, span, 1),
+        _ => return parse_postfix(state),
+    };
+    state.advance();
+    match op {
+        Some(op) => state.context(ContextName::UnaryOperation(op), |state| {
+            let operand = parse_unary(state)?;
+            let expr_span = Span::from_ends(span, expression_span(state, operand)).unwrap();
+            Ok(alloc_expression(
+                state,
+                expr_span,
+                ExpressionKind::Unary {
+                    op: span.spanned(op),
+                    operand,
+                },
+            ))
+        }),
+        None => state.context(ContextName::Reference, |state| {
+            let mutable = maybe_parse_token(state, Token::Mut)?.is_some();
+            let operand = parse_unary(state)?;
+            let end = expression_span(state, operand);
+            // With `&&`, the inner reference starts at the second `&`
+            let inner = Span::new(span.file(), span.start() + references - 1..span.end());
+            let mut expr = alloc_expression(
+                state,
+                Span::from_ends(inner, end).unwrap(),
+                ExpressionKind::Reference { mutable, operand },
+            );
+            if references == 2 {
+                expr = alloc_expression(
+                    state,
+                    Span::from_ends(span, end).unwrap(),
+                    ExpressionKind::Reference {
+                        mutable: false,
+                        operand: expr,
+                    },
+                );
+            }
+            Ok(expr)
+        }),
+    }
+}
+
+fn parse_postfix<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<ExprId, C::Error> {
+    let mut expr = parse_primary(state)?;
+    loop {
+        let start = expression_span(state, expr);
+        expr = match state.get_current_token() {
+            Some(Ok(Token::OpeningSqBracket)) => state.context(ContextName::Index, |state| {
+                state.advance();
+                let index = parse_expression(state)?;
+                let end = parse_token(state, Token::ClosingSqBracket)?;
+                Ok(alloc_expression(
+                    state,
+                    Span::from_ends(start, end).unwrap(),
+                    ExpressionKind::Index { base: expr, index },
+                ))
+            })?,
+            Some(Ok(Token::Dot)) => state.context(ContextName::TupleField, |state| {
+                state.advance();
+                if let Some(expr) = parse_nested_tuple_field(state, start, expr)? {
+                    return Ok(expr);
+                }
+                let field = parse_tuple_index(state)?;
+                Ok(alloc_expression(
+                    state,
+                    Span::from_ends(start, field.span).unwrap(),
+                    ExpressionKind::TupleField { base: expr, field },
+                ))
+            })?,
+            Some(Ok(Token::OpeningParen)) => state.context(ContextName::Call, |state| {
+                state.advance();
+                let (arguments, _, end) =
+                    parse_comma_separated(state, Token::ClosingParen, parse_expression)?;
+                Ok(alloc_expression(
+                    state,
+                    Span::from_ends(start, end).unwrap(),
+                    ExpressionKind::Call {
+                        callee: expr,
+                        arguments,
+                    },
+                ))
+            })?,
+            Some(Ok(Token::Question)) => state.context(ContextName::Try, |state| {
+                let end = parse_token(state, Token::Question)?;
+                Ok(alloc_expression(
+                    state,
+                    Span::from_ends(start, end).unwrap(),
+                    ExpressionKind::Try(expr),
+                ))
+            })?,
+            _ => break Ok(expr),
+        }
+    }
+}
+
+/// `t.0.1` lexes as `t`, `.` and the float `0.1`, which is split back into two fields of
+/// `base`. Returns `None` if the current token isn't such a float.
+fn parse_nested_tuple_field<'source, C, T>(
+    state: &mut ParserState<C, T>,
+    start: Span,
+    base: ExprId,
+) -> Result<Option<ExprId>, C::Error>
+where
+    C: Context<ContextName>,
+    T: TokenReader<'source, Token>,
+{
+    let Some((Ok(Token::FloatLiteral), span, slice)) = state.current() else {
+        return Ok(None);
+    };
+    let Some((first, second)) = slice.split_once('.') else {
+        return Ok(None);
+    };
+    if !second.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Ok(None);
+    }
+    let first_span = Span::new(span.file(), span.start()..span.start() + first.len());
+    let second_span = Span::new(span.file(), span.end() - second.len()..span.end());
+    let [first, second] = [(first, first_span), (second, second_span)].map(|(digits, span)| {
+        digits
+            .parse::<u32>()
+            .map(|index| span.spanned(index))
+            .map_err(|_| span.spanned(digits.parse().unwrap_or(u128::MAX)))
+    });
+    state.advance();
+    let (first, second) = match (first, second) {
+        (Ok(first), Ok(second)) => (first, second),
+        (Err(index), _) | (_, Err(index)) => {
+            return Err(state.message(ParseError::InvalidTupleIndex(index)))
+        }
+    };
+    let inner = alloc_expression(
+        state,
+        Span::from_ends(start, first.span).unwrap(),
+        ExpressionKind::TupleField { base, field: first },
+    );
+    Ok(Some(alloc_expression(
+        state,
+        Span::from_ends(start, second.span).unwrap(),
+        ExpressionKind::TupleField {
+            base: inner,
+            field: second,
+        },
+    )))
+}
+
+fn parse_tuple_index<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<Spanned<u32>, C::Error> {
+    let index = parse_integer_literal(state)?;
+    u32::try_from(index.data)
+        .map(|data| index.copy_new_data(data))
+        .map_err(|_| state.message(ParseError::InvalidTupleIndex(index)))
+}
+
+fn parse_integer_literal<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<Spanned<u128>, C::Error> {
+    let r = match state.current() {
+        Some((Ok(&Token::IntegerLiteral(Some(value))), span, _)) => Ok(span.spanned(value)),
+        Some((Ok(Token::IntegerLiteral(None)), span, slice)) => {
+            Err(state.message(ParseError::LiteralOutOfRange {
+                suffix: integer_suffix(slice),
+                span,
+            }))
+        }
+        Some((Ok(&token), span, _)) => {
+            Err(state.message(ParseError::UnexpectedTokenWithExpectation {
+                found: span.spanned(token),
+                expected: Token::IntegerLiteral(Some(0)),
+            }))
+        }
+        Some((Err(&err), span, _)) => Err(state.message(ParseError::lex(err, span))),
+        None => Err(state.message(ParseError::UnexpectedEOI(state.eoi_span()))),
+    };
+    state.advance();
+    r
+}
+
+fn parse_primary<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<ExprId, C::Error> {
+    match state.current() {
         Some((token, span, _)) => match token {
             Ok(&token) => match token {
                 Token::Identifier => {
-                    parse_identifier(state).map(|ident| span.spanned(Expression::Name(ident)))
+                    let ident = parse_identifier(state)?;
+                    // Nothing can follow an identifier with a `:` in an expression,
+                    // so that's taken as a mistyped path separator
+                    if matches!(
+                        state.get_current_token(),
+                        Some(Ok(Token::PathSep | Token::Colon))
+                    ) {
+                        let path = parse_path(state, ident)?;
+                        let span =
+                            Span::from_ends(span, path.segments.last().unwrap().span).unwrap();
+                        Ok(alloc_expression(state, span, ExpressionKind::Path(path)))
+                    } else {
+                        Ok(alloc_expression(state, span, ExpressionKind::Name(ident)))
+                    }
+                }
+                Token::IntrinsicIdentifier => {
+                    let (_, name) = state.current_slice().unwrap().split_at(1);
+                    let ident = span.spanned(name.into());
+                    state.advance();
+                    Ok(alloc_expression(
+                        state,
+                        span,
+                        ExpressionKind::Intrinsic(ident),
+                    ))
+                }
+                Token::IntegerLiteral(value) => {
+                    let suffix = integer_suffix(state.current_slice().unwrap());
+                    let value = integer_value(state, value, span, suffix);
+                    state.advance();
+                    Ok(alloc_expression(
+                        state,
+                        span,
+                        ExpressionKind::IntegerLiteral { value, suffix },
+                    ))
+                }
+                Token::FloatLiteral | Token::CharLiteral | Token::StringLiteral => {
+                    let slice = state.current_slice().unwrap();
+                    let kind = match token {
+                        Token::FloatLiteral => ExpressionKind::FloatLiteral(slice.into()),
+                        Token::CharLiteral => {
+                            let value = unescape(&slice[1..slice.len() - 1]);
+                            ExpressionKind::CharLiteral(value.chars().next().unwrap())
+                        }
+                        _ => ExpressionKind::StringLiteral(
+                            unescape(&slice[1..slice.len() - 1]).as_str().into(),
+                        ),
+                    };
+                    state.advance();
+                    Ok(alloc_expression(state, span, kind))
+                }
+                Token::True | Token::False => {
+                    state.advance();
+                    Ok(alloc_expression(
+                        state,
+                        span,
+                        ExpressionKind::BoolLiteral(token == Token::True),
+                    ))
+                }
+                Token::Return | Token::Break => parse_jump(state, token),
+                Token::Continue => {
+                    state.advance();
+                    Ok(alloc_expression(state, span, ExpressionKind::Continue))
+                }
+                Token::Pipe => parse_closure(state),
+                Token::Operator if state.current_slice() == Some("||") => parse_closure(state),
+                Token::OpeningParen => parse_tuple(state),
+                Token::OpeningSqBracket => parse_array(state),
+                Token::OpeningBracket => {
+                    let block = parse_block(state)?;
+                    Ok(alloc_expression(
+                        state,
+                        block.span,
+                        ExpressionKind::Block(block),
+                    ))
                 }
+                Token::If => parse_if(state),
+                Token::While => parse_while(state),
+                Token::For => parse_for(state),
+                Token::Loop => state.context(ContextName::Loop, |state| {
+                    state.advance();
+                    let body = parse_block(state)?;
+                    Ok(alloc_expression(
+                        state,
+                        Span::from_ends(span, body.span).unwrap(),
+                        ExpressionKind::Loop(body),
+                    ))
+                }),
+                Token::Match => parse_match(state),
                 _ => Err(state.message(ParseError::UnexpectedToken(span.spanned(token)))),
             },
-            Err(()) => Err(state.message(ParseError::InvalidToken(span))),
+            Err(&err) => Err(state.message(ParseError::lex(err, span))),
+        },
+        None => Err(state.message(ParseError::UnexpectedEOI(state.eoi_span()))),
+    }
+}
+
+/// The text of a character or string literal, which the lexer only lets through with valid escapes
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        unescaped.push(match chars.next() {
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some(other) => other,
+            None => break,
+        });
+    }
+    unescaped
+}
+
+/// Whether the current token can start an expression, which decides if a `return` or `break`
+/// has a value
+fn starts_expression<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &ParserState<C, T>,
+) -> bool {
+    match state.current() {
+        Some((Ok(Token::Operator), _, slice)) => matches!(slice, "-" | "*" | "&&" | "||"),
+        Some((Ok(token), _, _)) => matches!(
+            token,
+            Token::Identifier
+                | Token::IntrinsicIdentifier
+                | Token::IntegerLiteral(_)
+                | Token::FloatLiteral
+                | Token::CharLiteral
+                | Token::StringLiteral
+                | Token::True
+                | Token::False
+                | Token::OpeningParen
+                | Token::OpeningSqBracket
+                | Token::OpeningBracket
+                | Token::If
+                | Token::While
+                | Token::For
+                | Token::Loop
+                | Token::Match
+                | Token::Break
+                | Token::Continue
+                | Token::Return
+                | Token::Pipe
+                | Token::Ampersand
+                | Token::Bang
+        ),
+        _ => false,
+    }
+}
+
+/// Parses `return` or `break`, with a value if one follows
+fn parse_jump<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+    keyword: Token,
+) -> Result<ExprId, C::Error> {
+    let context = match keyword {
+        Token::Break => ContextName::Break,
+        _ => ContextName::Return,
+    };
+    state.context(context, |state| {
+        let start = parse_token(state, keyword)?;
+        let value = if starts_expression(state) {
+            Some(parse_expression(state)?)
+        } else {
+            None
+        };
+        let span = match value {
+            Some(value) => Span::from_ends(start, expression_span(state, value)).unwrap(),
+            None => start,
+        };
+        let kind = match keyword {
+            Token::Break => ExpressionKind::Break(value),
+            _ => ExpressionKind::Return(value),
+        };
+        Ok(alloc_expression(state, span, kind))
+    })
+}
+
+/// Parses `|params| body`, where `||` is a closure without parameters.
+/// Parameters are patterns with an optional type.
+fn parse_closure<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<ExprId, C::Error> {
+    state.context(ContextName::Closure, |state| {
+        let Some((_, start, slice)) = state.current() else {
+            return Err(state.message(ParseError::UnexpectedEOI(state.eoi_span())));
+        };
+        state.advance();
+        let params = if slice == "||" {
+            Vec::new()
+        } else {
+            let (params, _, _) = parse_comma_separated(state, Token::Pipe, |state| {
+                let pattern = parse_pattern(state)?;
+                let ty = if maybe_parse_token(state, Token::Colon)?.is_some() {
+                    Some(parse_type(state)?)
+                } else {
+                    None
+                };
+                Ok(ClosureParam { pattern, ty })
+            })?;
+            params
+        };
+        let body = parse_expression(state)?;
+        let span = Span::from_ends(start, expression_span(state, body)).unwrap();
+        Ok(alloc_expression(
+            state,
+            span,
+            ExpressionKind::Closure { params, body },
+        ))
+    })
+}
+
+/// Parses `if condition { .. }`, optionally followed by `else { .. }` or `else if ..`
+fn parse_if<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<ExprId, C::Error> {
+    state.context(ContextName::If, |state| {
+        let start = parse_token(state, Token::If)?;
+        let condition = parse_condition(state)?;
+        let then_branch = parse_block(state)?;
+        let mut end = then_branch.span;
+        let else_branch = if maybe_parse_token(state, Token::Else)?.is_some() {
+            let else_branch = if state.get_current_token() == Some(Ok(&Token::If)) {
+                parse_if(state)?
+            } else {
+                let block = parse_block(state)?;
+                alloc_expression(state, block.span, ExpressionKind::Block(block))
+            };
+            end = expression_span(state, else_branch);
+            Some(else_branch)
+        } else {
+            None
+        };
+        Ok(alloc_expression(
+            state,
+            Span::from_ends(start, end).unwrap(),
+            ExpressionKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            },
+        ))
+    })
+}
+
+fn parse_while<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<ExprId, C::Error> {
+    state.context(ContextName::While, |state| {
+        let start = parse_token(state, Token::While)?;
+        let condition = parse_condition(state)?;
+        let body = parse_block(state)?;
+        Ok(alloc_expression(
+            state,
+            Span::from_ends(start, body.span).unwrap(),
+            ExpressionKind::While { condition, body },
+        ))
+    })
+}
+
+/// Parses `for pattern in iterable { .. }`
+fn parse_for<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<ExprId, C::Error> {
+    state.context(ContextName::For, |state| {
+        let start = parse_token(state, Token::For)?;
+        let pattern = parse_pattern(state)?;
+        parse_token(state, Token::In)?;
+        let iterable = parse_range(state)?;
+        let body = parse_block(state)?;
+        Ok(alloc_expression(
+            state,
+            Span::from_ends(start, body.span).unwrap(),
+            ExpressionKind::For {
+                pattern,
+                iterable,
+                body,
+            },
+        ))
+    })
+}
+
+/// Parses `match scrutinee { pattern => body, .. }`.
+/// Like statements, arms whose body is block-like don't need a `,`, and their body ends with
+/// the block, so `{ a } (b, c) => ..` starts a new arm instead of calling `{ a }`.
+fn parse_match<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<ExprId, C::Error> {
+    state.context(ContextName::Match, |state| {
+        let start = parse_token(state, Token::Match)?;
+        let scrutinee = parse_range(state)?;
+        parse_token(state, Token::OpeningBracket)?;
+        let mut arms = Vec::new();
+        let end = loop {
+            if let Some(end) = maybe_parse_token(state, Token::ClosingBracket)? {
+                break end;
+            }
+            let pattern = parse_pattern(state)?;
+            parse_token(state, Token::FatArrow)?;
+            let block_like = matches!(
+                state.get_current_token(),
+                Some(Ok(Token::OpeningBracket
+                    | Token::If
+                    | Token::While
+                    | Token::For
+                    | Token::Loop
+                    | Token::Match))
+            );
+            let body = if block_like {
+                parse_primary(state)?
+            } else {
+                parse_expression(state)?
+            };
+            arms.push(MatchArm { pattern, body });
+            if maybe_parse_token(state, Token::Comma)?.is_none() && !block_like {
+                break parse_closing(state, Token::ClosingBracket)?;
+            }
+        };
+        Ok(alloc_expression(
+            state,
+            Span::from_ends(start, end).unwrap(),
+            ExpressionKind::Match { scrutinee, arms },
+        ))
+    })
+}
+
+/// An expression followed by the block of an `if` or `while`.
+/// `a = b` is reported and parsed as `a == b`, since assignments can't be conditions.
+fn parse_condition<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<ExprId, C::Error> {
+    let lhs = parse_range(state)?;
+    let Some(operator) = maybe_parse_token(state, Token::Assignment)? else {
+        return Ok(lhs);
+    };
+    state.message(ParseError::AssignmentInCondition(operator));
+    let rhs = parse_binary(state, Precedence::COMPARISON + 1)?;
+    let span = Span::from_ends(expression_span(state, lhs), expression_span(state, rhs)).unwrap();
+    Ok(alloc_expression(
+        state,
+        span,
+        ExpressionKind::Binary {
+            op: operator.spanned(BinaryOp::Eq),
+            lhs,
+            rhs,
+        },
+    ))
+}
+
+/// Parses `()`, `(expr)`, `(expr,)` and `(expr, expr, ...)`.
+/// A single parenthesized expression without a comma is not a tuple,
+/// but its span is widened to cover the parentheses.
+fn parse_tuple<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<ExprId, C::Error> {
+    state.context(ContextName::Tuple, |state| {
+        let start = parse_token(state, Token::OpeningParen)?;
+        let (elements, trailing_comma, end) =
+            parse_comma_separated(state, Token::ClosingParen, parse_expression)?;
+        let span = Span::from_ends(start, end).unwrap();
+        if elements.len() == 1 && !trailing_comma {
+            let inner = elements[0];
+            state.ast_mut().respan_expression(inner, span);
+            Ok(inner)
+        } else {
+            Ok(alloc_expression(
+                state,
+                span,
+                ExpressionKind::Tuple(elements),
+            ))
+        }
+    })
+}
+
+/// Parses `[]`, `[expr, expr, ...]` and `[expr; count]`.
+fn parse_array<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<ExprId, C::Error> {
+    state.context(ContextName::Array, |state| {
+        let start = parse_token(state, Token::OpeningSqBracket)?;
+        if let Some(end) = maybe_parse_token(state, Token::ClosingSqBracket)? {
+            return Ok(alloc_expression(
+                state,
+                Span::from_ends(start, end).unwrap(),
+                ExpressionKind::Array(Vec::new()),
+            ));
+        }
+        let first = parse_expression(state)?;
+        if maybe_parse_token(state, Token::Semicolon)?.is_some() {
+            let count = parse_expression(state)?;
+            let end = parse_token(state, Token::ClosingSqBracket)?;
+            return Ok(alloc_expression(
+                state,
+                Span::from_ends(start, end).unwrap(),
+                ExpressionKind::ArrayRepeat {
+                    value: first,
+                    count,
+                },
+            ));
+        }
+        let mut elements = vec![first];
+        let end = if maybe_parse_token(state, Token::Comma)?.is_some() {
+            let (rest, _, end) =
+                parse_comma_separated(state, Token::ClosingSqBracket, parse_expression)?;
+            elements.extend(rest);
+            end
+        } else {
+            parse_token(state, Token::ClosingSqBracket)?
+        };
+        Ok(alloc_expression(
+            state,
+            Span::from_ends(start, end).unwrap(),
+            ExpressionKind::Array(elements),
+        ))
+    })
+}
+
+pub fn parse_pattern<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<Pattern, C::Error> {
+    state.context(ContextName::Pattern, |state| match state.current() {
+        Some((Ok(&token), span, slice)) => match token {
+            Token::Identifier if slice == "_" => {
+                state.advance();
+                Ok(state.ast_mut().new_pattern(span, PatternKind::Wildcard))
+            }
+            Token::Identifier => {
+                let name = parse_identifier(state)?;
+                if maybe_parse_token(state, Token::OpeningParen)?.is_none() {
+                    return Ok(state
+                        .ast_mut()
+                        .new_pattern(span, PatternKind::Binding(name.data)));
+                }
+                let (fields, _, end) =
+                    parse_comma_separated(state, Token::ClosingParen, parse_pattern)?;
+                Ok(state.ast_mut().new_pattern(
+                    Span::from_ends(span, end).unwrap(),
+                    PatternKind::Variant { name, fields },
+                ))
+            }
+            Token::IntegerLiteral(_) | Token::CharLiteral | Token::True | Token::False => {
+                parse_literal_or_range_pattern(state)
+            }
+            Token::Operator if slice == "-" => parse_literal_or_range_pattern(state),
+            Token::OpeningParen => {
+                state.advance();
+                let (elements, trailing_comma, end) =
+                    parse_comma_separated(state, Token::ClosingParen, parse_pattern)?;
+                let span = Span::from_ends(span, end).unwrap();
+                if elements.len() == 1 && !trailing_comma {
+                    let mut inner = elements.into_iter().next().unwrap();
+                    state.ast_mut().respan_pattern(&mut inner, span);
+                    Ok(inner)
+                } else {
+                    Ok(state
+                        .ast_mut()
+                        .new_pattern(span, PatternKind::Tuple(elements)))
+                }
+            }
+            _ => {
+                state.advance();
+                Err(state.message(ParseError::UnexpectedToken(span.spanned(token))))
+            }
+        },
+        Some((Err(&err), span, _)) => {
+            state.advance();
+            Err(state.message(ParseError::lex(err, span)))
+        }
+        None => Err(state.message(ParseError::UnexpectedEOI(state.eoi_span()))),
+    })
+}
+
+/// Parses a literal pattern, or a range pattern if it's followed by `..` or `..=`
+fn parse_literal_or_range_pattern<'source, C, T>(
+    state: &mut ParserState<C, T>,
+) -> Result<Pattern, C::Error>
+where
+    C: Context<ContextName>,
+    T: TokenReader<'source, Token>,
+{
+    let start = parse_literal_pattern(state)?;
+    let inclusive = match state.get_current_token() {
+        Some(Ok(Token::DotDot)) => false,
+        Some(Ok(Token::DotDotEq)) => true,
+        _ => {
+            return Ok(state
+                .ast_mut()
+                .new_pattern(start.span, PatternKind::Literal(start.data)))
+        }
+    };
+    state.advance();
+    let end = parse_literal_pattern(state)?;
+    Ok(state.ast_mut().new_pattern(
+        Span::from_ends(start.span, end.span).unwrap(),
+        PatternKind::Range {
+            start: Box::new(start.data),
+            end: Box::new(end.data),
+            inclusive,
         },
-        None => Err(state.message(ParseError::UnexpectedEOI)),
+    ))
+}
+
+/// The type suffix of the integer literal `slice`, as in `1u8`
+fn integer_suffix(slice: &str) -> Option<Symbol> {
+    slice
+        .find(|c: char| !c.is_ascii_digit())
+        .map(|start| slice[start..].into())
+}
+
+/// The value of the integer literal at `span`, reporting it if it's too large for any type
+fn integer_value<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+    value: Option<u128>,
+    span: Span,
+    suffix: Option<Symbol>,
+) -> u128 {
+    value.unwrap_or_else(|| {
+        state.message(ParseError::LiteralOutOfRange { suffix, span });
+        u128::MAX
+    })
+}
+
+/// Parses `1`, `-1`, `1u8`, `'a'`, `true` or `false`
+fn parse_literal_pattern<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+) -> Result<Spanned<LiteralPattern>, C::Error> {
+    let minus = match state.current() {
+        Some((Ok(Token::Operator), span, "-")) => {
+            state.advance();
+            Some(span)
+        }
+        _ => None,
+    };
+    let r = match state.current() {
+        Some((Ok(&Token::IntegerLiteral(value)), span, slice)) => {
+            let suffix = integer_suffix(slice);
+            let value = integer_value(state, value, span, suffix);
+            let span = minus.map_or(span, |minus| Span::from_ends(minus, span).unwrap());
+            Ok(span.spanned(LiteralPattern::Int {
+                value,
+                negative: minus.is_some(),
+                suffix,
+            }))
+        }
+        Some((Ok(Token::CharLiteral), span, slice)) if minus.is_none() => {
+            let value = unescape(&slice[1..slice.len() - 1]);
+            Ok(span.spanned(LiteralPattern::Char(value.chars().next().unwrap())))
+        }
+        Some((Ok(&token @ (Token::True | Token::False)), span, _)) if minus.is_none() => {
+            Ok(span.spanned(LiteralPattern::Bool(token == Token::True)))
+        }
+        Some((Ok(&token), span, _)) => {
+            Err(state.message(ParseError::UnexpectedToken(span.spanned(token))))
+        }
+        Some((Err(&err), span, _)) => Err(state.message(ParseError::lex(err, span))),
+        None => Err(state.message(ParseError::UnexpectedEOI(state.eoi_span()))),
+    };
+    state.advance();
+    r
+}
+
+/// Parses the `::segment` continuations of a path starting with `first`.
+fn parse_path<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+    first: Spanned<Identifier>,
+) -> Result<Path, C::Error> {
+    state.context(ContextName::Path, |state| {
+        let mut segments = vec![first];
+        loop {
+            match state.current() {
+                Some((Ok(Token::PathSep), _, _)) => state.advance(),
+                Some((Ok(Token::Colon), span, _)) => {
+                    state.message(ParseError::SingleColonPath(span));
+                    state.advance();
+                }
+                _ => break,
+            }
+            segments.push(parse_identifier(state)?);
+        }
+        Ok(Path { segments })
     })
 }
 
 pub fn parse_identifier<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
     state: &mut ParserState<C, T>,
-) -> Result<Spanned<Identifier<'source>>, C::Error> {
+) -> Result<Spanned<Identifier>, C::Error> {
     state.context(ContextName::Identifier, |state| {
         let r = match state.current() {
             Some((Ok(&token), span, slice)) => match token {
                 Token::Identifier => Ok(span.spanned(slice.into())),
                 _ => Err(state.message(ParseError::UnexpectedToken(span.spanned(token)))),
             },
-            Some((Err(()), span, _)) => Err(state.message(ParseError::InvalidToken(span))),
-            None => Err(state.message(ParseError::UnexpectedEOI)),
+            Some((Err(&err), span, _)) => Err(state.message(ParseError::lex(err, span))),
+            None => Err(state.message(ParseError::UnexpectedEOI(state.eoi_span()))),
         };
         state.advance();
         r
@@ -163,24 +1188,127 @@ pub fn parse_identifier<'source, C: Context<ContextName>, T: TokenReader<'source
 
 pub fn parse_type<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
     state: &mut ParserState<C, T>,
-) -> Result<Spanned<Type<'source>>, C::Error> {
-    state.context(ContextName::Type, |state| {
-        let r = match state.current() {
-            Some((Ok(&token), span, slice)) => match token {
-                Token::Identifier => Ok(span.spanned(slice.into())),
-                _ => Err(state.message(ParseError::UnexpectedToken(span.spanned(token)))),
-            },
-            Some((Err(()), span, _)) => Err(state.message(ParseError::InvalidToken(span))),
-            None => Err(state.message(ParseError::UnexpectedEOI)),
-        };
-        state.advance();
-        r
+) -> Result<Spanned<Type>, C::Error> {
+    parse_type_with(state, true)
+}
+
+/// Parses a type, which only takes generic arguments such as `Option<i32>` if `generics` is set
+fn parse_type_with<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
+    state: &mut ParserState<C, T>,
+    generics: bool,
+) -> Result<Spanned<Type>, C::Error> {
+    state.context(ContextName::Type, |state| match state.current() {
+        Some((Ok(&token), span, slice)) => match token {
+            Token::Identifier => {
+                let name = Identifier::from(slice);
+                state.advance();
+                if !generics || !matches!(state.current(), Some((Ok(Token::Operator), _, "<"))) {
+                    return Ok(span.spanned(Type::Named(name)));
+                }
+                state.advance();
+                let mut arguments = Vec::new();
+                let end = loop {
+                    if let Some((Ok(Token::Operator), end, ">")) = state.current() {
+                        state.advance();
+                        break end;
+                    }
+                    arguments.push(parse_type(state)?);
+                    if maybe_parse_token(state, Token::Comma)?.is_none() {
+                        match state.current() {
+                            Some((Ok(Token::Operator), end, ">")) => {
+                                state.advance();
+                                break end;
+                            }
+                            Some((Ok(&token), span, _)) => {
+                                return Err(
+                                    state.message(ParseError::UnexpectedToken(span.spanned(token)))
+                                )
+                            }
+                            Some((Err(&err), span, _)) => {
+                                return Err(state.message(ParseError::lex(err, span)))
+                            }
+                            None => {
+                                return Err(
+                                    state.message(ParseError::UnexpectedEOI(state.eoi_span()))
+                                )
+                            }
+                        }
+                    }
+                };
+                Ok(Span::from_ends(span, end)
+                    .unwrap()
+                    .spanned(Type::Generic { name, arguments }))
+            }
+            Token::Ampersand => {
+                state.advance();
+                let mutable = maybe_parse_token(state, Token::Mut)?.is_some();
+                let inner = parse_type_with(state, generics)?;
+                Ok(Span::from_ends(span, inner.span)
+                    .unwrap()
+                    .spanned(Type::Reference {
+                        mutable,
+                        inner: Box::new(inner),
+                    }))
+            }
+            // `&&T` is a reference to a reference, as in expressions
+            Token::Operator if slice == "&&" => {
+                state.advance();
+                let mutable = maybe_parse_token(state, Token::Mut)?.is_some();
+                let inner = parse_type_with(state, generics)?;
+                // The inner reference starts at the second `&`
+                let second = Span::new(span.file(), span.start() + 1..span.end());
+                let inner = Span::from_ends(second, inner.span)
+                    .unwrap()
+                    .spanned(Type::Reference {
+                        mutable,
+                        inner: Box::new(inner),
+                    });
+                Ok(Span::from_ends(span, inner.span)
+                    .unwrap()
+                    .spanned(Type::Reference {
+                        mutable: false,
+                        inner: Box::new(inner),
+                    }))
+            }
+            Token::OpeningSqBracket => {
+                state.advance();
+                let element = parse_type(state)?;
+                parse_token(state, Token::Semicolon)?;
+                let length = parse_integer_literal(state)?;
+                let end = parse_token(state, Token::ClosingSqBracket)?;
+                Ok(Span::from_ends(span, end).unwrap().spanned(Type::Array {
+                    element: Box::new(element),
+                    length,
+                }))
+            }
+            Token::OpeningParen => {
+                state.advance();
+                let (elements, trailing_comma, end) =
+                    parse_comma_separated(state, Token::ClosingParen, parse_type)?;
+                let span = Span::from_ends(span, end).unwrap();
+                if elements.len() == 1 && !trailing_comma {
+                    let inner = elements.into_iter().next().unwrap();
+                    Ok(span.spanned(inner.data))
+                } else {
+                    Ok(span.spanned(Type::Tuple(elements)))
+                }
+            }
+            _ => {
+                state.advance();
+                Err(state.message(ParseError::UnexpectedToken(span.spanned(token))))
+            }
+        },
+        Some((Err(&err), span, _)) => {
+            state.advance();
+            Err(state.message(ParseError::lex(err, span)))
+        }
+        None => Err(state.message(ParseError::UnexpectedEOI(state.eoi_span()))),
     })
 }
 
 pub fn parse_block<'source, C: Context<ContextName>, T: TokenReader<'source, Token>>(
     state: &mut ParserState<C, T>,
-) -> Result<Spanned<Block<'source>>, C::Error> {
+) -> Result<Block, C::Error> {
     state.context(ContextName::Block, |state| {
         let start = parse_token(state, Token::OpeningBracket)?;
         let mut statements = Vec::new();
@@ -188,39 +1316,1014 @@ pub fn parse_block<'source, C: Context<ContextName>, T: TokenReader<'source, Tok
             if let Some(span) = maybe_parse_token(state, Token::ClosingBracket)? {
                 break span;
             }
-            let current_span = state.get_current_span();
             let statement = parse_statement(state);
             match statement {
                 Ok(statement) => {
-                    let return_stmnt = matches!(statement.data, Statement::ReturnExpr(_));
+                    let return_stmnt =
+                        matches!(state.ast()[statement].kind, StatementKind::ReturnExpr(_));
                     statements.push(statement);
                     if return_stmnt {
                         break parse_token(state, Token::ClosingBracket)?;
                     }
                 }
-                Err(err) => {
-                    if state.get_current_span() == current_span {
-                        return Err(err);
-                    }
-                }
+                // The error is already reported, carry on with the next statement
+                Err(err) => skip_statement(state).map_err(|()| err)?,
             }
         };
 
-        Ok(Span::from_ends(start, end).unwrap().spanned(statements))
+        Ok(state
+            .ast_mut()
+            .new_block(Span::from_ends(start, end).unwrap(), statements))
     })
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Error)]
 pub enum ParseError {
-    #[error("Unexpected end of input")]
-    UnexpectedEOI,
-    #[error("Unexpected token: {0:?}")]
+    #[error("unexpected end of input")]
+    UnexpectedEOI(Span),
+    #[error("unexpected {}", .0.data)]
     UnexpectedToken(Spanned<Token>),
-    #[error("Unexpected token: {found:?}; expected: {expected:?}")]
+    #[error("expected {expected}, found {}", .found.data)]
     UnexpectedTokenWithExpectation {
         found: Spanned<Token>,
         expected: Token,
     },
-    #[error("Invalid token")]
+    #[error("invalid token")]
     InvalidToken(Span),
+    /// Points at the `/*`, not at the rest of the file the comment swallowed
+    #[error("unterminated block comment")]
+    UnterminatedBlockComment(Span),
+    #[error("invalid tuple index `{}`", .0.data)]
+    InvalidTupleIndex(Spanned<u128>),
+    /// A closing token that the parser pretends was there to keep going
+    #[error("missing {expected}")]
+    Missing {
+        expected: Token,
+        /// The token `expected` should follow
+        after: Span,
+        found: Option<Spanned<Token>>,
+    },
+    /// `a:b` where `a::b` was meant
+    #[error("expected `::`, found `:`")]
+    SingleColonPath(Span),
+    /// `if a = b`, which is parsed as if it were `==`
+    #[error("expected `==` in condition, found `=`")]
+    AssignmentInCondition(Span),
+    /// An integer literal that doesn't even fit in a `u128`, which would otherwise be reported
+    /// by the type checker once the type is known
+    #[error(
+        "literal out of range for {}",
+        .suffix.map_or("any integer type".to_string(), |suffix| format!("`{suffix}`"))
+    )]
+    LiteralOutOfRange { suffix: Option<Symbol>, span: Span },
+}
+
+impl ParseError {
+    /// Reports the text at `span` that the lexer couldn't make a token of
+    pub fn lex(err: LexError, span: Span) -> Self {
+        match err {
+            LexError::InvalidToken => Self::InvalidToken(span),
+            LexError::UnterminatedBlockComment => Self::UnterminatedBlockComment(Span::new(
+                span.file(),
+                span.start()..span.start() + "/*".len(),
+            )),
+        }
+    }
+
+    /// Where the error should be reported
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::UnexpectedEOI(span)
+            | Self::InvalidToken(span)
+            | Self::UnterminatedBlockComment(span) => *span,
+            Self::UnexpectedToken(found) | Self::UnexpectedTokenWithExpectation { found, .. } => {
+                found.span
+            }
+            Self::InvalidTupleIndex(index) => index.span,
+            Self::Missing { after, .. } => *after,
+            Self::SingleColonPath(span)
+            | Self::AssignmentInCondition(span)
+            | Self::LiteralOutOfRange { span, .. } => *span,
+        }
+    }
+
+    /// Code of the error, registered with its explanation in [`crate::error::codes`]
+    pub const fn error_code(&self) -> &'static str {
+        match self {
+            Self::UnexpectedEOI(_) => "E0001",
+            Self::UnexpectedToken(_) => "E0002",
+            Self::UnexpectedTokenWithExpectation { .. } => "E0003",
+            Self::InvalidToken(_) => "E0004",
+            Self::UnterminatedBlockComment(_) => "E0036",
+            Self::InvalidTupleIndex(_) => "E0005",
+            Self::Missing { .. } => "E0006",
+            Self::SingleColonPath(_) => "E0007",
+            Self::AssignmentInCondition(_) => "E0008",
+            Self::LiteralOutOfRange { .. } => "E0025",
+        }
+    }
+
+    pub fn suggestions(&self) -> Vec<Suggestion> {
+        match self {
+            Self::Missing {
+                expected,
+                after,
+                found,
+            } => {
+                // Closing a parenthesis before the token that ends the enclosing construct
+                // can't change what the user meant, elsewhere the `)` may belong further on.
+                // Likewise a `;` before something that could continue the expression.
+                let applicability = match (expected, found.map(|found| found.data)) {
+                    (
+                        Token::Semicolon,
+                        Some(
+                            Token::OpeningParen
+                            | Token::OpeningSqBracket
+                            | Token::Dot
+                            | Token::Operator
+                            | Token::As,
+                        ),
+                    ) => Applicability::MaybeIncorrect,
+                    (Token::Semicolon, _)
+                    | (
+                        Token::ClosingParen,
+                        Some(
+                            Token::Semicolon
+                            | Token::OpeningBracket
+                            | Token::ClosingBracket
+                            | Token::ClosingSqBracket,
+                        )
+                        | None,
+                    ) => Applicability::MachineApplicable,
+                    _ => Applicability::MaybeIncorrect,
+                };
+                expected
+                    .text()
+                    .map(|text| Suggestion::insert_after(*after, text, applicability))
+                    .into_iter()
+                    .collect()
+            }
+            Self::SingleColonPath(span) => vec![Suggestion::new(
+                *span,
+                "::",
+                Applicability::MachineApplicable,
+            )],
+            Self::AssignmentInCondition(span) => vec![Suggestion::new(
+                *span,
+                "==",
+                Applicability::MachineApplicable,
+            )],
+            Self::UnexpectedEOI(_)
+            | Self::UnexpectedToken(_)
+            | Self::UnexpectedTokenWithExpectation { .. }
+            | Self::InvalidToken(_)
+            | Self::UnterminatedBlockComment(_)
+            | Self::InvalidTupleIndex(_)
+            | Self::LiteralOutOfRange { .. } => Vec::new(),
+        }
+    }
+}
+
+impl Diagnostic for ParseError {
+    fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        Some(Box::new(self.error_code()))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        match self {
+            Self::UnexpectedEOI(_) => Some(Box::new("the file ended in the middle of an item")),
+            Self::InvalidToken(_) => Some(Box::new(
+                "this character doesn't start any token of the language",
+            )),
+            Self::UnterminatedBlockComment(_) => Some(Box::new("close the comment with `*/`")),
+            Self::InvalidTupleIndex(_) => {
+                Some(Box::new(format!("tuple indices go up to {}", u32::MAX)))
+            }
+            Self::Missing { expected, .. } => Some(Box::new(format!("add {expected} here"))),
+            Self::SingleColonPath(_) => Some(Box::new("path segments are separated by `::`")),
+            Self::AssignmentInCondition(_) => Some(Box::new(
+                "`=` assigns a value, use `==` to compare two values",
+            )),
+            Self::LiteralOutOfRange { .. } => Some(Box::new(format!(
+                "the largest integer type, `u128`, holds values up to `{}`",
+                u128::MAX
+            ))),
+            Self::UnexpectedToken(_) | Self::UnexpectedTokenWithExpectation { .. } => None,
+        }
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        Some(snippet_labels(self.labelled_spans()))
+    }
+}
+
+impl ParseError {
+    /// The labels with the spans they point at, primary first
+    pub fn labelled_spans(&self) -> Vec<(Span, String)> {
+        if let Self::Missing {
+            expected,
+            after,
+            found,
+        } = self
+        {
+            let end = Span::new(after.file(), after.end()..after.end());
+            let found = found.map(|found| (found.span, format!("found {}", found.data)));
+            return std::iter::once((end, format!("expected {expected}")))
+                .chain(found)
+                .collect();
+        }
+        let label = match self {
+            Self::UnexpectedEOI(_) => "input ends here".to_string(),
+            Self::UnexpectedToken(found) => format!("unexpected {}", found.data),
+            Self::UnexpectedTokenWithExpectation { expected, .. } => format!("expected {expected}"),
+            Self::InvalidToken(_) => "invalid token".to_string(),
+            Self::UnterminatedBlockComment(_) => "this comment is never closed".to_string(),
+            Self::InvalidTupleIndex(_) => "index out of range".to_string(),
+            Self::Missing { .. } => unreachable!(),
+            Self::SingleColonPath(_) => "help: use `::`".to_string(),
+            Self::AssignmentInCondition(_) => "help: use `==`".to_string(),
+            Self::LiteralOutOfRange { suffix: None, .. } => {
+                "this doesn't fit in any integer type".to_string()
+            }
+            Self::LiteralOutOfRange {
+                suffix: Some(suffix),
+                ..
+            } => format!("this doesn't fit in `{suffix}`"),
+        };
+        vec![(self.span(), label)]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use id_arena::Arena;
+    use miette::Diagnostic;
+
+    use crate::{
+        ast::{
+            Ast, BinaryOp, ExprId, ExpressionKind, ItemKind, LiteralPattern, PatternKind,
+            StatementKind, Type, UnaryOp,
+        },
+        error::{codes, suggestion::Applicability, CollectingContext, PrintingContext},
+        fs::{File, FileId},
+        lexer::{tokens::Tokens, Token},
+        span::{Span, Spanned},
+        symbol::{sym, Symbol},
+        transaction::ParserState,
+    };
+
+    use super::{parse_expression, parse_file, parse_item, parse_statement, ParseError};
+
+    /// The file arena is dropped before returning, the AST doesn't borrow from it
+    fn parse(source: &'static str) -> (Ast, ExprId, FileId) {
+        let mut arena: Arena<File> = Arena::new();
+        let file = arena.alloc(File::new("test", String::from(source)));
+        let mut state = ParserState::new(
+            PrintingContext::new(&arena),
+            Tokens::from(Token::lexer_from_file(&arena, file)),
+        );
+        let expr = parse_expression(&mut state).expect("expression should parse");
+        (state.into_ast(), expr, file)
+    }
+
+    #[test]
+    fn binary_precedence() {
+        let (ast, expr, _) = parse("a + b * c == d");
+        let ExpressionKind::Binary { op, lhs, .. } = &ast[expr].kind else {
+            panic!("expected binary expression")
+        };
+        assert_eq!(op.data, BinaryOp::Eq);
+        let ExpressionKind::Binary { op, rhs, .. } = &ast[*lhs].kind else {
+            panic!("expected binary expression")
+        };
+        assert_eq!(op.data, BinaryOp::Add);
+        assert!(matches!(
+            ast[*rhs].kind,
+            ExpressionKind::Binary {
+                op: Spanned {
+                    data: BinaryOp::Mul,
+                    ..
+                },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn ranges() {
+        let (ast, expr, file) = parse("0..n - 1");
+        assert_eq!(ast[expr].span, Span::new(file, 0..8));
+        let ExpressionKind::Range {
+            start,
+            end,
+            inclusive,
+        } = ast[expr].kind
+        else {
+            panic!("expected range")
+        };
+        assert!(!inclusive);
+        assert!(matches!(
+            ast[start].kind,
+            ExpressionKind::IntegerLiteral { value: 0, .. }
+        ));
+        assert!(matches!(ast[end].kind, ExpressionKind::Binary { .. }));
+
+        let (ast, expr, _) = parse("a..=b");
+        assert!(matches!(
+            ast[expr].kind,
+            ExpressionKind::Range {
+                inclusive: true,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn arrays() {
+        let (ast, expr, _) = parse("[1, 2, 3,]");
+        let ExpressionKind::Array(elements) = &ast[expr].kind else {
+            panic!("expected array")
+        };
+        assert_eq!(elements.len(), 3);
+
+        let (ast, expr, file) = parse("[0; 16]");
+        assert_eq!(ast[expr].span, Span::new(file, 0..7));
+        let ExpressionKind::ArrayRepeat { value, count } = ast[expr].kind else {
+            panic!("expected array repeat")
+        };
+        assert!(matches!(
+            ast[value].kind,
+            ExpressionKind::IntegerLiteral { value: 0, .. }
+        ));
+        assert!(matches!(
+            ast[count].kind,
+            ExpressionKind::IntegerLiteral { value: 16, .. }
+        ));
+
+        let (ast, expr, _) = parse("[]");
+        assert!(matches!(&ast[expr].kind, ExpressionKind::Array(elements) if elements.is_empty()));
+    }
+
+    #[test]
+    fn tuples() {
+        let (ast, expr, _) = parse("(a, b)");
+        assert!(matches!(&ast[expr].kind, ExpressionKind::Tuple(elements) if elements.len() == 2));
+        let (ast, expr, _) = parse("(a,)");
+        assert!(matches!(&ast[expr].kind, ExpressionKind::Tuple(elements) if elements.len() == 1));
+        let (ast, expr, _) = parse("()");
+        assert!(matches!(&ast[expr].kind, ExpressionKind::Tuple(elements) if elements.is_empty()));
+        let (ast, expr, file) = parse("(a)");
+        assert_eq!(ast[expr].span, Span::new(file, 0..3));
+        assert_eq!(ast.span(ast[expr].id), Span::new(file, 0..3));
+        assert!(matches!(ast[expr].kind, ExpressionKind::Name(_)));
+    }
+
+    #[test]
+    fn index_and_tuple_field() {
+        let (ast, expr, file) = parse("xs[i].0");
+        assert_eq!(ast[expr].span, Span::new(file, 0..7));
+        let ExpressionKind::TupleField { base, field } = ast[expr].kind else {
+            panic!("expected tuple field")
+        };
+        assert_eq!(field, Span::new(file, 6..7).spanned(0));
+        assert_eq!(ast[base].span, Span::new(file, 0..5));
+        assert!(matches!(ast[base].kind, ExpressionKind::Index { .. }));
+    }
+
+    #[test]
+    fn prefix_operators() {
+        let (ast, expr, file) = parse("-a * b");
+        let ExpressionKind::Binary { op, lhs, .. } = &ast[expr].kind else {
+            panic!("expected binary expression")
+        };
+        assert_eq!(op.data, BinaryOp::Mul);
+        assert_eq!(ast[*lhs].span, Span::new(file, 0..2));
+        let ExpressionKind::Unary { op, .. } = ast[*lhs].kind else {
+            panic!("expected unary expression")
+        };
+        assert_eq!(op, Span::new(file, 0..1).spanned(UnaryOp::Neg));
+
+        let (ast, expr, file) = parse("!!a");
+        let ExpressionKind::Unary { op, operand } = ast[expr].kind else {
+            panic!("expected unary expression")
+        };
+        assert_eq!(op.span, Span::new(file, 0..1));
+        let ExpressionKind::Unary { op, .. } = ast[operand].kind else {
+            panic!("expected unary expression")
+        };
+        assert_eq!(op.span, Span::new(file, 1..2));
+
+        let (ast, expr, file) = parse("&mut xs[0]");
+        assert_eq!(ast[expr].span, Span::new(file, 0..10));
+        let ExpressionKind::Reference { mutable, operand } = ast[expr].kind else {
+            panic!("expected reference")
+        };
+        assert!(mutable);
+        assert!(matches!(ast[operand].kind, ExpressionKind::Index { .. }));
+
+        let (ast, expr, file) = parse("&&x");
+        assert_eq!(ast[expr].span, Span::new(file, 0..3));
+        let ExpressionKind::Reference {
+            mutable: false,
+            operand,
+        } = ast[expr].kind
+        else {
+            panic!("expected reference")
+        };
+        assert_eq!(ast[operand].span, Span::new(file, 1..3));
+        assert!(matches!(
+            ast[operand].kind,
+            ExpressionKind::Reference { mutable: false, .. }
+        ));
+    }
+
+    #[test]
+    fn casts_and_try() {
+        let (ast, expr, file) = parse("*p as u8 + 1");
+        let ExpressionKind::Binary { op, lhs, .. } = &ast[expr].kind else {
+            panic!("expected binary expression")
+        };
+        assert_eq!(op.data, BinaryOp::Add);
+        assert_eq!(ast[*lhs].span, Span::new(file, 0..8));
+        let ExpressionKind::Cast { expr, ty } = &ast[*lhs].kind else {
+            panic!("expected cast")
+        };
+        assert_eq!(ty.data.to_string(), "u8");
+        assert!(matches!(
+            ast[*expr].kind,
+            ExpressionKind::Unary {
+                op: Spanned {
+                    data: UnaryOp::Deref,
+                    ..
+                },
+                ..
+            }
+        ));
+
+        let (ast, expr, file) = parse("-t.0?");
+        let ExpressionKind::Unary { operand, .. } = ast[expr].kind else {
+            panic!("expected unary expression")
+        };
+        assert_eq!(ast[operand].span, Span::new(file, 1..5));
+        let ExpressionKind::Try(inner) = ast[operand].kind else {
+            panic!("expected try")
+        };
+        assert!(matches!(ast[inner].kind, ExpressionKind::TupleField { .. }));
+
+        let (ast, expr, _) = parse("x as &mut [i32; 4]");
+        let ExpressionKind::Cast { ty, .. } = &ast[expr].kind else {
+            panic!("expected cast")
+        };
+        assert_eq!(ty.data.to_string(), "&mut [i32; 4]");
+
+        let (ast, expr, file) = parse("x as &&mut u8");
+        let ExpressionKind::Cast { ty, .. } = &ast[expr].kind else {
+            panic!("expected cast")
+        };
+        assert_eq!(ty.span, Span::new(file, 5..13));
+        let Type::Reference {
+            mutable: false,
+            inner,
+        } = &ty.data
+        else {
+            panic!("expected reference to a reference")
+        };
+        assert_eq!(inner.span, Span::new(file, 6..13));
+        assert!(matches!(inner.data, Type::Reference { mutable: true, .. }));
+    }
+
+    #[test]
+    fn generic_types_outside_casts() {
+        let (ast, expr, _) = parse("x as u8 < y");
+        let ExpressionKind::Binary { op, lhs, .. } = &ast[expr].kind else {
+            panic!("expected comparison")
+        };
+        assert_eq!(op.data, BinaryOp::Lt);
+        assert!(matches!(ast[*lhs].kind, ExpressionKind::Cast { .. }));
+
+        let mut arena: Arena<File> = Arena::new();
+        let file = arena.alloc(File::new(
+            "test",
+            "let x: &Option<Result<(i32,), [u8; 2]>> = y;",
+        ));
+        let mut state = ParserState::new(
+            PrintingContext::new(&arena),
+            Tokens::from(Token::lexer_from_file(&arena, file)),
+        );
+        let stmt = parse_statement(&mut state).expect("statement should parse");
+        let ast = state.into_ast();
+        let StatementKind::Let { ty: Some(ty), .. } = &ast[stmt].kind else {
+            panic!("expected let with a type")
+        };
+        assert_eq!(ty.data.to_string(), "&Option<Result<(i32,), [u8; 2]>>");
+        assert_eq!(ty.span, Span::new(file, 7..39));
+    }
+
+    #[test]
+    fn literals_calls_and_return() {
+        let (ast, expr, file) = parse("f(1.5, 'a', \"a\\tb\", true,)(x)");
+        assert_eq!(ast[expr].span, Span::new(file, 0..29));
+        let ExpressionKind::Call { callee, arguments } = &ast[expr].kind else {
+            panic!("expected call")
+        };
+        assert_eq!(arguments.len(), 1);
+        let ExpressionKind::Call { arguments, .. } = &ast[*callee].kind else {
+            panic!("expected call")
+        };
+        assert!(matches!(
+            arguments.iter().map(|&id| &ast[id].kind).collect::<Vec<_>>()[..],
+            [
+                ExpressionKind::FloatLiteral(float),
+                ExpressionKind::CharLiteral('a'),
+                ExpressionKind::StringLiteral(string),
+                ExpressionKind::BoolLiteral(true),
+            ] if float.as_str() == "1.5" && string.as_str() == "a\tb"
+        ));
+
+        // `return` takes everything after it, and nothing before a `}` or `;`
+        let (ast, expr, file) = parse("return a + b");
+        assert_eq!(ast[expr].span, Span::new(file, 0..12));
+        let ExpressionKind::Return(Some(value)) = ast[expr].kind else {
+            panic!("expected return with a value")
+        };
+        assert!(matches!(ast[value].kind, ExpressionKind::Binary { .. }));
+        let (ast, expr, _) = parse("(return)");
+        assert!(matches!(ast[expr].kind, ExpressionKind::Return(None)));
+    }
+
+    #[test]
+    fn assignments_and_loops() {
+        // Assignments are right-associative and take whole ranges
+        let (ast, expr, file) = parse("a = b += 0..n");
+        assert_eq!(ast[expr].span, Span::new(file, 0..13));
+        let ExpressionKind::Assign { target, value } = ast[expr].kind else {
+            panic!("expected assignment")
+        };
+        assert!(matches!(ast[target].kind, ExpressionKind::Name(_)));
+        let ExpressionKind::CompoundAssign { op, value, .. } = ast[value].kind else {
+            panic!("expected compound assignment")
+        };
+        assert_eq!((op.data, op.span), (BinaryOp::Add, Span::new(file, 6..8)));
+        assert!(matches!(ast[value].kind, ExpressionKind::Range { .. }));
+
+        let (ast, expr, file) = parse("for (i, _) in 0..n { break i; continue }");
+        assert_eq!(ast[expr].span, Span::new(file, 0..40));
+        let ExpressionKind::For {
+            pattern,
+            iterable,
+            body,
+        } = &ast[expr].kind
+        else {
+            panic!("expected for")
+        };
+        assert!(matches!(&pattern.kind, PatternKind::Tuple(elements) if elements.len() == 2));
+        assert!(matches!(ast[*iterable].kind, ExpressionKind::Range { .. }));
+        assert!(matches!(
+            body.statements
+                .iter()
+                .map(|&id| &ast[id].kind)
+                .collect::<Vec<_>>()[..],
+            [StatementKind::Expr(brk), StatementKind::ReturnExpr(cont)]
+                if matches!(ast[*brk].kind, ExpressionKind::Break(Some(_)))
+                    && matches!(ast[*cont].kind, ExpressionKind::Continue)
+        ));
+
+        let (ast, expr, _) = parse("loop { break }");
+        assert!(
+            matches!(&ast[expr].kind, ExpressionKind::Loop(body) if body.statements.len() == 1)
+        );
+    }
+
+    #[test]
+    fn closures_and_suffixes() {
+        let (ast, expr, file) = parse("|x, (a, _): u8| x + 1u8");
+        assert_eq!(ast[expr].span, Span::new(file, 0..23));
+        let ExpressionKind::Closure { params, body } = &ast[expr].kind else {
+            panic!("expected closure")
+        };
+        assert_eq!(params.len(), 2);
+        assert!(params[0].ty.is_none());
+        assert!(matches!(params[1].pattern.kind, PatternKind::Tuple(_)));
+        assert_eq!(params[1].ty.as_ref().unwrap().span, Span::new(file, 12..14));
+        let ExpressionKind::Binary { rhs, .. } = ast[*body].kind else {
+            panic!("expected the body to take the whole addition")
+        };
+        assert!(matches!(
+            ast[rhs].kind,
+            ExpressionKind::IntegerLiteral { value: 1, suffix: Some(suffix) } if suffix.as_str() == "u8"
+        ));
+
+        let (ast, expr, _) = parse("|| || 1");
+        let ExpressionKind::Closure { params, body } = &ast[expr].kind else {
+            panic!("expected closure")
+        };
+        assert!(params.is_empty());
+        assert!(matches!(ast[*body].kind, ExpressionKind::Closure { .. }));
+        // `||` after an operand is still the operator
+        let (ast, expr, _) = parse("a || b");
+        assert!(matches!(ast[expr].kind, ExpressionKind::Binary { .. }));
+    }
+
+    #[test]
+    fn match_arms_and_patterns() {
+        let (ast, expr, file) = parse("match x { -1..=5 => a, Some(_) => { b } 'c' => c }");
+        assert_eq!(ast[expr].span, Span::new(file, 0..50));
+        let ExpressionKind::Match { arms, .. } = &ast[expr].kind else {
+            panic!("expected match")
+        };
+        assert_eq!(arms.len(), 3);
+        let PatternKind::Range {
+            start,
+            end,
+            inclusive: true,
+        } = &arms[0].pattern.kind
+        else {
+            panic!("expected an inclusive range")
+        };
+        assert_eq!(
+            (**start, **end),
+            (
+                LiteralPattern::Int {
+                    value: 1,
+                    negative: true,
+                    suffix: None
+                },
+                LiteralPattern::Int {
+                    value: 5,
+                    negative: false,
+                    suffix: None
+                }
+            )
+        );
+        assert_eq!(arms[0].pattern.span, Span::new(file, 10..16));
+        let PatternKind::Variant { name, fields } = &arms[1].pattern.kind else {
+            panic!("expected variant pattern")
+        };
+        assert_eq!(name.data.symbol(), sym::Some);
+        assert!(matches!(fields[..], [ref field] if matches!(field.kind, PatternKind::Wildcard)));
+        // A block-like arm doesn't need a comma
+        assert!(matches!(ast[arms[1].body].kind, ExpressionKind::Block(_)));
+        assert!(matches!(
+            arms[2].pattern.kind,
+            PatternKind::Literal(LiteralPattern::Char('c'))
+        ));
+
+        let (ast, expr, _) = parse("match x {}");
+        assert!(matches!(&ast[expr].kind, ExpressionKind::Match { arms, .. } if arms.is_empty()));
+    }
+
+    #[test]
+    fn nested_tuple_fields() {
+        let (ast, expr, file) = parse("t.0.12");
+        let ExpressionKind::TupleField { base, field } = ast[expr].kind else {
+            panic!("expected tuple field")
+        };
+        assert_eq!(field, Span::new(file, 4..6).spanned(12));
+        assert_eq!(ast[base].span, Span::new(file, 0..3));
+        let ExpressionKind::TupleField { field, .. } = ast[base].kind else {
+            panic!("expected tuple field")
+        };
+        assert_eq!(field, Span::new(file, 2..3).spanned(0));
+    }
+
+    #[test]
+    fn paths_and_intrinsics() {
+        let (ast, expr, file) = parse("std::mem::size + #line");
+        let ExpressionKind::Binary { lhs, rhs, .. } = &ast[expr].kind else {
+            panic!("expected binary expression")
+        };
+        assert_eq!(ast[*lhs].span, Span::new(file, 0..14));
+        let ExpressionKind::Path(path) = &ast[*lhs].kind else {
+            panic!("expected path")
+        };
+        assert_eq!(path.to_string(), "std::mem::size");
+        assert_eq!(path.segments[2].span, Span::new(file, 10..14));
+        let ExpressionKind::Intrinsic(name) = ast[*rhs].kind else {
+            panic!("expected intrinsic")
+        };
+        assert_eq!(name.data.symbol(), Symbol::intern("line"));
+
+        let (ast, expr, _) = parse("main");
+        let ExpressionKind::Name(name) = ast[expr].kind else {
+            panic!("expected name")
+        };
+        assert_eq!(name.data.symbol(), sym::main);
+    }
+
+    #[test]
+    fn node_ids() {
+        let (ast, expr, _) = parse("(a + b) * c");
+        assert_eq!(ast.node_count(), 5);
+        let mut ids: Vec<_> = ast.expressions.iter().map(|(_, expr)| expr.id).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 5);
+        for (_, expression) in ast.expressions.iter() {
+            assert_eq!(ast.span(expression.id), expression.span);
+        }
+        let ExpressionKind::Binary { lhs, .. } = ast[expr].kind else {
+            panic!("expected binary expression")
+        };
+        assert!(ast[lhs].id < ast[expr].id);
+    }
+
+    #[test]
+    fn let_tuple_pattern() {
+        let mut arena: Arena<File> = Arena::new();
+        let file = arena.alloc(File::new(
+            "test",
+            "let (a, _): (i32, [bool; 2]) = (1, [x, y]);",
+        ));
+        let mut state = ParserState::new(
+            PrintingContext::new(&arena),
+            Tokens::from(Token::lexer_from_file(&arena, file)),
+        );
+        let statement = parse_statement(&mut state).expect("statement should parse");
+        let ast = state.into_ast();
+        assert_eq!(ast[statement].span, Span::new(file, 0..43));
+        let StatementKind::Let { pattern, ty, .. } = &ast[statement].kind else {
+            panic!("expected let statement")
+        };
+        let PatternKind::Tuple(elements) = &pattern.kind else {
+            panic!("expected tuple pattern")
+        };
+        assert!(matches!(elements[0].kind, PatternKind::Binding(_)));
+        assert!(matches!(elements[1].kind, PatternKind::Wildcard));
+        assert_eq!(ast.span(elements[1].id), Span::new(file, 8..9));
+        assert_eq!(ty.as_ref().unwrap().data.to_string(), "(i32, [bool; 2])");
+
+        // The value can be left out and assigned later
+        let file = arena.alloc(File::new("test", "let (a, b);"));
+        let mut state = ParserState::new(
+            PrintingContext::new(&arena),
+            Tokens::from(Token::lexer_from_file(&arena, file)),
+        );
+        let statement = parse_statement(&mut state).expect("statement should parse");
+        let ast = state.into_ast();
+        assert_eq!(ast[statement].span, Span::new(file, 0..11));
+        assert!(matches!(
+            ast[statement].kind,
+            StatementKind::Let {
+                ty: None,
+                value: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn literals_too_large_for_any_type() {
+        let mut arena: Arena<File> = Arena::new();
+        let max = u128::MAX;
+        let file = arena.alloc(File::new("test", format!("{max}0u8 + {max}0")));
+        let mut state = ParserState::new(
+            CollectingContext::new(),
+            Tokens::from(Token::lexer_from_file(&arena, file)),
+        );
+        parse_expression(&mut state).expect("expression should parse after recovering");
+        let (ctx, _, _) = state.into_parts();
+        let errors: Vec<_> = ctx
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| (diagnostic.error.span(), diagnostic.error.to_string()))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (
+                    Span::new(file, 0..42),
+                    "literal out of range for `u8`".to_string()
+                ),
+                (
+                    Span::new(file, 45..85),
+                    "literal out of range for any integer type".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn one_syntax_error_one_diagnostic() {
+        let mut arena: Arena<File> = Arena::new();
+        let file = arena.alloc(File::new(
+            "test",
+            "fn main(): void {\n    let x = ;\n    let y = 2;\n    y\n}\n\nfn other(): i32 { 1 }",
+        ));
+        let mut state = ParserState::new(
+            CollectingContext::new(),
+            Tokens::from(Token::lexer_from_file(&arena, file)),
+        );
+        let items = parse_file(&mut state);
+        let (ctx, _, ast) = state.into_parts();
+        assert_eq!(ctx.error_count(), 1, "{:?}", ctx.diagnostics());
+        assert_eq!(
+            ctx.diagnostics()[0].error.to_string(),
+            "unexpected `;`".to_string()
+        );
+        // The statements after the broken one and the next function are kept
+        assert_eq!(items.len(), 2);
+        let ItemKind::Function { body, .. } = &ast[items[0]].kind;
+        assert_eq!(body.statements.len(), 2);
+    }
+
+    #[test]
+    fn stray_tokens_between_items() {
+        let mut arena: Arena<File> = Arena::new();
+        let file = arena.alloc(File::new("test", "let x = 1; y + 2 fn main(): void {}"));
+        let mut state = ParserState::new(
+            CollectingContext::new(),
+            Tokens::from(Token::lexer_from_file(&arena, file)),
+        );
+        let items = parse_file(&mut state);
+        let (ctx, _, _) = state.into_parts();
+        assert_eq!(ctx.error_count(), 1, "{:?}", ctx.diagnostics());
+        assert_eq!(items.len(), 1);
+    }
+
+    #[test]
+    fn unterminated_block_comment() {
+        let mut arena: Arena<File> = Arena::new();
+        let file = arena.alloc(File::new("test", "fn main(): void {} /* a\nb"));
+        let mut state = ParserState::new(
+            CollectingContext::new(),
+            Tokens::from(Token::lexer_from_file(&arena, file)),
+        );
+        parse_file(&mut state);
+        let (ctx, _, _) = state.into_parts();
+        let errors: Vec<_> = ctx
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| (diagnostic.error.span(), diagnostic.error.to_string()))
+            .collect();
+        // Only the `/*` is pointed at, rather than the rest of the file
+        assert_eq!(
+            errors,
+            [(
+                Span::new(file, 19..21),
+                "unterminated block comment".to_string()
+            )]
+        );
+    }
+
+    /// Parses `source` as the body of a function, returning the suggestions reported on the way
+    fn suggestions(source: &str) -> (FileId, Vec<(Span, String, Applicability)>) {
+        let mut arena: Arena<File> = Arena::new();
+        let file = arena.alloc(File::new("test", format!("fn f(): void {{ {source} }}")));
+        let mut state = ParserState::new(
+            CollectingContext::new(),
+            Tokens::from(Token::lexer_from_file(&arena, file)),
+        );
+        parse_item(&mut state).expect("item should parse after recovering");
+        let (ctx, _, _) = state.into_parts();
+        let suggestions = ctx
+            .diagnostics()
+            .iter()
+            .flat_map(|diagnostic| diagnostic.error.suggestions())
+            .map(|suggestion| {
+                (
+                    suggestion.span,
+                    suggestion.replacement,
+                    suggestion.applicability,
+                )
+            })
+            .collect();
+        (file, suggestions)
+    }
+
+    #[test]
+    fn fix_missing_closing_tokens() {
+        // `fn f(): void { ` is 15 bytes long
+        let (file, found) = suggestions("let x = (1, 2 } ");
+        assert_eq!(
+            found,
+            [
+                (
+                    Span::new(file, 28..28),
+                    ")".to_string(),
+                    Applicability::MachineApplicable
+                ),
+                (
+                    Span::new(file, 28..28),
+                    ";".to_string(),
+                    Applicability::MachineApplicable
+                ),
+            ]
+        );
+
+        // `b (1)` is a call, so the only missing `;` is after `a`
+        let (file, found) = suggestions("a\n b (1); c");
+        assert_eq!(
+            found,
+            [(
+                Span::new(file, 16..16),
+                ";".to_string(),
+                Applicability::MachineApplicable
+            )]
+        );
+    }
+
+    #[test]
+    fn fix_path_and_condition() {
+        let (file, found) = suggestions("while a:b = 1 {}");
+        assert_eq!(
+            found,
+            [
+                (
+                    Span::new(file, 22..23),
+                    "::".to_string(),
+                    Applicability::MachineApplicable
+                ),
+                (
+                    Span::new(file, 25..26),
+                    "==".to_string(),
+                    Applicability::MachineApplicable
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn if_else_chain() {
+        let (ast, expr, file) = parse("if a { b } else if c { d; } else { e }");
+        assert_eq!(ast[expr].span, Span::new(file, 0..38));
+        let ExpressionKind::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        } = &ast[expr].kind
+        else {
+            panic!("expected if expression")
+        };
+        assert_eq!(then_branch.statements.len(), 1);
+        let ExpressionKind::If {
+            else_branch: Some(last),
+            ..
+        } = &ast[*else_branch].kind
+        else {
+            panic!("expected else if")
+        };
+        assert!(matches!(ast[*last].kind, ExpressionKind::Block(_)));
+    }
+
+    // Exhaustive match: adding a variant fails to compile until it is listed here,
+    // and `error_codes_are_registered` fails until it has an example and a registered code
+    fn parse_error_variant(error: &ParseError) -> &'static str {
+        match error {
+            ParseError::UnexpectedEOI(_) => "UnexpectedEOI",
+            ParseError::UnexpectedToken(_) => "UnexpectedToken",
+            ParseError::UnexpectedTokenWithExpectation { .. } => "UnexpectedTokenWithExpectation",
+            ParseError::InvalidToken(_) => "InvalidToken",
+            ParseError::UnterminatedBlockComment(_) => "UnterminatedBlockComment",
+            ParseError::InvalidTupleIndex(_) => "InvalidTupleIndex",
+            ParseError::Missing { .. } => "Missing",
+            ParseError::SingleColonPath(_) => "SingleColonPath",
+            ParseError::AssignmentInCondition(_) => "AssignmentInCondition",
+            ParseError::LiteralOutOfRange { .. } => "LiteralOutOfRange",
+        }
+    }
+    const PARSE_ERROR_VARIANTS: usize = 10;
+
+    #[test]
+    fn error_codes_are_registered() {
+        let mut arena: Arena<File> = Arena::new();
+        let file = arena.alloc(File::new("test", ""));
+        let span = Span::new(file, 0..0);
+        let found = span.spanned(Token::Semicolon);
+        let examples = [
+            ParseError::UnexpectedEOI(span),
+            ParseError::UnexpectedToken(found),
+            ParseError::UnexpectedTokenWithExpectation {
+                found,
+                expected: Token::Colon,
+            },
+            ParseError::InvalidToken(span),
+            ParseError::UnterminatedBlockComment(span),
+            ParseError::InvalidTupleIndex(span.spanned(u128::MAX)),
+            ParseError::Missing {
+                expected: Token::Semicolon,
+                after: span,
+                found: None,
+            },
+            ParseError::SingleColonPath(span),
+            ParseError::AssignmentInCondition(span),
+            ParseError::LiteralOutOfRange { suffix: None, span },
+        ];
+
+        let variants: BTreeSet<_> = examples.iter().map(parse_error_variant).collect();
+        assert_eq!(variants.len(), PARSE_ERROR_VARIANTS);
+        let codes: BTreeSet<_> = examples.iter().map(ParseError::error_code).collect();
+        assert_eq!(codes.len(), PARSE_ERROR_VARIANTS, "codes must be unique");
+        for error in &examples {
+            let code = error.error_code();
+            assert!(
+                codes::explain(code).is_some(),
+                "{code} of {} isn't registered",
+                parse_error_variant(error)
+            );
+            let diagnostic_code = error.code().map(|code| code.to_string());
+            assert_eq!(diagnostic_code.as_deref(), Some(code));
+        }
+    }
 }
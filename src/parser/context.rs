@@ -1,19 +1,77 @@
-#[derive(Debug)]
+use crate::ast::{BinaryOp, Identifier, UnaryOp};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ContextName {
     Item,
     Fn,
-    FnName(String),
+    FnName(Identifier),
     Arguments,
     Block,
     Statement,
+    Let,
     Expression,
+    Assignment,
+    BinaryOperation(BinaryOp),
+    UnaryOperation(UnaryOp),
+    Reference,
+    Cast,
+    Try,
+    Call,
+    Return,
+    Break,
+    Closure,
+    Range,
+    Index,
+    TupleField,
+    Tuple,
+    Array,
+    If,
+    While,
+    For,
+    Loop,
+    Match,
+    Pattern,
+    Path,
     Identifier,
     Type,
 }
 
 impl std::fmt::Display for ContextName {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{self:?}")
+        match self {
+            Self::Item => write!(f, "item"),
+            Self::Fn => write!(f, "function"),
+            Self::FnName(name) => write!(f, "`{name}`"),
+            Self::Arguments => write!(f, "arguments"),
+            Self::Block => write!(f, "block"),
+            Self::Statement => write!(f, "statement"),
+            Self::Let => write!(f, "`let`"),
+            Self::Expression => write!(f, "expression"),
+            Self::Assignment => write!(f, "assignment"),
+            Self::BinaryOperation(op) => write!(f, "`{op}` operation"),
+            Self::UnaryOperation(op) => write!(f, "`{op}` operation"),
+            Self::Reference => write!(f, "reference"),
+            Self::Cast => write!(f, "cast"),
+            Self::Try => write!(f, "`?`"),
+            Self::Call => write!(f, "call"),
+            Self::Return => write!(f, "`return`"),
+            Self::Break => write!(f, "`break`"),
+            Self::Closure => write!(f, "closure"),
+            Self::Range => write!(f, "range"),
+            Self::Index => write!(f, "index"),
+            Self::TupleField => write!(f, "tuple field"),
+            Self::Tuple => write!(f, "tuple"),
+            Self::Array => write!(f, "array"),
+            Self::If => write!(f, "`if`"),
+            Self::While => write!(f, "`while`"),
+            Self::For => write!(f, "`for`"),
+            Self::Loop => write!(f, "`loop`"),
+            Self::Match => write!(f, "`match`"),
+            Self::Pattern => write!(f, "pattern"),
+            Self::Path => write!(f, "path"),
+            Self::Identifier => write!(f, "identifier"),
+            Self::Type => write!(f, "type"),
+        }
     }
 }
 
@@ -0,0 +1,244 @@
+//! A Wadler-style pretty printer.
+//!
+//! A [`Doc`] describes text with optional line breaks. Each [`Doc::Group`] is laid out flat,
+//! with its [`Doc::Line`]s as spaces, if it fits in what's left of the line, and broken, with
+//! every one of its lines as a newline, otherwise. Groups are tried outermost first.
+
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Doc {
+    Text(Cow<'static, str>),
+    /// A space, or a newline when the enclosing group is broken
+    Line,
+    /// Nothing, or a newline when the enclosing group is broken
+    SoftLine,
+    /// Always a newline, which breaks every enclosing group
+    HardLine,
+    /// `broken` when the enclosing group is broken, `flat` otherwise
+    IfBreak {
+        broken: Box<Doc>,
+        flat: Box<Doc>,
+    },
+    /// Indents the lines started inside by one more level
+    Nest(Box<Doc>),
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+impl Doc {
+    pub fn nil() -> Self {
+        Self::Concat(Vec::new())
+    }
+
+    pub fn text(text: impl Into<Cow<'static, str>>) -> Self {
+        Self::Text(text.into())
+    }
+
+    pub fn concat(docs: impl IntoIterator<Item = Self>) -> Self {
+        Self::Concat(docs.into_iter().collect())
+    }
+
+    /// `docs` with `separator` between each of them
+    pub fn join(docs: impl IntoIterator<Item = Self>, separator: &Self) -> Self {
+        let mut joined = Vec::new();
+        for (i, doc) in docs.into_iter().enumerate() {
+            if i != 0 {
+                joined.push(separator.clone());
+            }
+            joined.push(doc);
+        }
+        Self::Concat(joined)
+    }
+
+    pub fn if_break(broken: Self, flat: Self) -> Self {
+        Self::IfBreak {
+            broken: Box::new(broken),
+            flat: Box::new(flat),
+        }
+    }
+
+    #[must_use]
+    pub fn nest(self) -> Self {
+        Self::Nest(Box::new(self))
+    }
+
+    #[must_use]
+    pub fn group(self) -> Self {
+        Self::Group(Box::new(self))
+    }
+
+    /// The first non-empty text, which is where the rendered document starts
+    pub fn first_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) if !text.is_empty() => Some(text),
+            Self::Nest(doc) | Self::Group(doc) => doc.first_text(),
+            Self::Concat(docs) => docs.iter().find_map(Self::first_text),
+            _ => None,
+        }
+    }
+
+    /// Lays the document out in `width` columns, indenting nested lines by `indent` spaces.
+    /// Lines never end in whitespace.
+    pub fn render(&self, width: usize, indent: usize) -> String {
+        let mut out = String::new();
+        let mut column = 0;
+        // Indentation is only written once something follows it on the line
+        let mut pending_indent = None;
+        let mut stack = vec![(0, Mode::Break, self)];
+        while let Some((level, mode, doc)) = stack.pop() {
+            match doc {
+                Self::Text(text) => {
+                    if text.is_empty() {
+                        continue;
+                    }
+                    if let Some(indent) = pending_indent.take() {
+                        out.extend(std::iter::repeat_n(' ', indent));
+                    }
+                    out.push_str(text);
+                    column += text.chars().count();
+                }
+                Self::Line | Self::SoftLine | Self::HardLine => {
+                    if mode == Mode::Flat && !matches!(doc, Self::HardLine) {
+                        if matches!(doc, Self::Line) {
+                            out.push(' ');
+                            column += 1;
+                        }
+                        continue;
+                    }
+                    out.push('\n');
+                    column = level * indent;
+                    pending_indent = Some(column);
+                }
+                Self::IfBreak { broken, flat } => {
+                    stack.push((level, mode, if mode == Mode::Flat { flat } else { broken }))
+                }
+                Self::Nest(doc) => stack.push((level + 1, mode, doc)),
+                Self::Group(doc) => {
+                    let mode = if mode == Mode::Flat
+                        || fits(
+                            width.saturating_sub(column),
+                            (level, Mode::Flat, doc),
+                            &stack,
+                        ) {
+                        Mode::Flat
+                    } else {
+                        Mode::Break
+                    };
+                    stack.push((level, mode, doc));
+                }
+                Self::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (level, mode, doc))),
+            }
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+type Command<'a> = (usize, Mode, &'a Doc);
+
+/// Whether `next` fits in `remaining` columns, along with whatever follows it on its last line
+fn fits(remaining: usize, next: Command<'_>, rest: &[Command<'_>]) -> bool {
+    let mut remaining = remaining as isize;
+    let mut stack = vec![next];
+    let mut rest = rest.iter().rev();
+    loop {
+        let Some((level, mode, doc)) = stack.pop().or_else(|| rest.next().copied()) else {
+            return true;
+        };
+        match doc {
+            Doc::Text(text) => {
+                remaining -= text.chars().count() as isize;
+                if remaining < 0 {
+                    return false;
+                }
+            }
+            Doc::Line | Doc::SoftLine => match mode {
+                Mode::Break => return true,
+                Mode::Flat if matches!(doc, Doc::Line) => remaining -= 1,
+                Mode::Flat => {}
+            },
+            // A group with a hard line in it can't be flat
+            Doc::HardLine => return mode == Mode::Break,
+            Doc::IfBreak { broken, flat } => {
+                stack.push((level, mode, if mode == Mode::Flat { flat } else { broken }))
+            }
+            Doc::Nest(doc) | Doc::Group(doc) => stack.push((level, mode, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (level, mode, doc))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Doc;
+
+    /// `[a, b, c]` that breaks into one element per line
+    fn list(elements: &[&'static str]) -> Doc {
+        Doc::concat([
+            Doc::text("["),
+            Doc::concat([
+                Doc::SoftLine,
+                Doc::join(
+                    elements.iter().map(|&element| Doc::text(element)),
+                    &Doc::concat([Doc::text(","), Doc::Line]),
+                ),
+                Doc::if_break(Doc::text(","), Doc::nil()),
+            ])
+            .nest(),
+            Doc::SoftLine,
+            Doc::text("]"),
+        ])
+        .group()
+    }
+
+    #[test]
+    fn groups_break_when_too_wide() {
+        let doc = Doc::concat([Doc::text("let x = "), list(&["one", "two"]), Doc::text(";")]);
+        assert_eq!(doc.render(80, 4), "let x = [one, two];");
+        // The `;` after the group counts towards its width
+        assert_eq!(doc.render(19, 4), "let x = [one, two];");
+        assert_eq!(doc.render(18, 4), "let x = [\n    one,\n    two,\n];");
+    }
+
+    #[test]
+    fn inner_groups_stay_flat() {
+        let doc = list(&["aaaa", "bbbb"]);
+        let outer = Doc::concat([
+            Doc::text("f("),
+            Doc::concat([Doc::SoftLine, doc.clone(), Doc::text(","), Doc::Line, doc]).nest(),
+            Doc::SoftLine,
+            Doc::text(")"),
+        ])
+        .group();
+        assert_eq!(
+            outer.render(20, 2),
+            "f(\n  [aaaa, bbbb],\n  [aaaa, bbbb]\n)"
+        );
+    }
+
+    #[test]
+    fn hard_lines_break_groups_without_trailing_whitespace() {
+        let doc = Doc::concat([
+            Doc::text("{"),
+            Doc::concat([
+                Doc::HardLine,
+                Doc::text("a"),
+                Doc::HardLine,
+                Doc::HardLine,
+                Doc::text("b"),
+            ])
+            .nest(),
+            Doc::HardLine,
+            Doc::text("}"),
+        ])
+        .group();
+        assert_eq!(doc.render(80, 4), "{\n    a\n\n    b\n}");
+        assert_eq!(doc.first_text(), Some("{"));
+    }
+}
@@ -0,0 +1,730 @@
+//! Name resolution: binds every [`ExpressionKind::Name`] to the declaration it refers to.
+//!
+//! Scopes form a tree. The module scope holds the items and is visible from everywhere, so
+//! functions can call each other in any order. Below it each function gets a scope for its
+//! parameters and each block one of its own. Every `let` opens a scope for the rest of its
+//! block, which is how a later `let` shadows an earlier one and why `let x = x;` refers to
+//! the `x` from before.
+//!
+//! `Some`, `None`, `Ok` and `Err` are visible everywhere below the module, so a name that
+//! isn't declared anywhere can still be one of them. In patterns they always mean the variant,
+//! `None` can't be a binding.
+//!
+//! Paths and type names aren't resolved here, there's nothing they could refer to yet.
+
+use std::marker::PhantomData;
+
+use id_arena::{Arena, Id};
+use miette::{Diagnostic, LabeledSpan};
+use thiserror::Error;
+
+use crate::{
+    ast::{
+        visit::{walk_expression, Visitor},
+        Ast, Block, ExprId, ExpressionKind, Identifier, ItemId, ItemKind, NodeId, NodeMap, Pattern,
+        PatternKind, StatementKind, StmtId, Variant,
+    },
+    error::{
+        snippet_labels,
+        suggestion::{Applicability, Suggestion},
+        Context, ContextName,
+    },
+    span::{Span, Spanned},
+};
+
+pub type ScopeId = Id<Scope>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    Module,
+    /// The parameters of a function
+    Function(ItemId),
+    /// The parameters of a closure, keyed by the closure expression
+    Closure(NodeId),
+    Block(NodeId),
+    /// What a `let` binds, visible until the end of its block
+    Let(StmtId),
+    /// The pattern of a `for`, visible in its body, keyed by the loop expression
+    For(NodeId),
+    /// The pattern of a `match` arm, visible in its body, keyed by the pattern
+    Arm(NodeId),
+}
+
+#[derive(Debug)]
+pub struct Scope {
+    pub parent: Option<ScopeId>,
+    pub kind: ScopeKind,
+    /// What the scope declares, in source order
+    pub declarations: Vec<NodeId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeclarationKind {
+    Function(ItemId),
+    Param,
+    /// A binding in the pattern of a `let`, a `for` or a `match` arm
+    Local,
+}
+
+impl std::fmt::Display for DeclarationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Function(_) => "function",
+            Self::Param => "parameter",
+            Self::Local => "binding",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Declaration {
+    pub name: Identifier,
+    /// The name where it's declared
+    pub span: Span,
+    pub kind: DeclarationKind,
+    pub scope: ScopeId,
+}
+
+/// Everything the resolver found out, keyed by [`NodeId`]. Declarations are the item for
+/// functions, the [`crate::ast::Param`] for parameters and the binding [`Pattern`] for locals.
+#[derive(Debug)]
+pub struct Resolutions {
+    pub scopes: Arena<Scope>,
+    pub module: ScopeId,
+    pub declarations: NodeMap<Declaration>,
+    /// From each resolved name expression to its declaration
+    pub uses: NodeMap<NodeId>,
+    /// From each declaration to the one with the same name it hides, for lints about shadowing
+    pub shadows: NodeMap<NodeId>,
+    /// Name expressions and patterns naming a variant of `Option` or `Result`
+    pub variants: NodeMap<Variant>,
+}
+
+impl Resolutions {
+    /// The declaration the name expression `id` refers to, if it could be resolved
+    pub fn declaration_of(&self, id: NodeId) -> Option<(NodeId, &Declaration)> {
+        let declaration = *self.uses.get(id)?;
+        Some((declaration, &self.declarations[declaration]))
+    }
+}
+
+/// A declaration whose name is close to one that couldn't be resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Similar {
+    pub name: Spanned<Identifier>,
+    pub kind: DeclarationKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ResolveError {
+    #[error("cannot find `{}` in this scope", .name.data)]
+    UnresolvedName {
+        name: Spanned<Identifier>,
+        /// A visible name that is spelled almost the same, along with where it's declared
+        similar: Option<Similar>,
+    },
+    #[error("the {kind} `{name}` is defined more than once")]
+    Duplicate {
+        name: Identifier,
+        kind: DeclarationKind,
+        first: Span,
+        second: Span,
+    },
+}
+
+impl ResolveError {
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::UnresolvedName { name, .. } => name.span,
+            Self::Duplicate { second, .. } => *second,
+        }
+    }
+
+    /// Code of the error, registered with its explanation in [`crate::error::codes`]
+    pub const fn error_code(&self) -> &'static str {
+        match self {
+            Self::UnresolvedName { .. } => "E0009",
+            Self::Duplicate { .. } => "E0010",
+        }
+    }
+
+    pub fn suggestions(&self) -> Vec<Suggestion> {
+        match self {
+            Self::UnresolvedName {
+                name,
+                similar: Some(similar),
+            } => vec![Suggestion::new(
+                name.span,
+                similar.name.data.to_string(),
+                Applicability::MaybeIncorrect,
+            )],
+            Self::UnresolvedName { similar: None, .. } | Self::Duplicate { .. } => Vec::new(),
+        }
+    }
+}
+
+impl Diagnostic for ResolveError {
+    fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        Some(Box::new(self.error_code()))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        match self {
+            Self::UnresolvedName {
+                similar: Some(similar),
+                ..
+            } => Some(Box::new(format!(
+                "a {} with a similar name exists: `{}`",
+                similar.kind, similar.name.data
+            ))),
+            Self::UnresolvedName { similar: None, .. } => None,
+            Self::Duplicate { kind, .. } => Some(Box::new(match kind {
+                DeclarationKind::Function(_) => "items of a module must have distinct names",
+                DeclarationKind::Param => "parameters of a function must have distinct names",
+                DeclarationKind::Local => "a pattern can't bind the same name twice",
+            })),
+        }
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        Some(snippet_labels(self.labelled_spans()))
+    }
+}
+
+impl ResolveError {
+    /// The labels with the spans they point at, primary first
+    pub fn labelled_spans(&self) -> Vec<(Span, String)> {
+        let (primary, secondary) = match self {
+            Self::UnresolvedName { similar, .. } => (
+                "not found in this scope".to_string(),
+                similar.map(|similar| {
+                    (
+                        similar.name.span,
+                        format!("`{}` is declared here", similar.name.data),
+                    )
+                }),
+            ),
+            Self::Duplicate { name, first, .. } => (
+                format!("`{name}` redefined here"),
+                Some((*first, format!("first definition of `{name}`"))),
+            ),
+        };
+        std::iter::once((self.span(), primary))
+            .chain(secondary)
+            .collect()
+    }
+}
+
+/// Resolves the names in `items`, which all share one module scope, reporting the ones that
+/// can't be resolved and the duplicate definitions to `ctx`
+pub fn resolve<C: ContextName>(
+    ctx: &mut impl Context<C>,
+    ast: &Ast,
+    items: &[ItemId],
+) -> Resolutions {
+    let mut scopes = Arena::new();
+    let module = scopes.alloc(Scope {
+        parent: None,
+        kind: ScopeKind::Module,
+        declarations: Vec::new(),
+    });
+    let mut resolver = Resolver {
+        ctx,
+        resolutions: Resolutions {
+            scopes,
+            module,
+            declarations: NodeMap::new(),
+            uses: NodeMap::new(),
+            shadows: NodeMap::new(),
+            variants: NodeMap::new(),
+        },
+        scope: module,
+        _context: PhantomData,
+    };
+    for &item in items {
+        match &ast[item].kind {
+            ItemKind::Function { name, .. } => {
+                resolver.declare(ast[item].id, *name, DeclarationKind::Function(item))
+            }
+        }
+    }
+    for &item in items {
+        resolver.visit_item(ast, item);
+    }
+    resolver.resolutions
+}
+
+struct Resolver<'c, X, C> {
+    ctx: &'c mut X,
+    resolutions: Resolutions,
+    /// The innermost scope at the current point of the walk
+    scope: ScopeId,
+    _context: PhantomData<C>,
+}
+
+impl<X: Context<C>, C: ContextName> Resolver<'_, X, C> {
+    fn push_scope(&mut self, kind: ScopeKind) {
+        self.scope = self.resolutions.scopes.alloc(Scope {
+            parent: Some(self.scope),
+            kind,
+            declarations: Vec::new(),
+        });
+    }
+
+    /// Runs `f` in a new scope, going back to the current one afterwards along with any
+    /// `let` scopes `f` opened
+    fn in_scope(&mut self, kind: ScopeKind, f: impl FnOnce(&mut Self)) {
+        let outer = self.scope;
+        self.push_scope(kind);
+        f(self);
+        self.scope = outer;
+    }
+
+    /// The closest declaration of `name` visible from `scope`
+    fn lookup(&self, scope: ScopeId, name: Identifier) -> Option<NodeId> {
+        let mut scope = Some(scope);
+        while let Some(id) = scope {
+            let found = self.resolutions.scopes[id]
+                .declarations
+                .iter()
+                .rev()
+                .find(|&&declaration| self.resolutions.declarations[declaration].name == name);
+            if let Some(&found) = found {
+                return Some(found);
+            }
+            scope = self.resolutions.scopes[id].parent;
+        }
+        None
+    }
+
+    fn declare(&mut self, id: NodeId, name: Spanned<Identifier>, kind: DeclarationKind) {
+        let scope = self.scope;
+        self.resolutions.declarations.insert(
+            id,
+            Declaration {
+                name: name.data,
+                span: name.span,
+                kind,
+                scope,
+            },
+        );
+        let duplicate = self.resolutions.scopes[scope]
+            .declarations
+            .iter()
+            .find(|&&declaration| self.resolutions.declarations[declaration].name == name.data);
+        if let Some(&first) = duplicate {
+            // Uses keep resolving to the first definition
+            self.ctx.message(ResolveError::Duplicate {
+                name: name.data,
+                kind,
+                first: self.resolutions.declarations[first].span,
+                second: name.span,
+            });
+            return;
+        }
+        if let Some(parent) = self.resolutions.scopes[scope].parent {
+            if let Some(shadowed) = self.lookup(parent, name.data) {
+                self.resolutions.shadows.insert(id, shadowed);
+            }
+        }
+        self.resolutions.scopes[scope].declarations.push(id);
+    }
+
+    fn declare_pattern(&mut self, pattern: &Pattern, kind: DeclarationKind) {
+        match &pattern.kind {
+            PatternKind::Wildcard | PatternKind::Literal(_) | PatternKind::Range { .. } => {}
+            &PatternKind::Binding(name) => match Variant::from_name(name) {
+                Some(variant) => {
+                    self.resolutions.variants.insert(pattern.id, variant);
+                }
+                None => self.declare(
+                    pattern.id,
+                    Spanned {
+                        span: pattern.span,
+                        data: name,
+                    },
+                    kind,
+                ),
+            },
+            PatternKind::Tuple(elements) => {
+                for element in elements {
+                    self.declare_pattern(element, kind);
+                }
+            }
+            PatternKind::Variant { name, fields } => {
+                match Variant::from_name(name.data) {
+                    Some(variant) => {
+                        self.resolutions.variants.insert(pattern.id, variant);
+                    }
+                    None => {
+                        self.ctx.message(ResolveError::UnresolvedName {
+                            name: *name,
+                            similar: None,
+                        });
+                    }
+                }
+                for field in fields {
+                    self.declare_pattern(field, kind);
+                }
+            }
+        }
+    }
+
+    /// The visible name closest to `name` in spelling, if any is close enough to be a typo.
+    /// Replacing every character doesn't count, or a one-letter name would match any other.
+    fn similar(&self, name: Identifier) -> Option<Similar> {
+        let name = name.symbol().as_str();
+        let len = name.chars().count();
+        let max_distance = (len.max(3) / 3).min(len.saturating_sub(1));
+        let mut best: Option<(usize, &Declaration)> = None;
+        let mut scope = Some(self.scope);
+        while let Some(id) = scope {
+            for &declaration in self.resolutions.scopes[id].declarations.iter().rev() {
+                let declaration = &self.resolutions.declarations[declaration];
+                let distance = edit_distance(name, declaration.name.symbol().as_str());
+                // Closer scopes win ties
+                if distance <= max_distance && best.is_none_or(|(best, _)| distance < best) {
+                    best = Some((distance, declaration));
+                }
+            }
+            scope = self.resolutions.scopes[id].parent;
+        }
+        best.map(|(_, declaration)| Similar {
+            name: Spanned {
+                span: declaration.span,
+                data: declaration.name,
+            },
+            kind: declaration.kind,
+        })
+    }
+}
+
+impl<'ast, X: Context<C>, C: ContextName> Visitor<'ast> for Resolver<'_, X, C> {
+    fn visit_item(&mut self, ast: &'ast Ast, id: ItemId) {
+        match &ast[id].kind {
+            ItemKind::Function {
+                arguments, body, ..
+            } => self.in_scope(ScopeKind::Function(id), |this| {
+                for param in arguments {
+                    this.declare(param.id, param.name, DeclarationKind::Param);
+                }
+                this.visit_block(ast, body);
+            }),
+        }
+    }
+
+    fn visit_block(&mut self, ast: &'ast Ast, block: &'ast Block) {
+        self.in_scope(ScopeKind::Block(block.id), |this| {
+            for &statement in &block.statements {
+                this.visit_statement(ast, statement);
+            }
+        });
+    }
+
+    fn visit_statement(&mut self, ast: &'ast Ast, id: StmtId) {
+        match &ast[id].kind {
+            StatementKind::Let { pattern, value, .. } => {
+                if let Some(value) = value {
+                    self.visit_expression(ast, *value);
+                }
+                self.push_scope(ScopeKind::Let(id));
+                self.declare_pattern(pattern, DeclarationKind::Local);
+            }
+            StatementKind::Expr(expr) | StatementKind::ReturnExpr(expr) => {
+                self.visit_expression(ast, *expr)
+            }
+        }
+    }
+
+    fn visit_expression(&mut self, ast: &'ast Ast, id: ExprId) {
+        let name = match &ast[id].kind {
+            &ExpressionKind::Name(name) => name,
+            ExpressionKind::Closure { params, body } => {
+                // Parameter types can't refer to bindings, so there's nothing to resolve in them
+                return self.in_scope(ScopeKind::Closure(ast[id].id), |this| {
+                    for param in params {
+                        this.declare_pattern(&param.pattern, DeclarationKind::Param);
+                    }
+                    this.visit_expression(ast, *body);
+                });
+            }
+            ExpressionKind::For {
+                pattern,
+                iterable,
+                body,
+            } => {
+                self.visit_expression(ast, *iterable);
+                return self.in_scope(ScopeKind::For(ast[id].id), |this| {
+                    this.declare_pattern(pattern, DeclarationKind::Local);
+                    this.visit_block(ast, body);
+                });
+            }
+            ExpressionKind::Match { scrutinee, arms } => {
+                self.visit_expression(ast, *scrutinee);
+                for arm in arms {
+                    self.in_scope(ScopeKind::Arm(arm.pattern.id), |this| {
+                        this.declare_pattern(&arm.pattern, DeclarationKind::Local);
+                        this.visit_expression(ast, arm.body);
+                    });
+                }
+                return;
+            }
+            _ => return walk_expression(self, ast, id),
+        };
+        match (
+            self.lookup(self.scope, name.data),
+            Variant::from_name(name.data),
+        ) {
+            (Some(declaration), _) => {
+                self.resolutions.uses.insert(ast[id].id, declaration);
+            }
+            (None, Some(variant)) => {
+                self.resolutions.variants.insert(ast[id].id, variant);
+            }
+            (None, None) => {
+                let similar = self.similar(name.data);
+                self.ctx
+                    .message(ResolveError::UnresolvedName { name, similar });
+            }
+        }
+    }
+}
+
+/// Edit distance between `a` and `b` in characters, where swapping two adjacent characters
+/// counts as one edit like inserting, removing or replacing one does
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Rows of the distance matrix for the prefixes of `a` of length `i - 2`, `i - 1` and `i`
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let substitution = previous[j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = substitution.min(previous[j] + 1).min(current[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(before[j - 2] + 1);
+            }
+            current[j] = distance;
+        }
+        std::mem::swap(&mut before, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use miette::Diagnostic;
+
+    use crate::{
+        ast::{Ast, ExpressionKind, ItemId, StatementKind},
+        error::CoralError,
+        span::Span,
+        testing::Source,
+    };
+
+    use super::{edit_distance, DeclarationKind, Resolutions, ResolveError};
+
+    fn resolve_source(source: &str) -> (Ast, Vec<ItemId>, Resolutions, Vec<ResolveError>) {
+        let mut source = Source::parse(source);
+        let resolutions = source.resolve();
+        let errors = source.errors(|err| match err {
+            CoralError::ResolveError(err) => Some(err),
+            _ => None,
+        });
+        (source.ast, source.items, resolutions, errors)
+    }
+
+    /// The text each name expression in the file resolves to, as `name@declaration start`
+    fn uses(ast: &Ast, resolutions: &Resolutions) -> Vec<String> {
+        let mut uses: Vec<_> = ast
+            .expressions
+            .iter()
+            .filter_map(|(_, expression)| match expression.kind {
+                ExpressionKind::Name(name) => {
+                    Some((expression.span.start(), name.data, expression.id))
+                }
+                _ => None,
+            })
+            .map(|(start, name, id)| {
+                let declaration = resolutions
+                    .declaration_of(id)
+                    .map_or("?".to_string(), |(_, declaration)| {
+                        declaration.span.start().to_string()
+                    });
+                (start, format!("{name}@{declaration}"))
+            })
+            .collect();
+        uses.sort();
+        uses.into_iter().map(|(_, text)| text).collect()
+    }
+
+    #[test]
+    fn resolves_through_scopes() {
+        let source = "fn f(x: i32): i32 {\n    let y = x;\n    let x = (x, y);\n    { let y = g; y }\n    x\n}\nfn g(): void {}";
+        let (ast, _, resolutions, errors) = resolve_source(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        // The second `x` is bound after its value, so the value still sees the parameter
+        assert_eq!(
+            uses(&ast, &resolutions),
+            ["x@5", "x@5", "y@28", "g@87", "y@65", "x@43"]
+        );
+        let shadowed: Vec<_> = resolutions
+            .shadows
+            .iter()
+            .map(|(new, old)| (ast.span(new).start(), ast.span(*old).start()))
+            .collect();
+        assert_eq!(shadowed, [(43, 5), (65, 28)]);
+    }
+
+    #[test]
+    fn functions_are_visible_before_their_definition() {
+        let (ast, items, resolutions, errors) =
+            resolve_source("fn a(): void { b; }\nfn b(): void { a; }");
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(uses(&ast, &resolutions), ["b@23", "a@3"]);
+        let (_, declaration) = resolutions
+            .declaration_of(ast.expressions.iter().next().unwrap().1.id)
+            .unwrap();
+        assert_eq!(declaration.kind, DeclarationKind::Function(items[1]));
+    }
+
+    #[test]
+    fn suggests_similar_names() {
+        let (ast, _, resolutions, errors) = resolve_source(
+            "fn f(count: i32): void {\n    let total = 1;\n    totl + cuont + zzz;\n}",
+        );
+        assert_eq!(uses(&ast, &resolutions), ["totl@?", "cuont@?", "zzz@?"]);
+        let similar: Vec<_> = errors
+            .iter()
+            .map(|err| match err {
+                ResolveError::UnresolvedName { similar, .. } => {
+                    similar.map(|similar| similar.name.data.to_string())
+                }
+                ResolveError::Duplicate { .. } => panic!("unexpected {err:?}"),
+            })
+            .collect();
+        assert_eq!(
+            similar,
+            [Some("total".to_string()), Some("count".to_string()), None]
+        );
+        assert_eq!(errors[0].suggestions()[0].replacement, "total");
+        assert_eq!(
+            errors[1].help().unwrap().to_string(),
+            "a parameter with a similar name exists: `count`"
+        );
+    }
+
+    #[test]
+    fn similar_names_keep_their_kind() {
+        let (_, _, _, errors) = resolve_source(
+            "fn helper(): void {}
+fn f(x: i32): void { let y = 1; helpr(); z; }",
+        );
+        let help: Vec<_> = errors
+            .iter()
+            .map(|err| err.help().map(|help| help.to_string()))
+            .collect();
+        // A one-letter name is one edit away from any other, which isn't a typo
+        assert_eq!(
+            help,
+            [
+                Some("a function with a similar name exists: `helper`".to_string()),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_duplicates() {
+        let source = "fn f(a: i32, a: i32): void { let (b, b) = a; }\nfn f(): void {}";
+        let (_, _, _, errors) = resolve_source(source);
+        let duplicates: Vec<_> = errors
+            .iter()
+            .map(|err| match err {
+                ResolveError::Duplicate {
+                    name,
+                    kind,
+                    first,
+                    second,
+                } => (
+                    name.to_string(),
+                    kind.to_string(),
+                    first.start(),
+                    second.start(),
+                ),
+                ResolveError::UnresolvedName { .. } => panic!("unexpected {err:?}"),
+            })
+            .collect();
+        assert_eq!(
+            duplicates,
+            [
+                ("f".to_string(), "function".to_string(), 3, 50),
+                ("a".to_string(), "parameter".to_string(), 5, 13),
+                ("b".to_string(), "binding".to_string(), 34, 37),
+            ]
+        );
+        assert_eq!(errors[1].span(), Span::new(errors[1].span().file(), 13..14));
+    }
+
+    #[test]
+    fn let_value_sees_the_outer_binding() {
+        let (ast, _, resolutions, errors) = resolve_source("fn f(): void { let x = x; }");
+        assert_eq!(uses(&ast, &resolutions), ["x@?"]);
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            ast.statements.iter().next().unwrap().1.kind,
+            StatementKind::Let { .. }
+        ));
+    }
+
+    #[test]
+    fn closure_parameters_are_scoped_to_the_body() {
+        let (ast, _, resolutions, errors) =
+            resolve_source("fn f(x: i32): void { let g = |x, y| x + y; x; y; }");
+        assert_eq!(uses(&ast, &resolutions), ["x@30", "y@33", "x@5", "y@?"]);
+        assert_eq!(errors.len(), 1);
+        let (_, inner_x) = ast
+            .expressions
+            .iter()
+            .find(|(_, expression)| expression.span.start() == 36)
+            .unwrap();
+        let (_, declaration) = resolutions.declaration_of(inner_x.id).unwrap();
+        assert_eq!(declaration.kind, DeclarationKind::Param);
+        // The closure's `x` hides the function's
+        assert_eq!(resolutions.shadows.len(), 1);
+    }
+
+    #[test]
+    fn match_arms_bind_in_their_body_and_variants_are_not_names() {
+        let (ast, _, resolutions, errors) = resolve_source(
+            "fn f(x: Option<i32>): i32 { match x { Some(y) => y, None => Ok(x), Nope(z) => z } }",
+        );
+        assert_eq!(
+            uses(&ast, &resolutions),
+            ["x@5", "y@43", "Ok@?", "x@5", "z@72"]
+        );
+        // `Some`, `None` and `Ok`, but not `Nope`
+        assert_eq!(resolutions.variants.iter().count(), 3);
+        let [ResolveError::UnresolvedName {
+            name,
+            similar: None,
+        }] = &errors[..]
+        else {
+            panic!("expected `Nope` to be unresolved, got {errors:?}")
+        };
+        assert_eq!(name.span, Span::new(name.span.file(), 67..71));
+    }
+
+    #[test]
+    fn distances() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("total", "totl"), 1);
+        assert_eq!(edit_distance("éa", "ea"), 1);
+        assert_eq!(edit_distance("cuont", "count"), 1);
+    }
+}
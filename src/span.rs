@@ -1,9 +1,12 @@
 use miette::{SourceCode, SourceSpan};
 use std::ops::Range;
 
-use crate::{fs::FileId, FileArena};
+use crate::{
+    fs::{ColumnMode, FileId, LineCol},
+    FileArena,
+};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Span {
     file: FileId,
     start: usize,
@@ -18,19 +21,39 @@ impl Span {
             end: range.end,
         }
     }
-    const fn range(&self) -> Range<usize> {
+    pub const fn range(&self) -> Range<usize> {
         self.start..self.end
     }
+
+    pub const fn file(&self) -> FileId {
+        self.file
+    }
+
+    pub const fn start(&self) -> usize {
+        self.start
+    }
+
+    pub const fn end(&self) -> usize {
+        self.end
+    }
     pub fn as_miette_span<'a>(
         &self,
         arena: &'a FileArena,
         lines_before: usize,
         lines_after: usize,
     ) -> Result<Box<dyn miette::SpanContents<'a> + 'a>, miette::MietteError> {
-        let source_span: SourceSpan = self.range().into();
+        let source_span: SourceSpan = (*self).into();
         arena[self.file].read_span(&source_span, lines_before, lines_after)
     }
 
+    pub fn start_position(&self, arena: &FileArena, mode: ColumnMode) -> LineCol {
+        arena[self.file].offset_to_line_col(self.start, mode)
+    }
+
+    pub fn end_position(&self, arena: &FileArena, mode: ColumnMode) -> LineCol {
+        arena[self.file].offset_to_line_col(self.end, mode)
+    }
+
     pub fn get_slice<'a>(&self, arena: &'a FileArena) -> &'a str {
         &arena[self.file].contents[self.range()]
     }
@@ -57,6 +80,12 @@ impl Span {
     }
 }
 
+impl From<Span> for SourceSpan {
+    fn from(value: Span) -> Self {
+        value.range().into()
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Spanned<T> {
     pub span: Span,
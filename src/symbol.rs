@@ -0,0 +1,152 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+/// An interned string.
+/// Comparing two symbols is a `u32` comparison, and they can be stored
+/// without borrowing from the source file they came from.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+struct Interner {
+    names: HashMap<&'static str, Symbol>,
+    strings: Vec<&'static str>,
+}
+
+impl Interner {
+    fn prefilled() -> Self {
+        let mut interner = Self {
+            names: HashMap::with_capacity(PREDEFINED.len()),
+            strings: Vec::with_capacity(PREDEFINED.len()),
+        };
+        for name in PREDEFINED {
+            interner.intern(name);
+        }
+        interner
+    }
+
+    fn intern(&mut self, string: &str) -> Symbol {
+        if let Some(&symbol) = self.names.get(string) {
+            return symbol;
+        }
+        let symbol = Symbol(self.strings.len() as u32);
+        // Symbols live for the whole compilation, so leaking is fine
+        let string: &'static str = Box::leak(string.to_owned().into_boxed_str());
+        self.strings.push(string);
+        self.names.insert(string, symbol);
+        symbol
+    }
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(|| Mutex::new(Interner::prefilled()))
+}
+
+impl Symbol {
+    pub fn intern(string: &str) -> Self {
+        interner().lock().unwrap().intern(string)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        interner().lock().unwrap().strings[self.0 as usize]
+    }
+
+    pub const fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(value: &str) -> Self {
+        Self::intern(value)
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+macro_rules! predefined {
+    ($($name:ident: $string:literal),* $(,)?) => {
+        const PREDEFINED: &[&str] = &[$($string),*];
+
+        /// Symbols that are always interned, in the order of [`PREDEFINED`],
+        /// so they can be matched on without locking the interner.
+        pub mod sym {
+            use super::Symbol;
+            predefined!(@consts 0u32, $($name,)*);
+        }
+    };
+    (@consts $index:expr, $name:ident, $($rest:ident,)*) => {
+        // Named like the text, so `sym::u8` reads as the `u8` it is
+        #[allow(non_upper_case_globals)]
+        pub const $name: Symbol = Symbol($index);
+        predefined!(@consts $index + 1u32, $($rest,)*);
+    };
+    (@consts $index:expr,) => {};
+}
+
+predefined! {
+    underscore: "_",
+    main: "main",
+    void: "void",
+    bool: "bool",
+    i8: "i8",
+    i16: "i16",
+    i32: "i32",
+    i64: "i64",
+    i128: "i128",
+    isize: "isize",
+    u8: "u8",
+    u16: "u16",
+    u32: "u32",
+    u64: "u64",
+    u128: "u128",
+    usize: "usize",
+    f32: "f32",
+    f64: "f64",
+    char: "char",
+    str: "str",
+    Option: "Option",
+    Result: "Result",
+    Some: "Some",
+    None: "None",
+    Ok: "Ok",
+    Err: "Err",
+    line: "line",
+    column: "column",
+    file: "file",
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sym, Symbol};
+
+    #[test]
+    fn interning() {
+        let a = Symbol::intern("interning_test_a");
+        let b = Symbol::intern("interning_test_b");
+        assert_ne!(a, b);
+        assert_eq!(a, Symbol::intern("interning_test_a"));
+        assert_eq!(a.as_str(), "interning_test_a");
+        assert_eq!(b.to_string(), "interning_test_b");
+    }
+
+    #[test]
+    fn predefined() {
+        assert_eq!(Symbol::intern("_"), sym::underscore);
+        assert_eq!(Symbol::intern("main"), sym::main);
+        assert_eq!(Symbol::intern("str"), sym::str);
+        assert_eq!(sym::i128.as_str(), "i128");
+    }
+}
@@ -0,0 +1,78 @@
+//! The front of the pipeline, for the tests of the passes along it
+
+use id_arena::Arena;
+
+use crate::{
+    ast::{Ast, ItemId},
+    error::{CollectingContext, CoralError},
+    fs::File,
+    hir::{self, Hir},
+    lexer::{tokens::Tokens, Token},
+    parser::{parse_file, ContextName},
+    resolve::{resolve, Resolutions},
+    transaction::ParserState,
+    typeck::{typeck, TypeckResults},
+    FileArena,
+};
+
+/// A source file named `test` once it's parsed, with what the passes reported so far
+pub struct Source {
+    pub text: String,
+    pub files: FileArena,
+    pub ctx: CollectingContext<ContextName>,
+    pub ast: Ast,
+    pub items: Vec<ItemId>,
+}
+
+impl Source {
+    /// Parses `text`, which has to succeed
+    pub fn parse(text: &str) -> Self {
+        let mut files: FileArena = Arena::new();
+        let file = files.alloc(File::new("test", text.to_string()));
+        let mut state = ParserState::new(
+            CollectingContext::<ContextName>::new(),
+            Tokens::from(Token::lexer_from_file(&files, file)),
+        );
+        let items = parse_file(&mut state);
+        let (ctx, _, ast) = state.into_parts();
+        assert_eq!(ctx.error_count(), 0, "{text:?} should parse");
+        Self {
+            text: text.to_string(),
+            files,
+            ctx,
+            ast,
+            items,
+        }
+    }
+
+    pub fn resolve(&mut self) -> Resolutions {
+        resolve(&mut self.ctx, &self.ast, &self.items)
+    }
+
+    /// Resolves the names, which has to succeed, then checks the types
+    pub fn typeck(&mut self) -> (Resolutions, TypeckResults) {
+        let resolutions = self.resolve();
+        assert_eq!(self.ctx.error_count(), 0, "{:?} should resolve", self.text);
+        let results = typeck(&mut self.ctx, &self.ast, &self.items, &resolutions);
+        (resolutions, results)
+    }
+
+    /// Checks the file, which has to succeed, then lowers it
+    pub fn lower(&mut self) -> Hir {
+        let (resolutions, results) = self.typeck();
+        assert_eq!(self.ctx.error_count(), 0, "{:?} should check", self.text);
+        hir::lower(&self.ast, &self.items, &resolutions, &results, &self.files)
+    }
+
+    /// The diagnostics reported so far, which all have to be errors `pass` picks out
+    pub fn errors<E: Clone>(&self, pass: impl Fn(&CoralError) -> Option<&E>) -> Vec<E> {
+        self.ctx
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| match pass(&diagnostic.error) {
+                Some(err) => err.clone(),
+                None => panic!("unexpected {:?}", diagnostic.error),
+            })
+            .collect()
+    }
+}
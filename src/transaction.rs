@@ -1,6 +1,7 @@
 use logos::Logos;
 
 use crate::{
+    ast::Ast,
     error::{Context, ContextName},
     lexer::tokens::{RefData, TokenReader},
 };
@@ -17,23 +18,48 @@ pub trait Transaction: Transactionable {
     fn discard(self);
 }
 
-pub struct ParserState<C, T> {
+pub struct ParserState<C, T, A = Ast> {
     ctx: C,
     tokens: T,
+    ast: A,
 }
 
 impl<C, T> ParserState<C, T> {
-    pub const fn new(ctx: C, tokens: T) -> Self {
-        Self { ctx, tokens }
+    pub fn new(ctx: C, tokens: T) -> Self {
+        Self::with_ast(ctx, tokens, Ast::new())
     }
 }
 
-impl<C, T> Transactionable for ParserState<C, T>
+impl<C, T, A> ParserState<C, T, A> {
+    /// Parses into an existing [`Ast`], so nodes from several files share one id space
+    pub const fn with_ast(ctx: C, tokens: T, ast: A) -> Self {
+        Self { ctx, tokens, ast }
+    }
+
+    pub const fn ast(&self) -> &A {
+        &self.ast
+    }
+
+    pub fn ast_mut(&mut self) -> &mut A {
+        &mut self.ast
+    }
+
+    pub fn into_ast(self) -> A {
+        self.ast
+    }
+
+    pub fn into_parts(self) -> (C, T, A) {
+        (self.ctx, self.tokens, self.ast)
+    }
+}
+
+impl<C, T, A> Transactionable for ParserState<C, T, A>
 where
     C: Transactionable,
     T: Transactionable,
 {
-    type Transaction<'t> = ParserState<C::Transaction<'t>, T::Transaction<'t>>
+    type Transaction<'t>
+        = ParserState<C::Transaction<'t>, T::Transaction<'t>, &'t mut A>
     where
         Self: 't;
 
@@ -41,11 +67,12 @@ where
         ParserState {
             ctx: self.ctx.transaction(),
             tokens: self.tokens.transaction(),
+            ast: &mut self.ast,
         }
     }
 }
 
-impl<C, T> Transaction for ParserState<C, T>
+impl<C, T, A> Transaction for ParserState<C, T, A>
 where
     C: Transaction,
     T: Transaction,
@@ -61,7 +88,7 @@ where
     }
 }
 
-impl<C, T, CName: ContextName> Context<CName> for ParserState<C, T>
+impl<C, T, A, CName: ContextName> Context<CName> for ParserState<C, T, A>
 where
     C: Context<CName>,
 {
@@ -87,7 +114,7 @@ where
     }
 }
 
-impl<'source, C, T, Token> TokenReader<'source, Token> for ParserState<C, T>
+impl<'source, C, T, A, Token> TokenReader<'source, Token> for ParserState<C, T, A>
 where
     T: TokenReader<'source, Token>,
     Token: Logos<'source>,
@@ -100,6 +127,14 @@ where
         self.tokens.get_current_span()
     }
 
+    fn previous_span(&self) -> Option<crate::span::Span> {
+        self.tokens.previous_span()
+    }
+
+    fn eoi_span(&self) -> crate::span::Span {
+        self.tokens.eoi_span()
+    }
+
     fn current(&self) -> Option<RefData<'_, 'source, Token>> {
         self.tokens.current()
     }
@@ -0,0 +1,2174 @@
+//! Type checking: works out the type of every expression and checks it against what its
+//! context requires, such as the declared type of a `let`, a parameter or a return type.
+//!
+//! Checking is bidirectional. Where the context knows what type it wants, that's passed down
+//! as an [`Expected`] along with the span of the type annotation it comes from, so literals can
+//! take it and mismatches can point at both ends. Elsewhere the type comes from the expression
+//! alone, which can leave parts of it to be inferred from later uses: after `let x = 1;`, `x`
+//! is some integer until it's used as one type or another, and `i32` if it never is. See
+//! [`infer`] for how that works.
+
+use std::marker::PhantomData;
+
+use miette::{Diagnostic, LabeledSpan};
+use thiserror::Error;
+
+use crate::{
+    ast::{
+        Ast, BinaryOp, Block, ClosureParam, ExprId, ExpressionKind, Identifier, ItemId, ItemKind,
+        LiteralPattern, MatchArm, NodeId, NodeMap, Path, Pattern, PatternKind, StatementKind, Type,
+        UnaryOp, Variant,
+    },
+    error::{snippet_labels, Context, ContextName},
+    resolve::{DeclarationKind, Resolutions},
+    span::{Span, Spanned},
+    symbol::{sym, Symbol},
+};
+
+pub mod infer;
+pub mod literals;
+pub mod ty;
+
+use self::{
+    infer::{InferTable, UnifyError},
+    ty::{FloatTy, InferTy, IntTy, Ty},
+};
+
+/// What the checker found out, keyed by [`crate::ast::NodeId`]
+#[derive(Debug, Default)]
+pub struct TypeckResults {
+    /// The type of every expression
+    pub expressions: NodeMap<Ty>,
+    /// The type of every declaration the resolver knows of: functions, parameters and the
+    /// bindings of `let` patterns
+    pub declarations: NodeMap<Ty>,
+    /// The type of the values every literal, range and variant pattern is matched against
+    pub patterns: NodeMap<Ty>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum TypeError {
+    #[error("mismatched types: expected `{expected}`, found `{found}`")]
+    Mismatch {
+        expected: Ty,
+        found: Ty,
+        span: Span,
+        /// The annotation the expected type comes from, if it's written anywhere
+        expected_span: Option<Span>,
+    },
+    #[error("cannot find type `{}`", .0.data)]
+    UnknownType(Spanned<Identifier>),
+    #[error("`{name}` takes {expected} type argument{}, but {found} were given", plural(*.expected))]
+    TypeArgumentCount {
+        name: Identifier,
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    #[error("cannot apply `{op}` to `{lhs}` and `{rhs}`")]
+    InvalidOperands {
+        op: &'static str,
+        lhs: Ty,
+        rhs: Ty,
+        span: Span,
+    },
+    #[error("cannot apply unary `{}` to `{ty}`", .op.as_str())]
+    InvalidOperand { op: UnaryOp, ty: Ty, span: Span },
+    #[error("this function takes {expected} argument{}, but {found} were given", plural(*.expected))]
+    WrongArgumentCount {
+        expected: usize,
+        found: usize,
+        span: Span,
+        /// The name of the function being called
+        definition: Option<Span>,
+    },
+    #[error("expected a function, found `{ty}`")]
+    NotCallable { ty: Ty, span: Span },
+    #[error("cannot cast `{from}` as `{to}`")]
+    InvalidCast { from: Ty, to: Ty, span: Span },
+    #[error("no field `{}` on type `{ty}`", .field.data)]
+    NoField { ty: Ty, field: Spanned<u32> },
+    #[error("cannot index into a value of type `{ty}`")]
+    NotIndexable { ty: Ty, span: Span },
+    #[error("the `?` operator can only be applied to `Option` or `Result`, not `{ty}`")]
+    TryOperand { ty: Ty, span: Span },
+    #[error("the `?` operator on `{ty}` can't be used in a function that returns `{return_ty}`")]
+    TryReturn {
+        ty: Ty,
+        return_ty: Ty,
+        span: Span,
+        return_span: Span,
+    },
+    #[error("cannot find `{path}` in this scope")]
+    UnresolvedPath { path: Path, span: Span },
+    #[error("unknown intrinsic `#{}`", .0.data)]
+    UnknownIntrinsic(Spanned<Identifier>),
+    #[error("the length of an array repeat expression must be an integer literal")]
+    RepeatCount(Span),
+    #[error("cannot infer an infinite type, `_` would have to be `{ty}`")]
+    InfiniteType { ty: Ty, span: Span },
+    #[error("type annotations needed")]
+    CannotInfer {
+        /// As far as it could be inferred, with `_` for the unknown parts
+        ty: Ty,
+        /// The binding whose type is unknown, or the expression if it isn't a binding's
+        span: Span,
+        binding: Option<Identifier>,
+        /// Where the type had to be known already
+        used: Option<Span>,
+    },
+    #[error("literal out of range for `{}`", .ty.as_str())]
+    LiteralOutOfRange {
+        ty: IntTy,
+        /// As written, with the `-` if it's negated
+        literal: String,
+        span: Span,
+    },
+    #[error("`{keyword}` outside of a loop")]
+    OutsideLoop { keyword: &'static str, span: Span },
+    #[error("invalid left-hand side of assignment")]
+    InvalidAssignment(Span),
+    #[error("`{ty}` is not iterable")]
+    NotIterable { ty: Ty, span: Span },
+    #[error(
+        "this pattern has {found} field{}, but `{}` has {expected}",
+        plural(*.found),
+        .variant.as_str()
+    )]
+    VariantFields {
+        variant: Variant,
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+}
+
+const fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+impl TypeError {
+    pub const fn span(&self) -> Span {
+        match self {
+            Self::Mismatch { span, .. }
+            | Self::TypeArgumentCount { span, .. }
+            | Self::InvalidOperands { span, .. }
+            | Self::InvalidOperand { span, .. }
+            | Self::WrongArgumentCount { span, .. }
+            | Self::NotCallable { span, .. }
+            | Self::InvalidCast { span, .. }
+            | Self::NotIndexable { span, .. }
+            | Self::TryOperand { span, .. }
+            | Self::TryReturn { span, .. }
+            | Self::RepeatCount(span)
+            | Self::InfiniteType { span, .. }
+            | Self::CannotInfer { span, .. }
+            | Self::LiteralOutOfRange { span, .. }
+            | Self::OutsideLoop { span, .. }
+            | Self::InvalidAssignment(span)
+            | Self::NotIterable { span, .. }
+            | Self::VariantFields { span, .. }
+            | Self::UnresolvedPath { span, .. } => *span,
+            Self::UnknownType(name) | Self::UnknownIntrinsic(name) => name.span,
+            Self::NoField { field, .. } => field.span,
+        }
+    }
+
+    /// Code of the error, registered with its explanation in [`crate::error::codes`]
+    pub const fn error_code(&self) -> &'static str {
+        match self {
+            Self::Mismatch { .. } => "E0011",
+            Self::UnknownType(_) => "E0012",
+            Self::TypeArgumentCount { .. } => "E0013",
+            Self::InvalidOperands { .. } | Self::InvalidOperand { .. } => "E0014",
+            Self::WrongArgumentCount { .. } => "E0015",
+            Self::NotCallable { .. } => "E0016",
+            Self::InvalidCast { .. } => "E0017",
+            Self::NoField { .. } => "E0018",
+            Self::NotIndexable { .. } => "E0019",
+            Self::TryOperand { .. } | Self::TryReturn { .. } => "E0020",
+            Self::UnknownIntrinsic(_) => "E0021",
+            Self::RepeatCount(_) => "E0022",
+            Self::InfiniteType { .. } => "E0023",
+            Self::CannotInfer { .. } => "E0024",
+            Self::LiteralOutOfRange { .. } => "E0025",
+            Self::OutsideLoop { .. } => "E0026",
+            Self::InvalidAssignment(_) => "E0027",
+            Self::NotIterable { .. } => "E0028",
+            Self::VariantFields { .. } => "E0032",
+            Self::UnresolvedPath { .. } => "E0009",
+        }
+    }
+
+    /// The error with `f` applied to every type in it
+    fn map_types(self, f: impl Fn(&Ty) -> Ty) -> Self {
+        match self {
+            Self::Mismatch {
+                expected,
+                found,
+                span,
+                expected_span,
+            } => Self::Mismatch {
+                expected: f(&expected),
+                found: f(&found),
+                span,
+                expected_span,
+            },
+            Self::InvalidOperands { op, lhs, rhs, span } => Self::InvalidOperands {
+                op,
+                lhs: f(&lhs),
+                rhs: f(&rhs),
+                span,
+            },
+            Self::InvalidOperand { op, ty, span } => Self::InvalidOperand {
+                op,
+                ty: f(&ty),
+                span,
+            },
+            Self::NotCallable { ty, span } => Self::NotCallable { ty: f(&ty), span },
+            Self::InvalidCast { from, to, span } => Self::InvalidCast {
+                from: f(&from),
+                to: f(&to),
+                span,
+            },
+            Self::NoField { ty, field } => Self::NoField { ty: f(&ty), field },
+            Self::NotIndexable { ty, span } => Self::NotIndexable { ty: f(&ty), span },
+            Self::TryOperand { ty, span } => Self::TryOperand { ty: f(&ty), span },
+            Self::TryReturn {
+                ty,
+                return_ty,
+                span,
+                return_span,
+            } => Self::TryReturn {
+                ty: f(&ty),
+                return_ty: f(&return_ty),
+                span,
+                return_span,
+            },
+            Self::InfiniteType { ty, span } => Self::InfiniteType { ty: f(&ty), span },
+            Self::NotIterable { ty, span } => Self::NotIterable { ty: f(&ty), span },
+            Self::CannotInfer {
+                ty,
+                span,
+                binding,
+                used,
+            } => Self::CannotInfer {
+                ty: f(&ty),
+                span,
+                binding,
+                used,
+            },
+            err @ (Self::UnknownType(_)
+            | Self::TypeArgumentCount { .. }
+            | Self::WrongArgumentCount { .. }
+            | Self::UnknownIntrinsic(_)
+            | Self::RepeatCount(_)
+            | Self::LiteralOutOfRange { .. }
+            | Self::OutsideLoop { .. }
+            | Self::InvalidAssignment(_)
+            | Self::VariantFields { .. }
+            | Self::UnresolvedPath { .. }) => err,
+        }
+    }
+}
+
+impl Diagnostic for TypeError {
+    fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        Some(Box::new(self.error_code()))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        let help = match self {
+            Self::Mismatch {
+                expected, found, ..
+            } if expected.is_numeric()
+                && found.is_numeric()
+                && !matches!(expected, Ty::Infer(_)) =>
+            {
+                format!("numbers aren't converted implicitly, use `as {expected}` to convert")
+            }
+            Self::UnknownType(_) => "the primitive types are `void`, `bool`, `char`, `str`, \
+                the integers `i8` to `i128`, `isize`, `u8` to `u128` and `usize`, \
+                and `f32` and `f64`"
+                .to_string(),
+            Self::InvalidOperands { op, .. } => match *op {
+                "&&" | "||" => "both operands must be `bool`",
+                "==" | "!=" => "both operands must have the same type",
+                "<" | ">" | "<=" | ">=" | ".." | "..=" => {
+                    "both operands must be numbers or characters of the same type"
+                }
+                _ => "both operands must be numbers of the same type",
+            }
+            .to_string(),
+            Self::InvalidOperand { op, .. } => match op {
+                UnaryOp::Neg => "only signed integers and floats can be negated",
+                UnaryOp::Not => "`!` applies to `bool` and integers",
+                UnaryOp::Deref => "only references can be dereferenced",
+            }
+            .to_string(),
+            Self::InvalidCast { .. } => "`as` converts between numbers, from `bool` and `char` \
+                to integers, and from `u8` to `char`"
+                .to_string(),
+            Self::RepeatCount(_) => "write out the number of elements, as in `[0; 4]`".to_string(),
+            Self::InfiniteType { .. } => "the value is used as a part of itself".to_string(),
+            Self::CannotInfer {
+                ty: Ty::Infer(_) | Ty::Error,
+                ..
+            } => return None,
+            Self::CannotInfer { ty, .. } => {
+                format!("the type is `{ty}`, where `_` is what couldn't be inferred")
+            }
+            Self::LiteralOutOfRange { ty, .. } => format!(
+                "`{}` holds values from `{}` to `{}`",
+                ty.as_str(),
+                ty.min(),
+                ty.max()
+            ),
+            Self::InvalidAssignment(_) => "only bindings, indexing, tuple fields and \
+                dereferences can be assigned to"
+                .to_string(),
+            Self::NotIterable { .. } => {
+                "`for` loops over ranges of integers and characters, and arrays".to_string()
+            }
+            Self::VariantFields { variant, .. } if variant.has_value() => {
+                format!("match the value with `{}(_)`", variant.as_str())
+            }
+            Self::VariantFields { .. } => "`None` holds no value, write it on its own".to_string(),
+            _ => return None,
+        };
+        Some(Box::new(help))
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        Some(snippet_labels(self.labelled_spans()))
+    }
+}
+
+impl TypeError {
+    /// The labels with the spans they point at, primary first
+    pub fn labelled_spans(&self) -> Vec<(Span, String)> {
+        let (primary, secondary) = match self {
+            Self::Mismatch {
+                expected,
+                found,
+                expected_span,
+                ..
+            } => (
+                format!("expected `{expected}`, found `{found}`"),
+                expected_span.map(|span| ("expected because of this".to_string(), span)),
+            ),
+            Self::UnknownType(_) => ("not a known type".to_string(), None),
+            Self::TypeArgumentCount { expected, .. } => (
+                format!("expected {expected} type argument{}", plural(*expected)),
+                None,
+            ),
+            Self::InvalidOperands { lhs, rhs, .. } => (format!("`{lhs}` and `{rhs}`"), None),
+            Self::InvalidOperand { ty, .. } => (format!("this is `{ty}`"), None),
+            Self::WrongArgumentCount {
+                expected,
+                definition,
+                ..
+            } => (
+                format!("expected {expected} argument{}", plural(*expected)),
+                definition.map(|span| ("function defined here".to_string(), span)),
+            ),
+            Self::NotCallable { ty, .. } => (format!("this is `{ty}`"), None),
+            Self::InvalidCast { to, .. } => (format!("can't be cast to `{to}`"), None),
+            Self::NoField { ty, .. } => (
+                match ty {
+                    Ty::Tuple(elements) => format!(
+                        "the tuple only has {} field{}",
+                        elements.len(),
+                        plural(elements.len())
+                    ),
+                    _ => "unknown field".to_string(),
+                },
+                None,
+            ),
+            Self::NotIndexable { ty, .. } | Self::TryOperand { ty, .. } => {
+                (format!("this is `{ty}`"), None)
+            }
+            Self::TryReturn {
+                return_ty,
+                return_span,
+                ..
+            } => (
+                "the error can't be returned from here".to_string(),
+                Some((format!("the function returns `{return_ty}`"), *return_span)),
+            ),
+            Self::UnresolvedPath { .. } => (
+                "there are no modules, so paths can't refer to anything".to_string(),
+                None,
+            ),
+            Self::UnknownIntrinsic(_) => (
+                "the intrinsics are `#line`, `#column` and `#file`".to_string(),
+                None,
+            ),
+            Self::RepeatCount(_) => ("not an integer literal".to_string(), None),
+            Self::InfiniteType { .. } => ("this would make the type infinite".to_string(), None),
+            Self::CannotInfer { binding, used, .. } => (
+                match binding {
+                    Some(name) => format!("consider giving `{name}` a type"),
+                    None => "cannot infer the type of this".to_string(),
+                },
+                used.map(|span| ("the type must be known at this point".to_string(), span)),
+            ),
+            Self::LiteralOutOfRange { ty, literal, .. } => (
+                format!("`{literal}` doesn't fit in `{}`", ty.as_str()),
+                None,
+            ),
+            Self::OutsideLoop { keyword, .. } => {
+                (format!("cannot `{keyword}` outside of a loop"), None)
+            }
+            Self::InvalidAssignment(_) => ("cannot assign to this".to_string(), None),
+            Self::NotIterable { ty, .. } => (format!("this is `{ty}`"), None),
+            Self::VariantFields { expected, .. } => (
+                format!("expected {expected} field{}", plural(*expected)),
+                None,
+            ),
+        };
+        std::iter::once((self.span(), primary))
+            .chain(secondary.map(|(label, span)| (span, label)))
+            .collect()
+    }
+}
+
+/// Checks the functions in `items`, whose names were resolved into `resolutions`, reporting
+/// every type error to `ctx`
+pub fn typeck<C: ContextName>(
+    ctx: &mut impl Context<C>,
+    ast: &Ast,
+    items: &[ItemId],
+    resolutions: &Resolutions,
+) -> TypeckResults {
+    let mut checker = Checker {
+        ctx,
+        ast,
+        resolutions,
+        results: TypeckResults::default(),
+        return_ty: Expected::new(Ty::Void),
+        infer: InferTable::default(),
+        deferred: Vec::new(),
+        loops: Vec::new(),
+        bindings: Vec::new(),
+        pending: Vec::new(),
+        pending_patterns: Vec::new(),
+        _context: PhantomData,
+    };
+    // Signatures first, so calls can be checked no matter the order of the functions
+    for &item in items {
+        checker.declare_item(item);
+    }
+    for &item in items {
+        checker.check_item(item);
+    }
+    literals::check_literals(checker.ctx, ast, items, &checker.results);
+    checker.results
+}
+
+/// The type the context of an expression requires
+#[derive(Debug, Clone)]
+struct Expected {
+    ty: Ty,
+    /// The annotation that asks for `ty`, if there's one
+    span: Option<Span>,
+}
+
+impl Expected {
+    const fn new(ty: Ty) -> Self {
+        Self { ty, span: None }
+    }
+
+    const fn annotated(ty: Ty, span: Span) -> Self {
+        Self {
+            ty,
+            span: Some(span),
+        }
+    }
+
+    /// The expectation for a part of the value, which comes from the same annotation
+    fn part(&self, ty: &Ty) -> Self {
+        Self {
+            ty: ty.clone(),
+            span: self.span,
+        }
+    }
+}
+
+/// An operator check that has to wait until the type of the operands is known
+#[derive(Debug)]
+enum Deferred {
+    Binary {
+        op: &'static str,
+        lhs: Ty,
+        rhs: Ty,
+        span: Span,
+    },
+    Unary {
+        op: UnaryOp,
+        ty: Ty,
+        span: Span,
+    },
+}
+
+/// A loop being checked, which `break` and `continue` refer to
+struct Loop {
+    /// What a `break` gives the loop: the value of a `loop`, and nothing for the others
+    break_ty: Expected,
+    /// Whether a `break` was found yet, without which a `loop` never ends
+    broken: bool,
+}
+
+struct Checker<'c, 'a, X, C> {
+    ctx: &'c mut X,
+    ast: &'a Ast,
+    resolutions: &'a Resolutions,
+    results: TypeckResults,
+    /// The return type of the function or closure being checked
+    return_ty: Expected,
+    /// The type variables of the function being checked
+    infer: InferTable,
+    deferred: Vec<Deferred>,
+    /// The loops around the expression being checked, innermost last
+    loops: Vec<Loop>,
+    /// The bindings declared in the function so far, for pointing at them when their type
+    /// can't be inferred
+    bindings: Vec<(NodeId, Spanned<Identifier>)>,
+    /// The expressions checked in the function, whose types are resolved at its end
+    pending: Vec<NodeId>,
+    /// Like `pending`, for the patterns with a type in [`TypeckResults::patterns`]
+    pending_patterns: Vec<NodeId>,
+    _context: PhantomData<C>,
+}
+
+impl<X: Context<C>, C: ContextName> Checker<'_, '_, X, C> {
+    fn declare_item(&mut self, id: ItemId) {
+        let item = &self.ast[id];
+        match &item.kind {
+            ItemKind::Function {
+                arguments,
+                return_type,
+                ..
+            } => {
+                let params = arguments
+                    .iter()
+                    .map(|param| {
+                        let ty = self.lower_type(&param.ty);
+                        self.results.declarations.insert(param.id, ty.clone());
+                        ty
+                    })
+                    .collect();
+                let ret = Box::new(self.lower_type(return_type));
+                self.results
+                    .declarations
+                    .insert(item.id, Ty::Fn { params, ret });
+            }
+        }
+    }
+
+    fn check_item(&mut self, id: ItemId) {
+        let item = &self.ast[id];
+        match &item.kind {
+            ItemKind::Function {
+                return_type, body, ..
+            } => {
+                let Some(Ty::Fn { ret, .. }) = self.results.declarations.get(item.id) else {
+                    unreachable!("functions are declared before they're checked")
+                };
+                self.return_ty = Expected::annotated((**ret).clone(), return_type.span);
+                self.infer = InferTable::default();
+                let expected = self.return_ty.clone();
+                // Without a tail that has a value, whether the end of the body can be reached
+                // without returning one is left to the flow analysis, see `crate::flow`
+                let has_value = match body.statements.last().map(|&id| &self.ast[id].kind) {
+                    Some(&StatementKind::ReturnExpr(tail)) => !matches!(
+                        self.ast[tail].kind,
+                        ExpressionKind::If {
+                            else_branch: None,
+                            ..
+                        } | ExpressionKind::While { .. }
+                            | ExpressionKind::For { .. }
+                    ),
+                    _ => false,
+                };
+                self.check_block(body, has_value.then_some(&expected));
+                self.finish_inference();
+            }
+        }
+    }
+
+    /// Settles the types of the function that was just checked: literals nothing decided on
+    /// get their default type, the operators that waited for that are checked, and what
+    /// still isn't known is reported, at the binding it belongs to where there's one
+    fn finish_inference(&mut self) {
+        self.infer.apply_defaults();
+        for deferred in std::mem::take(&mut self.deferred) {
+            match deferred {
+                Deferred::Binary { op, lhs, rhs, span } => {
+                    if binary_operand(op, &self.infer.resolve(&lhs)) == Some(false) {
+                        self.error(TypeError::InvalidOperands { op, lhs, rhs, span });
+                    }
+                }
+                Deferred::Unary { op, ty, span } => {
+                    if unary_operand(op, &self.infer.resolve(&ty)) == Some(false) {
+                        self.error(TypeError::InvalidOperand { op, ty, span });
+                    }
+                }
+            }
+        }
+        for (id, name) in std::mem::take(&mut self.bindings) {
+            let ty = self.results.declarations[id].clone();
+            if !self.infer.unresolved(&ty).is_empty() {
+                self.error(TypeError::CannotInfer {
+                    ty: ty.clone(),
+                    span: name.span,
+                    binding: Some(name.data),
+                    used: None,
+                });
+                self.infer.give_up(&ty);
+            }
+            self.results
+                .declarations
+                .insert(id, self.infer.resolve(&ty));
+        }
+        for id in std::mem::take(&mut self.pending) {
+            let ty = self.results.expressions[id].clone();
+            if !self.infer.unresolved(&ty).is_empty() {
+                self.error(TypeError::CannotInfer {
+                    ty: ty.clone(),
+                    span: self.ast.span(id),
+                    binding: None,
+                    used: None,
+                });
+                self.infer.give_up(&ty);
+            }
+            self.results.expressions.insert(id, self.infer.resolve(&ty));
+        }
+        // A pattern's type is the type of a binding or an expression, which was reported
+        // already if it isn't known
+        for id in std::mem::take(&mut self.pending_patterns) {
+            let ty = self.results.patterns[id].clone();
+            self.infer.give_up(&ty);
+            self.results.patterns.insert(id, self.infer.resolve(&ty));
+        }
+    }
+
+    /// Reports `err` with what's known about its types so far
+    fn error(&mut self, err: TypeError) {
+        let err = err.map_types(|ty| self.infer.resolve(ty));
+        self.ctx.message(err);
+    }
+
+    /// Reports that `ty`, the type of the expression at `span`, isn't known where it has to be,
+    /// pointing at the binding it comes from if there's one
+    fn cannot_infer(&mut self, ty: &Ty, span: Span) -> Ty {
+        let binding = self
+            .bindings
+            .iter()
+            .find(|&&(id, _)| self.infer.shallow(&self.results.declarations[id]) == *ty)
+            .map(|&(_, name)| name);
+        self.error(TypeError::CannotInfer {
+            ty: ty.clone(),
+            span: binding.map_or(span, |name| name.span),
+            binding: binding.map(|name| name.data),
+            used: binding.is_some().then_some(span),
+        });
+        self.infer.give_up(ty);
+        Ty::Error
+    }
+
+    fn lower_type(&mut self, ty: &Spanned<Type>) -> Ty {
+        match &ty.data {
+            &Type::Named(name) => match name.symbol() {
+                sym::void => Ty::Void,
+                sym::bool => Ty::Bool,
+                sym::char => Ty::Char,
+                sym::str => Ty::Str,
+                sym::Option | sym::Result => self.lower_generic(ty.span, name, &[]),
+                symbol => IntTy::from_symbol(symbol)
+                    .map(Ty::Int)
+                    .or_else(|| FloatTy::from_symbol(symbol).map(Ty::Float))
+                    .unwrap_or_else(|| {
+                        self.ctx
+                            .message(TypeError::UnknownType(ty.span.spanned(name)));
+                        Ty::Error
+                    }),
+            },
+            Type::Array { element, length } => Ty::Array(
+                Box::new(self.lower_type(element)),
+                u64::try_from(length.data).unwrap_or(u64::MAX),
+            ),
+            Type::Tuple(elements) => Ty::tuple(
+                elements
+                    .iter()
+                    .map(|element| self.lower_type(element))
+                    .collect(),
+            ),
+            &Type::Reference { mutable, ref inner } => Ty::Ref {
+                mutable,
+                inner: Box::new(self.lower_type(inner)),
+            },
+            &Type::Generic {
+                name,
+                ref arguments,
+            } => self.lower_generic(ty.span, name, arguments),
+        }
+    }
+
+    /// `Option<T>` and `Result<T, E>` are the only types that take arguments
+    fn lower_generic(&mut self, span: Span, name: Identifier, arguments: &[Spanned<Type>]) -> Ty {
+        let expected = match name.symbol() {
+            sym::Option => 1,
+            sym::Result => 2,
+            symbol if is_primitive(symbol) => 0,
+            _ => {
+                self.ctx.message(TypeError::UnknownType(span.spanned(name)));
+                return Ty::Error;
+            }
+        };
+        let mut arguments: Vec<_> = arguments
+            .iter()
+            .map(|argument| self.lower_type(argument))
+            .collect();
+        if arguments.len() != expected {
+            self.ctx.message(TypeError::TypeArgumentCount {
+                name,
+                expected,
+                found: arguments.len(),
+                span,
+            });
+            return Ty::Error;
+        }
+        match name.symbol() {
+            sym::Option => Ty::Option(Box::new(arguments.pop().unwrap())),
+            sym::Result => {
+                let err = arguments.pop().unwrap();
+                Ty::Result(Box::new(arguments.pop().unwrap()), Box::new(err))
+            }
+            _ => unreachable!("primitives take no arguments"),
+        }
+    }
+
+    /// Reports a mismatch if a value of type `found` at `span` can't be used as `expected`,
+    /// returning whether it can
+    fn expect(&mut self, found: &Ty, span: Span, expected: &Expected) -> bool {
+        match self.infer.unify(found, &expected.ty) {
+            Ok(()) => true,
+            Err(UnifyError::Mismatch) => {
+                self.error(TypeError::Mismatch {
+                    expected: expected.ty.clone(),
+                    found: found.clone(),
+                    span,
+                    expected_span: expected.span,
+                });
+                false
+            }
+            Err(UnifyError::Occurs { ty }) => {
+                // The variable can't be anything, so it shouldn't be reported again later
+                self.infer.give_up(&ty);
+                self.error(TypeError::InfiniteType { ty, span });
+                false
+            }
+        }
+    }
+
+    /// The type of a block-like expression at `span` without a value, checked against `expected`
+    /// as [`Self::check_expression`] would
+    fn void(&mut self, span: Span, expected: Option<&Expected>) -> Ty {
+        match expected {
+            Some(expected) if !self.expect(&Ty::Void, span, expected) => expected.ty.clone(),
+            _ => Ty::Void,
+        }
+    }
+
+    fn check_block(&mut self, block: &Block, expected: Option<&Expected>) -> Ty {
+        let ast = self.ast;
+        let mut diverges = false;
+        let mut tail = None;
+        for &statement in &block.statements {
+            match &ast[statement].kind {
+                StatementKind::Let { pattern, ty, value } => {
+                    let declared = ty
+                        .as_ref()
+                        .map(|ty| Expected::annotated(self.lower_type(ty), ty.span));
+                    let ty = match *value {
+                        Some(value) => {
+                            let value_ty = self.check_expression(value, declared.as_ref());
+                            diverges |= value_ty == Ty::Never;
+                            declared.map_or(value_ty, |declared| declared.ty)
+                        }
+                        // Without a value the bindings get their types from what's assigned
+                        // to them later
+                        None => {
+                            declared.map_or_else(|| self.infer.new_var(), |declared| declared.ty)
+                        }
+                    };
+                    self.bind_pattern(pattern, ty);
+                }
+                &StatementKind::Expr(expr) => {
+                    diverges |= self.check_expression(expr, None) == Ty::Never;
+                }
+                &StatementKind::ReturnExpr(expr) => {
+                    tail = Some(self.check_expression(expr, expected))
+                }
+            }
+        }
+        match tail {
+            Some(ty) => ty,
+            None if diverges => Ty::Never,
+            None => self.void(block.span, expected),
+        }
+    }
+
+    /// Gives the bindings in `pattern` their part of `ty`, and checks that the literals and
+    /// variants in it can be of the type they're matched against
+    fn bind_pattern(&mut self, pattern: &Pattern, ty: Ty) {
+        match &pattern.kind {
+            PatternKind::Wildcard => {}
+            PatternKind::Binding(_) if self.resolutions.variants.contains(pattern.id) => {
+                self.bind_variant(pattern, &[], ty);
+            }
+            &PatternKind::Binding(name) => {
+                self.results.declarations.insert(pattern.id, ty);
+                self.bindings.push((pattern.id, pattern.span.spanned(name)));
+            }
+            PatternKind::Literal(literal) => {
+                self.check_literal_pattern(pattern, literal, &ty);
+                self.record_pattern(pattern, ty);
+            }
+            PatternKind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let op = if *inclusive { "..=" } else { ".." };
+                // Mismatched ends are only reported once
+                if self.check_literal_pattern(pattern, start, &ty)
+                    && self.check_literal_pattern(pattern, end, &ty)
+                    && binary_operand(op, &self.infer.shallow(&ty)) == Some(false)
+                {
+                    self.error(TypeError::InvalidOperands {
+                        op,
+                        lhs: ty.clone(),
+                        rhs: ty.clone(),
+                        span: pattern.span,
+                    });
+                }
+                self.record_pattern(pattern, ty);
+            }
+            PatternKind::Variant { fields, .. } => self.bind_variant(pattern, fields, ty),
+            PatternKind::Tuple(elements) => {
+                let tys = match self.infer.shallow(&ty) {
+                    Ty::Tuple(tys) if tys.len() == elements.len() => tys,
+                    Ty::Void if elements.is_empty() => Vec::new(),
+                    Ty::Error => vec![Ty::Error; elements.len()],
+                    var @ Ty::Infer(InferTy::Var(_)) => {
+                        let tys: Vec<_> = elements.iter().map(|_| self.infer.new_var()).collect();
+                        self.infer
+                            .unify(&Ty::tuple(tys.clone()), &var)
+                            .expect("a variable can be bound to a tuple of new variables");
+                        tys
+                    }
+                    ty => {
+                        self.error(TypeError::Mismatch {
+                            expected: ty,
+                            found: Ty::tuple(vec![Ty::Error; elements.len()]),
+                            span: pattern.span,
+                            expected_span: None,
+                        });
+                        vec![Ty::Error; elements.len()]
+                    }
+                };
+                for (element, ty) in elements.iter().zip(tys) {
+                    self.bind_pattern(element, ty);
+                }
+            }
+        }
+    }
+
+    /// Checks that `literal` in `pattern` can be a value of type `ty`, returning whether it can
+    fn check_literal_pattern(
+        &mut self,
+        pattern: &Pattern,
+        literal: &LiteralPattern,
+        ty: &Ty,
+    ) -> bool {
+        let literal_ty = match *literal {
+            LiteralPattern::Int {
+                suffix: Some(suffix),
+                ..
+            } => Ty::Int(IntTy::from_symbol(suffix).expect("the lexer only allows integer types")),
+            LiteralPattern::Int { suffix: None, .. } => match self.infer.shallow(ty) {
+                ty @ (Ty::Int(_) | Ty::Infer(InferTy::Int(_))) => ty,
+                _ => self.infer.new_int(),
+            },
+            LiteralPattern::Char(_) => Ty::Char,
+            LiteralPattern::Bool(_) => Ty::Bool,
+        };
+        self.expect(&literal_ty, pattern.span, &Expected::new(ty.clone()))
+    }
+
+    /// Binds the `fields` of the variant `pattern`, which is matched against `ty`
+    fn bind_variant(&mut self, pattern: &Pattern, fields: &[Pattern], ty: Ty) {
+        // Unknown variants were reported by the resolver
+        let Some(&variant) = self.resolutions.variants.get(pattern.id) else {
+            for field in fields {
+                self.bind_pattern(field, Ty::Error);
+            }
+            return;
+        };
+        let value = match (self.infer.shallow(&ty), variant) {
+            (Ty::Option(value), Variant::Some | Variant::None)
+            | (Ty::Result(value, _), Variant::Ok)
+            | (Ty::Result(_, value), Variant::Err) => *value,
+            (Ty::Error, _) => Ty::Error,
+            (var @ Ty::Infer(InferTy::Var(_)), _) => {
+                let (whole, value) = self.variant_ty(variant);
+                self.infer
+                    .unify(&whole, &var)
+                    .expect("a variable can be bound to a type of new variables");
+                value
+            }
+            (ty, _) => {
+                let found = match variant {
+                    Variant::Some | Variant::None => Ty::Option(Box::new(Ty::Error)),
+                    Variant::Ok | Variant::Err => {
+                        Ty::Result(Box::new(Ty::Error), Box::new(Ty::Error))
+                    }
+                };
+                self.error(TypeError::Mismatch {
+                    expected: ty,
+                    found,
+                    span: pattern.span,
+                    expected_span: None,
+                });
+                Ty::Error
+            }
+        };
+        let expected = usize::from(variant.has_value());
+        if fields.len() != expected {
+            self.error(TypeError::VariantFields {
+                variant,
+                expected,
+                found: fields.len(),
+                span: pattern.span,
+            });
+            for field in fields {
+                self.bind_pattern(field, Ty::Error);
+            }
+        } else if let Some(field) = fields.first() {
+            self.bind_pattern(field, value);
+        }
+        self.record_pattern(pattern, ty);
+    }
+
+    fn record_pattern(&mut self, pattern: &Pattern, ty: Ty) {
+        self.results.patterns.insert(pattern.id, ty);
+        self.pending_patterns.push(pattern.id);
+    }
+
+    /// A type with `variant` whose other parts are still to be inferred, along with the type
+    /// of the value `variant` holds, which is `void` for `None`
+    fn variant_ty(&mut self, variant: Variant) -> (Ty, Ty) {
+        let value = self.infer.new_var();
+        let whole = match variant {
+            Variant::Some => Ty::Option(Box::new(value.clone())),
+            Variant::None => {
+                return (Ty::Option(Box::new(value)), Ty::Void);
+            }
+            Variant::Ok => Ty::Result(Box::new(value.clone()), Box::new(self.infer.new_var())),
+            Variant::Err => Ty::Result(Box::new(self.infer.new_var()), Box::new(value.clone())),
+        };
+        (whole, value)
+    }
+
+    /// `None`, or the function that wraps its argument in another variant
+    fn variant_constructor(&mut self, variant: Variant) -> Ty {
+        let (whole, value) = self.variant_ty(variant);
+        match variant {
+            Variant::None => whole,
+            _ => Ty::Fn {
+                params: vec![value],
+                ret: Box::new(whole),
+            },
+        }
+    }
+
+    /// The arms of a `match` have to agree on a type like the branches of an `if` do, which
+    /// without a type from the context is the type of the first arm with a value
+    fn check_match(
+        &mut self,
+        scrutinee: ExprId,
+        arms: &[MatchArm],
+        expected: Option<&Expected>,
+    ) -> Ty {
+        let scrutinee_ty = match self.check_expression(scrutinee, None) {
+            // Nothing is ever matched, so the patterns don't have to fit
+            Ty::Never => Ty::Error,
+            ty => ty,
+        };
+        let mut expected = expected.cloned();
+        let mut ty = None;
+        for arm in arms {
+            self.bind_pattern(&arm.pattern, scrutinee_ty.clone());
+            let arm_ty = self.check_expression(arm.body, expected.as_ref());
+            if expected.is_none() && !arm_ty.is_error_or_never() {
+                expected = Some(Expected::annotated(arm_ty.clone(), self.ast[arm.body].span));
+            }
+            if ty.is_none() && arm_ty != Ty::Never {
+                ty = Some(arm_ty);
+            }
+        }
+        ty.unwrap_or(Ty::Never)
+    }
+
+    /// The type of `id`, which is checked against `expected` if there's one. After a mismatch
+    /// it's the expected type, so that whatever contains `id` doesn't mismatch as well.
+    fn check_expression(&mut self, id: ExprId, expected: Option<&Expected>) -> Ty {
+        let expression = &self.ast[id];
+        // Whatever is bound already is seen through when matching on the expected type
+        let expected = expected.map(|expected| expected.part(&self.infer.shallow(&expected.ty)));
+        let expected = expected.as_ref();
+        let ty = self.infer_expression(id, expected);
+        let ty = self.infer.shallow(&ty);
+        self.results.expressions.insert(expression.id, ty.clone());
+        self.pending.push(expression.id);
+        match expected {
+            // Block-like expressions check their tails and branches themselves, which is where
+            // a mismatch should point
+            Some(expected)
+                if !expression.kind.is_block_like()
+                    && !self.expect(&ty, expression.span, expected) =>
+            {
+                expected.ty.clone()
+            }
+            _ => self.infer.shallow(&ty),
+        }
+    }
+
+    /// Whether `id` is a number literal, whose type depends on where it's used
+    fn is_literal(&self, id: ExprId) -> bool {
+        match self.ast[id].kind {
+            ExpressionKind::IntegerLiteral { .. } | ExpressionKind::FloatLiteral(_) => true,
+            ExpressionKind::Unary {
+                op: Spanned {
+                    data: UnaryOp::Neg, ..
+                },
+                operand,
+            } => self.is_literal(operand),
+            _ => false,
+        }
+    }
+
+    fn infer_expression(&mut self, id: ExprId, expected: Option<&Expected>) -> Ty {
+        let ast = self.ast;
+        let expression = &ast[id];
+        let span = expression.span;
+        match &expression.kind {
+            ExpressionKind::Name(_) if self.resolutions.variants.contains(expression.id) => {
+                self.variant_constructor(self.resolutions.variants[expression.id])
+            }
+            ExpressionKind::Name(_) => self
+                .resolutions
+                .declaration_of(expression.id)
+                .and_then(|(declaration, _)| self.results.declarations.get(declaration))
+                .cloned()
+                // Already reported by the resolver
+                .unwrap_or(Ty::Error),
+            // Paths can't refer to anything yet, see `resolve`
+            ExpressionKind::Path(path) => {
+                self.error(TypeError::UnresolvedPath {
+                    path: path.clone(),
+                    span,
+                });
+                Ty::Error
+            }
+            &ExpressionKind::Intrinsic(name) => match name.data.symbol() {
+                sym::line | sym::column => Ty::Int(IntTy::U32),
+                sym::file => Ty::Str,
+                _ => {
+                    self.ctx.message(TypeError::UnknownIntrinsic(name));
+                    Ty::Error
+                }
+            },
+            &ExpressionKind::IntegerLiteral {
+                suffix: Some(suffix),
+                ..
+            } => Ty::Int(IntTy::from_symbol(suffix).expect("the lexer only allows integer types")),
+            ExpressionKind::IntegerLiteral { suffix: None, .. } => {
+                match expected.map(|expected| &expected.ty) {
+                    Some(ty @ (Ty::Int(_) | Ty::Infer(InferTy::Int(_)))) => ty.clone(),
+                    _ => self.infer.new_int(),
+                }
+            }
+            ExpressionKind::FloatLiteral(_) => match expected.map(|expected| &expected.ty) {
+                Some(ty @ (Ty::Float(_) | Ty::Infer(InferTy::Float(_)))) => ty.clone(),
+                _ => self.infer.new_float(),
+            },
+            ExpressionKind::CharLiteral(_) => Ty::Char,
+            ExpressionKind::StringLiteral(_) => Ty::Str,
+            ExpressionKind::BoolLiteral(_) => Ty::Bool,
+            &ExpressionKind::Binary { op, lhs, rhs } => {
+                self.check_binary(span, op.data, lhs, rhs, expected)
+            }
+            &ExpressionKind::Unary { op, operand } => {
+                self.check_unary(span, op.data, operand, expected)
+            }
+            &ExpressionKind::Reference { mutable, operand } => {
+                let inner = match expected.map(|expected| (expected, &expected.ty)) {
+                    Some((expected, Ty::Ref { inner, .. })) => Some(expected.part(inner)),
+                    _ => None,
+                };
+                Ty::Ref {
+                    mutable,
+                    inner: Box::new(self.check_expression(operand, inner.as_ref())),
+                }
+            }
+            ExpressionKind::Cast { expr, ty } => {
+                let to = self.lower_type(ty);
+                let from = self.check_expression(*expr, None);
+                if matches!(from, Ty::Infer(InferTy::Var(_))) {
+                    self.cannot_infer(&from, ast[*expr].span);
+                } else if !can_cast(&from, &to) {
+                    self.error(TypeError::InvalidCast {
+                        from,
+                        to: to.clone(),
+                        span,
+                    });
+                }
+                to
+            }
+            &ExpressionKind::Try(operand) => self.check_try(span, operand),
+            ExpressionKind::Array(elements) => {
+                let element_expected = match expected.map(|expected| (expected, &expected.ty)) {
+                    Some((expected, Ty::Array(element, _))) => Some(expected.part(element)),
+                    _ => None,
+                };
+                let mut element_ty = element_expected
+                    .as_ref()
+                    .map(|expected| expected.ty.clone());
+                let mut element_expected = element_expected;
+                for &element in elements {
+                    let ty = self.check_expression(element, element_expected.as_ref());
+                    if element_expected.is_none() && !ty.is_error_or_never() {
+                        // Without an annotation, the first element decides the type of the rest
+                        element_expected = Some(Expected::annotated(ty.clone(), ast[element].span));
+                        element_ty = Some(ty);
+                    }
+                }
+                // Only an empty array leaves the type of its elements to be inferred
+                let element_ty = element_ty.unwrap_or_else(|| {
+                    if elements.is_empty() {
+                        self.infer.new_var()
+                    } else {
+                        Ty::Error
+                    }
+                });
+                Ty::Array(Box::new(element_ty), elements.len() as u64)
+            }
+            &ExpressionKind::ArrayRepeat { value, count } => {
+                let element_expected = match expected.map(|expected| (expected, &expected.ty)) {
+                    Some((expected, Ty::Array(element, _))) => Some(expected.part(element)),
+                    _ => None,
+                };
+                let element = self.check_expression(value, element_expected.as_ref());
+                self.check_expression(count, Some(&Expected::new(Ty::Int(IntTy::Usize))));
+                match ast[count].kind {
+                    ExpressionKind::IntegerLiteral { value: length, .. } => {
+                        Ty::Array(Box::new(element), u64::try_from(length).unwrap_or(u64::MAX))
+                    }
+                    _ => {
+                        self.error(TypeError::RepeatCount(ast[count].span));
+                        Ty::Error
+                    }
+                }
+            }
+            ExpressionKind::Tuple(elements) => {
+                let element_expected: Vec<_> =
+                    match expected.map(|expected| (expected, &expected.ty)) {
+                        Some((expected, Ty::Tuple(tys))) if tys.len() == elements.len() => {
+                            tys.iter().map(|ty| Some(expected.part(ty))).collect()
+                        }
+                        _ => vec![None; elements.len()],
+                    };
+                Ty::tuple(
+                    elements
+                        .iter()
+                        .zip(element_expected)
+                        .map(|(&element, expected)| {
+                            self.check_expression(element, expected.as_ref())
+                        })
+                        .collect(),
+                )
+            }
+            &ExpressionKind::Index { base, index } => {
+                let base_ty = self.check_expression(base, None);
+                self.check_expression(index, Some(&Expected::new(Ty::Int(IntTy::Usize))));
+                match self.auto_deref(&base_ty) {
+                    Ty::Array(element, _) => *element,
+                    ty @ Ty::Infer(InferTy::Var(_)) => self.cannot_infer(&ty, ast[base].span),
+                    ty if ty.is_error_or_never() => Ty::Error,
+                    _ => {
+                        self.error(TypeError::NotIndexable {
+                            ty: base_ty,
+                            span: ast[base].span,
+                        });
+                        Ty::Error
+                    }
+                }
+            }
+            &ExpressionKind::TupleField { base, field } => {
+                let base_ty = self.check_expression(base, None);
+                match self.auto_deref(&base_ty) {
+                    Ty::Tuple(mut elements) if (field.data as usize) < elements.len() => {
+                        elements.swap_remove(field.data as usize)
+                    }
+                    ty @ Ty::Infer(InferTy::Var(_)) => self.cannot_infer(&ty, ast[base].span),
+                    ty if ty.is_error_or_never() => Ty::Error,
+                    ty => {
+                        self.error(TypeError::NoField { ty, field });
+                        Ty::Error
+                    }
+                }
+            }
+            &ExpressionKind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let element_expected = match expected.map(|expected| (expected, &expected.ty)) {
+                    Some((expected, Ty::Range(element))) => Some(expected.part(element)),
+                    _ => None,
+                };
+                let start_ty = self.check_expression(start, element_expected.as_ref());
+                let end_expected = element_expected
+                    .unwrap_or_else(|| Expected::annotated(start_ty.clone(), ast[start].span));
+                let end_ty = self.check_expression(end, Some(&end_expected));
+                let op = if inclusive { "..=" } else { ".." };
+                match binary_operand(op, &start_ty) {
+                    Some(true) => {}
+                    None => self.deferred.push(Deferred::Binary {
+                        op,
+                        lhs: start_ty.clone(),
+                        rhs: end_ty,
+                        span,
+                    }),
+                    Some(false) => {
+                        self.error(TypeError::InvalidOperands {
+                            op,
+                            lhs: start_ty,
+                            rhs: end_ty,
+                            span,
+                        });
+                        return Ty::Error;
+                    }
+                }
+                Ty::Range(Box::new(start_ty))
+            }
+            ExpressionKind::Block(block) => self.check_block(block, expected),
+            ExpressionKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.check_expression(*condition, Some(&Expected::new(Ty::Bool)));
+                let Some(else_branch) = else_branch else {
+                    self.check_block(then_branch, Some(&Expected::new(Ty::Void)));
+                    return self.void(span, expected);
+                };
+                let then_ty = self.check_block(then_branch, expected);
+                // Without a type from the context, the `else` branch has to match the `then` one
+                let else_expected = match expected {
+                    Some(expected) => Some(expected.clone()),
+                    None if then_ty.is_error_or_never() => None,
+                    None => Some(Expected::annotated(
+                        then_ty.clone(),
+                        self.tail_span(then_branch),
+                    )),
+                };
+                let else_ty = self.check_expression(*else_branch, else_expected.as_ref());
+                if then_ty == Ty::Never {
+                    else_ty
+                } else {
+                    then_ty
+                }
+            }
+            ExpressionKind::While { condition, body } => {
+                self.check_expression(*condition, Some(&Expected::new(Ty::Bool)));
+                self.check_loop_body(body, Expected::new(Ty::Void));
+                self.void(span, expected)
+            }
+            ExpressionKind::For {
+                pattern,
+                iterable,
+                body,
+            } => {
+                let element = self.check_iterable(*iterable);
+                self.bind_pattern(pattern, element);
+                self.check_loop_body(body, Expected::new(Ty::Void));
+                self.void(span, expected)
+            }
+            ExpressionKind::Loop(body) => {
+                let break_ty = expected
+                    .cloned()
+                    .unwrap_or_else(|| Expected::new(self.infer.new_var()));
+                match self.check_loop_body(body, break_ty.clone()) {
+                    true => break_ty.ty,
+                    false => Ty::Never,
+                }
+            }
+            &ExpressionKind::Break(value) => {
+                let Some(innermost) = self.loops.last_mut() else {
+                    self.error(TypeError::OutsideLoop {
+                        keyword: "break",
+                        span,
+                    });
+                    if let Some(value) = value {
+                        self.check_expression(value, None);
+                    }
+                    return Ty::Never;
+                };
+                innermost.broken = true;
+                let break_ty = innermost.break_ty.clone();
+                match value {
+                    Some(value) => {
+                        self.check_expression(value, Some(&break_ty));
+                    }
+                    None => {
+                        self.expect(&Ty::Void, span, &break_ty);
+                    }
+                }
+                Ty::Never
+            }
+            ExpressionKind::Continue => {
+                if self.loops.is_empty() {
+                    self.error(TypeError::OutsideLoop {
+                        keyword: "continue",
+                        span,
+                    });
+                }
+                Ty::Never
+            }
+            &ExpressionKind::Assign { target, value } => {
+                let target_ty = self.check_place(target);
+                let expected = Expected::annotated(target_ty, ast[target].span);
+                self.check_expression(value, Some(&expected));
+                Ty::Void
+            }
+            &ExpressionKind::CompoundAssign { op, target, value } => {
+                let target_ty = self.check_place(target);
+                let expected = Expected::annotated(target_ty.clone(), ast[target].span);
+                let value_ty = self.check_expression(value, Some(&expected));
+                match binary_operand(op.data.as_str(), &target_ty) {
+                    Some(true) => {}
+                    None => self.deferred.push(Deferred::Binary {
+                        op: op.data.as_str(),
+                        lhs: target_ty,
+                        rhs: value_ty,
+                        span,
+                    }),
+                    Some(false) => self.error(TypeError::InvalidOperands {
+                        op: op.data.as_str(),
+                        lhs: target_ty,
+                        rhs: value_ty,
+                        span,
+                    }),
+                }
+                Ty::Void
+            }
+            ExpressionKind::Call { callee, arguments } => self.check_call(span, *callee, arguments),
+            &ExpressionKind::Return(value) => {
+                let expected = self.return_ty.clone();
+                match value {
+                    Some(value) => {
+                        self.check_expression(value, Some(&expected));
+                    }
+                    None => {
+                        self.expect(&Ty::Void, span, &expected);
+                    }
+                }
+                Ty::Never
+            }
+            ExpressionKind::Closure { params, body } => self.check_closure(params, *body, expected),
+            ExpressionKind::Match { scrutinee, arms } => {
+                self.check_match(*scrutinee, arms, expected)
+            }
+        }
+    }
+
+    /// Parameters without a type get it from the expected function type if there's one, or
+    /// are left to be inferred from the body and the calls otherwise. So is the return type.
+    fn check_closure(
+        &mut self,
+        params: &[ClosureParam],
+        body: ExprId,
+        expected: Option<&Expected>,
+    ) -> Ty {
+        let (mut expected_params, expected_ret) = match expected.map(|expected| &expected.ty) {
+            Some(Ty::Fn { params: tys, ret }) if tys.len() == params.len() => (
+                tys.iter().cloned().map(Some).collect(),
+                Some((**ret).clone()),
+            ),
+            _ => (vec![None; params.len()], None),
+        };
+        let params = params
+            .iter()
+            .zip(&mut expected_params)
+            .map(|(param, expected)| {
+                let ty = match (&param.ty, expected.take()) {
+                    (Some(annotation), expected) => {
+                        let ty = self.lower_type(annotation);
+                        // The closure has to take what it's going to be called with
+                        if let Some(expected) = expected {
+                            let annotated = Expected::annotated(ty.clone(), annotation.span);
+                            self.expect(&expected, annotation.span, &annotated);
+                        }
+                        ty
+                    }
+                    (None, Some(expected)) => expected,
+                    (None, None) => self.infer.new_var(),
+                };
+                self.bind_pattern(&param.pattern, ty.clone());
+                ty
+            })
+            .collect();
+        let ret = expected_ret.unwrap_or_else(|| self.infer.new_var());
+        let outer = std::mem::replace(&mut self.return_ty, Expected::new(ret.clone()));
+        // Loops outside the closure can't be broken out of from inside it
+        let loops = std::mem::take(&mut self.loops);
+        self.check_expression(body, Some(&Expected::new(ret.clone())));
+        self.loops = loops;
+        self.return_ty = outer;
+        Ty::Fn {
+            params,
+            ret: Box::new(ret),
+        }
+    }
+
+    /// Checks the body of a loop whose `break`s give it `break_ty`, returning whether there
+    /// were any
+    fn check_loop_body(&mut self, body: &Block, break_ty: Expected) -> bool {
+        self.loops.push(Loop {
+            break_ty,
+            broken: false,
+        });
+        self.check_block(body, Some(&Expected::new(Ty::Void)));
+        self.loops.pop().is_some_and(|innermost| innermost.broken)
+    }
+
+    /// The type of the elements `for` goes through in `iterable`
+    fn check_iterable(&mut self, iterable: ExprId) -> Ty {
+        let ty = self.check_expression(iterable, None);
+        match &ty {
+            Ty::Range(element) | Ty::Array(element, _) => (**element).clone(),
+            Ty::Ref { mutable, inner } => match self.infer.shallow(inner) {
+                Ty::Array(element, _) => Ty::Ref {
+                    mutable: *mutable,
+                    inner: element,
+                },
+                _ => self.not_iterable(ty, iterable),
+            },
+            Ty::Infer(InferTy::Var(_)) => self.cannot_infer(&ty, self.ast[iterable].span),
+            _ if ty.is_error_or_never() => Ty::Error,
+            _ => self.not_iterable(ty, iterable),
+        }
+    }
+
+    fn not_iterable(&mut self, ty: Ty, iterable: ExprId) -> Ty {
+        self.error(TypeError::NotIterable {
+            ty,
+            span: self.ast[iterable].span,
+        });
+        Ty::Error
+    }
+
+    /// The type of `target`, which is assigned to and so has to be a place
+    fn check_place(&mut self, target: ExprId) -> Ty {
+        let ty = self.check_expression(target, None);
+        match self.ast[target].kind {
+            ExpressionKind::Name(_)
+            | ExpressionKind::Index { .. }
+            | ExpressionKind::TupleField { .. }
+            | ExpressionKind::Unary {
+                op:
+                    Spanned {
+                        data: UnaryOp::Deref,
+                        ..
+                    },
+                ..
+            } => ty,
+            _ => {
+                self.error(TypeError::InvalidAssignment(self.ast[target].span));
+                Ty::Error
+            }
+        }
+    }
+
+    /// Where the value of `block` comes from, for pointing at it
+    fn tail_span(&self, block: &Block) -> Span {
+        match block.statements.last().map(|&id| &self.ast[id].kind) {
+            Some(&StatementKind::ReturnExpr(expr)) => self.ast[expr].span,
+            _ => block.span,
+        }
+    }
+
+    fn check_binary(
+        &mut self,
+        span: Span,
+        op: BinaryOp,
+        lhs: ExprId,
+        rhs: ExprId,
+        expected: Option<&Expected>,
+    ) -> Ty {
+        if matches!(op, BinaryOp::And | BinaryOp::Or) {
+            self.check_expression(lhs, Some(&Expected::new(Ty::Bool)));
+            self.check_expression(rhs, Some(&Expected::new(Ty::Bool)));
+            return Ty::Bool;
+        }
+        let arithmetic = matches!(
+            op,
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem
+        );
+        // A literal takes the type of the other operand, so that one goes first
+        let (first, second) = if self.is_literal(lhs) && !self.is_literal(rhs) {
+            (rhs, lhs)
+        } else {
+            (lhs, rhs)
+        };
+        let hint = expected.filter(|expected| arithmetic && expected.ty.is_numeric());
+        let first_ty = self.check_expression(first, hint);
+        let valid = binary_operand(op.as_str(), &first_ty);
+        let second_ty = if valid == Some(false) {
+            self.check_expression(second, None)
+        } else {
+            let expected = Expected::annotated(first_ty.clone(), self.ast[first].span);
+            self.check_expression(second, Some(&expected))
+        };
+        let (lhs_ty, rhs_ty) = if first == lhs {
+            (first_ty.clone(), second_ty.clone())
+        } else {
+            (second_ty.clone(), first_ty.clone())
+        };
+        match valid {
+            Some(true) => {}
+            None => self.deferred.push(Deferred::Binary {
+                op: op.as_str(),
+                lhs: lhs_ty,
+                rhs: rhs_ty,
+                span,
+            }),
+            Some(false) => {
+                self.error(TypeError::InvalidOperands {
+                    op: op.as_str(),
+                    lhs: lhs_ty,
+                    rhs: rhs_ty,
+                    span,
+                });
+                return if arithmetic { Ty::Error } else { Ty::Bool };
+            }
+        }
+        if !arithmetic {
+            Ty::Bool
+        } else if first_ty == Ty::Never {
+            second_ty
+        } else {
+            first_ty
+        }
+    }
+
+    fn check_unary(
+        &mut self,
+        span: Span,
+        op: UnaryOp,
+        operand: ExprId,
+        expected: Option<&Expected>,
+    ) -> Ty {
+        let ty = match op {
+            UnaryOp::Neg => {
+                let hint = expected.filter(|expected| expected.ty.is_numeric());
+                self.check_expression(operand, hint)
+            }
+            UnaryOp::Not => {
+                let hint = expected.filter(|expected| {
+                    matches!(
+                        expected.ty,
+                        Ty::Bool | Ty::Int(_) | Ty::Infer(InferTy::Int(_))
+                    )
+                });
+                self.check_expression(operand, hint)
+            }
+            UnaryOp::Deref => match self.check_expression(operand, None) {
+                Ty::Ref { inner, .. } => return *inner,
+                ty @ Ty::Infer(InferTy::Var(_)) => {
+                    return self.cannot_infer(&ty, self.ast[operand].span)
+                }
+                ty => ty,
+            },
+        };
+        // The literal checker reports both a negated literal that doesn't fit and one of an
+        // unsigned type, since it also knows the value
+        if op == UnaryOp::Neg
+            && matches!(
+                self.ast[operand].kind,
+                ExpressionKind::IntegerLiteral { .. }
+            )
+        {
+            return ty;
+        }
+        match unary_operand(op, &ty) {
+            Some(true) => ty,
+            None => {
+                self.deferred.push(Deferred::Unary {
+                    op,
+                    ty: ty.clone(),
+                    span,
+                });
+                ty
+            }
+            Some(false) => {
+                self.error(TypeError::InvalidOperand { op, ty, span });
+                Ty::Error
+            }
+        }
+    }
+
+    /// A closure without a return type gets one from its first `?`
+    fn check_try(&mut self, span: Span, operand: ExprId) -> Ty {
+        let ty = self.check_expression(operand, None);
+        let return_ty = self.infer.shallow(&self.return_ty.ty);
+        let (value, returnable) = match &ty {
+            Ty::Option(value) => (
+                value,
+                match return_ty {
+                    Ty::Option(_) | Ty::Error => true,
+                    Ty::Infer(InferTy::Var(_)) => {
+                        let option = Ty::Option(Box::new(self.infer.new_var()));
+                        self.infer.unify(&option, &return_ty).is_ok()
+                    }
+                    _ => false,
+                },
+            ),
+            Ty::Result(value, err) => (
+                value,
+                match &return_ty {
+                    Ty::Result(_, return_err) => self.infer.unify(err, return_err).is_ok(),
+                    Ty::Error => true,
+                    Ty::Infer(InferTy::Var(_)) => {
+                        let result = Ty::Result(Box::new(self.infer.new_var()), err.clone());
+                        self.infer.unify(&result, &return_ty).is_ok()
+                    }
+                    _ => false,
+                },
+            ),
+            Ty::Infer(InferTy::Var(_)) => return self.cannot_infer(&ty, self.ast[operand].span),
+            _ if ty.is_error_or_never() => return ty,
+            _ => {
+                self.error(TypeError::TryOperand {
+                    ty,
+                    span: self.ast[operand].span,
+                });
+                return Ty::Error;
+            }
+        };
+        let value = (**value).clone();
+        if !returnable {
+            self.error(TypeError::TryReturn {
+                ty,
+                return_ty,
+                span,
+                return_span: self.return_ty.span.unwrap_or(span),
+            });
+        }
+        value
+    }
+
+    fn check_call(&mut self, span: Span, callee: ExprId, arguments: &[ExprId]) -> Ty {
+        let ast = self.ast;
+        let callee_ty = self.check_expression(callee, None);
+        // A callee that isn't known yet, such as a closure parameter, is a function of as many
+        // parameters as there are arguments, whose types are inferred from the call
+        let callee_ty = match callee_ty {
+            var @ Ty::Infer(InferTy::Var(_)) => {
+                let ty = Ty::Fn {
+                    params: arguments.iter().map(|_| self.infer.new_var()).collect(),
+                    ret: Box::new(self.infer.new_var()),
+                };
+                self.expect(&var, ast[callee].span, &Expected::new(ty.clone()));
+                ty
+            }
+            ty => ty,
+        };
+        let (params, ret) = match callee_ty {
+            Ty::Fn { params, ret } => (params, *ret),
+            ty => {
+                if !ty.is_error_or_never() {
+                    self.error(TypeError::NotCallable {
+                        ty,
+                        span: ast[callee].span,
+                    });
+                }
+                for &argument in arguments {
+                    self.check_expression(argument, None);
+                }
+                return Ty::Error;
+            }
+        };
+        // Calling a function by name lets mismatches point at its parameters
+        let function =
+            self.resolutions
+                .declaration_of(ast[callee].id)
+                .and_then(|(_, declaration)| match declaration.kind {
+                    DeclarationKind::Function(item) => Some((item, declaration.span)),
+                    _ => None,
+                });
+        if arguments.len() != params.len() {
+            self.error(TypeError::WrongArgumentCount {
+                expected: params.len(),
+                found: arguments.len(),
+                span,
+                definition: function.map(|(_, span)| span),
+            });
+        }
+        for (i, &argument) in arguments.iter().enumerate() {
+            let expected = params.get(i).map(|param| {
+                let span = function.map(|(item, _)| match &ast[item].kind {
+                    ItemKind::Function { arguments, .. } => arguments[i].ty.span,
+                });
+                Expected {
+                    ty: param.clone(),
+                    span,
+                }
+            });
+            self.check_expression(argument, expected.as_ref());
+        }
+        ret
+    }
+
+    /// Field accesses and indexing see through references
+    fn auto_deref(&self, ty: &Ty) -> Ty {
+        match self.infer.shallow(ty) {
+            Ty::Ref { inner, .. } => self.auto_deref(&inner),
+            ty => ty,
+        }
+    }
+}
+
+/// Whether the binary operator `op` applies to operands of type `ty`, or `None` if that
+/// depends on what's still to be inferred of it
+fn binary_operand(op: &str, ty: &Ty) -> Option<bool> {
+    Some(match ty {
+        Ty::Error | Ty::Never => true,
+        Ty::Infer(InferTy::Var(_)) => return None,
+        ty => match op {
+            "==" | "!=" => !matches!(ty, Ty::Fn { .. }),
+            "<" | ">" | "<=" | ">=" => ty.is_numeric() || *ty == Ty::Char,
+            ".." | "..=" => matches!(ty, Ty::Int(_) | Ty::Infer(InferTy::Int(_)) | Ty::Char),
+            _ => ty.is_numeric(),
+        },
+    })
+}
+
+/// Like [`binary_operand`] for unary operators other than `*`
+fn unary_operand(op: UnaryOp, ty: &Ty) -> Option<bool> {
+    Some(match (op, ty) {
+        (_, Ty::Error | Ty::Never) => true,
+        // Whether an integer can be negated depends on whether it's signed
+        (_, Ty::Infer(InferTy::Var(_))) | (UnaryOp::Neg, Ty::Infer(InferTy::Int(_))) => {
+            return None
+        }
+        (UnaryOp::Neg, Ty::Int(int)) => int.is_signed(),
+        (UnaryOp::Neg, Ty::Float(_) | Ty::Infer(InferTy::Float(_))) => true,
+        (UnaryOp::Not, Ty::Bool | Ty::Int(_) | Ty::Infer(InferTy::Int(_))) => true,
+        _ => false,
+    })
+}
+
+fn is_primitive(symbol: Symbol) -> bool {
+    matches!(symbol, sym::void | sym::bool | sym::char | sym::str)
+        || IntTy::from_symbol(symbol).is_some()
+        || FloatTy::from_symbol(symbol).is_some()
+}
+
+fn can_cast(from: &Ty, to: &Ty) -> bool {
+    match (from, to) {
+        (Ty::Error | Ty::Never, _) | (_, Ty::Error) => true,
+        (from, to) if from.is_numeric() && to.is_numeric() => true,
+        (Ty::Bool | Ty::Char, Ty::Int(_)) => true,
+        (Ty::Int(IntTy::U8), Ty::Char) => true,
+        (from, to) => from == to,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use miette::Diagnostic;
+
+    use crate::{
+        ast::{Ast, ExpressionKind, ItemId},
+        error::CoralError,
+        testing::Source,
+    };
+
+    use super::{
+        ty::{IntTy, Ty},
+        TypeError, TypeckResults,
+    };
+
+    fn check(source: &str) -> (Ast, Vec<ItemId>, TypeckResults, Vec<TypeError>) {
+        let mut source = Source::parse(source);
+        let (_, results) = source.typeck();
+        let errors = source.errors(|err| match err {
+            CoralError::TypeError(err) => Some(err),
+            _ => None,
+        });
+        (source.ast, source.items, results, errors)
+    }
+
+    /// The errors as `code@start..end`, in the order they were reported
+    fn codes(errors: &[TypeError]) -> Vec<String> {
+        errors
+            .iter()
+            .map(|err| {
+                let span = err.span();
+                format!("{}@{}..{}", err.error_code(), span.start(), span.end())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn checks_a_valid_program() {
+        let source = "fn f(x: u8, t: &(bool, [i32; 2])): Option<u8> {
+            let y = 1 + x * 2;
+            let (a, b): (u8, f32) = (y, -1.5);
+            let c = [0; 3];
+            if t.0 && c[0] < t.1[1] { return g(x); }
+            while a > 1 { 'c' as u32 + #line; }
+            let o = g(-b as u8)?;
+            g(o)
+        }
+        fn g(x: u8): Option<u8> { g(x) }";
+        let (ast, _, results, errors) = check(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let literals: Vec<_> = ast
+            .expressions
+            .iter()
+            .filter(|(_, expression)| {
+                matches!(expression.kind, ExpressionKind::IntegerLiteral { .. })
+            })
+            .map(|(_, expression)| results.expressions.get(expression.id).unwrap().to_string())
+            .collect();
+        // The `1` is checked after `x * 2`, whose `2` comes after `x`
+        assert_eq!(
+            literals,
+            ["u8", "u8", "i32", "usize", "usize", "usize", "u8"]
+        );
+    }
+
+    #[test]
+    fn mismatches_point_at_both_ends() {
+        let (_, _, _, errors) = check("fn f(): i32 {\n    let x: bool = 1;\n    true\n}");
+        assert_eq!(codes(&errors), ["E0011@32..33", "E0011@39..43"]);
+        let TypeError::Mismatch {
+            expected,
+            found,
+            expected_span: Some(expected_span),
+            ..
+        } = &errors[1]
+        else {
+            panic!("expected a mismatch with an annotation, found {errors:?}")
+        };
+        assert_eq!((expected, found), (&Ty::Int(IntTy::I32), &Ty::Bool));
+        assert_eq!(expected_span.start()..expected_span.end(), 8..11);
+
+        // Both branches have to agree, and the tail has to match the return type
+        let (_, _, _, errors) = check("fn f(c: bool): void { let x = if c { 1 } else { c }; x }");
+        assert_eq!(codes(&errors), ["E0011@48..49", "E0011@53..54"]);
+        assert_eq!(
+            errors[0].to_string(),
+            "mismatched types: expected `{integer}`, found `bool`"
+        );
+        // Whether a function without a tail gets to its end is up to the flow analysis
+        let (_, _, _, errors) = check("fn f(): u8 { }\nfn g(): u8 { return 1; }");
+        assert!(errors.is_empty(), "{errors:?}");
+    }
+
+    #[test]
+    fn returns_match_the_signature() {
+        let (_, _, _, errors) =
+            check("fn f(x: i32): (i32, u8) { if x < 0 { return; } return (x, x); }");
+        assert_eq!(codes(&errors), ["E0011@37..43", "E0011@58..59"]);
+        let TypeError::Mismatch { expected_span, .. } = &errors[1] else {
+            panic!("expected a mismatch")
+        };
+        assert_eq!(expected_span.map(|span| span.start()), Some(14));
+    }
+
+    #[test]
+    fn operators_need_compatible_operands() {
+        let source = "fn f(x: u8, b: bool): void {
+            1 + x; x + b; b + 1; -x; !b; *x; b < b; b && x; x as bool; x == x;
+        }";
+        let (_, _, _, errors) = check(source);
+        let found: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            found,
+            [
+                "mismatched types: expected `u8`, found `bool`",
+                "cannot apply `+` to `bool` and `{integer}`",
+                "cannot apply unary `-` to `u8`",
+                "cannot apply unary `*` to `u8`",
+                "cannot apply `<` to `bool` and `bool`",
+                "mismatched types: expected `bool`, found `u8`",
+                "cannot cast `u8` as `bool`",
+            ]
+        );
+    }
+
+    #[test]
+    fn calls_check_their_arguments() {
+        let source = "fn f(a: i32, b: bool): i32 { a }
+        fn g(): void { f(1, 2); f(1); let x = 1; x(); }";
+        let (_, _, _, errors) = check(source);
+        assert_eq!(
+            codes(&errors),
+            ["E0011@61..62", "E0015@65..69", "E0016@82..83"]
+        );
+        let TypeError::Mismatch { expected_span, .. } = &errors[0] else {
+            panic!("expected a mismatch")
+        };
+        assert_eq!(expected_span.map(|span| span.start()), Some(16));
+        let TypeError::WrongArgumentCount { definition, .. } = &errors[1] else {
+            panic!("expected a wrong argument count")
+        };
+        assert_eq!(definition.map(|span| span.start()), Some(3));
+    }
+
+    #[test]
+    fn places_and_annotations() {
+        let source = "fn f(t: (i32, bool), o: Result<i32, u8>): Option<i32> {
+            t.2; t[0]; o?; let (a, b, c) = t; let n: Option = 1; let x: int = #row;
+        }";
+        let (_, _, _, errors) = check(source);
+        let found: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            found,
+            [
+                "no field `2` on type `(i32, bool)`",
+                "cannot index into a value of type `(i32, bool)`",
+                "the `?` operator on `Result<i32, u8>` can't be used in a function that \
+                 returns `Option<i32>`",
+                "mismatched types: expected `(i32, bool)`, found `(_, _, _)`",
+                "`Option` takes 1 type argument, but 0 were given",
+                "cannot find type `int`",
+                "unknown intrinsic `#row`",
+            ]
+        );
+    }
+
+    #[test]
+    fn infers_bindings_and_closures() {
+        let source = "fn f(): u8 {
+            let x = 1; let y: u8 = x;
+            let add = |a, b: u8| a + b; let z = 2u16;
+            let w = 3; let v = [w, 4i64];
+            add(y, 1) + (z as u8)
+        }";
+        let (ast, _, results, errors) = check(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let literals: Vec<_> = ast
+            .expressions
+            .iter()
+            .filter(|(_, expression)| {
+                matches!(expression.kind, ExpressionKind::IntegerLiteral { .. })
+            })
+            .map(|(_, expression)| results.expressions.get(expression.id).unwrap().to_string())
+            .collect();
+        assert_eq!(literals, ["u8", "u16", "i64", "i64", "u8"]);
+        // Nothing decides on the type of `n`, so it gets the default
+        let (ast, _, results, errors) = check("fn f(): void { let n = 1; let m = n; }");
+        assert!(errors.is_empty(), "{errors:?}");
+        let (_, last) = ast.expressions.iter().next_back().unwrap();
+        assert_eq!(
+            results.expressions.get(last.id).unwrap(),
+            &Ty::Int(IntTy::I32)
+        );
+        // A `let` without a value gets its type from what's assigned later
+        let (ast, _, results, errors) = check("fn f(): void { let x; x = 1u16; let y = x; }");
+        assert!(errors.is_empty(), "{errors:?}");
+        let (_, last) = ast.expressions.iter().next_back().unwrap();
+        assert_eq!(
+            results.expressions.get(last.id).unwrap(),
+            &Ty::Int(IntTy::U16)
+        );
+    }
+
+    #[test]
+    fn unresolved_types_need_annotations() {
+        let source = "fn f(): void {
+            let a = []; let g = |x| x; let h = |p| p.0;
+            let k = |q| [q, [q]];
+        }";
+        let (_, _, _, errors) = check(source);
+        assert_eq!(
+            codes(&errors),
+            [
+                "E0024@63..64",
+                "E0023@99..102",
+                "E0024@31..32",
+                "E0024@48..49"
+            ]
+        );
+        assert_eq!(
+            errors[1].to_string(),
+            "cannot infer an infinite type, `_` would have to be `[_; 1]`"
+        );
+        // The field access is where the type had to be known
+        let TypeError::CannotInfer {
+            binding: Some(binding),
+            used: Some(used),
+            ..
+        } = &errors[0]
+        else {
+            panic!("expected the parameter and its use, found {errors:?}")
+        };
+        assert_eq!((binding.to_string(), used.start()), ("p".to_string(), 66));
+    }
+
+    #[test]
+    fn calls_infer_the_callee() {
+        let source = "fn f(): u8 {
+            let apply = |h, x| h(x);
+            apply(|y: u8| y + 1, 2)
+        }";
+        let (_, _, _, errors) = check(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        // Calling a value with itself would need a function taking itself
+        let (_, _, _, errors) = check("fn f(): void { let g = |x| x(x); }");
+        assert_eq!(codes(&errors), ["E0023@29..30"]);
+    }
+
+    #[test]
+    fn loops_and_assignments() {
+        let source = "fn f(xs: [u8; 3]): u8 {
+            let n = loop { break 3; }; let total: u8 = 0;
+            for x in xs { total += x; } for (i, c) in [(1, 'a')] { n = i; }
+            for i in 0..n { if i == 2 { continue; } total = total - 1; }
+            while true { break; } let v: u8 = loop {}; for r in &xs { total = total + *r; }
+            total
+        }";
+        let (_, _, _, errors) = check(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let source = "fn f(): void {
+            break; let c = || continue; while true { break 1; }
+            1 = 2; let b = true; b += b; for x in 5 {}
+        }";
+        let (_, _, _, errors) = check(source);
+        assert_eq!(
+            codes(&errors),
+            [
+                "E0026@27..32",
+                "E0026@45..53",
+                "E0011@74..75",
+                "E0027@91..92",
+                "E0014@112..118",
+                "E0028@129..130"
+            ]
+        );
+        assert_eq!(errors[0].to_string(), "`break` outside of a loop");
+    }
+
+    #[test]
+    fn paths_are_reported() {
+        // The value of `v` has an error already, so its type isn't asked for
+        let (_, _, _, errors) = check("fn f(): void { std::x; let v = Vec::new(); }");
+        assert_eq!(codes(&errors), ["E0009@15..21", "E0009@31..39"]);
+        assert_eq!(
+            errors[1].to_string(),
+            "cannot find `Vec::new` in this scope"
+        );
+    }
+
+    #[test]
+    fn literals_fit_their_type() {
+        let source = "fn f(): u8 {
+            let a: i8 = -128; let b = 128i8; let c = [256, 0u8];
+            let d = 2147483648; let e: u128 = 340282366920938463463374607431768211455;
+            let g: i128 = -170141183460469231731687303715884105729; let h = -0u16;
+            255
+        }";
+        let (_, _, _, errors) = check(source);
+        assert_eq!(
+            codes(&errors),
+            [
+                "E0025@51..56",
+                "E0025@67..70",
+                "E0025@98..108",
+                "E0025@191..231",
+                "E0014@241..246"
+            ]
+        );
+        assert_eq!(
+            errors[..4]
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "literal out of range for `i8`",
+                "literal out of range for `u8`",
+                "literal out of range for `i32`",
+                "literal out of range for `i128`",
+            ]
+        );
+        assert_eq!(
+            errors[1].help().unwrap().to_string(),
+            "`u8` holds values from `0` to `255`"
+        );
+        // Only the range is reported for a negative literal of an unsigned type
+        let (_, _, _, errors) = check("fn f(): u8 { -1 }");
+        assert_eq!(codes(&errors), ["E0025@13..15"]);
+        assert_eq!(errors[0].to_string(), "literal out of range for `u8`");
+    }
+
+    #[test]
+    fn match_arms_agree_and_patterns_fit() {
+        let source = "fn f(x: Option<u8>, r: Result<i8, bool>): u8 {
+            let y = match x { Some(0..=9) => 1, Some(n) => n, None => 0 };
+            let s = Some; let o = s(y);
+            match r { Ok(-5) => y, Err(true) => return 1, _ => 2 }
+        }";
+        let (_, _, _, errors) = check(source);
+        assert!(errors.is_empty(), "{errors:?}");
+        let source = "fn f(x: Option<u8>): void {
+            match x { Some(a, b) => 1, None(c) => 2, 'a' => 3, Some(300) => 4, None => true };
+        }";
+        let (_, _, _, errors) = check(source);
+        assert_eq!(
+            codes(&errors),
+            [
+                "E0032@50..60",
+                "E0032@67..74",
+                "E0011@81..84",
+                "E0011@115..119",
+                "E0025@96..99"
+            ]
+        );
+        assert_eq!(
+            errors[..2]
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "this pattern has 2 fields, but `Some` has 1",
+                "this pattern has 1 field, but `None` has 0",
+            ]
+        );
+        assert_eq!(
+            errors[1].help().unwrap().to_string(),
+            "`None` holds no value, write it on its own"
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Type variables and unification, for the types that aren't written down: those of `let`
+//! bindings and closure parameters without an annotation, and of number literals.
+//!
+//! A variable is bound at most once, to a type that can contain other variables. The checker
+//! gets a new [`InferTable`] for every function and resolves everything in it once the function
+//! is checked, so no variable outlives the function it's created in.
+
+use super::ty::{FloatTy, InferTy, IntTy, Ty};
+
+/// Why [`InferTable::unify`] failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnifyError {
+    Mismatch,
+    /// A variable would have to contain itself, as in `x = [x]`
+    Occurs {
+        ty: Ty,
+    },
+}
+
+#[derive(Debug, Default)]
+pub struct InferTable {
+    /// Every variable, with the type it's bound to
+    vars: Vec<(InferTy, Option<Ty>)>,
+}
+
+impl InferTable {
+    fn new_infer(&mut self, kind: fn(u32) -> InferTy) -> Ty {
+        let var = kind(self.vars.len() as u32);
+        self.vars.push((var, None));
+        Ty::Infer(var)
+    }
+
+    /// A variable for any type
+    pub fn new_var(&mut self) -> Ty {
+        self.new_infer(InferTy::Var)
+    }
+
+    /// A variable for the type of an integer literal
+    pub fn new_int(&mut self) -> Ty {
+        self.new_infer(InferTy::Int)
+    }
+
+    /// A variable for the type of a float literal
+    pub fn new_float(&mut self) -> Ty {
+        self.new_infer(InferTy::Float)
+    }
+
+    fn value(&self, var: InferTy) -> Option<&Ty> {
+        self.vars[var.index() as usize].1.as_ref()
+    }
+
+    /// What `ty` is bound to if it's a bound variable, so that it can be matched on.
+    /// The types it contains can still be variables.
+    pub fn shallow(&self, ty: &Ty) -> Ty {
+        match ty {
+            &Ty::Infer(var) => match self.value(var) {
+                Some(bound) => self.shallow(bound),
+                None => ty.clone(),
+            },
+            ty => ty.clone(),
+        }
+    }
+
+    /// `ty` with every bound variable in it replaced
+    pub fn resolve(&self, ty: &Ty) -> Ty {
+        match ty {
+            &Ty::Infer(var) => match self.value(var) {
+                Some(bound) => self.resolve(bound),
+                None => ty.clone(),
+            },
+            Ty::Tuple(elements) => Ty::Tuple(elements.iter().map(|ty| self.resolve(ty)).collect()),
+            Ty::Array(element, length) => Ty::Array(Box::new(self.resolve(element)), *length),
+            &Ty::Ref { mutable, ref inner } => Ty::Ref {
+                mutable,
+                inner: Box::new(self.resolve(inner)),
+            },
+            Ty::Option(inner) => Ty::Option(Box::new(self.resolve(inner))),
+            Ty::Result(ok, err) => {
+                Ty::Result(Box::new(self.resolve(ok)), Box::new(self.resolve(err)))
+            }
+            Ty::Range(element) => Ty::Range(Box::new(self.resolve(element))),
+            Ty::Fn { params, ret } => Ty::Fn {
+                params: params.iter().map(|ty| self.resolve(ty)).collect(),
+                ret: Box::new(self.resolve(ret)),
+            },
+            ty => ty.clone(),
+        }
+    }
+
+    /// The variables in `ty` that aren't bound yet, in the order they appear
+    pub fn unresolved(&self, ty: &Ty) -> Vec<InferTy> {
+        let mut vars = Vec::new();
+        self.collect_unresolved(ty, &mut vars);
+        vars
+    }
+
+    fn collect_unresolved(&self, ty: &Ty, vars: &mut Vec<InferTy>) {
+        match ty {
+            &Ty::Infer(var) => match self.value(var) {
+                Some(bound) => self.collect_unresolved(bound, vars),
+                None if !vars.contains(&var) => vars.push(var),
+                None => {}
+            },
+            Ty::Tuple(elements) => {
+                for element in elements {
+                    self.collect_unresolved(element, vars);
+                }
+            }
+            Ty::Array(inner, _) | Ty::Ref { inner, .. } | Ty::Option(inner) | Ty::Range(inner) => {
+                self.collect_unresolved(inner, vars)
+            }
+            Ty::Result(ok, err) => {
+                self.collect_unresolved(ok, vars);
+                self.collect_unresolved(err, vars);
+            }
+            Ty::Fn { params, ret } => {
+                for param in params {
+                    self.collect_unresolved(param, vars);
+                }
+                self.collect_unresolved(ret, vars);
+            }
+            _ => {}
+        }
+    }
+
+    /// Binds the variables left in `ty` to [`Ty::Error`], once they've been reported as
+    /// impossible to infer
+    pub fn give_up(&mut self, ty: &Ty) {
+        for var in self.unresolved(ty) {
+            self.vars[var.index() as usize].1 = Some(Ty::Error);
+        }
+    }
+
+    /// Gives integer variables that nothing decided on the type `i32`, and float ones `f64`
+    pub fn apply_defaults(&mut self) {
+        for (var, value) in &mut self.vars {
+            if value.is_none() {
+                *value = match var {
+                    InferTy::Var(_) => None,
+                    InferTy::Int(_) => Some(Ty::Int(IntTy::I32)),
+                    InferTy::Float(_) => Some(Ty::Float(FloatTy::F64)),
+                };
+            }
+        }
+    }
+
+    fn bind(&mut self, var: InferTy, ty: Ty) -> Result<(), UnifyError> {
+        if self
+            .unresolved(&ty)
+            .iter()
+            .any(|other| other.index() == var.index())
+        {
+            return Err(UnifyError::Occurs {
+                ty: self.resolve(&ty),
+            });
+        }
+        self.vars[var.index() as usize].1 = Some(ty);
+        Ok(())
+    }
+
+    /// Binds variables so that a value of type `found` can be used where `expected` is, which
+    /// allows the same coercions as the checker does without variables: from `!` and from
+    /// `&mut T` to `&T`. Variables bound before an error stay bound.
+    pub fn unify(&mut self, found: &Ty, expected: &Ty) -> Result<(), UnifyError> {
+        let found = self.shallow(found);
+        let expected = self.shallow(expected);
+        match (&found, &expected) {
+            (Ty::Infer(a), Ty::Infer(b)) if a.index() == b.index() => Ok(()),
+            (&Ty::Infer(var @ InferTy::Var(_)), _) => self.bind(var, expected),
+            (_, &Ty::Infer(var @ InferTy::Var(_))) => self.bind(var, found),
+            (Ty::Error | Ty::Never, _) | (_, Ty::Error) => Ok(()),
+            (&Ty::Infer(var @ InferTy::Int(_)), Ty::Int(_) | Ty::Infer(InferTy::Int(_)))
+            | (&Ty::Infer(var @ InferTy::Float(_)), Ty::Float(_) | Ty::Infer(InferTy::Float(_))) => {
+                self.bind(var, expected)
+            }
+            (Ty::Int(_), &Ty::Infer(var @ InferTy::Int(_)))
+            | (Ty::Float(_), &Ty::Infer(var @ InferTy::Float(_))) => self.bind(var, found),
+            (Ty::Tuple(found), Ty::Tuple(expected)) if found.len() == expected.len() => found
+                .iter()
+                .zip(expected)
+                .try_for_each(|(found, expected)| self.unify(found, expected)),
+            (Ty::Array(found, found_length), Ty::Array(expected, expected_length))
+                if found_length == expected_length =>
+            {
+                self.unify(found, expected)
+            }
+            (
+                Ty::Ref { mutable, inner },
+                Ty::Ref {
+                    mutable: expected_mutable,
+                    inner: expected,
+                },
+            ) if *mutable || !expected_mutable => self.unify(inner, expected),
+            (Ty::Option(found), Ty::Option(expected)) | (Ty::Range(found), Ty::Range(expected)) => {
+                self.unify(found, expected)
+            }
+            (Ty::Result(ok, err), Ty::Result(expected_ok, expected_err)) => {
+                self.unify(ok, expected_ok)?;
+                self.unify(err, expected_err)
+            }
+            (
+                Ty::Fn { params, ret },
+                Ty::Fn {
+                    params: expected_params,
+                    ret: expected_ret,
+                },
+            ) if params.len() == expected_params.len() => {
+                // A function can be used where one taking more general parameters is expected
+                for (param, expected) in params.iter().zip(expected_params) {
+                    self.unify(expected, param)?;
+                }
+                self.unify(ret, expected_ret)
+            }
+            (found, expected) if found == expected => Ok(()),
+            _ => Err(UnifyError::Mismatch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InferTable, UnifyError};
+    use crate::typeck::ty::{FloatTy, IntTy, Ty};
+
+    #[test]
+    fn binds_through_structure() {
+        let mut table = InferTable::default();
+        let (a, b) = (table.new_var(), table.new_var());
+        let found = Ty::tuple(vec![a.clone(), Ty::Option(Box::new(Ty::Bool))]);
+        let expected = Ty::tuple(vec![Ty::Char, Ty::Option(Box::new(b.clone()))]);
+        assert_eq!(table.unify(&found, &expected), Ok(()));
+        assert_eq!(
+            table.resolve(&Ty::Array(Box::new(a), 2)).to_string(),
+            "[char; 2]"
+        );
+        assert_eq!(table.shallow(&b), Ty::Bool);
+    }
+
+    #[test]
+    fn literals_only_take_their_kind_of_number() {
+        let mut table = InferTable::default();
+        let (int, other_int, float) = (table.new_int(), table.new_int(), table.new_float());
+        assert_eq!(table.unify(&int, &other_int), Ok(()));
+        assert_eq!(table.unify(&float, &int), Err(UnifyError::Mismatch));
+        assert_eq!(table.unify(&Ty::Bool, &int), Err(UnifyError::Mismatch));
+        assert_eq!(table.unify(&other_int, &Ty::Int(IntTy::U8)), Ok(()));
+        table.apply_defaults();
+        assert_eq!(table.resolve(&int), Ty::Int(IntTy::U8));
+        assert_eq!(table.resolve(&float), Ty::Float(FloatTy::F64));
+    }
+
+    #[test]
+    fn occurs_check() {
+        let mut table = InferTable::default();
+        let a = table.new_var();
+        let array = Ty::Array(Box::new(a.clone()), 1);
+        assert_eq!(
+            table.unify(&array, &a),
+            Err(UnifyError::Occurs { ty: array.clone() })
+        );
+        assert_eq!(table.unresolved(&array).len(), 1);
+        table.give_up(&array);
+        assert_eq!(table.resolve(&array), Ty::Array(Box::new(Ty::Error), 1));
+    }
+}
@@ -0,0 +1,122 @@
+//! Checks that every integer literal fits the type it ended up with, which is only known once
+//! inference is done with the function the literal is in.
+//!
+//! A `-` right in front of a literal is part of what's checked, so that `-128i8` is fine even
+//! though `128i8` on its own isn't. The same goes for the literals in patterns, where the `-`
+//! is part of the literal. Negating a literal of an unsigned type is reported here too, rather
+//! than by the checker of the `-`, so that `-1` as a `u8` gets a single error.
+
+use std::marker::PhantomData;
+
+use crate::{
+    ast::{
+        visit::{walk_expression, walk_pattern, Visitor},
+        Ast, ExprId, ExpressionKind, ItemId, LiteralPattern, Pattern, PatternKind, UnaryOp,
+    },
+    error::{Context, ContextName},
+    span::Span,
+};
+
+use super::{
+    ty::{IntTy, Ty},
+    TypeError, TypeckResults,
+};
+
+/// Reports the integer literals in `items` that are out of range for their type in `results`
+pub fn check_literals<C: ContextName>(
+    ctx: &mut impl Context<C>,
+    ast: &Ast,
+    items: &[ItemId],
+    results: &TypeckResults,
+) {
+    let mut checker = LiteralChecker {
+        ctx,
+        results,
+        _context: PhantomData,
+    };
+    for &item in items {
+        checker.visit_item(ast, item);
+    }
+}
+
+struct LiteralChecker<'c, 'a, X, C> {
+    ctx: &'c mut X,
+    results: &'a TypeckResults,
+    _context: PhantomData<C>,
+}
+
+impl<X: Context<C>, C: ContextName> LiteralChecker<'_, '_, X, C> {
+    /// Checks the literal `id`, which is negated if `negated`, with `span` covering the `-`
+    fn check(&mut self, ast: &Ast, id: ExprId, negated: bool, span: Span) {
+        let expression = &ast[id];
+        let ExpressionKind::IntegerLiteral { value, .. } = expression.kind else {
+            return;
+        };
+        // Anything else already had an error reported
+        if let Some(&Ty::Int(ty)) = self.results.expressions.get(expression.id) {
+            if self.check_value(ty, value, negated, span) && negated && !ty.is_signed() {
+                self.ctx.message(TypeError::InvalidOperand {
+                    op: UnaryOp::Neg,
+                    ty: Ty::Int(ty),
+                    span,
+                });
+            }
+        }
+    }
+
+    fn check_pattern(&mut self, pattern: &Pattern, literal: &LiteralPattern) {
+        if let (
+            &LiteralPattern::Int {
+                value, negative, ..
+            },
+            Some(&Ty::Int(ty)),
+        ) = (literal, self.results.patterns.get(pattern.id))
+        {
+            self.check_value(ty, value, negative, pattern.span);
+        }
+    }
+
+    /// Reports `value` if it doesn't fit in `ty`, returning whether it does
+    fn check_value(&mut self, ty: IntTy, value: u128, negated: bool, span: Span) -> bool {
+        let fits = if negated {
+            value <= ty.min().unsigned_abs()
+        } else {
+            value <= ty.max()
+        };
+        if !fits {
+            self.ctx.message(TypeError::LiteralOutOfRange {
+                ty,
+                literal: format!("{}{value}", if negated { "-" } else { "" }),
+                span,
+            });
+        }
+        fits
+    }
+}
+
+impl<'ast, X: Context<C>, C: ContextName> Visitor<'ast> for LiteralChecker<'_, '_, X, C> {
+    fn visit_expression(&mut self, ast: &'ast Ast, id: ExprId) {
+        let expression = &ast[id];
+        match expression.kind {
+            ExpressionKind::Unary { op, operand }
+                if op.data == UnaryOp::Neg
+                    && matches!(ast[operand].kind, ExpressionKind::IntegerLiteral { .. }) =>
+            {
+                self.check(ast, operand, true, expression.span)
+            }
+            ExpressionKind::IntegerLiteral { .. } => self.check(ast, id, false, expression.span),
+            _ => walk_expression(self, ast, id),
+        }
+    }
+
+    fn visit_pattern(&mut self, ast: &'ast Ast, pattern: &'ast Pattern) {
+        match &pattern.kind {
+            PatternKind::Literal(literal) => self.check_pattern(pattern, literal),
+            PatternKind::Range { start, end, .. } => {
+                self.check_pattern(pattern, start);
+                self.check_pattern(pattern, end);
+            }
+            _ => walk_pattern(self, ast, pattern),
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! The types the checker works with, as opposed to the [`crate::ast::Type`]s written in the
+//! source that they're lowered from.
+
+use crate::symbol::{sym, Symbol};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntTy {
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    Isize,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    Usize,
+}
+
+impl IntTy {
+    pub fn from_symbol(name: Symbol) -> Option<Self> {
+        Some(match name {
+            sym::i8 => Self::I8,
+            sym::i16 => Self::I16,
+            sym::i32 => Self::I32,
+            sym::i64 => Self::I64,
+            sym::i128 => Self::I128,
+            sym::isize => Self::Isize,
+            sym::u8 => Self::U8,
+            sym::u16 => Self::U16,
+            sym::u32 => Self::U32,
+            sym::u64 => Self::U64,
+            sym::u128 => Self::U128,
+            sym::usize => Self::Usize,
+            _ => return None,
+        })
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::I8 => "i8",
+            Self::I16 => "i16",
+            Self::I32 => "i32",
+            Self::I64 => "i64",
+            Self::I128 => "i128",
+            Self::Isize => "isize",
+            Self::U8 => "u8",
+            Self::U16 => "u16",
+            Self::U32 => "u32",
+            Self::U64 => "u64",
+            Self::U128 => "u128",
+            Self::Usize => "usize",
+        }
+    }
+
+    /// The smallest value of the type, with `isize` and `usize` taken to be 64 bits wide
+    pub const fn min(self) -> i128 {
+        match self {
+            Self::I8 => i8::MIN as i128,
+            Self::I16 => i16::MIN as i128,
+            Self::I32 => i32::MIN as i128,
+            Self::I64 | Self::Isize => i64::MIN as i128,
+            Self::I128 => i128::MIN,
+            Self::U8 | Self::U16 | Self::U32 | Self::U64 | Self::U128 | Self::Usize => 0,
+        }
+    }
+
+    /// The largest value of the type, see [`Self::min`]
+    pub const fn max(self) -> u128 {
+        match self {
+            Self::I8 => i8::MAX as u128,
+            Self::I16 => i16::MAX as u128,
+            Self::I32 => i32::MAX as u128,
+            Self::I64 | Self::Isize => i64::MAX as u128,
+            Self::I128 => i128::MAX as u128,
+            Self::U8 => u8::MAX as u128,
+            Self::U16 => u16::MAX as u128,
+            Self::U32 => u32::MAX as u128,
+            Self::U64 | Self::Usize => u64::MAX as u128,
+            Self::U128 => u128::MAX,
+        }
+    }
+
+    pub const fn is_signed(self) -> bool {
+        matches!(
+            self,
+            Self::I8 | Self::I16 | Self::I32 | Self::I64 | Self::I128 | Self::Isize
+        )
+    }
+
+    /// Width of the type in bits, see [`Self::min`]
+    pub const fn bits(self) -> u32 {
+        match self {
+            Self::I8 | Self::U8 => 8,
+            Self::I16 | Self::U16 => 16,
+            Self::I32 | Self::U32 => 32,
+            Self::I64 | Self::Isize | Self::U64 | Self::Usize => 64,
+            Self::I128 | Self::U128 => 128,
+        }
+    }
+
+    /// The low [`Self::bits`] of `value`, which is how values of the type are stored: a
+    /// negative value is its two's complement
+    pub const fn truncate(self, value: u128) -> u128 {
+        match self.bits() {
+            128 => value,
+            bits => value & ((1 << bits) - 1),
+        }
+    }
+
+    /// `bits` as written in the source, negative if the type is signed and the sign bit is set
+    pub fn format_bits(self, bits: u128) -> String {
+        let bits = self.truncate(bits);
+        let width = self.bits();
+        if self.is_signed() && bits >> (width - 1) == 1 {
+            // Sign-extended to 128 bits, the value reads the same as an `i128`
+            let extended = if width == 128 {
+                bits
+            } else {
+                bits | (u128::MAX << width)
+            };
+            (extended as i128).to_string()
+        } else {
+            bits.to_string()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FloatTy {
+    F32,
+    F64,
+}
+
+impl FloatTy {
+    pub fn from_symbol(name: Symbol) -> Option<Self> {
+        match name {
+            sym::f32 => Some(Self::F32),
+            sym::f64 => Some(Self::F64),
+            _ => None,
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::F32 => "f32",
+            Self::F64 => "f64",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Ty {
+    /// `void`, which `()` is another name for
+    Void,
+    Bool,
+    Int(IntTy),
+    Float(FloatTy),
+    Char,
+    Str,
+    /// The type of expressions that never produce a value, such as `return`
+    Never,
+    /// Never empty, that's [`Ty::Void`]
+    Tuple(Vec<Ty>),
+    Array(Box<Ty>, u64),
+    Ref {
+        mutable: bool,
+        inner: Box<Ty>,
+    },
+    Option(Box<Ty>),
+    Result(Box<Ty>, Box<Ty>),
+    /// `start..end` or `start..=end`
+    Range(Box<Ty>),
+    Fn {
+        params: Vec<Ty>,
+        ret: Box<Ty>,
+    },
+    /// A type that's still being inferred, see [`super::infer`]
+    Infer(InferTy),
+    /// The type of something that had an error already reported, which is compatible with
+    /// everything so that one mistake doesn't cause more errors further on
+    Error,
+}
+
+/// A type variable, along with what kind of type it can stand for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InferTy {
+    /// Any type
+    Var(u32),
+    /// An integer type, from an integer literal without a suffix
+    Int(u32),
+    /// A float type, from a float literal
+    Float(u32),
+}
+
+impl InferTy {
+    pub const fn index(self) -> u32 {
+        match self {
+            Self::Var(index) | Self::Int(index) | Self::Float(index) => index,
+        }
+    }
+}
+
+impl Ty {
+    pub fn tuple(elements: Vec<Self>) -> Self {
+        if elements.is_empty() {
+            Self::Void
+        } else {
+            Self::Tuple(elements)
+        }
+    }
+
+    /// Whether the type is a number, including the not yet known type of a literal
+    pub const fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Self::Int(_) | Self::Float(_) | Self::Infer(InferTy::Int(_) | InferTy::Float(_))
+        )
+    }
+
+    /// Whether an error about this type would only repeat an earlier one or be wrong,
+    /// because the expression never produces a value
+    pub const fn is_error_or_never(&self) -> bool {
+        matches!(self, Self::Error | Self::Never)
+    }
+}
+
+impl std::fmt::Display for Ty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Void => f.write_str("void"),
+            Self::Bool => f.write_str("bool"),
+            Self::Int(int) => f.write_str(int.as_str()),
+            Self::Float(float) => f.write_str(float.as_str()),
+            Self::Char => f.write_str("char"),
+            Self::Str => f.write_str("str"),
+            Self::Never => f.write_str("!"),
+            Self::Tuple(elements) => {
+                f.write_str("(")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{element}")?;
+                }
+                if elements.len() == 1 {
+                    f.write_str(",")?;
+                }
+                f.write_str(")")
+            }
+            Self::Array(element, length) => write!(f, "[{element}; {length}]"),
+            Self::Ref { mutable, inner } => {
+                write!(f, "&{}{inner}", if *mutable { "mut " } else { "" })
+            }
+            Self::Option(inner) => write!(f, "Option<{inner}>"),
+            Self::Result(ok, err) => write!(f, "Result<{ok}, {err}>"),
+            Self::Range(element) => write!(f, "Range<{element}>"),
+            Self::Fn { params, ret } => {
+                f.write_str("fn(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{param}")?;
+                }
+                write!(f, "): {ret}")
+            }
+            Self::Infer(InferTy::Var(_)) | Self::Error => f.write_str("_"),
+            Self::Infer(InferTy::Int(_)) => f.write_str("{integer}"),
+            Self::Infer(InferTy::Float(_)) => f.write_str("{float}"),
+        }
+    }
+}